@@ -97,6 +97,18 @@ class SyncWork(appContext: Context, workerParams: WorkerParameters) :
                 is SyncAllError.ClientUpdateRequired -> {
                     Timber.e("Client update required.")
                 }
+                is SyncAllError.Cancelled -> {
+                    Timber.e("Sync cancelled.")
+                }
+                is SyncAllError.ServerTampering -> {
+                    Timber.e("Server sent changes that were not signed by this account.")
+                }
+                is SyncAllError.DocumentCorrupted -> {
+                    Timber.e("A downloaded document was corrupted.")
+                }
+                is SyncAllError.DataDirLocked -> {
+                    Timber.e("Another app is changing the local data.")
+                }
                 is SyncAllError.Unexpected -> {
                     Timber.e("Unable to sync all files: ${error.error}")
                 }
@@ -41,6 +41,15 @@ class SyncModel(
         _updateSyncSnackBar.postValue(Pair(newStatus.total, newStatus.progress))
     }
 
+    fun updateSyncPhaseAndBytes(phase: String, bytesTransferred: Long, totalBytes: Long) { // used by core over ffi
+        val currentStatus = syncStatus
+        if (currentStatus is SyncStatus.IsSyncing) {
+            currentStatus.phase = phase
+            currentStatus.bytesTransferred = bytesTransferred
+            currentStatus.totalBytes = totalBytes
+        }
+    }
+
     private fun sync(resources: Resources) {
         val upToDateMsg =
             resources.getString(R.string.list_files_sync_finished_snackbar)
@@ -64,5 +73,11 @@ class SyncModel(
 
 sealed class SyncStatus {
     object IsNotSyncing : SyncStatus()
-    data class IsSyncing(var total: Int, var progress: Int) : SyncStatus()
+    data class IsSyncing(
+        var total: Int,
+        var progress: Int,
+        var phase: String = "Calculating",
+        var bytesTransferred: Long = 0,
+        var totalBytes: Long = 0
+    ) : SyncStatus()
 }
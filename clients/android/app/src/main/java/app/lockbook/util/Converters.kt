@@ -89,6 +89,7 @@ val migrateDBConverter = object : Converter {
                     Err(
                         when (error) {
                             MigrationError.StateRequiresCleaning::class.simpleName -> MigrationError.StateRequiresCleaning
+                            MigrationError.DataDirLocked::class.simpleName -> MigrationError.DataDirLocked
                             else -> MigrationError.Unexpected("migrateDBConverter $unmatchedUiError $error")
                         }
                     )
@@ -128,6 +129,7 @@ val createAccountConverter = object : Converter {
                             CreateAccountError.CouldNotReachServer::class.simpleName -> CreateAccountError.CouldNotReachServer
                             CreateAccountError.AccountExistsAlready::class.simpleName -> CreateAccountError.AccountExistsAlready
                             CreateAccountError.ClientUpdateRequired::class.simpleName -> CreateAccountError.ClientUpdateRequired
+                            CreateAccountError.DataDirLocked::class.simpleName -> CreateAccountError.DataDirLocked
                             else -> CreateAccountError.Unexpected("createAccountConverter $unmatchedUiError $error")
                         }
                     )
@@ -168,6 +170,7 @@ val importAccountConverter = object : Converter {
                             ImportError.UsernamePKMismatch::class.simpleName -> ImportError.UsernamePKMismatch
                             ImportError.CouldNotReachServer::class.simpleName -> ImportError.CouldNotReachServer
                             ImportError.ClientUpdateRequired::class.simpleName -> ImportError.ClientUpdateRequired
+                            ImportError.DataDirLocked::class.simpleName -> ImportError.DataDirLocked
                             else -> ImportError.Unexpected("importAccountConverter $unmatchedUiError $error")
                         }
                     )
@@ -281,6 +284,19 @@ val setLastSyncedConverter = object : Converter {
     override fun fromJson(jv: JsonValue): Any = when (jv.obj?.string("tag")) {
         okTag -> Ok(Unit)
         errTag -> when (val errorTag = jv.obj?.obj("content")?.string("tag")) {
+            uiErrorTag -> {
+                val error = jv.obj?.obj("content")?.string("content")
+                if (error != null) {
+                    Err(
+                        when (error) {
+                            SetLastSyncedError.DataDirLocked::class.simpleName -> SetLastSyncedError.DataDirLocked
+                            else -> SetLastSyncedError.Unexpected("setLastSyncedConverter $unmatchedUiError $error")
+                        }
+                    )
+                } else {
+                    Err(SetLastSyncedError.Unexpected("setLastSyncedConverter $unableToGetUiError ${jv.obj?.toJsonString()}"))
+                }
+            }
             unexpectedTag -> {
                 val error = jv.obj?.obj("content")?.string("content")
                 if (error != null) {
@@ -515,7 +531,9 @@ val renameFileConverter = object : Converter {
                             RenameFileError.NewNameContainsSlash::class.simpleName -> RenameFileError.NewNameContainsSlash
                             RenameFileError.FileNameNotAvailable::class.simpleName -> RenameFileError.FileNameNotAvailable
                             RenameFileError.NewNameEmpty::class.simpleName -> RenameFileError.NewNameEmpty
+                            RenameFileError.NewNameInvalid::class.simpleName -> RenameFileError.NewNameInvalid
                             RenameFileError.CannotRenameRoot::class.simpleName -> RenameFileError.CannotRenameRoot
+                            RenameFileError.DataDirLocked::class.simpleName -> RenameFileError.DataDirLocked
                             else -> RenameFileError.Unexpected("renameFileConverter $unmatchedUiError $error")
                         }
                     )
@@ -563,6 +581,8 @@ val createFileConverter = object : Converter {
                             CreateFileError.CouldNotFindAParent::class.simpleName -> CreateFileError.CouldNotFindAParent
                             CreateFileError.FileNameContainsSlash::class.simpleName -> CreateFileError.FileNameContainsSlash
                             CreateFileError.FileNameEmpty::class.simpleName -> CreateFileError.FileNameEmpty
+                            CreateFileError.FileNameInvalid::class.simpleName -> CreateFileError.FileNameInvalid
+                            CreateFileError.DataDirLocked::class.simpleName -> CreateFileError.DataDirLocked
                             else -> CreateFileError.Unexpected("createFileConverter $unmatchedUiError $error")
                         }
                     )
@@ -599,6 +619,7 @@ val deleteFileConverter = object : Converter {
                         when (error) {
                             FileDeleteError.FileDoesNotExist::class.simpleName -> FileDeleteError.FileDoesNotExist
                             FileDeleteError.CannotDeleteRoot::class.simpleName -> FileDeleteError.CannotDeleteRoot
+                            FileDeleteError.DataDirLocked::class.simpleName -> FileDeleteError.DataDirLocked
                             else -> FileDeleteError.Unexpected("deleteFileConverter $unmatchedUiError $error")
                         }
                     )
@@ -643,6 +664,8 @@ val readDocumentConverter = object : Converter {
                             ReadDocumentError.TreatedFolderAsDocument::class.simpleName -> ReadDocumentError.TreatedFolderAsDocument
                             ReadDocumentError.NoAccount::class.simpleName -> ReadDocumentError.NoAccount
                             ReadDocumentError.FileDoesNotExist::class.simpleName -> ReadDocumentError.FileDoesNotExist
+                            ReadDocumentError.NotAvailableOffline::class.simpleName -> ReadDocumentError.NotAvailableOffline
+                            ReadDocumentError.DocumentCorrupted::class.simpleName -> ReadDocumentError.DocumentCorrupted
                             else -> ReadDocumentError.Unexpected("readDocumentConverter $unmatchedUiError $error")
                         }
                     )
@@ -682,6 +705,8 @@ val saveDocumentToDiskConverter = object : Converter {
                             SaveDocumentToDiskError.FileDoesNotExist::class.simpleName -> SaveDocumentToDiskError.FileDoesNotExist
                             SaveDocumentToDiskError.BadPath::class.simpleName -> SaveDocumentToDiskError.BadPath
                             SaveDocumentToDiskError.FileAlreadyExistsInDisk::class.simpleName -> SaveDocumentToDiskError.FileAlreadyExistsInDisk
+                            SaveDocumentToDiskError.NotAvailableOffline::class.simpleName -> SaveDocumentToDiskError.NotAvailableOffline
+                            SaveDocumentToDiskError.DocumentCorrupted::class.simpleName -> SaveDocumentToDiskError.DocumentCorrupted
                             else -> SaveDocumentToDiskError.Unexpected("saveDocumentToDiskConverter $unmatchedUiError $error")
                         }
                     )
@@ -804,6 +829,7 @@ val writeDocumentConverter = object : Converter {
                             WriteToDocumentError.FolderTreatedAsDocument::class.simpleName -> WriteToDocumentError.FolderTreatedAsDocument
                             WriteToDocumentError.NoAccount::class.simpleName -> WriteToDocumentError.NoAccount
                             WriteToDocumentError.FileDoesNotExist::class.simpleName -> WriteToDocumentError.FileDoesNotExist
+                            WriteToDocumentError.DataDirLocked::class.simpleName -> WriteToDocumentError.DataDirLocked
                             else -> WriteToDocumentError.Unexpected("writeDocumentConverter $unmatchedUiError $error")
                         }
                     )
@@ -845,6 +871,7 @@ val moveFileConverter = object : Converter {
                             MoveFileError.TargetParentHasChildNamedThat::class.simpleName -> MoveFileError.TargetParentHasChildNamedThat
                             MoveFileError.CannotMoveRoot::class.simpleName -> MoveFileError.CannotMoveRoot
                             MoveFileError.FolderMovedIntoItself::class.simpleName -> MoveFileError.FolderMovedIntoItself
+                            MoveFileError.DataDirLocked::class.simpleName -> MoveFileError.DataDirLocked
                             else -> MoveFileError.Unexpected("moveFileConverter $unmatchedUiError $error")
                         }
                     )
@@ -882,6 +909,10 @@ val syncConverter = object : Converter {
                             SyncAllError.CouldNotReachServer::class.simpleName -> SyncAllError.CouldNotReachServer
                             SyncAllError.NoAccount::class.simpleName -> SyncAllError.NoAccount
                             SyncAllError.ClientUpdateRequired::class.simpleName -> SyncAllError.ClientUpdateRequired
+                            SyncAllError.Cancelled::class.simpleName -> SyncAllError.Cancelled
+                            SyncAllError.ServerTampering::class.simpleName -> SyncAllError.ServerTampering
+                            SyncAllError.DocumentCorrupted::class.simpleName -> SyncAllError.DocumentCorrupted
+                            SyncAllError.DataDirLocked::class.simpleName -> SyncAllError.DataDirLocked
                             else -> SyncAllError.Unexpected("syncAllConverter $unmatchedUiError $error")
                         }
                     )
@@ -926,6 +957,7 @@ val calculateWorkConverter = object : Converter {
                             CalculateWorkError.CouldNotReachServer::class.simpleName -> CalculateWorkError.CouldNotReachServer
                             CalculateWorkError.NoAccount::class.simpleName -> CalculateWorkError.NoAccount
                             CalculateWorkError.ClientUpdateRequired::class.simpleName -> CalculateWorkError.ClientUpdateRequired
+                            CalculateWorkError.ServerTampering::class.simpleName -> CalculateWorkError.ServerTampering
                             else -> CalculateWorkError.Unexpected("calculateSyncWorkConverter $unmatchedUiError $error")
                         }
                     )
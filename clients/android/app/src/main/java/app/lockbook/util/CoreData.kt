@@ -51,6 +51,8 @@ data class UsageMetrics(
     val usages: List<FileUsage>,
     @Json(name = "server_usage")
     val serverUsage: UsageItemMetric,
+    @Json(name = "logical_usage")
+    val logicalUsage: UsageItemMetric,
     @Json(name = "data_cap")
     val dataCap: UsageItemMetric,
 )
@@ -28,6 +28,24 @@ sealed class CoreError {
         ImportError.CouldNotReachServer,
         SyncAllError.CouldNotReachServer,
         CalculateWorkError.CouldNotReachServer -> LbError.newUserError(getString(res, R.string.could_not_reach_server))
+        SyncAllError.Cancelled -> LbError.newUserError(getString(res, R.string.sync_cancelled))
+        SyncAllError.ServerTampering,
+        CalculateWorkError.ServerTampering -> LbError.newUserError(getString(res, R.string.server_tampering))
+        ReadDocumentError.NotAvailableOffline,
+        SaveDocumentToDiskError.NotAvailableOffline -> LbError.newUserError(getString(res, R.string.not_available_offline))
+        ReadDocumentError.DocumentCorrupted,
+        SaveDocumentToDiskError.DocumentCorrupted,
+        SyncAllError.DocumentCorrupted -> LbError.newUserError(getString(res, R.string.document_corrupted))
+        MigrationError.DataDirLocked,
+        CreateAccountError.DataDirLocked,
+        ImportError.DataDirLocked,
+        SetLastSyncedError.DataDirLocked,
+        WriteToDocumentError.DataDirLocked,
+        CreateFileError.DataDirLocked,
+        FileDeleteError.DataDirLocked,
+        RenameFileError.DataDirLocked,
+        MoveFileError.DataDirLocked,
+        SyncAllError.DataDirLocked -> LbError.newUserError(getString(res, R.string.data_dir_locked))
         MigrationError.StateRequiresCleaning -> LbError.newUserError(getString(res, R.string.state_requires_cleaning))
         CreateAccountError.AccountExistsAlready,
         ImportError.AccountExistsAlready -> LbError.newUserError(getString(res, R.string.account_exists_already))
@@ -56,6 +74,8 @@ sealed class CoreError {
         RenameFileError.NewNameContainsSlash -> LbError.newUserError(getString(res, R.string.file_name_contains_slash))
         CreateFileError.FileNameEmpty,
         RenameFileError.NewNameEmpty -> LbError.newUserError(getString(res, R.string.file_name_empty))
+        CreateFileError.FileNameInvalid,
+        RenameFileError.NewNameInvalid -> LbError.newUserError(getString(res, R.string.file_name_invalid))
         CreateFileError.FileNameNotAvailable,
         RenameFileError.FileNameNotAvailable -> LbError.newUserError(getString(res, R.string.file_name_not_available))
         GetFileByIdError.NoFileWithThatId -> LbError.newUserError(getString(res, R.string.no_file_with_that_id))
@@ -115,6 +135,7 @@ sealed class GetStateError : CoreError() {
 
 sealed class MigrationError : CoreError() {
     object StateRequiresCleaning : MigrationError()
+    object DataDirLocked : MigrationError()
     data class Unexpected(val error: String) : MigrationError()
 }
 
@@ -124,6 +145,7 @@ sealed class CreateAccountError : CoreError() {
     object CouldNotReachServer : CreateAccountError()
     object AccountExistsAlready : CreateAccountError()
     object ClientUpdateRequired : CreateAccountError()
+    object DataDirLocked : CreateAccountError()
     data class Unexpected(val error: String) : CreateAccountError()
 }
 
@@ -134,6 +156,7 @@ sealed class ImportError : CoreError() {
     object UsernamePKMismatch : ImportError()
     object CouldNotReachServer : ImportError()
     object ClientUpdateRequired : ImportError()
+    object DataDirLocked : ImportError()
     data class Unexpected(val error: String) : ImportError()
 }
 
@@ -148,6 +171,7 @@ sealed class GetAccountError : CoreError() {
 }
 
 sealed class SetLastSyncedError : CoreError() {
+    object DataDirLocked : SetLastSyncedError()
     data class Unexpected(val error: String) : SetLastSyncedError()
 }
 
@@ -160,6 +184,7 @@ sealed class WriteToDocumentError : CoreError() {
     object NoAccount : WriteToDocumentError()
     object FileDoesNotExist : WriteToDocumentError()
     object FolderTreatedAsDocument : WriteToDocumentError()
+    object DataDirLocked : WriteToDocumentError()
     data class Unexpected(val error: String) : WriteToDocumentError()
 }
 
@@ -170,6 +195,8 @@ sealed class CreateFileError : CoreError() {
     object FileNameNotAvailable : CreateFileError()
     object FileNameContainsSlash : CreateFileError()
     object FileNameEmpty : CreateFileError()
+    object FileNameInvalid : CreateFileError()
+    object DataDirLocked : CreateFileError()
     data class Unexpected(val error: String) : CreateFileError()
 }
 
@@ -185,6 +212,7 @@ sealed class GetFileByIdError : CoreError() {
 sealed class FileDeleteError : CoreError() {
     object FileDoesNotExist : FileDeleteError()
     object CannotDeleteRoot : FileDeleteError()
+    object DataDirLocked : FileDeleteError()
     data class Unexpected(val error: String) : FileDeleteError()
 }
 
@@ -192,6 +220,8 @@ sealed class ReadDocumentError : CoreError() {
     object TreatedFolderAsDocument : ReadDocumentError()
     object NoAccount : ReadDocumentError()
     object FileDoesNotExist : ReadDocumentError()
+    object NotAvailableOffline : ReadDocumentError()
+    object DocumentCorrupted : ReadDocumentError()
     data class Unexpected(val error: String) : ReadDocumentError()
 }
 
@@ -201,6 +231,8 @@ sealed class SaveDocumentToDiskError : CoreError() {
     object FileDoesNotExist : SaveDocumentToDiskError()
     object BadPath : SaveDocumentToDiskError()
     object FileAlreadyExistsInDisk : SaveDocumentToDiskError()
+    object NotAvailableOffline : SaveDocumentToDiskError()
+    object DocumentCorrupted : SaveDocumentToDiskError()
     data class Unexpected(val error: String) : SaveDocumentToDiskError()
 }
 
@@ -227,7 +259,9 @@ sealed class RenameFileError : CoreError() {
     object NewNameContainsSlash : RenameFileError()
     object FileNameNotAvailable : RenameFileError()
     object NewNameEmpty : RenameFileError()
+    object NewNameInvalid : RenameFileError()
     object CannotRenameRoot : RenameFileError()
+    object DataDirLocked : RenameFileError()
     data class Unexpected(val error: String) : RenameFileError()
 }
 
@@ -239,6 +273,7 @@ sealed class MoveFileError : CoreError() {
     object TargetParentHasChildNamedThat : MoveFileError()
     object CannotMoveRoot : MoveFileError()
     object FolderMovedIntoItself : MoveFileError()
+    object DataDirLocked : MoveFileError()
     data class Unexpected(val error: String) : MoveFileError()
 }
 
@@ -246,6 +281,10 @@ sealed class SyncAllError : CoreError() {
     object NoAccount : SyncAllError()
     object CouldNotReachServer : SyncAllError()
     object ClientUpdateRequired : SyncAllError()
+    object Cancelled : SyncAllError()
+    object ServerTampering : SyncAllError()
+    object DocumentCorrupted : SyncAllError()
+    object DataDirLocked : SyncAllError()
     data class Unexpected(val error: String) : SyncAllError()
 }
 
@@ -253,6 +292,7 @@ sealed class CalculateWorkError : CoreError() {
     object NoAccount : CalculateWorkError()
     object CouldNotReachServer : CalculateWorkError()
     object ClientUpdateRequired : CalculateWorkError()
+    object ServerTampering : CalculateWorkError()
     data class Unexpected(val error: String) : CalculateWorkError()
 }
 
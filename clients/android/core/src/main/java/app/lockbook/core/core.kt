@@ -26,5 +26,17 @@ external fun writeDocument(config: String, id: String, content: String): String
 external fun moveFile(config: String, id: String, parentId: String): String
 external fun syncAll(config: String, fragment: Any): String
 external fun backgroundSync(config: String): String
+external fun cancelSync(config: String): String
 external fun calculateWork(config: String): String
+external fun subscribe(config: String, listener: Any): String
+external fun unsubscribe(subscriptionId: Long): String
+external fun startSyncScheduler(config: String, listener: Any): String
+external fun syncNow(config: String): String
+external fun stopSyncScheduler(config: String): String
+external fun setLazySync(config: String, lazy: Boolean): String
+external fun getLazySync(config: String): String
+external fun pinFile(config: String, id: String): String
+external fun unpinFile(config: String, id: String): String
+external fun getPinnedFiles(config: String): String
+external fun call(method: String, args: String): String
 external fun getAllErrorVariants(): String
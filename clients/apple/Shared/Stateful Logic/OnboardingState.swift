@@ -43,6 +43,8 @@ class OnboardingState: ObservableObject {
                         self.createAccountError = "That username is not valid!"
                     case .UsernameTaken:
                         self.createAccountError = "That username is not available!"
+                    case .DataDirLocked:
+                        self.createAccountError = "Another app is changing your files, try again in a moment!"
                     }
                     break;
                 case .Unexpected:
@@ -88,6 +90,8 @@ class OnboardingState: ObservableObject {
                         self.importAccountError = "Could not reach \(ConfigHelper.get(.apiLocation))!"
                     case .UsernamePKMismatch:
                         self.importAccountError = "That username does not match the public key stored on this server!"
+                    case .DataDirLocked:
+                        self.importAccountError = "Another app is changing your files, try again in a moment!"
                     }
                 case .Unexpected:
                     self.core.handleError(error)
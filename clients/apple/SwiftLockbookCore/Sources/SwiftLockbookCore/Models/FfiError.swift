@@ -63,6 +63,7 @@ public enum GetStateError: String, UiError {
 
 public enum MigrationError: String, UiError {
     case StateRequiresCleaning
+    case DataDirLocked
 }
 
 public enum CreateAccountError: String, UiError {
@@ -71,6 +72,7 @@ public enum CreateAccountError: String, UiError {
     case CouldNotReachServer
     case InvalidUsername
     case UsernameTaken
+    case DataDirLocked
 }
 
 public enum ImportError: String, UiError {
@@ -80,6 +82,7 @@ public enum ImportError: String, UiError {
     case ClientUpdateRequired
     case CouldNotReachServer
     case UsernamePKMismatch
+    case DataDirLocked
 }
 
 public enum AccountExportError: String, UiError {
@@ -93,16 +96,19 @@ public enum GetAccountError: String, UiError {
 public enum CreateFileAtPathError: String, UiError {
     case DocumentTreatedAsFolder
     case FileAlreadyExists
+    case FileNameInvalid
     case NoAccount
     case NoRoot
     case PathContainsEmptyFile
     case PathDoesntStartWithRoot
+    case DataDirLocked
 }
 
 public enum WriteToDocumentError: String, UiError {
     case FileDoesNotExist
     case FolderTreatedAsDocument
     case NoAccount
+    case DataDirLocked
 }
 
 public enum CreateFileError: String, UiError {
@@ -110,8 +116,10 @@ public enum CreateFileError: String, UiError {
     case DocumentTreatedAsFolder
     case FileNameContainsSlash
     case FileNameEmpty
+    case FileNameInvalid
     case FileNameNotAvailable
     case NoAccount
+    case DataDirLocked
 }
 
 public enum GetRootError: String, UiError {
@@ -134,6 +142,8 @@ public enum ReadDocumentError: String, UiError {
     case FileDoesNotExist
     case NoAccount
     case TreatedFolderAsDocument
+    case NotAvailableOffline
+    case DocumentCorrupted
 }
 
 public enum ListPathsError: String, UiError {
@@ -150,6 +160,8 @@ public enum RenameFileError: String, UiError {
     case FileNameNotAvailable
     case NewNameContainsSlash
     case NewNameEmpty
+    case NewNameInvalid
+    case DataDirLocked
 }
 
 public enum MoveFileError: String, UiError {
@@ -160,20 +172,26 @@ public enum MoveFileError: String, UiError {
     case NoAccount
     case TargetParentDoesNotExist
     case TargetParentHasChildNamedThat
+    case DataDirLocked
 }
 
 public enum SyncAllError: String, UiError {
     case NoAccount
     case ClientUpdateRequired
     case CouldNotReachServer
+    case Cancelled
+    case ServerTampering
+    case DocumentCorrupted
+    case DataDirLocked
 }
 public enum CalculateWorkError: String, UiError {
     case NoAccount
     case CouldNotReachServer
     case ClientUpdateRequired
+    case ServerTampering
 }
 public enum SetLastSyncedError: String, UiError {
-    case Stub
+    case DataDirLocked
 }
 public enum GetLastSyncedError: String, UiError {
     case Stub
@@ -187,6 +205,7 @@ public enum GetUsageError: String, UiError {
 public enum FileDeleteError: String, UiError {
     case CannotDeleteRoot
     case FileDoesNotExist
+    case DataDirLocked
 }
 public enum GetLocalChangesError: String, UiError {
     case Stub
@@ -204,6 +223,7 @@ public enum SaveDrawingError: String, UiError {
     case FileDoesNotExist
     case FolderTreatedAsDrawing
     case InvalidDrawing
+    case DataDirLocked
 }
 
 public enum ExportDrawingError: String, UiError {
@@ -3,6 +3,7 @@ import Foundation
 public struct UsageMetrics: Codable {
     public var usages: [FileUsage]
     public var serverUsage: UsageItemMetric
+    public var logicalUsage: UsageItemMetric
     public var dataCap: UsageItemMetric
 }
 
@@ -106,6 +106,8 @@ struct NewFileSheet: View {
                     errors = "File names cannot contain slashes"
                 case .FileNameEmpty:
                     errors = "File name cannot be empty"
+                case .FileNameInvalid:
+                    errors = "File names cannot contain control characters or be reserved by the OS"
                 case .FileNameNotAvailable:
                     errors = "A file with that name exists in this folder already"
                 default:
@@ -1,6 +1,7 @@
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
 use std::{env, fs};
 
 use chrono::{DateTime, Utc};
@@ -72,19 +73,38 @@ pub fn backup() -> CliResult<()> {
 
     println!("Writing {} documents", docs.len());
     for doc in docs {
-        let path = backup_directory.join(PathBuf::from(&doc));
-
-        let mut document = File::create(&path)
-            .map_err(|err| err!(OsCouldNotCreateFile(path_string!(path), err)))?;
-
         let document_metadata = get_file_by_path(&get_config(), &doc).map_err(|err| match err {
             CoreError::UiError(GetFileByPathError::NoFileAtThatPath) | CoreError::Unexpected(_) => {
                 err_unexpected!("couldn't get file metadata for: {} error: {:?}", &doc, err)
             }
         })?;
 
+        let path = backup_directory.join(PathBuf::from(&doc));
+        let mut document = match create_new(&path) {
+            // On case-insensitive (or normalizing) file systems, names lockbook tells apart can
+            // collide. Rather than overwrite the document already written, write this one beside it.
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                let renamed = path.with_file_name(format!(
+                    "{}-NAME-CONFLICT-{}",
+                    document_metadata.name, document_metadata.id
+                ));
+                eprintln!(
+                    "{} collides with another file on this file system, writing it to {}",
+                    doc,
+                    path_string!(renamed)
+                );
+                create_new(&renamed)
+                    .map_err(|err| err!(OsCouldNotCreateFile(path_string!(renamed), err)))?
+            }
+            result => result.map_err(|err| err!(OsCouldNotCreateFile(path_string!(path), err)))?,
+        };
+
         let document_content =
             read_document(&get_config(), document_metadata.id).map_err(|err| match err {
+                CoreError::UiError(ReadDocumentError::NotAvailableOffline) => err!(NetworkIssue),
+                CoreError::UiError(ReadDocumentError::DocumentCorrupted) => {
+                    err!(DocumentCorrupted(doc.clone()))
+                }
                 CoreError::UiError(ReadDocumentError::TreatedFolderAsDocument)
                 | CoreError::UiError(ReadDocumentError::NoAccount)
                 | CoreError::UiError(ReadDocumentError::FileDoesNotExist)
@@ -100,3 +120,7 @@ pub fn backup() -> CliResult<()> {
 
     Ok(())
 }
+
+fn create_new(path: &Path) -> io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
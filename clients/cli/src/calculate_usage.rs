@@ -23,22 +23,36 @@ pub fn calculate_usage(exact: bool) -> CliResult<()> {
             }
         })?;
 
-    let (uncompressed, server_usage, data_cap) = if exact {
+    let (uncompressed, server_usage, logical_usage, data_cap) = if exact {
         (
             format!("{} B", uncompressed_usage.exact),
             format!("{} B", usage.server_usage.exact),
+            format!("{} B", usage.logical_usage.exact),
             format!("{} B", usage.data_cap.exact),
         )
     } else {
         (
             uncompressed_usage.readable,
             usage.server_usage.readable,
+            usage.logical_usage.readable,
             usage.data_cap.readable,
         )
     };
 
     println!("Uncompressed File Size: {}", uncompressed);
+    for codec_usage in uncompressed_usage.by_codec {
+        let savings = if exact {
+            format!("{} B", codec_usage.savings.exact)
+        } else {
+            codec_usage.savings.readable
+        };
+        println!(
+            "  {:?}: {} documents, {} saved",
+            codec_usage.codec, codec_usage.documents, savings
+        );
+    }
     println!("Server Utilization: {}", server_usage);
+    println!("  Without Padding: {}", logical_usage);
     println!("Server Data Cap: {}", data_cap);
     Ok(())
 }
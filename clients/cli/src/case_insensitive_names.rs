@@ -0,0 +1,21 @@
+use crate::err_unexpected;
+use crate::error::CliResult;
+use crate::utils::{get_account_or_exit, get_config};
+
+pub fn case_insensitive_names(setting: Option<String>) -> CliResult<()> {
+    get_account_or_exit();
+
+    match setting {
+        Some(setting) => {
+            lockbook_core::set_case_insensitive_names(&get_config(), setting == "on")
+                .map_err(|err| err_unexpected!("{:?}", err))?;
+        }
+        None => {
+            let case_insensitive = lockbook_core::get_case_insensitive_names(&get_config())
+                .map_err(|err| err_unexpected!("{:?}", err))?;
+            println!("{}", if case_insensitive { "on" } else { "off" });
+        }
+    }
+
+    Ok(())
+}
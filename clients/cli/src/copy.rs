@@ -1,11 +1,13 @@
 use std::fs;
-use std::fs::DirEntry;
+use std::fs::{DirEntry, File};
+use std::io::{BufReader, Read};
 use std::path::PathBuf;
 
 use lockbook_core::model::state::Config;
+use lockbook_core::service::document_stream_service::STREAMING_THRESHOLD;
 use lockbook_core::{
-    create_file_at_path, get_file_by_path, write_document, CreateFileAtPathError,
-    Error as CoreError, GetFileByPathError,
+    create_file_at_path, get_file_by_path, write_document, write_document_from,
+    CreateFileAtPathError, Error as CoreError, GetFileByPathError, WriteToDocumentError,
 };
 
 use crate::error::{CliResult, Error};
@@ -79,6 +81,8 @@ fn recursive_copy_folder(
                     CreateFileAtPathError::DocumentTreatedAsFolder => eprintln!("A file along the target destination is a document that cannot be used as a folder: {}", lockbook_path),
                     CreateFileAtPathError::PathContainsEmptyFile => eprintln!("Input destination {} contains an empty file!", lockbook_path),
                     CreateFileAtPathError::PathDoesntStartWithRoot => err!(PathNoRoot(lockbook_path.to_string())).exit(),
+                    CreateFileAtPathError::FileNameInvalid => eprintln!("Input destination {} has a name that isn't allowed!", lockbook_path),
+                    CreateFileAtPathError::DataDirLocked => err!(DataDirLocked).exit(),
                 }
                 CoreError::Unexpected(msg) => err_unexpected!("{}", msg).exit(),
             }
@@ -92,7 +96,7 @@ fn copy_file(
     config: &Config,
     edit: bool,
 ) -> Result<String, Error> {
-    let content = fs::read(&filesystem_path)
+    let content = File::open(&filesystem_path)
         .map_err(|err| err!(OsCouldNotReadFile(path_string!(filesystem_path), err)))?;
 
     let fs_absolute_path = fs::canonicalize(&filesystem_path)
@@ -142,13 +146,33 @@ fn copy_file(
                 CreateFileAtPathError::PathDoesntStartWithRoot => {
                     err!(PathNoRoot(lb_path_with_filename.to_string())).exit()
                 }
+                CreateFileAtPathError::FileNameInvalid => {
+                    return Err(err!(FileNameInvalid(lb_path_with_filename)));
+                }
+                CreateFileAtPathError::DataDirLocked => err!(DataDirLocked).exit(),
             },
             CoreError::Unexpected(msg) => err_unexpected!("{}", msg).exit(),
         },
     };
 
-    match write_document(config, file_metadata.id, content.as_slice()) {
+    let size = content
+        .metadata()
+        .map_err(|err| err!(OsCouldNotReadFile(path_string!(filesystem_path), err)))?
+        .len();
+    let mut reader = BufReader::new(content);
+    let result = if size < STREAMING_THRESHOLD {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|err| err!(OsCouldNotReadFile(path_string!(filesystem_path), err)))?;
+        write_document(config, file_metadata.id, &bytes)
+    } else {
+        write_document_from(config, file_metadata.id, &mut reader)
+    };
+
+    match result {
         Ok(_) => Ok(format!("imported to {}", lb_path_with_filename)),
+        Err(CoreError::UiError(WriteToDocumentError::DataDirLocked)) => Err(err!(DataDirLocked)),
         Err(err) => Err(err_unexpected!("{:#?}", err)),
     }
 }
@@ -0,0 +1,46 @@
+use lockbook_core::service::sync_scheduler_service::{
+    SyncScheduler, SyncSchedulerEvent, SyncSchedulerSettings,
+};
+use std::sync::mpsc;
+
+use crate::err;
+use crate::error::CliResult;
+use crate::utils::{get_account_or_exit, get_config};
+
+pub fn daemon() -> CliResult<()> {
+    get_account_or_exit();
+
+    let (sender, receiver) = mpsc::channel();
+    let scheduler = SyncScheduler::start(
+        &get_config(),
+        SyncSchedulerSettings::default(),
+        Box::new(move |event| {
+            // The receiver only goes away once we're shutting down
+            let _ = sender.send(event);
+        }),
+    );
+
+    println!("Syncing in the background, press Ctrl-C to stop.");
+
+    for event in receiver {
+        match event {
+            SyncSchedulerEvent::SyncStarted => println!("Syncing..."),
+            SyncSchedulerEvent::SyncCompleted => println!("Sync complete."),
+            SyncSchedulerEvent::Offline { retry_in_ms } => println!(
+                "Could not reach server, retrying in {} seconds.",
+                retry_in_ms / 1000
+            ),
+            SyncSchedulerEvent::DataDirLocked { retry_in_ms } => println!(
+                "Another lockbook is changing your files, retrying in {} seconds.",
+                retry_in_ms / 1000
+            ),
+            SyncSchedulerEvent::SyncFailed(msg) => eprintln!("Sync failed: {}", msg),
+            SyncSchedulerEvent::ClientUpdateRequired => {
+                scheduler.stop();
+                return Err(err!(UpdateRequired));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use crate::error::CliResult;
+use crate::utils::{get_account_or_exit, get_config};
+use crate::{err, err_unexpected};
+use lockbook_core::model::state::Config;
+use lockbook_core::{
+    diff_local_document, get_file_by_path, get_local_change_details, DiffLocalDocumentError,
+    Error as CoreError, GetFileByPathError, GetLocalChangeDetailsError,
+};
+use lockbook_models::file_metadata::FileType::Document;
+use uuid::Uuid;
+
+pub fn diff(path: Option<&str>) -> CliResult<()> {
+    get_account_or_exit();
+    let config = get_config();
+
+    match path {
+        Some(path) => {
+            let file_metadata = get_file_by_path(&config, path).map_err(|err| match err {
+                CoreError::UiError(GetFileByPathError::NoFileAtThatPath) => {
+                    err!(FileNotFound(path.to_string()))
+                }
+                CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+            })?;
+
+            print!("{}", diff_document(&config, file_metadata.id, path)?);
+        }
+        None => {
+            let changes = get_local_change_details(&config).map_err(|err| match err {
+                CoreError::UiError(GetLocalChangeDetailsError::NoAccount) => err!(NoAccount),
+                CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+            })?;
+
+            for change in changes
+                .iter()
+                .filter(|change| change.edited && change.file_type == Document)
+            {
+                // Binary documents and the like shouldn't hide the diffs of the rest
+                match diff_document(&config, change.id, &change.path) {
+                    Ok(diff) => print!("{}", diff),
+                    Err(err) => err.print(),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn diff_document(config: &Config, id: Uuid, path: &str) -> CliResult<String> {
+    let diff = diff_local_document(config, id).map_err(|err| match err {
+        CoreError::UiError(err) => match err {
+            DiffLocalDocumentError::NoAccount => err!(NoAccount),
+            DiffLocalDocumentError::TreatedFolderAsDocument => {
+                err!(FolderTreatedAsDoc(path.to_string()))
+            }
+            DiffLocalDocumentError::DocumentNotText => err!(DocumentNotText(path.to_string())),
+            DiffLocalDocumentError::OldVersionNotAvailable => {
+                err!(OldVersionNotAvailable(path.to_string()))
+            }
+            DiffLocalDocumentError::DocumentCorrupted => {
+                err!(DocumentCorrupted(path.to_string()))
+            }
+            DiffLocalDocumentError::FileDoesNotExist => err_unexpected!("FileDoesNotExist!"),
+        },
+        CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+    })?;
+
+    Ok(diff
+        .replacen("--- original", &format!("--- {} (synced)", path), 1)
+        .replacen("+++ modified", &format!("+++ {}", path), 1))
+}
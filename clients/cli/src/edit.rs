@@ -28,6 +28,10 @@ pub fn edit(file_name: &str) -> CliResult<()> {
         CoreError::UiError(ReadDocumentError::TreatedFolderAsDocument) => {
             err!(FolderTreatedAsDoc(file_name.to_string()))
         }
+        CoreError::UiError(ReadDocumentError::NotAvailableOffline) => err!(NetworkIssue),
+        CoreError::UiError(ReadDocumentError::DocumentCorrupted) => {
+            err!(DocumentCorrupted(file_name.to_string()))
+        }
         CoreError::UiError(ReadDocumentError::NoAccount)
         | CoreError::UiError(ReadDocumentError::FileDoesNotExist)
         | CoreError::Unexpected(_) => err_unexpected!("reading encrypted doc: {:#?}", err),
@@ -1,3 +1,5 @@
+use lockbook_core::model::error_catalog::CATALOG;
+
 pub type CliResult<T> = Result<T, Error>;
 
 pub enum Error {
@@ -53,7 +55,14 @@ macro_rules! make_errkind_enum {
             }
 
             pub fn print_table() -> CliResult<()> {
+                println!("exit codes:");
                 $( println!("{:>6}  {}", $codes, stringify!($variants)); )*
+
+                println!("\ncore error codes:");
+                for info in CATALOG {
+                    let category = format!("{:?}", info.category);
+                    println!("{:>6}  {:<30} {:<12} {}", info.code, info.variant, category, info.message);
+                }
                 Ok(())
             }
         }
@@ -72,6 +81,10 @@ make_errkind_enum!(
     7 => ExpectedStdin,
     8 => NoCliLocation,
     9 => NoRoot,
+    10 => ServerTampering,
+    11 => DownloadCorrupted,
+    12 => IntegrityProblems(usize),
+    13 => DataDirLocked,
 
     // Account (20s)
     20 => NoAccount,
@@ -106,6 +119,17 @@ make_errkind_enum!(
     49 => NoRootOps(&'static str),
     50 => InvalidDrawing(String),
     51 => FolderTreatedAsDoc(String),
+    52 => DocumentCorrupted(String),
+    53 => DocumentNotText(String),
+    54 => OldVersionNotAvailable(String),
+    55 => FileNameInvalid(String),
+
+    // Profiles (60s)
+    60 => ProfileNotFound(String),
+    61 => ProfileAlreadyExists(String),
+    62 => ProfileNameInvalid(String),
+    63 => CannotRemoveDefaultProfile,
+    64 => CannotRemoveActiveProfile(String),
 );
 
 impl ErrorKind {
@@ -118,6 +142,10 @@ impl ErrorKind {
             Self::ExpectedStdin => "expected stdin".to_string(),
             Self::NoCliLocation => "Could not read env var LOCKBOOK_CLI_LOCATION HOME or HOMEPATH, don't know where to place your `.lockbook` folder".to_string(),
             Self::NoRoot => "No root folder, have you synced yet?".to_string(),
+            Self::ServerTampering => "The server sent changes that weren't made by this account, nothing was applied.".to_string(),
+            Self::DownloadCorrupted => "A document was damaged on its way from the server, try syncing again.".to_string(),
+            Self::IntegrityProblems(count) => format!("{} problem(s) left, `lockbook fsck --repair` fixes what it can.", count),
+            Self::DataDirLocked => "Another lockbook is changing your files, try again in a moment.".to_string(),
 
             Self::NoAccount => "No account! Run 'new-account' or 'import-private-key' to get started!".to_string(),
             Self::AccountAlreadyExists => "Account already exists. Run `lockbook erase-everything` to erase your local state.".to_string(),
@@ -149,6 +177,16 @@ impl ErrorKind {
             Self::NoRootOps(op) => format!("cannot {} your root directory!", op),
             Self::InvalidDrawing(name) => format!("'{}' is an invalid drawing", name),
             Self::FolderTreatedAsDoc(path) => format!("a file in path '{}' is a folder being treated as a document", path),
+            Self::DocumentCorrupted(path) => format!("the content of '{}' is damaged", path),
+            Self::DocumentNotText(path) => format!("'{}' is not a text document", path),
+            Self::OldVersionNotAvailable(path) => format!("the synced version of '{}' isn't kept on this device", path),
+            Self::FileNameInvalid(name) => format!("File name '{}' has a control character or is reserved by the OS.", name),
+
+            Self::ProfileNotFound(name) => format!("no profile named '{}', see `lockbook profile list`", name),
+            Self::ProfileAlreadyExists(name) => format!("the profile '{}' already exists", name),
+            Self::ProfileNameInvalid(name) => format!("profile name '{}' invalid (a-z || A-Z || 0-9 || - || _)", name),
+            Self::CannotRemoveDefaultProfile => "The default profile cannot be removed.".to_string(),
+            Self::CannotRemoveActiveProfile(name) => format!("'{}' is the active profile, switch to another one before removing it", name),
         }
     }
 }
@@ -0,0 +1,58 @@
+use crate::error::CliResult;
+use crate::utils::{get_account_or_exit, get_config};
+use crate::{err, err_unexpected};
+use lockbook_core::service::integrity_service::TestRepoError::*;
+use lockbook_core::service::integrity_service::{RepairPolicy, TestRepoError};
+use lockbook_core::{Error as CoreError, RepairRepoError};
+
+pub fn fsck(repair: bool, discard_unsynced: bool) -> CliResult<()> {
+    get_account_or_exit();
+    let config = get_config();
+
+    let remaining = if repair {
+        let policy = if discard_unsynced {
+            RepairPolicy::DiscardUnrecoverable
+        } else {
+            RepairPolicy::Safe
+        };
+        let report = lockbook_core::repair_repo(&config, policy).map_err(|err| match err {
+            CoreError::UiError(RepairRepoError::NoAccount) => err!(NoAccount),
+            CoreError::UiError(RepairRepoError::DataDirLocked) => err!(DataDirLocked),
+            CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+        })?;
+
+        for problem in &report.repaired {
+            println!("fixed: {}", describe(problem));
+        }
+        report.unrepaired
+    } else {
+        lockbook_core::test_repo_integrity(&config).map_err(|err| err_unexpected!("{:?}", err))?
+    };
+
+    for problem in &remaining {
+        println!("{}", describe(problem));
+    }
+
+    if remaining.is_empty() {
+        println!("No problems found");
+        Ok(())
+    } else {
+        Err(err!(IntegrityProblems(remaining.len())))
+    }
+}
+
+fn describe(problem: &TestRepoError) -> String {
+    match problem {
+        NoRootFolder => "there is no root folder".to_string(),
+        DocumentTreatedAsFolder(id) => format!("document {} has children", id),
+        FileOrphaned(id) => format!("the parent of {} is missing", id),
+        CycleDetected(id) => format!("{} is inside itself", id),
+        FileNameEmpty(id) => format!("the name of {} is empty", id),
+        FileNameContainsSlash(id) => format!("the name of {} has a slash", id),
+        NameConflictDetected(id) => format!("the name of {} is taken by a sibling", id),
+        DocumentUnreadable(id) => format!("the content of {} is damaged", id),
+        DocumentMissing(id) => format!("the content of {} is missing", id),
+        DanglingLocalChange(id) => format!("a change is tracked for {}, which doesn't exist", id),
+        Core(err) => format!("{:?}", err),
+    }
+}
@@ -29,6 +29,7 @@ pub fn import_private_key() -> CliResult<()> {
                 ImportError::UsernamePKMismatch => err!(UsernamePkMismatch),
                 ImportError::CouldNotReachServer => err!(NetworkIssue),
                 ImportError::ClientUpdateRequired => err!(UpdateRequired),
+                ImportError::DataDirLocked => err!(DataDirLocked),
             },
             CoreError::Unexpected(msg) => err_unexpected!("{}", msg).exit(),
         })?;
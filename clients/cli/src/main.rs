@@ -1,3 +1,4 @@
+use std::env;
 use std::path::PathBuf;
 
 use structopt::StructOpt;
@@ -7,19 +8,26 @@ use lockbook_core::service::path_service::Filter::{DocumentsOnly, FoldersOnly, L
 
 mod backup;
 mod calculate_usage;
+mod case_insensitive_names;
 mod copy;
+mod daemon;
+mod diff;
 mod edit;
 mod error;
 mod export_drawing;
 mod export_private_key;
+mod fsck;
 mod import_private_key;
 mod list;
 mod move_file;
 mod new;
 mod new_account;
+mod padding;
 mod print;
+mod profile;
 mod remove;
 mod rename;
+mod revert;
 mod status;
 mod sync;
 mod utils;
@@ -27,10 +35,28 @@ mod whoami;
 
 #[derive(Debug, PartialEq, StructOpt)]
 #[structopt(about = "A secure and intuitive notebook.")]
+struct Cli {
+    /// Use this profile instead of the active one, also read from LOCKBOOK_PROFILE
+    #[structopt(long, global = true)]
+    profile: Option<String>,
+
+    #[structopt(subcommand)]
+    command: Lockbook,
+}
+
+#[derive(Debug, PartialEq, StructOpt)]
 enum Lockbook {
     /// Backup your Lockbook files and structure to the current directory
     Backup,
 
+    /// Print whether new file names must differ from their siblings' even when case is ignored, or
+    /// change it. Turn it on if you back up to a case-insensitive file system.
+    CaseInsensitiveNames {
+        /// Leave out to print the current setting
+        #[structopt(possible_values = &["on", "off"])]
+        setting: Option<String>,
+    },
+
     /// Copy a file from your file system into your Lockbook
     Copy {
         /// Overwrite the file if it exists already
@@ -45,6 +71,15 @@ enum Lockbook {
         destination: String,
     },
 
+    /// Sync in the foreground, pushing local changes shortly after they are made
+    Daemon,
+
+    /// Show unsynced edits to text documents as a unified diff
+    Diff {
+        /// Absolute path of a document, all edited documents if omitted
+        path: Option<String>,
+    },
+
     /// Open a document for editing
     Edit {
         /// The lockbook location of the file you want to edit. Will use the LOCKBOOK_EDITOR env var
@@ -53,7 +88,7 @@ enum Lockbook {
         path: String,
     },
 
-    /// Print out what each error code means
+    /// Print out what each exit code and core error code means
     Errors,
 
     /// Export a drawing as an image
@@ -68,6 +103,17 @@ enum Lockbook {
     /// Export your private key, if piped, account string, otherwise qr code
     ExportPrivateKey,
 
+    /// Check your local files for problems, and fix what can be fixed
+    Fsck {
+        /// Fix what can be fixed. Misplaced files are moved to lost+found
+        #[structopt(long)]
+        repair: bool,
+
+        /// When repairing, also give up unsynced edits to documents whose content is damaged
+        #[structopt(long)]
+        discard_unsynced: bool,
+    },
+
     /// How much space does your Lockbook occupy on the server
     GetUsage {
         /// Show the amount in bytes, don't show a human readable interpretation
@@ -109,6 +155,17 @@ enum Lockbook {
     /// Create a new Lockbook account
     NewAccount,
 
+    /// Print how documents are padded to hide their size from the server, or change it. Only
+    /// documents written afterwards are padded the new way.
+    Padding {
+        /// Leave out to print the current scheme
+        #[structopt(possible_values = &["Off", "PowerOfTwo", "Padme"])]
+        scheme: Option<String>,
+    },
+
+    /// List, create, switch between or remove profiles, each with its own account
+    Profile(ProfileCommand),
+
     /// Print the contents of a file to stdout
     Print {
         /// Absolute path of a document (lockbook list-docs)
@@ -134,8 +191,30 @@ enum Lockbook {
         name: String,
     },
 
+    /// Undo unsynced changes to a file. New files are removed
+    Revert {
+        /// Absolute path of a file (lockbook list-all)
+        path: String,
+
+        /// Only restore the old name
+        #[structopt(long)]
+        name: bool,
+
+        /// Only restore the old location
+        #[structopt(long)]
+        parent: bool,
+
+        /// Only restore the old content
+        #[structopt(long)]
+        content: bool,
+    },
+
     /// What operations a sync would perform
-    Status,
+    Status {
+        /// Also list each unsynced change
+        #[structopt(short, long)]
+        verbose: bool,
+    },
 
     /// Get updates, push changes
     Sync,
@@ -145,22 +224,56 @@ enum Lockbook {
     WhoAmI,
 }
 
+#[derive(Debug, PartialEq, StructOpt)]
+enum ProfileCommand {
+    /// List profiles, the active one is marked with a *
+    List,
+
+    /// Create a profile
+    New { name: String },
+
+    /// Make a profile the active one
+    Switch { name: String },
+
+    /// Delete a profile and everything stored for it
+    Remove {
+        name: String,
+
+        /// Skip the confirmation check
+        #[structopt(short, long)]
+        force: bool,
+    },
+}
+
 fn main() {
     init_logger_or_print();
-    let args = Lockbook::from_args();
+    let args = Cli::from_args();
+
+    if let Some(profile) = &args.profile {
+        env::set_var("LOCKBOOK_PROFILE", profile);
+    }
 
     if let Err(err) = check_and_perform_migrations() {
         err.exit()
     }
 
-    if let Err(err) = match args {
+    if let Err(err) = match args.command {
+        Lockbook::CaseInsensitiveNames { setting } => {
+            case_insensitive_names::case_insensitive_names(setting)
+        }
         Lockbook::Copy {
             file,
             destination,
             edit,
         } => copy::copy(file, &destination, edit),
+        Lockbook::Daemon => daemon::daemon(),
+        Lockbook::Diff { path } => diff::diff(path.as_deref().map(str::trim)),
         Lockbook::Edit { path } => edit::edit(&path.trim()),
         Lockbook::ExportPrivateKey => export_private_key::export_private_key(),
+        Lockbook::Fsck {
+            repair,
+            discard_unsynced,
+        } => fsck::fsck(repair, discard_unsynced),
         Lockbook::ImportPrivateKey => import_private_key::import_private_key(),
         Lockbook::NewAccount => new_account::new_account(),
         Lockbook::List => list::list(Some(LeafNodesOnly)),
@@ -169,10 +282,23 @@ fn main() {
         Lockbook::ListFolders => list::list(Some(FoldersOnly)),
         Lockbook::Move { target, new_parent } => move_file::move_file(&target, &new_parent),
         Lockbook::New { path } => new::new(&path.trim()),
+        Lockbook::Padding { scheme } => padding::padding(scheme),
         Lockbook::Print { path } => print::print(&path.trim()),
+        Lockbook::Profile(command) => match command {
+            ProfileCommand::List => profile::list(),
+            ProfileCommand::New { name } => profile::new(&name),
+            ProfileCommand::Switch { name } => profile::switch(&name),
+            ProfileCommand::Remove { name, force } => profile::remove(&name, force),
+        },
         Lockbook::Remove { path, force } => remove::remove(&path.trim(), force),
         Lockbook::Rename { path, name } => rename::rename(&path, &name),
-        Lockbook::Status => status::status(),
+        Lockbook::Revert {
+            path,
+            name,
+            parent,
+            content,
+        } => revert::revert(&path.trim(), name, parent, content),
+        Lockbook::Status { verbose } => status::status(verbose),
         Lockbook::Sync => sync::sync(),
         Lockbook::WhoAmI => whoami::whoami(),
         Lockbook::Backup => backup::backup(),
@@ -38,6 +38,7 @@ pub fn move_file(path1: &str, path2: &str) -> CliResult<()> {
                     file_metadata.name,
                     target_file_metadata.name
                 ),
+                MoveFileError::DataDirLocked => err!(DataDirLocked),
             },
             CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
         }
@@ -31,6 +31,10 @@ pub fn new(file_name: &str) -> CliResult<()> {
             CreateFileAtPathError::DocumentTreatedAsFolder => {
                 err!(DocTreatedAsFolder(file_name.to_string()))
             }
+            CreateFileAtPathError::FileNameInvalid => {
+                err!(FileNameInvalid(file_name.to_string()))
+            }
+            CreateFileAtPathError::DataDirLocked => err!(DataDirLocked),
         },
         CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
     })?;
@@ -0,0 +1,25 @@
+use crate::err_unexpected;
+use crate::error::CliResult;
+use crate::utils::{get_account_or_exit, get_config};
+use lockbook_core::repo::padding_repo::PaddingScheme;
+use std::str::FromStr;
+
+pub fn padding(scheme: Option<String>) -> CliResult<()> {
+    get_account_or_exit();
+
+    match scheme {
+        Some(scheme) => {
+            let scheme = PaddingScheme::from_str(&scheme)
+                .map_err(|_| err_unexpected!("unknown padding scheme {}", scheme))?;
+            lockbook_core::set_padding_scheme(&get_config(), scheme)
+                .map_err(|err| err_unexpected!("{:?}", err))?;
+        }
+        None => {
+            let scheme = lockbook_core::get_padding_scheme(&get_config())
+                .map_err(|err| err_unexpected!("{:?}", err))?;
+            println!("{:?}", scheme);
+        }
+    }
+
+    Ok(())
+}
@@ -1,7 +1,9 @@
 use crate::error::CliResult;
 use crate::utils::{get_account_or_exit, get_config};
 use crate::{err, err_unexpected};
-use lockbook_core::{get_file_by_path, read_document, Error as CoreError, GetFileByPathError};
+use lockbook_core::{
+    get_file_by_path, read_document_to, Error as CoreError, GetFileByPathError, ReadDocumentError,
+};
 use std::io;
 use std::io::Write;
 
@@ -16,11 +18,16 @@ pub fn print(file_name: &str) -> CliResult<()> {
         CoreError::Unexpected(msg) => err_unexpected!("{}", msg).exit(),
     })?;
 
-    let content =
-        read_document(&cfg, file_metadata.id).map_err(|err| err_unexpected!("{:?}", err))?;
-    print!("{}", String::from_utf8_lossy(&content));
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    read_document_to(&cfg, file_metadata.id, &mut stdout).map_err(|err| match err {
+        CoreError::UiError(ReadDocumentError::DocumentCorrupted) => {
+            err!(DocumentCorrupted(file_name.to_string()))
+        }
+        err => err_unexpected!("{:?}", err),
+    })?;
 
-    io::stdout()
+    stdout
         .flush()
         .map_err(|err| err_unexpected!("flushing stdin: {:#?}", err))
 }
@@ -0,0 +1,75 @@
+use std::io;
+use std::io::Write;
+
+use lockbook_core::{
+    create_profile, get_active_profile, list_profiles, remove_profile, switch_profile,
+    CreateProfileError, Error as CoreError, RemoveProfileError, SwitchProfileError,
+};
+
+use crate::error::CliResult;
+use crate::utils::{exit_success, get_base_config};
+use crate::{err, err_unexpected};
+
+pub fn list() -> CliResult<()> {
+    let base = get_base_config();
+    let active = get_active_profile(&base).map_err(|err| err_unexpected!("{:?}", err))?;
+    let profiles = list_profiles(&base).map_err(|err| err_unexpected!("{:?}", err))?;
+
+    for profile in profiles {
+        let marker = if profile == active { "*" } else { " " };
+        println!("{} {}", marker, profile);
+    }
+
+    Ok(())
+}
+
+pub fn new(name: &str) -> CliResult<()> {
+    create_profile(&get_base_config(), name).map_err(|err| match err {
+        CoreError::UiError(err) => match err {
+            CreateProfileError::ProfileNameInvalid => err!(ProfileNameInvalid(name.to_string())),
+            CreateProfileError::ProfileExists => err!(ProfileAlreadyExists(name.to_string())),
+        },
+        CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+    })
+}
+
+pub fn switch(name: &str) -> CliResult<()> {
+    switch_profile(&get_base_config(), name).map_err(|err| match err {
+        CoreError::UiError(SwitchProfileError::ProfileDoesNotExist) => {
+            err!(ProfileNotFound(name.to_string()))
+        }
+        CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+    })
+}
+
+pub fn remove(name: &str, force: bool) -> CliResult<()> {
+    if !force {
+        print!(
+            "Are you sure you want to delete everything stored for '{}', including unsynced changes? [y/n]: ",
+            name
+        );
+        io::stdout().flush().unwrap();
+
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .expect("Failed to read from stdin");
+        answer.retain(|c| c != '\n' && c != '\r');
+
+        if answer != "y" && answer != "Y" {
+            exit_success("Aborted.")
+        }
+    }
+
+    remove_profile(&get_base_config(), name).map_err(|err| match err {
+        CoreError::UiError(err) => match err {
+            RemoveProfileError::ProfileDoesNotExist => err!(ProfileNotFound(name.to_string())),
+            RemoveProfileError::CannotRemoveDefaultProfile => err!(CannotRemoveDefaultProfile),
+            RemoveProfileError::CannotRemoveActiveProfile => {
+                err!(CannotRemoveActiveProfile(name.to_string()))
+            }
+            RemoveProfileError::DataDirLocked => err!(DataDirLocked),
+        },
+        CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+    })
+}
@@ -56,6 +56,7 @@ pub fn remove(path: &str, force: bool) -> CliResult<()> {
     delete_file(&config, meta.id).map_err(|err| match err {
         UiError(FileDeleteError::FileDoesNotExist) => err!(FileNotFound(path.to_string())),
         UiError(FileDeleteError::CannotDeleteRoot) => err!(NoRootOps("delete")),
+        UiError(FileDeleteError::DataDirLocked) => err!(DataDirLocked),
         UnexpectedError(msg) => err_unexpected!("{}", msg),
     })
 }
@@ -22,10 +22,12 @@ pub fn rename(path: &str, new_name: &str) -> CliResult<()> {
             RenameFileError::NewNameContainsSlash => {
                 err!(FileNameHasSlash(new_name.to_string()))
             }
+            RenameFileError::NewNameInvalid => err!(FileNameInvalid(new_name.to_string())),
             RenameFileError::FileNameNotAvailable => {
                 err!(FileNameNotAvailable(new_name.to_string()))
             }
             RenameFileError::FileDoesNotExist => err_unexpected!("FileDoesNotExist!"),
+            RenameFileError::DataDirLocked => err!(DataDirLocked),
         },
         CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
     })
@@ -0,0 +1,46 @@
+use crate::error::CliResult;
+use crate::utils::{get_account_or_exit, get_config};
+use crate::{err, err_unexpected};
+use lockbook_core::{
+    get_file_by_path, revert_local_change, Error as CoreError, GetFileByPathError,
+    RevertLocalChangeError,
+};
+use lockbook_models::local_changes::ChangeField;
+
+pub fn revert(path: &str, name: bool, parent: bool, content: bool) -> CliResult<()> {
+    get_account_or_exit();
+
+    let file_metadata = get_file_by_path(&get_config(), path).map_err(|err| match err {
+        CoreError::UiError(GetFileByPathError::NoFileAtThatPath) => {
+            err!(FileNotFound(path.to_string()))
+        }
+        CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+    })?;
+
+    let fields = if name || parent || content {
+        ChangeField::ALL
+            .iter()
+            .zip(&[name, parent, content])
+            .filter(|(_, &chosen)| chosen)
+            .map(|(&field, _)| field)
+            .collect()
+    } else {
+        ChangeField::ALL.to_vec()
+    };
+
+    revert_local_change(&get_config(), file_metadata.id, &fields).map_err(|err| match err {
+        CoreError::UiError(err) => match err {
+            RevertLocalChangeError::NoAccount => err!(NoAccount),
+            RevertLocalChangeError::CouldNotReachServer => err!(NetworkIssue),
+            RevertLocalChangeError::FileNameNotAvailable => {
+                err!(FileNameNotAvailable(path.to_string()))
+            }
+            RevertLocalChangeError::ParentDoesNotExist => {
+                err!(FileNotFound(format!("the old parent of {}", path)))
+            }
+            RevertLocalChangeError::FileDoesNotExist => err_unexpected!("FileDoesNotExist!"),
+            RevertLocalChangeError::DataDirLocked => err!(DataDirLocked),
+        },
+        CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+    })
+}
@@ -1,22 +1,39 @@
-use lockbook_core::{calculate_work, CalculateWorkError, Error as CoreError};
+use lockbook_core::model::client_conversion::ClientLocalChange;
+use lockbook_core::{
+    calculate_work, get_local_change_details, CalculateWorkError, Error as CoreError,
+    GetLocalChangeDetailsError,
+};
 
 use crate::error::CliResult;
 use crate::utils::{get_account_or_exit, get_config, print_last_successful_sync};
 use crate::{err, err_unexpected};
 
-pub fn status() -> CliResult<()> {
+pub fn status(verbose: bool) -> CliResult<()> {
     get_account_or_exit();
 
     let work = calculate_work(&get_config()).map_err(|err| match err {
         CoreError::UiError(CalculateWorkError::NoAccount) => err!(NoAccount),
         CoreError::UiError(CalculateWorkError::CouldNotReachServer) => err!(NetworkIssue),
         CoreError::UiError(CalculateWorkError::ClientUpdateRequired) => err!(UpdateRequired),
+        CoreError::UiError(CalculateWorkError::ServerTampering) => err!(ServerTampering),
         CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
     })?;
 
-    work.local_files
-        .into_iter()
-        .for_each(|metadata| println!("{} needs to be pushed", metadata.name));
+    if verbose {
+        get_local_change_details(&get_config())
+            .map_err(|err| match err {
+                CoreError::UiError(GetLocalChangeDetailsError::NoAccount) => err!(NoAccount),
+                CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+            })?
+            .iter()
+            .for_each(|change| {
+                println!("{} needs to be pushed: {}", change.path, describe(change))
+            });
+    } else {
+        work.local_files
+            .into_iter()
+            .for_each(|metadata| println!("{} needs to be pushed", metadata.name));
+    }
     work.server_files
         .into_iter()
         .for_each(|metadata| println!("{} needs to be pulled", metadata.name));
@@ -27,3 +44,23 @@ pub fn status() -> CliResult<()> {
 
     print_last_successful_sync()
 }
+
+fn describe(change: &ClientLocalChange) -> String {
+    let mut kinds = vec![];
+    if change.new {
+        kinds.push("new".to_string());
+    }
+    if let Some(old_name) = &change.renamed_from {
+        kinds.push(format!("renamed from {}", old_name));
+    }
+    if let Some(old_path) = &change.moved_from {
+        kinds.push(format!("moved from {}", old_path));
+    }
+    if change.edited {
+        kinds.push("edited".to_string());
+    }
+    if change.deleted {
+        kinds.push("deleted".to_string());
+    }
+    kinds.join(", ")
+}
@@ -1,19 +1,52 @@
-use lockbook_core::service::sync_service::SyncProgress;
+use lockbook_core::service::sync_service::{SyncPhase, SyncProgress};
 use lockbook_core::{sync_all, Error, SyncAllError};
 
 use crate::error::CliResult;
 use crate::utils::get_config;
 use crate::{err, err_unexpected};
 use lockbook_core::model::client_conversion::ClientWorkUnit;
+use std::cell::Cell;
+use std::rc::Rc;
 
 pub fn sync() -> CliResult<()> {
     let config = get_config();
-    let closure = |sync_progress: SyncProgress| {
-        match sync_progress.current_work_unit {
-            ClientWorkUnit::ServerUnknownName(_) => println!("Pulling: New File"),
-            ClientWorkUnit::Server(metadata) => println!("Pulling: {}", metadata.name),
-            ClientWorkUnit::Local(metadata) => println!("Pushing: {}", metadata.name),
+    let bytes_transferred = Rc::new(Cell::new(0));
+    let last_step = Cell::new(None);
+
+    let closure_bytes_transferred = Rc::clone(&bytes_transferred);
+    let closure = move |sync_progress: SyncProgress| {
+        closure_bytes_transferred.set(sync_progress.bytes_transferred);
+
+        // Progress is reported as bytes arrive, only print when sync moves on to something new
+        let step = (sync_progress.phase, sync_progress.progress);
+        if last_step.replace(Some(step)) == Some(step) {
+            return;
+        }
+
+        let phase = match sync_progress.phase {
+            SyncPhase::Calculating => {
+                println!("Calculating work");
+                return;
+            }
+            SyncPhase::Pushing => "Pushing",
+            SyncPhase::Pulling => "Pulling",
+            SyncPhase::Merging => "Merging",
         };
+
+        let name = match sync_progress.current_work_unit {
+            Some(ClientWorkUnit::ServerUnknownName(_)) | None => "New File".to_string(),
+            Some(ClientWorkUnit::Server(metadata)) | Some(ClientWorkUnit::Local(metadata)) => {
+                metadata.name
+            }
+        };
+
+        println!(
+            "[{}/{}] {}: {}",
+            sync_progress.progress + 1,
+            sync_progress.total,
+            phase,
+            name
+        );
     };
 
     sync_all(&config, Some(Box::new(closure))).map_err(|err| match err {
@@ -21,11 +54,18 @@ pub fn sync() -> CliResult<()> {
             SyncAllError::NoAccount => err!(NoAccount),
             SyncAllError::ClientUpdateRequired => err!(UpdateRequired),
             SyncAllError::CouldNotReachServer => err!(NetworkIssue),
+            SyncAllError::Cancelled => err_unexpected!("sync was cancelled"),
+            SyncAllError::ServerTampering => err!(ServerTampering),
+            SyncAllError::DocumentCorrupted => err!(DownloadCorrupted),
+            SyncAllError::DataDirLocked => err!(DataDirLocked),
         },
         Error::Unexpected(msg) => err_unexpected!("{}", msg),
     })?;
 
-    println!("Sync complete.");
+    println!(
+        "Sync complete, {} bytes transferred.",
+        bytes_transferred.get()
+    );
 
     Ok(())
 }
@@ -1,7 +1,7 @@
 use lockbook_core::model::state::Config;
 use lockbook_core::{
-    get_account, get_db_state, get_last_synced_human_string, init_logger, migrate_db,
-    GetAccountError, GetStateError, MigrationError,
+    get_account, get_db_state, get_last_synced_human_string, get_profile_config, init_logger,
+    migrate_db, GetAccountError, GetProfileConfigError, GetStateError, MigrationError,
 };
 use lockbook_core::{write_document, Error as CoreError, WriteToDocumentError};
 use std::{env, fs};
@@ -18,8 +18,12 @@ use lockbook_core::service::drawing_service::SupportedImageFormats::{
 };
 use lockbook_models::account::Account;
 use std::path::Path;
+use std::thread;
+use std::time::Duration;
 use uuid::Uuid;
 
+const SAVE_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
 #[macro_export]
 macro_rules! path_string {
     ($pb:expr) => {
@@ -28,7 +32,7 @@ macro_rules! path_string {
 }
 
 pub fn init_logger_or_print() {
-    if let Err(err) = init_logger(&get_config().path()) {
+    if let Err(err) = init_logger(&get_base_config().path()) {
         eprintln!("Logger failed to initialize! {:#?}", err)
     }
 }
@@ -61,6 +65,7 @@ pub fn check_and_perform_migrations() -> CliResult<()> {
                 CoreError::UiError(MigrationError::StateRequiresCleaning) => {
                     err!(UninstallRequired)
                 }
+                CoreError::UiError(MigrationError::DataDirLocked) => err!(DataDirLocked),
                 CoreError::Unexpected(msg) => err_extra!(
                     Unexpected(msg),
                     "It's possible you need to clear your local state and resync."
@@ -77,7 +82,8 @@ pub fn check_and_perform_migrations() -> CliResult<()> {
     Ok(())
 }
 
-pub fn get_config() -> Config {
+/// The data directory of this installation, which holds every profile.
+pub fn get_base_config() -> Config {
     let path = match (
         env::var("LOCKBOOK_CLI_LOCATION"),
         env::var("HOME"),
@@ -91,6 +97,23 @@ pub fn get_config() -> Config {
 
     Config {
         writeable_path: path,
+        storage: None,
+    }
+}
+
+/// The data directory of the profile named by `--profile` or `LOCKBOOK_PROFILE`, or of the active
+/// profile if neither is set.
+pub fn get_config() -> Config {
+    let profile = env::var("LOCKBOOK_PROFILE").ok();
+    match get_profile_config(&get_base_config(), profile.as_deref()) {
+        Ok(config) => config,
+        Err(err) => match err {
+            CoreError::UiError(GetProfileConfigError::ProfileDoesNotExist) => {
+                err!(ProfileNotFound(profile.unwrap_or_default()))
+            }
+            CoreError::Unexpected(msg) => err_unexpected!("{}", msg),
+        }
+        .exit(),
     }
 }
 
@@ -282,7 +305,23 @@ pub fn save_temp_file_contents(
         }
     };
 
-    match write_document(&get_config(), file_metadata.id, &secret) {
+    let config = get_config();
+    let mut waiting = false;
+    let result = loop {
+        match write_document(&config, file_metadata.id, &secret) {
+            // Syncs hold the data directory for as long as they run, the save waits them out
+            Err(CoreError::UiError(WriteToDocumentError::DataDirLocked)) => {
+                if !silent && !waiting {
+                    println!("Waiting for another lockbook to finish changing your files...");
+                }
+                waiting = true;
+                thread::sleep(SAVE_RETRY_INTERVAL);
+            }
+            result => break result,
+        }
+    };
+
+    match result {
         Ok(_) => {
             if !silent {
                 exit_success("Document encrypted and saved. Cleaning up temporary file.")
@@ -302,6 +341,9 @@ pub fn save_temp_file_contents(
                     CoreError::UiError(WriteToDocumentError::FolderTreatedAsDocument) => {
                         err_unexpected!("CannotWriteToFolder").exit()
                     }
+                    CoreError::UiError(WriteToDocumentError::DataDirLocked) => {
+                        err!(DataDirLocked).exit()
+                    }
                 }
             }
         }
@@ -26,7 +26,8 @@ use crate::messages::{Messenger, Msg, MsgFn};
 use crate::settings::Settings;
 use crate::util::{gui as gui_util, gui::RIGHT_CLICK};
 use gspell::TextViewExt as GtkTextViewExt;
-use lockbook_core::model::client_conversion::{ClientFileMetadata, ClientWorkUnit};
+use lockbook_core::model::client_conversion::ClientFileMetadata;
+use lockbook_core::service::sync_service::SyncPhase;
 use std::sync::Arc;
 
 pub struct AccountScreen {
@@ -320,11 +321,17 @@ impl StatusPanel {
     }
 
     pub fn set_sync_progress(&self, s: &LbSyncMsg) {
-        let prefix = match s.work {
-            ClientWorkUnit::Server(_) | ClientWorkUnit::ServerUnknownName(_) => "Pulling",
-            ClientWorkUnit::Local(_) => "Pushing",
+        let prefix = match s.phase {
+            SyncPhase::Calculating => "Calculating work",
+            SyncPhase::Pushing => "Pushing",
+            SyncPhase::Pulling => "Pulling",
+            SyncPhase::Merging => "Merging",
         };
-        self.set_status(&format!("{}: {}", prefix, s.name), None);
+        let status = match s.work {
+            None => prefix.to_string(),
+            Some(_) => format!("{}: {}", prefix, s.name),
+        };
+        self.set_status(&status, Some(&s.bytes_progress()));
         self.sync_progress
             .set_fraction(s.index as f64 / s.total as f64);
     }
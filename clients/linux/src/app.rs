@@ -11,8 +11,9 @@ use gtk::Orientation::{Horizontal, Vertical};
 use gtk::{
     AboutDialog as GtkAboutDialog, AccelGroup as GtkAccelGroup, Align as GtkAlign,
     Application as GtkApp, ApplicationWindow as GtkAppWindow, Box as GtkBox, Button,
-    CellRendererText as GtkCellRendererText, CheckButton as GtkCheckBox, Dialog as GtkDialog,
-    Entry as GtkEntry, EntryCompletion as GtkEntryCompletion, Image as GtkImage, Label as GtkLabel,
+    CellRendererText as GtkCellRendererText, CheckButton as GtkCheckBox,
+    ComboBoxText as GtkComboBoxText, Dialog as GtkDialog, Entry as GtkEntry,
+    EntryCompletion as GtkEntryCompletion, Image as GtkImage, Label as GtkLabel,
     ListStore as GtkListStore, Notebook as GtkNotebook, ProgressBar as GtkProgressBar,
     ResponseType as GtkResponseType, SelectionMode as GtkSelectionMode,
     SortColumn as GtkSortColumn, SortType as GtkSortType, Spinner as GtkSpinner, Stack as GtkStack,
@@ -40,6 +41,8 @@ use crate::settings::Settings;
 use crate::util;
 use crate::{closure, progerr, tree_iter_value, uerr, uerr_dialog};
 use lockbook_core::model::client_conversion::ClientFileMetadata;
+use lockbook_core::service::profile_service::DEFAULT_PROFILE;
+use lockbook_core::service::sync_scheduler_service::SyncSchedulerEvent;
 use std::thread;
 use std::time::Duration;
 
@@ -97,6 +100,7 @@ impl LbApp {
                 Msg::PerformSync => lb.perform_sync(),
                 Msg::RefreshSyncStatus => lb.refresh_sync_status(),
                 Msg::RefreshUsageStatus => lb.refresh_usage_status(),
+                Msg::SwitchProfile(name) => lb.switch_profile(name),
                 Msg::Quit => lb.quit(),
 
                 Msg::AccountScreenShown => lb.account_screen_shown(),
@@ -122,6 +126,8 @@ impl LbApp {
                 Msg::ShowDialogPreferences => lb.show_dialog_preferences(),
                 Msg::ShowDialogUsage => lb.show_dialog_usage(),
                 Msg::ShowDialogAbout => lb.show_dialog_about(),
+                Msg::ShowDialogNewProfile => lb.show_dialog_new_profile(),
+                Msg::ShowDialogRemoveProfile => lb.show_dialog_remove_profile(),
 
                 Msg::ToggleAutoSave(auto_save) => lb.toggle_auto_save(auto_save),
                 Msg::ToggleAutoSync(auto_sync) => lb.toggle_auto_sync(auto_sync),
@@ -280,10 +286,6 @@ impl LbApp {
     }
 
     fn perform_sync(&self) -> LbResult<()> {
-        if let Ok(mut background_work) = self.state.borrow().background_work.try_lock() {
-            background_work.auto_sync_state.last_sync = BackgroundWork::current_time();
-        }
-
         let sync_ui = self.gui.account.status().clone();
         sync_ui.set_syncing(true);
 
@@ -344,19 +346,72 @@ impl LbApp {
         Ok(())
     }
 
+    fn switch_profile(&self, name: String) -> LbResult<()> {
+        if name == self.core.active_profile()? {
+            return self.gui.refresh_profiles(&self.core);
+        }
+
+        // Save now rather than in the background, which could happen after the switch
+        let dirty_file = {
+            let state = self.state.borrow();
+            state.opened_file.clone().filter(|_| state.open_file_dirty)
+        };
+        if let Some(f) = dirty_file {
+            self.core.save(f.id, self.gui.account.text_content())?;
+            self.state.borrow_mut().open_file_dirty = false;
+        }
+        self.close_file()?;
+
+        // Started again for the new profile once its account screen is shown
+        self.core.stop_auto_sync()?;
+        self.core.switch_profile(&name)?;
+        self.gui.show(&self.core)
+    }
+
     fn quit(&self) -> LbResult<()> {
         self.gui.win.close();
         Ok(())
     }
 
     fn account_screen_shown(&self) -> LbResult<()> {
-        let background_work = self.state.borrow().background_work.clone();
+        // The account screen is shown again after switching profiles
+        let mut state = self.state.borrow_mut();
+        state.account_screen_shown = true;
+        if !state.background_work_started {
+            state.background_work_started = true;
+            let background_work = state.background_work.clone();
+            thread::spawn(move || BackgroundWork::init_background_work(background_work));
+        }
 
-        thread::spawn(move || BackgroundWork::init_background_work(background_work));
+        if state.auto_sync {
+            self.core.start_auto_sync(self.auto_sync_events())?;
+        }
 
         Ok(())
     }
 
+    // Scheduled syncs report back like the sync button does
+    fn auto_sync_events(&self) -> Box<dyn Fn(SyncSchedulerEvent) + Send> {
+        let m = self.messenger.clone();
+        Box::new(move |event| match event {
+            SyncSchedulerEvent::SyncStarted => {}
+            SyncSchedulerEvent::SyncCompleted => {
+                m.send(Msg::RefreshSyncStatus);
+                m.send(Msg::RefreshTree);
+            }
+            SyncSchedulerEvent::Offline { .. } => m.send_err_status_panel("Offline."),
+            SyncSchedulerEvent::DataDirLocked { .. } => {
+                m.send_err_status_panel("Another app is changing your files.")
+            }
+            SyncSchedulerEvent::ClientUpdateRequired => {
+                m.send_err_dialog("syncing", uerr_dialog!("Client upgrade required."))
+            }
+            SyncSchedulerEvent::SyncFailed(msg) => {
+                m.send_err_dialog("syncing", progerr!("{}", msg))
+            }
+        })
+    }
+
     fn new_file(&self, file_type: FileType) -> LbResult<()> {
         let file_type_string = match file_type {
             FileType::Document => "Document",
@@ -914,18 +969,128 @@ impl LbApp {
         Ok(())
     }
 
-    fn toggle_auto_sync(&self, auto_sync: bool) -> LbResult<()> {
-        self.state
-            .borrow()
-            .background_work
-            .lock()
-            .unwrap()
-            .auto_sync_state
-            .is_active = auto_sync;
+    fn show_dialog_new_profile(&self) -> LbResult<()> {
+        let lbl = util::gui::text_left("Enter a name for the new profile:");
+        lbl.set_margin_top(12);
+
+        let entry = GtkEntry::new();
+        util::gui::set_marginy(&entry, 16);
+        entry.set_margin_start(8);
+        entry.set_activates_default(true);
+
+        let errlbl = util::gui::text_left("");
+        util::gui::set_widget_name(&errlbl, "err");
+        errlbl.set_margin_start(8);
+        errlbl.set_margin_bottom(8);
+
+        let d = self.gui.new_dialog("New Profile");
+        util::gui::set_marginx(&d.get_content_area(), 16);
+        d.set_default_size(300, -1);
+        d.get_content_area().add(&lbl);
+        d.get_content_area().add(&entry);
+        d.add_button("Create", GtkResponseType::Ok);
+        d.set_default_response(GtkResponseType::Ok);
 
+        d.connect_response(closure!(self as lb => move |d, resp| {
+            if resp != GtkResponseType::Ok {
+                d.close();
+                return;
+            }
+
+            let name = entry.get_text().to_string();
+            match lb.core.create_profile(&name) {
+                Ok(_) => {
+                    d.close();
+                    lb.messenger.send(Msg::SwitchProfile(name));
+                }
+                Err(err) => match err.kind() {
+                    UserErr => {
+                        util::gui::add(&d.get_content_area(), &errlbl);
+                        errlbl.set_text(&err.msg());
+                        errlbl.show();
+                    }
+                    ProgErr => {
+                        d.close();
+                        lb.messenger.send_err_dialog("creating profile", err);
+                    }
+                },
+            }
+        }));
+
+        d.show_all();
         Ok(())
     }
 
+    fn show_dialog_remove_profile(&self) -> LbResult<()> {
+        let active = self.core.active_profile()?;
+        let removable: Vec<String> = self
+            .core
+            .profiles()?
+            .into_iter()
+            .filter(|name| *name != active && name != DEFAULT_PROFILE)
+            .collect();
+
+        if removable.is_empty() {
+            return Err(uerr_dialog!(
+                "There are no profiles to remove, the default and the active profile are kept."
+            ));
+        }
+
+        let lbl = util::gui::text_left(
+            "Everything stored for the profile, including unsynced changes, will be deleted:",
+        );
+        lbl.set_margin_top(12);
+
+        let profiles = GtkComboBoxText::new();
+        util::gui::set_marginy(&profiles, 16);
+        for name in &removable {
+            profiles.append_text(name);
+        }
+        profiles.set_active(Some(0));
+
+        let d = self.gui.new_dialog("Remove Profile");
+        util::gui::set_marginx(&d.get_content_area(), 16);
+        d.set_default_size(300, -1);
+        d.get_content_area().add(&lbl);
+        d.get_content_area().add(&profiles);
+        d.add_button("Remove", GtkResponseType::Ok);
+
+        d.connect_response(closure!(self as lb => move |d, resp| {
+            d.close();
+            if resp != GtkResponseType::Ok {
+                return;
+            }
+
+            if let Some(name) = profiles.get_active_text() {
+                match lb.core.remove_profile(&name) {
+                    Ok(_) => {
+                        if let Err(err) = lb.gui.refresh_profiles(&lb.core) {
+                            lb.messenger.send_err_dialog("listing profiles", err);
+                        }
+                    }
+                    Err(err) => lb.messenger.send_err_dialog("removing profile", err),
+                }
+            }
+        }));
+
+        d.show_all();
+        Ok(())
+    }
+
+    fn toggle_auto_sync(&self, auto_sync: bool) -> LbResult<()> {
+        let mut state = self.state.borrow_mut();
+        state.auto_sync = auto_sync;
+
+        // Without an account there's nothing to sync yet, it starts with the account screen
+        if !state.account_screen_shown {
+            Ok(())
+        } else if auto_sync {
+            self.core.start_auto_sync(self.auto_sync_events())
+        } else {
+            self.core.stop_auto_sync()
+        }
+    }
+
     fn toggle_auto_save(&self, auto_save: bool) -> LbResult<()> {
         self.state
             .borrow()
@@ -961,6 +1126,9 @@ struct LbState {
     opened_file: Option<ClientFileMetadata>,
     open_file_dirty: bool,
     background_work: Arc<Mutex<BackgroundWork>>,
+    background_work_started: bool,
+    account_screen_shown: bool,
+    auto_sync: bool,
 }
 
 impl LbState {
@@ -970,6 +1138,9 @@ impl LbState {
             opened_file: None,
             open_file_dirty: false,
             background_work: Arc::new(Mutex::new(BackgroundWork::default(&m))),
+            background_work_started: false,
+            account_screen_shown: false,
+            auto_sync: false,
         }
     }
 
@@ -1115,6 +1286,7 @@ impl Gui {
 
     fn show(&self, core: &LbCore) -> LbResult<()> {
         self.win.show_all();
+        self.refresh_profiles(&core)?;
         if core.has_account()? {
             self.show_account_screen(&core)
         } else {
@@ -1139,6 +1311,12 @@ impl Gui {
         Ok(())
     }
 
+    fn refresh_profiles(&self, core: &LbCore) -> LbResult<()> {
+        self.menubar
+            .set_profiles(&core.profiles()?, &core.active_profile()?);
+        Ok(())
+    }
+
     fn new_dialog(&self, title: &str) -> GtkDialog {
         let d = GtkDialog::new();
         d.set_transient_for(Some(&self.win));
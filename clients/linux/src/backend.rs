@@ -7,12 +7,15 @@ use uuid::Uuid;
 
 use lockbook_core::model::state::Config;
 use lockbook_core::service::db_state_service::State as DbState;
-use lockbook_core::service::sync_service::SyncProgress;
+use lockbook_core::service::sync_scheduler_service::SyncSchedulerEvent;
+use lockbook_core::service::sync_service::{SyncPhase, SyncProgress};
 use lockbook_core::{
-    calculate_work, create_account, create_file, delete_file, export_account, get_account,
-    get_and_get_children_recursively, get_children, get_db_state, get_file_by_id, get_file_by_path,
-    get_last_synced, get_root, get_usage, import_account, list_paths, migrate_db, move_file,
-    read_document, rename_file, sync_all, write_document,
+    calculate_work, create_account, create_file, create_profile, delete_file, export_account,
+    get_account, get_active_profile, get_and_get_children_recursively, get_children, get_db_state,
+    get_file_by_id, get_file_by_path, get_last_synced, get_profile_config, get_root, get_usage,
+    import_account, list_paths, list_profiles, migrate_db, move_file, read_document,
+    remove_profile, rename_file, start_sync_scheduler, stop_sync_scheduler, switch_profile,
+    sync_all, write_document, StartSyncSchedulerError,
 };
 use lockbook_models::account::Account;
 use lockbook_models::crypto::DecryptedDocument;
@@ -40,6 +43,13 @@ macro_rules! match_core_err {
 }
 
 macro_rules! map_core_err {
+    // For functions whose only errors are unexpected ones
+    () => {
+        |err| match err {
+            lockbook_core::Error::UiError(err) => match err {},
+            lockbook_core::Error::Unexpected(msg) => progerr!("{}", msg),
+        }
+    };
     ($enum:ident, $( $variants:ident => $matches:expr ,)+) => {
         |err| match_core_err!(err, $enum,
             $( $variants  => $matches ),+,
@@ -65,23 +75,53 @@ fn api_url() -> String {
 }
 
 pub struct LbSyncMsg {
-    pub work: ClientWorkUnit,
+    pub work: Option<ClientWorkUnit>,
     pub name: String,
     pub index: usize,
     pub total: usize,
+    pub phase: SyncPhase,
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+}
+
+impl LbSyncMsg {
+    pub fn bytes_progress(&self) -> String {
+        format!(
+            "{} / {}",
+            bytes_to_human(self.bytes_transferred),
+            bytes_to_human(self.total_bytes)
+        )
+    }
 }
 
 pub struct LbCore {
-    config: Config,
+    base: Config,
+    config: RwLock<Config>,
     account: RwLock<Option<Account>>,
 }
 
 impl LbCore {
     pub fn new(cfg_path: &str) -> LbResult<Self> {
-        let config = Config {
+        let base = Config {
             writeable_path: cfg_path.to_string(),
+            storage: None,
         };
 
+        let config = get_profile_config(&base, None).map_err(map_core_err!(
+            GetProfileConfigError,
+            ProfileDoesNotExist => panic!("impossible"),
+        ))?;
+        let account = Self::load(&config)?;
+
+        Ok(Self {
+            base,
+            config: RwLock::new(config),
+            account: RwLock::new(account),
+        })
+    }
+
+    // Gets a profile's data ready to use and returns its account, if it has one.
+    fn load(config: &Config) -> LbResult<Option<Account>> {
         match get_db_state(&config).map_err(map_core_err!(GetStateError,
             Stub => panic!("impossible"),
         ))? {
@@ -91,36 +131,81 @@ impl LbCore {
                 println!("Local state requires migration! Performing migration now...");
                 migrate_db(&config).map_err(map_core_err!(MigrationError,
                     StateRequiresCleaning => uerr_dialog!("{}", STATE_REQ_CLEAN_MSG),
+                    DataDirLocked => uerr_dialog!("{}", DATA_DIR_LOCKED_MSG),
                 ))?;
             }
         }
 
-        let account = RwLock::new(match get_account(&config) {
-            Ok(acct) => Some(acct),
+        match get_account(&config) {
+            Ok(acct) => Ok(Some(acct)),
             Err(err) => match_core_err!(err, GetAccountError,
-                NoAccount => None,
-                @Unexpected(msg) => return Err(progerr!("{}", msg)),
+                NoAccount => Ok(None),
+                @Unexpected(msg) => Err(progerr!("{}", msg)),
             ),
-        });
+        }
+    }
+
+    fn config(&self) -> LbResult<Config> {
+        Ok(lock!(self.config, read)?.clone())
+    }
+
+    pub fn profiles(&self) -> LbResult<Vec<String>> {
+        list_profiles(&self.base).map_err(map_core_err!())
+    }
+
+    pub fn active_profile(&self) -> LbResult<String> {
+        get_active_profile(&self.base).map_err(map_core_err!())
+    }
 
-        Ok(Self { config, account })
+    pub fn create_profile(&self, name: &str) -> LbResult<()> {
+        create_profile(&self.base, name).map_err(map_core_err!(CreateProfileError,
+            ProfileNameInvalid => uerr_dialog!("Invalid profile name '{}' ({}).", name, PROFILE_NAME_REQS),
+            ProfileExists => uerr_dialog!("The profile '{}' already exists.", name),
+        ))
+    }
+
+    pub fn remove_profile(&self, name: &str) -> LbResult<()> {
+        remove_profile(&self.base, name).map_err(map_core_err!(RemoveProfileError,
+            ProfileDoesNotExist => uerr_dialog!("There is no profile named '{}'.", name),
+            CannotRemoveDefaultProfile => uerr_dialog!("The default profile cannot be removed."),
+            CannotRemoveActiveProfile => uerr_dialog!("Switch to another profile before removing '{}'.", name),
+            DataDirLocked => uerr_dialog!("{}", DATA_DIR_LOCKED_MSG),
+        ))
+    }
+
+    /// Makes `name` the active profile, everything after this works with its data and account.
+    pub fn switch_profile(&self, name: &str) -> LbResult<()> {
+        let config = get_profile_config(&self.base, Some(name)).map_err(map_core_err!(
+            GetProfileConfigError,
+            ProfileDoesNotExist => uerr_dialog!("There is no profile named '{}'.", name),
+        ))?;
+        let account = Self::load(&config)?;
+
+        switch_profile(&self.base, name).map_err(map_core_err!(SwitchProfileError,
+            ProfileDoesNotExist => uerr_dialog!("There is no profile named '{}'.", name),
+        ))?;
+
+        *lock!(self.config, write)? = config;
+        *lock!(self.account, write)? = account;
+        Ok(())
     }
 
     pub fn create_account(&self, uname: &str) -> LbResult<()> {
         let api_url = api_url();
-        let new_acct = create_account(&self.config, &uname, &api_url).map_err(map_core_err!(
+        let new_acct = create_account(&self.config()?, &uname, &api_url).map_err(map_core_err!(
             CreateAccountError,
             UsernameTaken => uerr_dialog!("The username '{}' is already taken.", uname),
             InvalidUsername => uerr_dialog!("Invalid username '{}' ({}).", uname, UNAME_REQS),
             AccountExistsAlready => uerr_dialog!("An account already exists."),
             CouldNotReachServer => uerr_dialog!("Unable to connect to the server."),
             ClientUpdateRequired => uerr_dialog!("Client upgrade required."),
+            DataDirLocked => uerr_dialog!("{}", DATA_DIR_LOCKED_MSG),
         ))?;
         self.set_account(new_acct)
     }
 
     pub fn import_account(&self, privkey: &str) -> LbResult<()> {
-        let new_acct = import_account(&self.config, privkey).map_err(map_core_err!(
+        let new_acct = import_account(&self.config()?, privkey).map_err(map_core_err!(
             ImportError,
             AccountStringCorrupted => uerr_dialog!("Your account's private key is corrupted."),
             AccountExistsAlready => uerr_dialog!("An account already exists."),
@@ -128,13 +213,15 @@ impl LbCore {
             UsernamePKMismatch => uerr_dialog!("The account private key does not match username."),
             CouldNotReachServer => uerr_dialog!("Unable to connect to the server."),
             ClientUpdateRequired => uerr_dialog!("Client upgrade required."),
+            DataDirLocked => uerr_dialog!("{}", DATA_DIR_LOCKED_MSG),
         ))?;
         self.set_account(new_acct)
     }
 
     pub fn export_account(&self) -> LbResult<String> {
-        export_account(&self.config).map_err(map_core_err!(AccountExportError,
+        export_account(&self.config()?).map_err(map_core_err!(AccountExportError,
             NoAccount => uerr_dialog!("No account found."),
+            ServerTampering => uerr_dialog!("The server sent changes that weren't made by this account."),
         ))
     }
 
@@ -144,40 +231,44 @@ impl LbCore {
         parent: Uuid,
         file_type: FileType,
     ) -> LbResult<ClientFileMetadata> {
-        create_file(&self.config, name, parent, file_type).map_err(map_core_err!(CreateFileError,
+        let config = self.config()?;
+        create_file(&config, name, parent, file_type).map_err(map_core_err!(CreateFileError,
             FileNameNotAvailable => uerr_dialog!("That file name is not available."),
             NoAccount => uerr_dialog!("No account found."),
             DocumentTreatedAsFolder => uerr_dialog!("A document is being treated as folder."),
             CouldNotFindAParent => uerr_dialog!("Could not find parent."),
             FileNameEmpty => uerr_dialog!("Cannot create file with no name."),
             FileNameContainsSlash => uerr_dialog!("The file name cannot contain a slash."),
+            FileNameInvalid => uerr_dialog!("{}", FILE_NAME_INVALID_MSG),
+            DataDirLocked => uerr_dialog!("{}", DATA_DIR_LOCKED_MSG),
         ))
     }
 
     pub fn save(&self, id: Uuid, content: String) -> LbResult<()> {
         let bytes = content.as_bytes();
 
-        write_document(&self.config, id, bytes).map_err(map_core_err!(WriteToDocumentError,
+        write_document(&self.config()?, id, bytes).map_err(map_core_err!(WriteToDocumentError,
             NoAccount => uerr_dialog!("No account found."),
             FileDoesNotExist => uerr_dialog!("The file with id '{}' does not exist.", id),
             FolderTreatedAsDocument => uerr_dialog!(""),
+            DataDirLocked => uerr_dialog!("{}", DATA_DIR_LOCKED_MSG),
         ))
     }
 
     pub fn root(&self) -> LbResult<ClientFileMetadata> {
-        get_root(&self.config).map_err(map_core_err!(GetRootError,
+        get_root(&self.config()?).map_err(map_core_err!(GetRootError,
             NoRoot => uerr_dialog!("No root folder found."),
         ))
     }
 
     pub fn children(&self, parent: &ClientFileMetadata) -> LbResult<Vec<ClientFileMetadata>> {
-        get_children(&self.config, parent.id).map_err(map_core_err!(GetChildrenError,
+        get_children(&self.config()?, parent.id).map_err(map_core_err!(GetChildrenError,
             Stub => panic!("impossible"),
         ))
     }
 
     pub fn get_children_recursively(&self, id: Uuid) -> LbResult<Vec<FileMetadata>> {
-        get_and_get_children_recursively(&self.config, id).map_err(map_core_err!(
+        get_and_get_children_recursively(&self.config()?, id).map_err(map_core_err!(
             GetAndGetChildrenError,
             FileDoesNotExist => uerr_dialog!("File with id '{}' does not exist.", id),
             DocumentTreatedAsFolder => uerr_dialog!("A document is being treated as folder."),
@@ -185,7 +276,7 @@ impl LbCore {
     }
 
     pub fn file_by_id(&self, id: Uuid) -> LbResult<ClientFileMetadata> {
-        get_file_by_id(&self.config, id).map_err(map_core_err!(GetFileByIdError,
+        get_file_by_id(&self.config()?, id).map_err(map_core_err!(GetFileByIdError,
             NoFileWithThatId => uerr_dialog!("No file found with ID '{}'.", id),
         ))
     }
@@ -195,44 +286,49 @@ impl LbCore {
         let acct = account!(acct_lock)?;
         let p = format!("{}/{}", acct.username, path);
 
-        get_file_by_path(&self.config, &p).map_err(map_core_err!(GetFileByPathError,
+        get_file_by_path(&self.config()?, &p).map_err(map_core_err!(GetFileByPathError,
             NoFileAtThatPath => uerr_dialog!("No file at path '{}'.", p),
         ))
     }
 
     pub fn delete(&self, id: &Uuid) -> LbResult<()> {
-        delete_file(&self.config, *id).map_err(map_core_err!(FileDeleteError,
+        delete_file(&self.config()?, *id).map_err(map_core_err!(FileDeleteError,
             CannotDeleteRoot => uerr_dialog!("Deleting the root folder is not permitted."),
             FileDoesNotExist => uerr_dialog!("File with id '{}' does not exist.", id),
+            DataDirLocked => uerr_dialog!("{}", DATA_DIR_LOCKED_MSG),
         ))
     }
 
     pub fn read(&self, id: Uuid) -> LbResult<DecryptedDocument> {
-        read_document(&self.config, id).map_err(map_core_err!(ReadDocumentError,
+        read_document(&self.config()?, id).map_err(map_core_err!(ReadDocumentError,
             TreatedFolderAsDocument => uerr_dialog!("There is a folder treated as a document."),
             NoAccount => uerr_dialog!("No account found."),
             FileDoesNotExist => uerr_dialog!("File with id '{}' does not exist.", id),
+            NotAvailableOffline => uerr_dialog!("This document hasn't been downloaded and you're offline."),
+            DocumentCorrupted => uerr_dialog!("This document's content is damaged."),
         ))
     }
 
     pub fn list_paths(&self) -> LbResult<Vec<String>> {
-        list_paths(&self.config, None).map_err(map_core_err!(ListPathsError,
+        list_paths(&self.config()?, None).map_err(map_core_err!(ListPathsError,
             Stub => panic!("impossible"),
         ))
     }
 
     pub fn rename(&self, id: &Uuid, new_name: &str) -> LbResult<()> {
-        rename_file(&self.config, *id, new_name).map_err(map_core_err!(RenameFileError,
+        rename_file(&self.config()?, *id, new_name).map_err(map_core_err!(RenameFileError,
             CannotRenameRoot => uerr_dialog!("The root folder cannot be renamed."),
             FileDoesNotExist => uerr_dialog!("The file you are trying to rename does not exist."),
             FileNameNotAvailable => uerr_dialog!("The new file name is not available."),
             NewNameContainsSlash => uerr_dialog!("File names cannot contain slashes."),
             NewNameEmpty => uerr_dialog!("File names cannot be blank."),
+            NewNameInvalid => uerr_dialog!("{}", FILE_NAME_INVALID_MSG),
+            DataDirLocked => uerr_dialog!("{}", DATA_DIR_LOCKED_MSG),
         ))
     }
 
     pub fn move_file(&self, id: &Uuid, parent: Uuid) -> LbResult<()> {
-        move_file(&self.config, *id, parent).map_err(map_core_err!(MoveFileError,
+        move_file(&self.config()?, *id, parent).map_err(map_core_err!(MoveFileError,
             CannotMoveRoot => uerr_dialog!("The root folder cannot be moved."),
             DocumentTreatedAsFolder => uerr_dialog!("A document is being treated as folder."),
             FileDoesNotExist => uerr_dialog!("File with id '{}' does not exist.", id),
@@ -240,17 +336,20 @@ impl LbCore {
             NoAccount => uerr_dialog!("No account found."),
             TargetParentDoesNotExist => uerr_dialog!("The folder does not exist."),
             TargetParentHasChildNamedThat => uerr_dialog!("The folder already has a child named that."),
+            DataDirLocked => uerr_dialog!("{}", DATA_DIR_LOCKED_MSG),
         ))
     }
 
     pub fn sync(&self, ch: glib::Sender<Option<LbSyncMsg>>) -> LbResult<()> {
+        let config = self.config()?;
         let closure = closure!(ch => move |sync_progress: SyncProgress| {
             let wu = sync_progress.current_work_unit;
 
             let name = match &wu {
-                ClientWorkUnit::ServerUnknownName(_) => "New file".to_string(),
-                ClientWorkUnit::Server(metadata) => metadata.name.clone(),
-                ClientWorkUnit::Local(metadata) => metadata.name.clone(),
+                None => "".to_string(),
+                Some(ClientWorkUnit::ServerUnknownName(_)) => "New file".to_string(),
+                Some(ClientWorkUnit::Server(metadata)) => metadata.name.clone(),
+                Some(ClientWorkUnit::Local(metadata)) => metadata.name.clone(),
             };
 
             let data = LbSyncMsg {
@@ -258,16 +357,23 @@ impl LbCore {
                 name: name,
                 index: sync_progress.progress + 1,
                 total: sync_progress.total,
+                phase: sync_progress.phase,
+                bytes_transferred: sync_progress.bytes_transferred,
+                total_bytes: sync_progress.total_bytes,
             };
 
             ch.send(Some(data)).unwrap();
         });
 
         let sync =
-            sync_all(&self.config, Some(Box::new(closure))).map_err(map_core_err!(SyncAllError,
+            sync_all(&config, Some(Box::new(closure))).map_err(map_core_err!(SyncAllError,
                 CouldNotReachServer => uerr_status_panel!("Offline."),
                 ClientUpdateRequired => uerr_dialog!("Client upgrade required."),
                 NoAccount => uerr_dialog!("No account found."),
+                Cancelled => uerr_status_panel!("Sync cancelled."),
+                ServerTampering => uerr_dialog!("The server sent changes that weren't made by this account, nothing was applied."),
+                DocumentCorrupted => uerr_dialog!("A document was damaged on its way from the server, try syncing again."),
+                DataDirLocked => uerr_status_panel!("Another app is changing your files."),
             ));
 
         ch.send(None).unwrap();
@@ -278,7 +384,7 @@ impl LbCore {
     }
 
     pub fn calculate_work(&self) -> LbResult<ClientWorkCalculated> {
-        calculate_work(&self.config).map_err(map_core_err!(CalculateWorkError,
+        calculate_work(&self.config()?).map_err(map_core_err!(CalculateWorkError,
             CouldNotReachServer => uerr_status_panel!("Offline."),
             ClientUpdateRequired => uerr_dialog!("Client upgrade required."),
             NoAccount => uerr_dialog!("No account found."),
@@ -286,13 +392,13 @@ impl LbCore {
     }
 
     pub fn get_last_synced(&self) -> LbResult<i64> {
-        get_last_synced(&self.config).map_err(map_core_err!(GetLastSyncedError,
+        get_last_synced(&self.config()?).map_err(map_core_err!(GetLastSyncedError,
             Stub => panic!("impossible"),
         ))
     }
 
     pub fn get_usage(&self) -> LbResult<UsageMetrics> {
-        get_usage(&self.config).map_err(map_core_err!(GetUsageError,
+        get_usage(&self.config()?).map_err(map_core_err!(GetUsageError,
             NoAccount => uerr_dialog!("No account found."),
             CouldNotReachServer => uerr_status_panel!("Offline."),
             ClientUpdateRequired => uerr_dialog!("Client upgrade required."),
@@ -312,7 +418,7 @@ impl LbCore {
 
     pub fn account_qrcode(&self) -> LbResult<String> {
         let privkey = self.export_account()?;
-        let path = format!("{}/account-qr.png", self.config.writeable_path);
+        let path = format!("{}/account-qr.png", self.config()?.writeable_path);
         if !Path::new(&path).exists() {
             let bytes = privkey.as_bytes();
             qrcode_generator::to_png_to_file(bytes, QrCodeEcc::Low, 400, &path).unwrap();
@@ -358,6 +464,28 @@ impl LbCore {
         Ok((meta, String::from_utf8_lossy(&decrypted).to_string()))
     }
 
+    /// Syncs the active profile in the background until `stop_auto_sync`. `on_event` is called on
+    /// the scheduler's thread.
+    pub fn start_auto_sync(
+        &self,
+        on_event: Box<dyn Fn(SyncSchedulerEvent) + Send>,
+    ) -> LbResult<()> {
+        match start_sync_scheduler(&self.config()?, on_event) {
+            Err(lockbook_core::Error::UiError(
+                StartSyncSchedulerError::SchedulerAlreadyRunning,
+            )) => Ok(()),
+            result => result.map_err(map_core_err!(StartSyncSchedulerError,
+                NoAccount => uerr_dialog!("No account found."),
+                SchedulerAlreadyRunning => panic!("impossible"),
+            )),
+        }
+    }
+
+    pub fn stop_auto_sync(&self) -> LbResult<()> {
+        stop_sync_scheduler(&self.config()?).map_err(map_core_err!())?;
+        Ok(())
+    }
+
     pub fn sync_status(&self) -> LbResult<String> {
         match self.get_last_synced()? {
             0 => Ok("✘  Never synced.".to_string()),
@@ -401,6 +529,10 @@ impl LbCore {
 }
 
 const UNAME_REQS: &str = "letters and numbers only";
+const PROFILE_NAME_REQS: &str = "letters, numbers, - and _ only";
 const STATE_REQ_CLEAN_MSG: &str =
     "Your local state cannot be migrated, please re-sync with a fresh client.";
+const DATA_DIR_LOCKED_MSG: &str = "Another app is changing your files, try again in a moment.";
+const FILE_NAME_INVALID_MSG: &str =
+    "File names cannot contain control characters or be a name reserved by the operating system.";
 const USAGE_WARNING_THRESHOLD: f32 = 0.9;
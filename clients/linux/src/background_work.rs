@@ -6,7 +6,6 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 pub struct BackgroundWork {
     pub messenger: Messenger,
     pub auto_save_state: AutoSaveState,
-    pub auto_sync_state: AutoSyncState,
 }
 
 impl BackgroundWork {
@@ -14,7 +13,6 @@ impl BackgroundWork {
         Self {
             messenger: m.clone(),
             auto_save_state: AutoSaveState::default(),
-            auto_sync_state: AutoSyncState::default(),
         }
     }
 
@@ -33,30 +31,6 @@ impl BackgroundWork {
             if bgs.auto_save_state.is_active {
                 bgs.auto_save();
             }
-
-            if bgs.auto_sync_state.is_active {
-                bgs.auto_sync();
-            }
-        }
-    }
-
-    pub fn auto_sync(&mut self) {
-        let current_time = Self::current_time();
-
-        if self.auto_save_state.last_save < self.auto_save_state.last_change {
-            return;
-        }
-
-        let millis_since_edit = current_time - self.auto_save_state.last_change;
-        let millis_since_sync = current_time - self.auto_sync_state.last_sync;
-
-        let unsynced_changes = self.auto_sync_state.last_sync < self.auto_save_state.last_save;
-
-        if (unsynced_changes && millis_since_edit > Self::SYNC_AFTER_EDIT_DELAY)
-            || (!unsynced_changes && millis_since_sync > Self::SYNC_AFTER_SYNC_DELAY)
-        {
-            self.auto_sync_state.last_sync = current_time;
-            self.messenger.send(Msg::PerformSync);
         }
     }
 
@@ -74,9 +48,7 @@ impl BackgroundWork {
         }
     }
 
-    // TODO: make a setting to adjust these durations
-    pub const SYNC_AFTER_SYNC_DELAY: u128 = 1800000;
-    pub const SYNC_AFTER_EDIT_DELAY: u128 = 90000;
+    // TODO: make a setting to adjust this duration
     pub const SAVE_AFTER_EDIT_DELAY: u128 = 1000;
 }
 
@@ -99,17 +71,3 @@ impl AutoSaveState {
         self.last_change = BackgroundWork::current_time();
     }
 }
-
-pub struct AutoSyncState {
-    pub is_active: bool,
-    pub last_sync: u128,
-}
-
-impl AutoSyncState {
-    pub fn default() -> Self {
-        Self {
-            is_active: false,
-            last_sync: 0,
-        }
-    }
-}
@@ -89,7 +89,13 @@ impl IntroScreen {
     }
 
     pub fn sync_progress(&self, s: &LbSyncMsg) {
-        let status = format!("Syncing :: {} ({}/{})", s.name, s.index, s.total);
+        let status = format!(
+            "Syncing :: {} ({}/{}, {})",
+            s.name,
+            s.index,
+            s.total,
+            s.bytes_progress()
+        );
         self.doing.status.set_text(&status);
     }
 
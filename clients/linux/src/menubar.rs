@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use gtk::prelude::*;
 use gtk::AccelGroup as GtkAccelGroup;
+use gtk::CheckMenuItem as GtkCheckMenuItem;
 use gtk::Menu as GtkMenu;
 use gtk::MenuBar as GtkMenuBar;
 use gtk::MenuItem as GtkMenuItem;
@@ -20,17 +21,7 @@ use crate::messages::{Messenger, Msg};
 //    that I could not get to the bottom of.
 macro_rules! menu_set {
     ($menu:expr, $item_map:expr, $( $items:ident ),*) => {
-        let submenu = {
-            if let Some(m) = $menu.get_submenu() {
-                let m = m.downcast::<GtkMenu>().unwrap();
-                m.foreach(|child| m.remove(child));
-                m
-            } else {
-                let m = GtkMenu::new();
-                $menu.set_submenu(Some(&m));
-                m
-            }
-        };
+        let submenu = empty_submenu(&$menu);
         $(
             match Item::$items {
                 Item::Separator => submenu.append(&GtkSeparatorMenuItem::new()),
@@ -41,13 +32,27 @@ macro_rules! menu_set {
     };
 }
 
+fn empty_submenu(menu: &GtkMenuItem) -> GtkMenu {
+    if let Some(m) = menu.get_submenu() {
+        let m = m.downcast::<GtkMenu>().unwrap();
+        m.foreach(|child| m.remove(child));
+        m
+    } else {
+        let m = GtkMenu::new();
+        menu.set_submenu(Some(&m));
+        m
+    }
+}
+
 pub struct Menubar {
     items: HashMap<Item, GtkMenuItem>,
     file: GtkMenuItem,
     edit: GtkMenuItem,
     acct: GtkMenuItem,
+    profile: GtkMenuItem,
     help: GtkMenuItem,
     mbar: GtkMenuBar,
+    messenger: Messenger,
 }
 
 impl Menubar {
@@ -57,10 +62,11 @@ impl Menubar {
         let file = GtkMenuItem::with_label("File");
         let edit = GtkMenuItem::with_label("Edit");
         let acct = GtkMenuItem::with_label("Account");
+        let profile = GtkMenuItem::with_label("Profile");
         let help = GtkMenuItem::with_label("Help");
 
         let mbar = GtkMenuBar::new();
-        for menu in &[&file, &edit, &acct, &help] {
+        for menu in &[&file, &edit, &acct, &profile, &help] {
             mbar.append(*menu);
         }
 
@@ -71,8 +77,10 @@ impl Menubar {
             file,
             edit,
             acct,
+            profile,
             help,
             mbar,
+            messenger: m.clone(),
         }
     }
 
@@ -115,6 +123,26 @@ impl Menubar {
         }
     }
 
+    // Profiles come and go, so their items are made here rather than in `Item`.
+    pub fn set_profiles(&self, profiles: &[String], active: &str) {
+        let submenu = empty_submenu(&self.profile);
+        for name in profiles {
+            let mi = GtkCheckMenuItem::with_label(name);
+            mi.set_draw_as_radio(true);
+            mi.set_active(name == active);
+
+            let m = self.messenger.clone();
+            let name = name.clone();
+            mi.connect_activate(move |_| m.send(Msg::SwitchProfile(name.clone())));
+
+            submenu.append(&mi);
+        }
+        submenu.append(&GtkSeparatorMenuItem::new());
+        submenu.append(self.items.get(&Item::ProfileNew).unwrap());
+        submenu.append(self.items.get(&Item::ProfileRemove).unwrap());
+        self.profile.show_all();
+    }
+
     pub fn for_intro_screen(&self) {
         self.mbar.foreach(|w| {
             if *w == self.file || *w == self.edit || *w == self.acct {
@@ -125,7 +153,13 @@ impl Menubar {
 
     pub fn for_account_screen(&self) {
         self.mbar.foreach(|w| self.mbar.remove(w));
-        for menu in &[&self.file, &self.edit, &self.acct, &self.help] {
+        for menu in &[
+            &self.file,
+            &self.edit,
+            &self.acct,
+            &self.profile,
+            &self.help,
+        ] {
             self.mbar.append(*menu);
         }
     }
@@ -149,6 +183,9 @@ enum Item {
     AccountUsage,
     AccountExport,
 
+    ProfileNew,
+    ProfileRemove,
+
     HelpAbout,
 
     Separator,
@@ -184,6 +221,8 @@ impl Item {
             (Self::AccountSync, ("Sync", "", || Msg::PerformSync)),
             (Self::AccountUsage, ("Usage", "", || Msg::ShowDialogUsage)),
             (Self::AccountExport, ("Export", "", || Msg::ExportAccount)),
+            (Self::ProfileNew, ("New Profile", "", || Msg::ShowDialogNewProfile)),
+            (Self::ProfileRemove, ("Remove Profile", "", || Msg::ShowDialogRemoveProfile)),
             (Self::HelpAbout, ("About", "", || Msg::ShowDialogAbout)),
         ]
     }
@@ -13,6 +13,7 @@ pub enum Msg {
     PerformSync,
     RefreshSyncStatus,
     RefreshUsageStatus,
+    SwitchProfile(String),
     Quit,
 
     NewFile(FileType),
@@ -37,6 +38,8 @@ pub enum Msg {
     ShowDialogPreferences,
     ShowDialogUsage,
     ShowDialogAbout,
+    ShowDialogNewProfile,
+    ShowDialogRemoveProfile,
 
     ToggleAutoSave(bool),
     ToggleAutoSync(bool),
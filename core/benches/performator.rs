@@ -27,6 +27,7 @@ impl Profiler for CpuProfiler {
 pub fn bench_performator(c: &mut Criterion) {
     let config = &Config {
         writeable_path: format!("/tmp/perf-{}", Uuid::new_v4().to_string()),
+        storage: None,
     };
 
     let _ = account_service::create_account(
@@ -18,6 +18,7 @@ fn random_string() -> String {
 pub fn bench_throughput(c: &mut Criterion) {
     let config = &Config {
         writeable_path: format!("/tmp/throughput{}", random_string()),
+        storage: None,
     };
 
     let mut group = c.benchmark_group("Throughput");
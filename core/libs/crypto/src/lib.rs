@@ -1,3 +1,4 @@
 pub mod clock_service;
 pub mod pubkey;
+pub mod stream;
 pub mod symkey;
@@ -73,6 +73,11 @@ pub fn verify<T: Serialize>(
         ));
     }
 
+    verify_signature(signed)
+}
+
+/// Checks that `signed` was signed by its public key, however long ago.
+pub fn verify_signature<T: Serialize>(signed: &ECSigned<T>) -> Result<(), ECVerifyError> {
     let serialized =
         bincode::serialize(&signed.timestamped_value).map_err(ECVerifyError::Serialization)?;
 
@@ -94,11 +99,12 @@ pub enum GetAesKeyError {
     SharedSecretError(libsecp256k1::Error),
 }
 
-pub fn get_aes_key(sk: &SecretKey, pk: &PublicKey) -> Result<AESKey, GetAesKeyError> {
+pub fn get_aes_key(sk: &SecretKey, pk: &PublicKey) -> Result<SecretAESKey, GetAesKeyError> {
     SharedSecret::<Sha256>::new(&pk, &sk)
         .map_err(GetAesKeyError::SharedSecretError)?
         .as_ref()
         .try_into()
+        .map(Secret::new)
         .map_err(|_| GetAesKeyError::SharedSecretUnexpectedSize)
 }
 
@@ -139,6 +145,16 @@ mod unit_test_pubkey {
         .unwrap_err();
     }
 
+    #[test]
+    fn ec_test_verify_signature_tampered() {
+        let key = generate_key();
+        let mut value = sign(&key, String::from("Test"), EARLY_CLOCK).unwrap();
+        verify_signature(&value).unwrap();
+
+        value.timestamped_value.value = String::from("Tampered");
+        verify_signature(&value).unwrap_err();
+    }
+
     #[test]
     fn ec_test_shared_secret_one_party() {
         // Just sanity checks
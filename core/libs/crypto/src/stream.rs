@@ -0,0 +1,304 @@
+extern crate rand;
+
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use aead::{generic_array::GenericArray, Aead};
+use aes_gcm::Aes256Gcm;
+use lockbook_models::crypto::AESKey;
+
+use self::rand::rngs::OsRng;
+use self::rand::RngCore;
+
+use crate::symkey::convert_key;
+
+/// Identifies content encrypted by `EncryptingWriter`, followed by a format version
+pub const MAGIC: &[u8; 8] = b"LBSTREAM";
+const VERSION: u8 = 1;
+const NONCE_PREFIX_LEN: usize = 7;
+pub const HEADER_LEN: usize = MAGIC.len() + 1 + NONCE_PREFIX_LEN;
+
+/// Plaintext is encrypted in segments of this size, only one segment is held in memory at a time
+pub const SEGMENT_SIZE: usize = 64 * 1024;
+/// Set on a segment's length prefix when it is the final segment
+const LAST_SEGMENT: u32 = 1 << 31;
+const TAG_LEN: usize = 16;
+/// Each segment's length prefix and authentication tag
+const SEGMENT_OVERHEAD: usize = 4 + TAG_LEN;
+
+/// Each segment is sealed with a nonce made of a random prefix for the stream, the segment's index
+/// and whether it's the final segment. Segments can't be reordered, dropped or appended to without
+/// failing decryption, and a stream cut short is detected because its final segment is missing.
+fn segment_nonce(prefix: &[u8; NONCE_PREFIX_LEN], index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..NONCE_PREFIX_LEN + 4].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+pub fn is_stream(content: &[u8]) -> bool {
+    content.starts_with(MAGIC)
+}
+
+/// The length of the plaintext in a complete stream that's `len` bytes long.
+pub fn plaintext_len(len: u64) -> u64 {
+    let segments_len = len.saturating_sub(HEADER_LEN as u64);
+    let overhead = SEGMENT_OVERHEAD as u64;
+    let full_segment = SEGMENT_SIZE as u64 + overhead;
+    // Every segment but the last is full, the last one may be empty
+    let segments = ((segments_len + full_segment - 1) / full_segment).max(1);
+    segments_len.saturating_sub(segments * overhead)
+}
+
+/// Encrypts everything written to it into `inner`. `finish` must be called to write the final
+/// segment, a stream that's dropped without it won't decrypt.
+pub struct EncryptingWriter<W: Write> {
+    inner: W,
+    cipher: Aes256Gcm,
+    header: [u8; HEADER_LEN],
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    index: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    pub fn new(key: &AESKey, mut inner: W) -> Result<EncryptingWriter<W>> {
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        OsRng.fill_bytes(&mut nonce_prefix);
+
+        let mut header = [0u8; HEADER_LEN];
+        header[..MAGIC.len()].copy_from_slice(MAGIC);
+        header[MAGIC.len()] = VERSION;
+        header[MAGIC.len() + 1..].copy_from_slice(&nonce_prefix);
+        inner.write_all(&header)?;
+
+        Ok(EncryptingWriter {
+            inner,
+            cipher: convert_key(key),
+            header,
+            nonce_prefix,
+            index: 0,
+            buffer: Vec::with_capacity(SEGMENT_SIZE),
+        })
+    }
+
+    fn write_segment(&mut self, last: bool) -> Result<()> {
+        let nonce = segment_nonce(&self.nonce_prefix, self.index, last);
+        let encrypted = self
+            .cipher
+            .encrypt(
+                GenericArray::from_slice(&nonce),
+                aead::Payload {
+                    msg: &self.buffer,
+                    aad: &self.header,
+                },
+            )
+            .map_err(|err| Error::new(ErrorKind::Other, format!("{:?}", err)))?;
+
+        let mut len = encrypted.len() as u32;
+        if last {
+            len |= LAST_SEGMENT;
+        }
+        self.inner.write_all(&len.to_be_bytes())?;
+        self.inner.write_all(&encrypted)?;
+
+        self.buffer.clear();
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or_else(|| Error::new(ErrorKind::Other, "stream too long"))?;
+        Ok(())
+    }
+
+    /// Writes the final segment and returns the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.write_segment(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // A full segment is only written once more data arrives, the final segment is written
+        // by `finish` and may be empty
+        if self.buffer.len() == SEGMENT_SIZE {
+            self.write_segment(false)?;
+        }
+
+        let len = buf.len().min(SEGMENT_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts a stream written by `EncryptingWriter`. Reads fail with `ErrorKind::InvalidData` if the
+/// stream was tampered with and `ErrorKind::UnexpectedEof` if it was cut short.
+pub struct DecryptingReader<R: Read> {
+    inner: R,
+    cipher: Aes256Gcm,
+    header: [u8; HEADER_LEN],
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    index: u32,
+    segment: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    pub fn new(key: &AESKey, mut inner: R) -> Result<DecryptingReader<R>> {
+        let mut header = [0u8; HEADER_LEN];
+        inner.read_exact(&mut header)?;
+        if !is_stream(&header) || header[MAGIC.len()] != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not an encrypted stream",
+            ));
+        }
+
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        nonce_prefix.copy_from_slice(&header[MAGIC.len() + 1..]);
+
+        Ok(DecryptingReader {
+            inner,
+            cipher: convert_key(key),
+            header,
+            nonce_prefix,
+            index: 0,
+            segment: vec![],
+            position: 0,
+            finished: false,
+        })
+    }
+
+    fn read_segment(&mut self) -> Result<()> {
+        let mut len = [0u8; 4];
+        self.inner.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len);
+        let last = len & LAST_SEGMENT != 0;
+        let len = (len & !LAST_SEGMENT) as usize;
+        if len > SEGMENT_SIZE + TAG_LEN {
+            return Err(Error::new(ErrorKind::InvalidData, "segment too large"));
+        }
+
+        let mut encrypted = vec![0u8; len];
+        self.inner.read_exact(&mut encrypted)?;
+
+        let nonce = segment_nonce(&self.nonce_prefix, self.index, last);
+        self.segment = self
+            .cipher
+            .decrypt(
+                GenericArray::from_slice(&nonce),
+                aead::Payload {
+                    msg: &encrypted,
+                    aad: &self.header,
+                },
+            )
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "segment failed to decrypt"))?;
+        self.position = 0;
+        self.index = self.index.wrapping_add(1);
+        self.finished = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.position == self.segment.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.read_segment()?;
+        }
+
+        let len = buf.len().min(self.segment.len() - self.position);
+        buf[..len].copy_from_slice(&self.segment[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use std::io::{ErrorKind, Read, Write};
+
+    use crate::stream;
+    use crate::stream::{DecryptingReader, EncryptingWriter, HEADER_LEN, SEGMENT_SIZE};
+    use crate::symkey::generate_key;
+
+    fn encrypt(key: &[u8; 32], content: &[u8]) -> Vec<u8> {
+        let mut writer = EncryptingWriter::new(key, vec![]).unwrap();
+        writer.write_all(content).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn decrypt(key: &[u8; 32], encrypted: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut decrypted = vec![];
+        DecryptingReader::new(key, encrypted)?.read_to_end(&mut decrypted)?;
+        Ok(decrypted)
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = generate_key();
+        for len in &[0, 1, SEGMENT_SIZE - 1, SEGMENT_SIZE, SEGMENT_SIZE * 3 + 7] {
+            let content: Vec<u8> = (0..*len).map(|i| (i % 251) as u8).collect();
+            assert_eq!(decrypt(&key, &encrypt(&key, &content)).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn plaintext_len() {
+        let key = generate_key();
+        for len in &[0, 1, SEGMENT_SIZE - 1, SEGMENT_SIZE, SEGMENT_SIZE * 3 + 7] {
+            let encrypted = encrypt(&key, &vec![1u8; *len]);
+            assert_eq!(stream::plaintext_len(encrypted.len() as u64), *len as u64);
+        }
+    }
+
+    #[test]
+    fn wrong_key() {
+        let encrypted = encrypt(&generate_key(), b"secret");
+        let err = decrypt(&generate_key(), &encrypted).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn tampered() {
+        let key = generate_key();
+        let mut encrypted = encrypt(&key, b"secret");
+        encrypted[HEADER_LEN + 5] ^= 1;
+        let err = decrypt(&key, &encrypted).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated() {
+        let key = generate_key();
+        let content = vec![7u8; SEGMENT_SIZE * 2 + 1];
+        let encrypted = encrypt(&key, &content);
+
+        // Cut right after the first segment, which is complete and valid on its own
+        let first_segment_end = HEADER_LEN + 4 + SEGMENT_SIZE + 16;
+        let err = decrypt(&key, &encrypted[..first_segment_end]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn unfinished() {
+        let key = generate_key();
+        let mut writer = EncryptingWriter::new(&key, vec![]).unwrap();
+        writer.write_all(&vec![7u8; SEGMENT_SIZE * 2]).unwrap();
+        let encrypted = writer.inner.clone();
+
+        assert!(decrypt(&key, &encrypted).is_err());
+    }
+}
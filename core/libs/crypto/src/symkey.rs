@@ -14,6 +14,8 @@ use hmac::crypto_mac::{InvalidKeyLength, MacError};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use sha2::Sha256;
+use std::io;
+use std::io::Write;
 
 type HmacSha256 = Hmac<Sha256>;
 
@@ -33,7 +35,8 @@ pub fn encrypt<T: Serialize + DeserializeOwned>(
     key: &AESKey,
     to_encrypt: &T,
 ) -> Result<AESEncrypted<T>, AESEncryptError> {
-    let serialized = bincode::serialize(to_encrypt).map_err(AESEncryptError::Serialization)?;
+    let serialized =
+        Secret::new(bincode::serialize(to_encrypt).map_err(AESEncryptError::Serialization)?);
     let nonce = &generate_nonce();
     let encrypted = convert_key(key)
         .encrypt(
@@ -58,7 +61,8 @@ pub fn encrypt_and_hmac(
     key: &AESKey,
     to_encrypt: &str,
 ) -> Result<SecretFileName, EncryptAndHmacError> {
-    let serialized = bincode::serialize(to_encrypt).map_err(EncryptAndHmacError::Serialization)?;
+    let serialized =
+        Secret::new(bincode::serialize(to_encrypt).map_err(EncryptAndHmacError::Serialization)?);
 
     let hmac = {
         let mut mac =
@@ -88,6 +92,40 @@ pub fn encrypt_and_hmac(
     })
 }
 
+pub fn hmac(key: &AESKey, to_hmac: &[u8]) -> Result<[u8; 32], InvalidKeyLength> {
+    let mut mac = HmacSha256::new_from_slice(key)?;
+    mac.update(to_hmac);
+    Ok(mac.finalize().into_bytes().into())
+}
+
+/// Computes `hmac` of everything written to it, for content that doesn't fit in memory.
+pub struct HmacWriter {
+    mac: HmacSha256,
+}
+
+impl HmacWriter {
+    pub fn new(key: &AESKey) -> Result<Self, InvalidKeyLength> {
+        Ok(HmacWriter {
+            mac: HmacSha256::new_from_slice(key)?,
+        })
+    }
+
+    pub fn finish(self) -> [u8; 32] {
+        self.mac.finalize().into_bytes().into()
+    }
+}
+
+impl Write for HmacWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.mac.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub enum AESDecryptError {
     Decryption(aead::Error),
@@ -99,20 +137,30 @@ pub fn decrypt<T: DeserializeOwned>(
     to_decrypt: &AESEncrypted<T>,
 ) -> Result<T, AESDecryptError> {
     let nonce = GenericArray::from_slice(&to_decrypt.nonce);
-    let decrypted = convert_key(key)
-        .decrypt(
-            &nonce,
-            aead::Payload {
-                msg: &to_decrypt.value,
-                aad: &[],
-            },
-        )
-        .map_err(AESDecryptError::Decryption)?;
+    let decrypted = Secret::new(
+        convert_key(key)
+            .decrypt(
+                &nonce,
+                aead::Payload {
+                    msg: &to_decrypt.value,
+                    aad: &[],
+                },
+            )
+            .map_err(AESDecryptError::Decryption)?,
+    );
     let deserialized =
         bincode::deserialize(&decrypted).map_err(AESDecryptError::Deserialization)?;
     Ok(deserialized)
 }
 
+/// `decrypt` for access keys, which are wiped from memory once the caller is done with them.
+pub fn decrypt_key(
+    key: &AESKey,
+    to_decrypt: &AESEncrypted<AESKey>,
+) -> Result<SecretAESKey, AESDecryptError> {
+    decrypt(key, to_decrypt).map(Secret::new)
+}
+
 #[derive(Debug)]
 pub enum DecryptAndVerifyError {
     Decryption(aead::Error),
@@ -126,15 +174,17 @@ pub fn decrypt_and_verify(
     to_decrypt: &SecretFileName,
 ) -> Result<String, DecryptAndVerifyError> {
     let nonce = GenericArray::from_slice(&to_decrypt.encrypted_value.nonce);
-    let decrypted = convert_key(key)
-        .decrypt(
-            &nonce,
-            aead::Payload {
-                msg: &to_decrypt.encrypted_value.value,
-                aad: &[],
-            },
-        )
-        .map_err(DecryptAndVerifyError::Decryption)?;
+    let decrypted = Secret::new(
+        convert_key(key)
+            .decrypt(
+                &nonce,
+                aead::Payload {
+                    msg: &to_decrypt.encrypted_value.value,
+                    aad: &[],
+                },
+            )
+            .map_err(DecryptAndVerifyError::Decryption)?,
+    );
     let deserialized =
         bincode::deserialize(&decrypted).map_err(DecryptAndVerifyError::Deserialization)?;
 
@@ -147,7 +197,7 @@ pub fn decrypt_and_verify(
     Ok(deserialized)
 }
 
-fn convert_key(to_convert: &AESKey) -> Aes256Gcm {
+pub(crate) fn convert_key(to_convert: &AESKey) -> Aes256Gcm {
     Aes256Gcm::new(GenericArray::clone_from_slice(to_convert))
 }
 
@@ -161,7 +211,11 @@ fn generate_nonce() -> [u8; 12] {
 mod unit_test_symmetric {
     use uuid::Uuid;
 
-    use crate::symkey::{decrypt, decrypt_and_verify, encrypt, encrypt_and_hmac, generate_key};
+    use crate::symkey::{
+        decrypt, decrypt_and_verify, decrypt_key, encrypt, encrypt_and_hmac, generate_key, hmac,
+        HmacWriter,
+    };
+    use std::io::Write;
 
     #[test]
     fn test_key_generation() {
@@ -207,4 +261,27 @@ mod unit_test_symmetric {
 
         decrypt_and_verify(&key, &enc_hash2).unwrap_err();
     }
+
+    #[test]
+    fn test_hmac_writer_matches_hmac() {
+        let key = generate_key();
+        let content = Uuid::new_v4().to_string().repeat(100).into_bytes();
+
+        let mut writer = HmacWriter::new(&key).unwrap();
+        for piece in content.chunks(7) {
+            writer.write_all(piece).unwrap();
+        }
+
+        assert_eq!(writer.finish(), hmac(&key, &content).unwrap());
+    }
+
+    #[test]
+    fn decrypted_keys_are_redacted() {
+        let key = generate_key();
+        let file_key = generate_key();
+        let decrypted = decrypt_key(&key, &encrypt(&key, &file_key).unwrap()).unwrap();
+
+        assert_eq!(*decrypted, file_key);
+        assert_eq!(format!("{:?}", decrypted), "Secret(<redacted>)");
+    }
 }
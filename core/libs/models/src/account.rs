@@ -1,15 +1,19 @@
 use libsecp256k1::{PublicKey, SecretKey};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+use std::{mem, slice};
+use zeroize::Zeroize;
 
 pub type Username = String;
 pub type ApiUrl = String;
 
-#[derive(PartialEq, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Serialize, Deserialize)]
 pub struct Account {
     pub username: Username,
     pub api_url: ApiUrl,
     #[serde(with = "secret_key_serializer")]
-    pub private_key: SecretKey,
+    pub private_key: PrivateKey,
 }
 
 impl Account {
@@ -18,26 +22,77 @@ impl Account {
     }
 }
 
+impl fmt::Debug for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Account")
+            .field("username", &self.username)
+            .field("api_url", &self.api_url)
+            .field("private_key", &"<redacted>")
+            .finish()
+    }
+}
+
+/// An account's private key, overwritten with zeros when dropped rather than left in freed memory.
+/// Derefs to the key. `Debug` doesn't print it.
+#[derive(Clone, PartialEq)]
+pub struct PrivateKey(SecretKey);
+
+impl From<SecretKey> for PrivateKey {
+    fn from(key: SecretKey) -> Self {
+        PrivateKey(key)
+    }
+}
+
+impl Deref for PrivateKey {
+    type Target = SecretKey;
+
+    fn deref(&self) -> &SecretKey {
+        &self.0
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        // SecretKey is plain integers with no Zeroize impl of its own, so its bytes are zeroed
+        unsafe {
+            slice::from_raw_parts_mut(
+                &mut self.0 as *mut SecretKey as *mut u8,
+                mem::size_of::<SecretKey>(),
+            )
+        }
+        .zeroize();
+    }
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PrivateKey(<redacted>)")
+    }
+}
+
 pub mod secret_key_serializer {
+    use crate::account::PrivateKey;
     use libsecp256k1::SecretKey;
     use serde::de::Deserialize;
     use serde::de::Deserializer;
     use serde::ser::Serializer;
+    use zeroize::Zeroize;
 
-    pub fn serialize<S>(sk: &SecretKey, serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S>(sk: &PrivateKey, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
         serializer.serialize_bytes(&sk.serialize())
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<SecretKey, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<PrivateKey, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let key = <Vec<u8>>::deserialize(deserializer)?;
-        let sk = SecretKey::parse_slice(&key).map_err(serde::de::Error::custom)?;
-        Ok(sk)
+        let mut key = <Vec<u8>>::deserialize(deserializer)?;
+        let sk = SecretKey::parse_slice(&key).map_err(serde::de::Error::custom);
+        key.zeroize();
+        Ok(PrivateKey::from(sk?))
     }
 }
 
@@ -52,7 +107,7 @@ mod test_account_serialization {
         let account1 = Account {
             username: "test".to_string(),
             api_url: "test.com".to_string(),
-            private_key: SecretKey::random(&mut OsRng),
+            private_key: SecretKey::random(&mut OsRng).into(),
         };
 
         let encoded: Vec<u8> = bincode::serialize(&account1).unwrap();
@@ -60,4 +115,20 @@ mod test_account_serialization {
 
         assert_eq!(account1, account2);
     }
+
+    #[test]
+    fn account_debug_redacts_private_key() {
+        let account = Account {
+            username: "test".to_string(),
+            api_url: "test.com".to_string(),
+            private_key: SecretKey::random(&mut OsRng).into(),
+        };
+
+        let printed = format!("{:?}", account);
+        assert!(printed.contains("test.com"));
+        assert!(!printed.contains(&format!("{:?}", *account.private_key)));
+        assert!(
+            !format!("{:?}", account.private_key).contains(&format!("{:?}", *account.private_key))
+        );
+    }
 }
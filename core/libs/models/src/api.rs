@@ -1,10 +1,12 @@
 use crate::account::Account;
 use crate::account::Username;
 use crate::crypto::*;
-use crate::file_metadata::FileMetadata;
+use crate::file_metadata::{FileMetadata, MetadataSignature};
 use libsecp256k1::PublicKey;
 use reqwest::Method;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 pub trait Request {
@@ -12,6 +14,12 @@ pub trait Request {
     type Error;
     const METHOD: Method;
     const ROUTE: &'static str;
+
+    /// Whether clients that send this request can predate `error`. The server tells those clients
+    /// to update instead of sending them an error they can't read.
+    fn requires_client_update(_error: &Self::Error) -> bool {
+        false
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -29,11 +37,70 @@ pub enum ErrorWrapper<E> {
     InternalError,
     BadRequest,
 }
+
+/// How requests and responses are encoded. A request's `Content-Type` names its encoding and its
+/// `Accept` the encoding it wants the response in. Older clients send neither and get JSON.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WireFormat {
+    Json,
+    Bincode,
+}
+
+#[derive(Debug)]
+pub enum WireError {
+    Json(serde_json::Error),
+    Bincode(bincode::Error),
+}
+
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+pub const BINCODE_CONTENT_TYPE: &str = "application/x-lockbook-bincode";
+
+impl WireFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            WireFormat::Json => JSON_CONTENT_TYPE,
+            WireFormat::Bincode => BINCODE_CONTENT_TYPE,
+        }
+    }
+
+    /// Parses a `Content-Type`, or one of the media types in an `Accept`, ignoring parameters.
+    pub fn from_content_type(content_type: &str) -> Option<WireFormat> {
+        let media_type = content_type.split(';').next().unwrap_or("").trim();
+        if media_type.eq_ignore_ascii_case(JSON_CONTENT_TYPE) {
+            Some(WireFormat::Json)
+        } else if media_type.eq_ignore_ascii_case(BINCODE_CONTENT_TYPE) {
+            Some(WireFormat::Bincode)
+        } else {
+            None
+        }
+    }
+
+    /// The first format in an `Accept` header that's understood.
+    pub fn from_accept(accept: &str) -> Option<WireFormat> {
+        accept.split(',').find_map(WireFormat::from_content_type)
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<Vec<u8>, WireError> {
+        match self {
+            WireFormat::Json => serde_json::to_vec(value).map_err(WireError::Json),
+            WireFormat::Bincode => bincode::serialize(value).map_err(WireError::Bincode),
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, WireError> {
+        match self {
+            WireFormat::Json => serde_json::from_slice(bytes).map_err(WireError::Json),
+            WireFormat::Bincode => bincode::deserialize(bytes).map_err(WireError::Bincode),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct ChangeDocumentContentRequest {
     pub id: Uuid,
     pub old_metadata_version: u64,
     pub new_content: EncryptedDocument,
+    pub content_checksum: Option<[u8; 32]>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -58,6 +125,70 @@ impl Request for ChangeDocumentContentRequest {
     const ROUTE: &'static str = "/change-document-content";
 }
 
+/// Uploads a new version of a document as content-defined chunks. `chunks` lists the ids of the
+/// new version in order, and only chunks the server doesn't already hold for this document need
+/// to be in `new_chunks`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ChangeDocumentContentChunksRequest {
+    pub id: Uuid,
+    pub old_metadata_version: u64,
+    pub chunks: Vec<String>,
+    pub new_chunks: HashMap<String, EncryptedChunk>,
+    pub content_checksum: Option<[u8; 32]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum ChangeDocumentContentChunksError {
+    InvalidUsername,
+    NotPermissioned,
+    UserNotFound,
+    DocumentNotFound,
+    EditConflict,
+    DocumentDeleted,
+    ChunksMissing(Vec<String>),
+}
+
+impl Request for ChangeDocumentContentChunksRequest {
+    type Response = ChangeDocumentContentResponse;
+    type Error = ChangeDocumentContentChunksError;
+    const METHOD: Method = Method::PUT;
+    const ROUTE: &'static str = "/change-document-content-chunks";
+}
+
+/// Streamed requests carry their serialized `RequestWrapper` in this header and the content in the
+/// body. Successful streamed responses carry their serialized result in it instead.
+pub const STREAMED_REQUEST_HEADER: &str = "lockbook-request";
+pub const STREAMED_RESPONSE_HEADER: &str = "lockbook-response";
+
+/// Uploads a new version of a document encrypted with `lockbook_crypto::stream`, the content is
+/// the body of the request.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ChangeDocumentContentStreamRequest {
+    pub id: Uuid,
+    pub old_metadata_version: u64,
+    pub size: u64,
+    pub content_checksum: Option<[u8; 32]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum ChangeDocumentContentStreamError {
+    InvalidUsername,
+    NotPermissioned,
+    UserNotFound,
+    DocumentNotFound,
+    EditConflict,
+    DocumentDeleted,
+    /// The body didn't match `size`, e.g. because the upload was interrupted
+    SizeMismatch,
+}
+
+impl Request for ChangeDocumentContentStreamRequest {
+    type Response = ChangeDocumentContentResponse;
+    type Error = ChangeDocumentContentStreamError;
+    const METHOD: Method = Method::PUT;
+    const ROUTE: &'static str = "/change-document-content-stream";
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct CreateDocumentRequest {
     pub id: Uuid,
@@ -65,6 +196,8 @@ pub struct CreateDocumentRequest {
     pub parent: Uuid,
     pub content: EncryptedDocument,
     pub parent_access_key: EncryptedFolderAccessKey,
+    pub signature: Option<MetadataSignature>,
+    pub content_checksum: Option<[u8; 32]>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -91,6 +224,8 @@ impl CreateDocumentRequest {
             parent: file_metadata.parent,
             content,
             parent_access_key: file_metadata.folder_access_keys.clone(),
+            signature: file_metadata.signature.clone(),
+            content_checksum: file_metadata.content_checksum,
         }
     }
 }
@@ -105,6 +240,7 @@ impl Request for CreateDocumentRequest {
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct DeleteDocumentRequest {
     pub id: Uuid,
+    pub signature: Option<MetadataSignature>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -122,6 +258,15 @@ pub enum DeleteDocumentError {
     DocumentDeleted,
 }
 
+impl DeleteDocumentRequest {
+    pub fn new(file_metadata: &FileMetadata) -> Self {
+        DeleteDocumentRequest {
+            id: file_metadata.id,
+            signature: file_metadata.signature.clone(),
+        }
+    }
+}
+
 impl Request for DeleteDocumentRequest {
     type Response = DeleteDocumentResponse;
     type Error = DeleteDocumentError;
@@ -135,6 +280,7 @@ pub struct MoveDocumentRequest {
     pub old_metadata_version: u64,
     pub new_parent: Uuid,
     pub new_folder_access: EncryptedFolderAccessKey,
+    pub signature: Option<MetadataSignature>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -163,6 +309,7 @@ impl MoveDocumentRequest {
             old_metadata_version: file_metadata.metadata_version,
             new_parent: file_metadata.parent,
             new_folder_access: file_metadata.folder_access_keys.clone(),
+            signature: file_metadata.signature.clone(),
         }
     }
 }
@@ -179,6 +326,7 @@ pub struct RenameDocumentRequest {
     pub id: Uuid,
     pub old_metadata_version: u64,
     pub new_name: SecretFileName,
+    pub signature: Option<MetadataSignature>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -203,6 +351,7 @@ impl RenameDocumentRequest {
             id: file_metadata.id,
             old_metadata_version: file_metadata.metadata_version,
             new_name: file_metadata.name.clone(),
+            signature: file_metadata.signature.clone(),
         }
     }
 }
@@ -228,6 +377,10 @@ pub struct GetDocumentResponse {
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum GetDocumentError {
     DocumentNotFound,
+    /// This version was uploaded in chunks, use `GetDocumentChunksRequest`
+    DocumentIsChunked,
+    /// This version was streamed, use `GetDocumentStreamRequest`
+    DocumentIsStreamed,
 }
 
 impl Request for GetDocumentRequest {
@@ -235,6 +388,80 @@ impl Request for GetDocumentRequest {
     type Error = GetDocumentError;
     const METHOD: Method = Method::GET;
     const ROUTE: &'static str = "/get-document";
+
+    // Only clients from before chunks and streams still send this, and they can't download them
+    fn requires_client_update(error: &GetDocumentError) -> bool {
+        matches!(
+            error,
+            GetDocumentError::DocumentIsChunked | GetDocumentError::DocumentIsStreamed
+        )
+    }
+}
+
+/// Fetches a document version however it was uploaded. For chunked versions, chunks listed in
+/// `known_chunks` are left out of the response.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct GetDocumentChunksRequest {
+    pub id: Uuid,
+    pub content_version: u64,
+    pub known_chunks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum DocumentContent {
+    Whole(EncryptedDocument),
+    Chunked {
+        manifest: DocumentManifest,
+        chunks: HashMap<String, EncryptedChunk>,
+    },
+    /// This version was streamed, use `GetDocumentStreamRequest`
+    Streamed {
+        size: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct GetDocumentChunksResponse {
+    pub content: DocumentContent,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum GetDocumentChunksError {
+    DocumentNotFound,
+}
+
+impl Request for GetDocumentChunksRequest {
+    type Response = GetDocumentChunksResponse;
+    type Error = GetDocumentChunksError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/get-document-chunks";
+}
+
+/// Downloads a version uploaded with `ChangeDocumentContentStreamRequest`. On success the content
+/// is the body of the response.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct GetDocumentStreamRequest {
+    pub id: Uuid,
+    pub content_version: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct GetDocumentStreamResponse {
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum GetDocumentStreamError {
+    DocumentNotFound,
+    /// This version wasn't streamed, use `GetDocumentChunksRequest`
+    DocumentIsNotStreamed,
+}
+
+impl Request for GetDocumentStreamRequest {
+    type Response = GetDocumentStreamResponse;
+    type Error = GetDocumentStreamError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/get-document-stream";
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -243,6 +470,7 @@ pub struct CreateFolderRequest {
     pub name: SecretFileName,
     pub parent: Uuid,
     pub parent_access_key: EncryptedFolderAccessKey,
+    pub signature: Option<MetadataSignature>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -268,6 +496,7 @@ impl CreateFolderRequest {
             name: file_metadata.name.clone(),
             parent: file_metadata.parent,
             parent_access_key: file_metadata.folder_access_keys.clone(),
+            signature: file_metadata.signature.clone(),
         }
     }
 }
@@ -282,6 +511,7 @@ impl Request for CreateFolderRequest {
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct DeleteFolderRequest {
     pub id: Uuid,
+    pub signature: Option<MetadataSignature>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -301,6 +531,15 @@ pub enum DeleteFolderError {
     ClientUpdateRequired,
 }
 
+impl DeleteFolderRequest {
+    pub fn new(file_metadata: &FileMetadata) -> Self {
+        DeleteFolderRequest {
+            id: file_metadata.id,
+            signature: file_metadata.signature.clone(),
+        }
+    }
+}
+
 impl Request for DeleteFolderRequest {
     type Response = DeleteFolderResponse;
     type Error = DeleteFolderError;
@@ -314,6 +553,7 @@ pub struct MoveFolderRequest {
     pub old_metadata_version: u64,
     pub new_parent: Uuid,
     pub new_folder_access: EncryptedFolderAccessKey,
+    pub signature: Option<MetadataSignature>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -343,6 +583,7 @@ impl MoveFolderRequest {
             old_metadata_version: file_metadata.metadata_version,
             new_parent: file_metadata.parent,
             new_folder_access: file_metadata.folder_access_keys.clone(),
+            signature: file_metadata.signature.clone(),
         }
     }
 }
@@ -359,6 +600,7 @@ pub struct RenameFolderRequest {
     pub id: Uuid,
     pub old_metadata_version: u64,
     pub new_name: SecretFileName,
+    pub signature: Option<MetadataSignature>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -384,6 +626,7 @@ impl RenameFolderRequest {
             id: file_metadata.id,
             old_metadata_version: file_metadata.metadata_version,
             new_name: file_metadata.name.clone(),
+            signature: file_metadata.signature.clone(),
         }
     }
 }
@@ -475,6 +718,29 @@ impl Request for GetUpdatesRequest {
     const ROUTE: &'static str = "/get-updates";
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct WaitForUpdatesRequest {
+    pub since_metadata_version: u64,
+    pub timeout_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct WaitForUpdatesResponse {
+    pub most_recent_metadata_version: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum WaitForUpdatesError {
+    UserNotFound,
+}
+
+impl Request for WaitForUpdatesRequest {
+    type Response = WaitForUpdatesResponse;
+    type Error = WaitForUpdatesError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/wait-for-updates";
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct NewAccountRequest {
     pub username: Username,
@@ -542,3 +808,53 @@ impl Request for GetBuildInfoRequest {
     const METHOD: Method = Method::GET;
     const ROUTE: &'static str = "/get-build-info";
 }
+
+#[cfg(test)]
+mod test_wire_format {
+    use crate::api::{
+        ChangeDocumentContentRequest, ChangeDocumentContentResponse, ErrorWrapper, WireFormat,
+        BINCODE_CONTENT_TYPE,
+    };
+    use crate::crypto::EncryptedDocument;
+    use uuid::Uuid;
+
+    #[test]
+    fn formats_round_trip() {
+        let request = ChangeDocumentContentRequest {
+            id: Uuid::new_v4(),
+            old_metadata_version: 7,
+            new_content: EncryptedDocument::new(vec![200u8; 1000], vec![1u8; 12]),
+            content_checksum: Some([3u8; 32]),
+        };
+
+        for format in &[WireFormat::Json, WireFormat::Bincode] {
+            let bytes = format.serialize(&request).unwrap();
+            let decoded: ChangeDocumentContentRequest = format.deserialize(&bytes).unwrap();
+            assert_eq!(decoded, request);
+
+            let response: Result<ChangeDocumentContentResponse, ErrorWrapper<()>> =
+                Err(ErrorWrapper::ExpiredAuth);
+            let bytes = format.serialize(&response).unwrap();
+            let decoded: Result<ChangeDocumentContentResponse, ErrorWrapper<()>> =
+                format.deserialize(&bytes).unwrap();
+            assert_eq!(decoded, response);
+        }
+
+        let json = WireFormat::Json.serialize(&request).unwrap();
+        let bincode = WireFormat::Bincode.serialize(&request).unwrap();
+        assert!(bincode.len() * 3 < json.len());
+    }
+
+    #[test]
+    fn content_negotiation() {
+        assert_eq!(
+            WireFormat::from_content_type("application/json; charset=utf-8"),
+            Some(WireFormat::Json)
+        );
+        assert_eq!(
+            WireFormat::from_accept(&format!("text/html, {}", BINCODE_CONTENT_TYPE)),
+            Some(WireFormat::Bincode)
+        );
+        assert_eq!(WireFormat::from_accept("*/*"), None);
+    }
+}
@@ -1,14 +1,64 @@
 use libsecp256k1::PublicKey;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use zeroize::Zeroize;
 
 pub type AESKey = [u8; 32];
 pub type DecryptedDocument = Vec<u8>;
 pub type EncryptedDocument = AESEncrypted<DecryptedDocument>;
 pub type EncryptedUserAccessKey = AESEncrypted<AESKey>;
 pub type EncryptedFolderAccessKey = AESEncrypted<AESKey>;
+pub type EncryptedChunk = AESEncrypted<DecryptedDocument>;
+pub type SecretAESKey = Secret<AESKey>;
+pub type SecretDocument = Secret<DecryptedDocument>;
+
+/// Key material or plaintext that is overwritten with zeros when dropped, rather than left in freed
+/// memory. Derefs to the value it holds. `Debug` doesn't print the value, so it can't leak through
+/// logged errors.
+#[derive(Clone, PartialEq)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T: Zeroize> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> DerefMut for Secret<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct AESEncrypted<T: DeserializeOwned> {
@@ -65,3 +115,17 @@ impl PartialEq for SecretFileName {
         self.hmac == other.hmac
     }
 }
+
+/// A piece of a document that was uploaded in chunks. `id` is a keyed hash of the chunk's
+/// plaintext, so unchanged chunks keep their id across versions without revealing their content.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ChunkRef {
+    pub id: String,
+    pub size: u64,
+}
+
+/// The chunks that make up one version of a document, in order.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct DocumentManifest {
+    pub chunks: Vec<ChunkRef>,
+}
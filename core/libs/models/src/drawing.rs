@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Drawing {
     pub scale: f32,
     pub translation_x: f32,
@@ -10,7 +10,7 @@ pub struct Drawing {
     pub theme: Option<HashMap<ColorAlias, ColorRGB>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Stroke {
     pub points_x: Vec<f32>,
     pub points_y: Vec<f32>,
@@ -19,7 +19,7 @@ pub struct Stroke {
     pub alpha: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ColorAlias {
     Black,
     Red,
@@ -31,7 +31,7 @@ pub enum ColorAlias {
     White,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ColorRGB {
     pub r: u8,
     pub g: u8,
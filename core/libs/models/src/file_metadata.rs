@@ -1,5 +1,8 @@
 use crate::account::Username;
-use crate::crypto::{EncryptedFolderAccessKey, SecretFileName, UserAccessInfo};
+use crate::crypto::{
+    ECSigned, EncryptedFolderAccessKey, SecretFileName, Timestamped, UserAccessInfo,
+};
+use libsecp256k1::PublicKey;
 use serde::{Deserialize, Serialize};
 use std::clone::Clone;
 use std::collections::HashMap;
@@ -35,4 +38,70 @@ pub struct FileMetadata {
     pub deleted: bool,
     pub user_access_keys: HashMap<Username, UserAccessInfo>,
     pub folder_access_keys: EncryptedFolderAccessKey,
+    /// Signs `signed_fields` as of the owner's last change. Files created before metadata was
+    /// signed have none.
+    #[serde(default)]
+    pub signature: Option<MetadataSignature>,
+    /// HMAC of the content as of `content_version`, keyed with the file's key. Documents written
+    /// before content was checksummed, and folders, have none.
+    #[serde(default)]
+    pub content_checksum: Option<[u8; 32]>,
+}
+
+impl FileMetadata {
+    pub fn signed_fields(&self) -> SignedFields {
+        SignedFields {
+            id: self.id,
+            file_type: self.file_type,
+            parent: self.parent,
+            name: self.name.clone(),
+            owner: self.owner.clone(),
+            deleted: self.deleted,
+        }
+    }
+}
+
+/// The parts of a file's metadata only its owner may change. Versions are assigned by the server
+/// and are checked by clients for going backwards instead. Access keys are left out so that a
+/// move can be signed before the file is re-keyed under its new parent.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SignedFields {
+    pub id: Uuid,
+    pub file_type: FileType,
+    pub parent: Uuid,
+    pub name: SecretFileName,
+    pub owner: String,
+    pub deleted: bool,
+}
+
+/// An `ECSigned<SignedFields>` without the fields, which are read from the metadata it's attached
+/// to.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MetadataSignature {
+    pub timestamp: i64,
+    #[serde(with = "serde_bytes")]
+    pub signature: Vec<u8>,
+    pub public_key: PublicKey,
+}
+
+impl MetadataSignature {
+    pub fn new(signed: ECSigned<SignedFields>) -> Self {
+        MetadataSignature {
+            timestamp: signed.timestamped_value.timestamp,
+            signature: signed.signature,
+            public_key: signed.public_key,
+        }
+    }
+
+    /// The signature as it would be over `fields`, for `pubkey::verify_signature`.
+    pub fn over(&self, fields: SignedFields) -> ECSigned<SignedFields> {
+        ECSigned {
+            timestamped_value: Timestamped {
+                value: fields,
+                timestamp: self.timestamp,
+            },
+            signature: self.signature.clone(),
+            public_key: self.public_key,
+        }
+    }
 }
@@ -23,6 +23,19 @@ impl LocalChange {
     }
 }
 
+/// The parts of a file that a `LocalChange` can be reverted for.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeField {
+    Name,
+    Parent,
+    Content,
+}
+
+impl ChangeField {
+    pub const ALL: [ChangeField; 3] =
+        [ChangeField::Name, ChangeField::Parent, ChangeField::Content];
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Renamed {
     pub old_value: String,
@@ -55,7 +68,9 @@ impl From<Uuid> for Moved {
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Edited {
-    pub old_value: EncryptedDocument, // Stored so sync can perform merges
+    /// Stored so sync can perform merges, `None` when the old version was written as a stream or
+    /// wasn't available offline
+    pub old_value: Option<EncryptedDocument>,
     pub access_info: UserAccessInfo,
     pub old_content_checksum: Vec<u8>,
 }
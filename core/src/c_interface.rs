@@ -1,12 +1,14 @@
 use serde_json::json;
 use std::ffi::{CStr, CString};
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_void};
 use std::str::FromStr;
 use uuid::Uuid;
 
 use crate::get_all_error_variants;
+use crate::json_interface;
 use crate::json_interface::translate;
 use crate::model::state::Config;
+use crate::repo::padding_repo::PaddingScheme;
 use crate::service::drawing_service::SupportedImageFormats;
 use crate::service::path_service::{filter_from_str, Filter};
 use lockbook_models::file_metadata::FileType;
@@ -32,6 +34,7 @@ unsafe fn str_from_ptr(s: *const c_char) -> String {
 unsafe fn config_from_ptr(s: *const c_char) -> Config {
     Config {
         writeable_path: str_from_ptr(s),
+        storage: None,
     }
 }
 
@@ -43,6 +46,10 @@ unsafe fn file_type_from_ptr(s: *const c_char) -> FileType {
     FileType::from_str(&str_from_ptr(s)).expect("Could not String -> FileType")
 }
 
+unsafe fn padding_scheme_from_ptr(s: *const c_char) -> PaddingScheme {
+    PaddingScheme::from_str(&str_from_ptr(s)).expect("Could not String -> PaddingScheme")
+}
+
 unsafe fn filter_from_ptr(s: *const c_char) -> Option<Filter> {
     filter_from_str(&str_from_ptr(s)).expect("Could not String -> Option<Filter>")
 }
@@ -102,16 +109,16 @@ pub unsafe extern "C" fn import_account(
 
 #[no_mangle]
 pub unsafe extern "C" fn export_account(writeable_path: *const c_char) -> *const c_char {
-    c_string(translate(crate::export_account(&Config {
-        writeable_path: str_from_ptr(writeable_path),
-    })))
+    c_string(translate(crate::export_account(&config_from_ptr(
+        writeable_path,
+    ))))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn get_account(writeable_path: *const c_char) -> *const c_char {
-    c_string(translate(crate::get_account(&Config {
-        writeable_path: str_from_ptr(writeable_path),
-    })))
+    c_string(translate(crate::get_account(&config_from_ptr(
+        writeable_path,
+    ))))
 }
 
 #[no_mangle]
@@ -155,9 +162,7 @@ pub unsafe extern "C" fn create_file(
 
 #[no_mangle]
 pub unsafe extern "C" fn get_root(writeable_path: *const c_char) -> *const c_char {
-    c_string(translate(crate::get_root(&Config {
-        writeable_path: str_from_ptr(writeable_path),
-    })))
+    c_string(translate(crate::get_root(&config_from_ptr(writeable_path))))
 }
 
 #[no_mangle]
@@ -263,9 +268,9 @@ pub unsafe extern "C" fn move_file(
 
 #[no_mangle]
 pub unsafe extern "C" fn calculate_work(writeable_path: *const c_char) -> *const c_char {
-    c_string(translate(crate::calculate_work(&Config {
-        writeable_path: str_from_ptr(writeable_path),
-    })))
+    c_string(translate(crate::calculate_work(&config_from_ptr(
+        writeable_path,
+    ))))
 }
 
 #[no_mangle]
@@ -274,6 +279,13 @@ pub unsafe extern "C" fn sync_all(writeable_path: *const c_char) -> *const c_cha
     c_string(translate(crate::sync_all(config, None)))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn cancel_sync(writeable_path: *const c_char) -> *const c_char {
+    c_string(translate(crate::cancel_sync(&config_from_ptr(
+        writeable_path,
+    ))))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn set_last_synced(
     writeable_path: *const c_char,
@@ -287,35 +299,197 @@ pub unsafe extern "C" fn set_last_synced(
 
 #[no_mangle]
 pub unsafe extern "C" fn get_last_synced(writeable_path: *const c_char) -> *const c_char {
-    c_string(translate(crate::get_last_synced(&Config {
-        writeable_path: str_from_ptr(writeable_path),
-    })))
+    c_string(translate(crate::get_last_synced(&config_from_ptr(
+        writeable_path,
+    ))))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn get_last_synced_human_string(
     writeable_path: *const c_char,
 ) -> *const c_char {
-    c_string(translate(crate::get_last_synced_human_string(&Config {
-        writeable_path: str_from_ptr(writeable_path),
-    })))
+    c_string(translate(crate::get_last_synced_human_string(
+        &config_from_ptr(writeable_path),
+    )))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn get_usage(writeable_path: *const c_char) -> *const c_char {
-    c_string(translate(crate::get_usage(&Config {
-        writeable_path: str_from_ptr(writeable_path),
-    })))
+    c_string(translate(crate::get_usage(&config_from_ptr(
+        writeable_path,
+    ))))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn get_local_changes(writeable_path: *const c_char) -> *const c_char {
-    c_string(translate(crate::get_local_changes(&Config {
-        writeable_path: str_from_ptr(writeable_path),
-    })))
+    c_string(translate(crate::get_local_changes(&config_from_ptr(
+        writeable_path,
+    ))))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wait_for_remote_changes(
+    writeable_path: *const c_char,
+    timeout_ms: u64,
+) -> *const c_char {
+    c_string(translate(crate::wait_for_remote_changes(
+        &config_from_ptr(writeable_path),
+        timeout_ms,
+    )))
+}
+
+/// Called with a subscription's `context` and each event as JSON. The string belongs to core and
+/// is only valid for the duration of the call.
+pub type EventCallback = unsafe extern "C" fn(context: *mut c_void, event: *const c_char);
+
+/// `context` is passed back to `callback` untouched. It must stay valid until `unsubscribe`, and
+/// `callback` must be safe to call from any thread, as events fire on whichever thread made the
+/// change.
+#[no_mangle]
+pub unsafe extern "C" fn subscribe(
+    writeable_path: *const c_char,
+    callback: EventCallback,
+    context: *mut c_void,
+) -> *const c_char {
+    let context = context as usize;
+    c_string(translate(crate::subscribe(
+        &config_from_ptr(writeable_path),
+        Box::new(move |event| {
+            let event =
+                CString::new(json!(event).to_string()).expect("Could not Rust String -> C String");
+            callback(context as *mut c_void, event.as_ptr());
+        }),
+    )))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn unsubscribe(subscription_id: u64) -> *const c_char {
+    c_string(translate(crate::unsubscribe(subscription_id)))
+}
+
+/// `callback` is called with `context` and each `SyncSchedulerEvent` as JSON, on the scheduler's
+/// thread. `context` must stay valid until `stop_sync_scheduler`.
+#[no_mangle]
+pub unsafe extern "C" fn start_sync_scheduler(
+    writeable_path: *const c_char,
+    callback: EventCallback,
+    context: *mut c_void,
+) -> *const c_char {
+    let context = context as usize;
+    c_string(translate(crate::start_sync_scheduler(
+        &config_from_ptr(writeable_path),
+        Box::new(move |event| {
+            let event =
+                CString::new(json!(event).to_string()).expect("Could not Rust String -> C String");
+            callback(context as *mut c_void, event.as_ptr());
+        }),
+    )))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sync_now(writeable_path: *const c_char) -> *const c_char {
+    c_string(translate(crate::sync_now(&config_from_ptr(writeable_path))))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn stop_sync_scheduler(writeable_path: *const c_char) -> *const c_char {
+    c_string(translate(crate::stop_sync_scheduler(&config_from_ptr(
+        writeable_path,
+    ))))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_lazy_sync(writeable_path: *const c_char, lazy: bool) -> *const c_char {
+    c_string(translate(crate::set_lazy_sync(
+        &config_from_ptr(writeable_path),
+        lazy,
+    )))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_lazy_sync(writeable_path: *const c_char) -> *const c_char {
+    c_string(translate(crate::get_lazy_sync(&config_from_ptr(
+        writeable_path,
+    ))))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_padding_scheme(
+    writeable_path: *const c_char,
+    scheme: *const c_char,
+) -> *const c_char {
+    c_string(translate(crate::set_padding_scheme(
+        &config_from_ptr(writeable_path),
+        padding_scheme_from_ptr(scheme),
+    )))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_padding_scheme(writeable_path: *const c_char) -> *const c_char {
+    c_string(translate(crate::get_padding_scheme(&config_from_ptr(
+        writeable_path,
+    ))))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_case_insensitive_names(
+    writeable_path: *const c_char,
+    case_insensitive: bool,
+) -> *const c_char {
+    c_string(translate(crate::set_case_insensitive_names(
+        &config_from_ptr(writeable_path),
+        case_insensitive,
+    )))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_case_insensitive_names(
+    writeable_path: *const c_char,
+) -> *const c_char {
+    c_string(translate(crate::get_case_insensitive_names(
+        &config_from_ptr(writeable_path),
+    )))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn pin_file(
+    writeable_path: *const c_char,
+    id: *const c_char,
+) -> *const c_char {
+    c_string(translate(crate::pin_file(
+        &config_from_ptr(writeable_path),
+        uuid_from_ptr(id),
+    )))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn unpin_file(
+    writeable_path: *const c_char,
+    id: *const c_char,
+) -> *const c_char {
+    c_string(translate(crate::unpin_file(
+        &config_from_ptr(writeable_path),
+        uuid_from_ptr(id),
+    )))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_pinned_files(writeable_path: *const c_char) -> *const c_char {
+    c_string(translate(crate::get_pinned_files(&config_from_ptr(
+        writeable_path,
+    ))))
+}
+
+/// Runs any core function by name, see `json_interface::call`.
+#[no_mangle]
+pub unsafe extern "C" fn call(method: *const c_char, json_args: *const c_char) -> *const c_char {
+    c_string(json_interface::call(
+        &str_from_ptr(method),
+        &str_from_ptr(json_args),
+    ))
 }
 
-// FOR INTEGRATION TESTS ONLY
+// Error variants for integration tests, and the error catalog
 #[no_mangle]
 pub unsafe extern "C" fn get_variants() -> *const c_char {
     json_c_string(get_all_error_variants())
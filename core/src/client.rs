@@ -4,10 +4,14 @@ use lockbook_crypto::pubkey;
 use lockbook_crypto::pubkey::ECSignError;
 use lockbook_models::account::Account;
 use lockbook_models::api::*;
-use reqwest::blocking::Client as ReqwestClient;
+use reqwest::blocking::{Body, Client as ReqwestClient, Response};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
 use reqwest::Error as ReqwestError;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::io::{Read, Write};
+
+const RESPONSE_CHUNK_SIZE: usize = 64 * 1024;
 
 impl<E> From<ErrorWrapper<E>> for ApiError<E> {
     fn from(err: ErrorWrapper<E>) -> Self {
@@ -31,10 +35,13 @@ pub enum ApiError<E> {
     InternalError,
     BadRequest,
     Sign(ECSignError),
-    Serialize(serde_json::error::Error),
+    Serialize(WireError),
     SendFailed(ReqwestError),
     ReceiveFailed(ReqwestError),
-    Deserialize(serde_json::error::Error),
+    ReadFailed(std::io::Error),
+    WriteFailed(std::io::Error),
+    Cancelled,
+    Deserialize(WireError),
 }
 
 pub fn request<
@@ -46,34 +53,222 @@ pub fn request<
     request_helper(account, request, get_code_version, get_time)
 }
 
-fn request_helper<
+/// Like `request`, but reads the response in chunks and reports `(bytes_received, total_bytes)`
+/// after each one. `total_bytes` is 0 if the server didn't say. Returning `false` from
+/// `on_progress` abandons the request with `ApiError::Cancelled`.
+pub fn request_with_progress<
+    T: Request<Response = impl DeserializeOwned, Error = impl DeserializeOwned> + Serialize,
+>(
+    account: &Account,
+    request: T,
+    on_progress: &mut dyn FnMut(u64, u64) -> bool,
+) -> Result<T::Response, ApiError<T::Error>> {
+    request_helper_with_progress(account, request, get_code_version, get_time, on_progress)
+}
+
+/// Sends a streamed request, with `size` bytes read from `content` as the body.
+pub fn upload_stream<
+    T: Request<Response = impl DeserializeOwned, Error = impl DeserializeOwned> + Serialize,
+>(
+    account: &Account,
+    request: T,
+    content: impl Read + Send + 'static,
+    size: u64,
+) -> Result<T::Response, ApiError<T::Error>> {
+    // Headers are text, so streamed requests are always JSON
+    let serialized_request = serialize_request(
+        account,
+        request,
+        WireFormat::Json,
+        get_code_version,
+        get_time,
+    )?;
+    let mut response = ReqwestClient::new()
+        .request(
+            T::METHOD,
+            format!("{}{}", account.api_url, T::ROUTE).as_str(),
+        )
+        .header(STREAMED_REQUEST_HEADER, serialized_request)
+        .body(Body::sized(content, size))
+        .send()
+        .map_err(ApiError::SendFailed)?;
+
+    let format = response_format(&response);
+    let mut serialized_response = vec![];
+    response
+        .read_to_end(&mut serialized_response)
+        .map_err(ApiError::ReadFailed)?;
+    let response: Result<T::Response, ErrorWrapper<T::Error>> = format
+        .deserialize(&serialized_response)
+        .map_err(ApiError::Deserialize)?;
+    response.map_err(ApiError::from)
+}
+
+/// Sends a streamed request and writes the content the server responds with to `destination`,
+/// reporting progress as `request_with_progress` does.
+pub fn download_stream<
     T: Request<Response = impl DeserializeOwned, Error = impl DeserializeOwned> + Serialize,
 >(
     account: &Account,
     request: T,
+    destination: &mut dyn Write,
+    on_progress: &mut dyn FnMut(u64, u64) -> bool,
+) -> Result<T::Response, ApiError<T::Error>> {
+    let serialized_request = serialize_request(
+        account,
+        request,
+        WireFormat::Json,
+        get_code_version,
+        get_time,
+    )?;
+    let mut response = ReqwestClient::new()
+        .request(
+            T::METHOD,
+            format!("{}{}", account.api_url, T::ROUTE).as_str(),
+        )
+        .header(STREAMED_REQUEST_HEADER, serialized_request)
+        .send()
+        .map_err(ApiError::SendFailed)?;
+
+    // Only successful responses are streamed, errors come back like any other response
+    let (format, serialized_response) = match response.headers().get(STREAMED_RESPONSE_HEADER) {
+        Some(header) => (WireFormat::Json, header.as_bytes().to_vec()),
+        None => {
+            let format = response_format(&response);
+            let mut serialized_response = vec![];
+            response
+                .read_to_end(&mut serialized_response)
+                .map_err(ApiError::ReadFailed)?;
+            (format, serialized_response)
+        }
+    };
+    let result: Result<T::Response, ErrorWrapper<T::Error>> = format
+        .deserialize(&serialized_response)
+        .map_err(ApiError::Deserialize)?;
+    let result = result.map_err(ApiError::from)?;
+
+    let total_bytes = response.content_length().unwrap_or(0);
+    let mut received = 0;
+    let mut chunk = vec![0u8; RESPONSE_CHUNK_SIZE];
+    loop {
+        let read = response.read(&mut chunk).map_err(ApiError::ReadFailed)?;
+        if read == 0 {
+            break;
+        }
+        destination
+            .write_all(&chunk[..read])
+            .map_err(ApiError::WriteFailed)?;
+        received += read as u64;
+        if !on_progress(received, total_bytes) {
+            return Err(ApiError::Cancelled);
+        }
+    }
+
+    Ok(result)
+}
+
+fn serialize_request<T: Request + Serialize>(
+    account: &Account,
+    request: T,
+    format: WireFormat,
     get_code_version: fn() -> &'static str,
     get_time: fn() -> Timestamp,
-) -> Result<T::Response, ApiError<T::Error>> {
-    let client = ReqwestClient::new();
+) -> Result<Vec<u8>, ApiError<T::Error>> {
+    format
+        .serialize(&sign_request(account, request, get_code_version, get_time)?)
+        .map_err(ApiError::Serialize)
+}
+
+fn sign_request<T: Request + Serialize>(
+    account: &Account,
+    request: T,
+    get_code_version: fn() -> &'static str,
+    get_time: fn() -> Timestamp,
+) -> Result<RequestWrapper<T>, ApiError<T::Error>> {
     let signed_request =
         pubkey::sign(&account.private_key, request, get_time).map_err(ApiError::Sign)?;
-    let serialized_request = serde_json::to_vec(&RequestWrapper {
+    Ok(RequestWrapper {
         signed_request,
         client_version: String::from(get_code_version()),
     })
-    .map_err(ApiError::Serialize)?;
-    let serialized_response = client
+}
+
+/// Servers that predate content negotiation respond with JSON and don't say so.
+fn response_format(response: &Response) -> WireFormat {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(WireFormat::from_content_type)
+        .unwrap_or(WireFormat::Json)
+}
+
+fn request_helper<
+    T: Request<Response = impl DeserializeOwned, Error = impl DeserializeOwned> + Serialize,
+>(
+    account: &Account,
+    request: T,
+    get_code_version: fn() -> &'static str,
+    get_time: fn() -> Timestamp,
+) -> Result<T::Response, ApiError<T::Error>> {
+    request_helper_with_progress(account, request, get_code_version, get_time, &mut |_, _| {
+        true
+    })
+}
+
+fn request_helper_with_progress<
+    T: Request<Response = impl DeserializeOwned, Error = impl DeserializeOwned> + Serialize,
+>(
+    account: &Account,
+    request: T,
+    get_code_version: fn() -> &'static str,
+    get_time: fn() -> Timestamp,
+    on_progress: &mut dyn FnMut(u64, u64) -> bool,
+) -> Result<T::Response, ApiError<T::Error>> {
+    let request = sign_request(account, request, get_code_version, get_time)?;
+    match send(account, &request, WireFormat::Bincode, on_progress) {
+        // Servers that predate bincode read every request as JSON, and find this one bad
+        Err(ApiError::BadRequest) => send(account, &request, WireFormat::Json, on_progress),
+        result => result,
+    }
+}
+
+fn send<T: Request<Response = impl DeserializeOwned, Error = impl DeserializeOwned> + Serialize>(
+    account: &Account,
+    request: &RequestWrapper<T>,
+    format: WireFormat,
+    on_progress: &mut dyn FnMut(u64, u64) -> bool,
+) -> Result<T::Response, ApiError<T::Error>> {
+    let serialized_request = format.serialize(request).map_err(ApiError::Serialize)?;
+    let mut response = ReqwestClient::new()
         .request(
             T::METHOD,
             format!("{}{}", account.api_url, T::ROUTE).as_str(),
         )
+        .header(CONTENT_TYPE, format.content_type())
+        .header(ACCEPT, format.content_type())
         .body(serialized_request)
         .send()
-        .map_err(ApiError::SendFailed)?
-        .bytes()
-        .map_err(ApiError::ReceiveFailed)?;
-    let response: Result<T::Response, ErrorWrapper<T::Error>> =
-        serde_json::from_slice(&serialized_response).map_err(ApiError::Deserialize)?;
+        .map_err(ApiError::SendFailed)?;
+
+    let format = response_format(&response);
+    let total_bytes = response.content_length().unwrap_or(0);
+    let mut serialized_response = Vec::with_capacity(total_bytes as usize);
+    let mut chunk = vec![0u8; RESPONSE_CHUNK_SIZE];
+    loop {
+        let read = response.read(&mut chunk).map_err(ApiError::ReadFailed)?;
+        if read == 0 {
+            break;
+        }
+        serialized_response.extend_from_slice(&chunk[..read]);
+        if !on_progress(serialized_response.len() as u64, total_bytes) {
+            return Err(ApiError::Cancelled);
+        }
+    }
+
+    let response: Result<T::Response, ErrorWrapper<T::Error>> = format
+        .deserialize(&serialized_response)
+        .map_err(ApiError::Deserialize)?;
     response.map_err(ApiError::from)
 }
 
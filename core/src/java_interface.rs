@@ -3,20 +3,25 @@
 use std::path::Path;
 
 use jni::objects::{JClass, JObject, JString, JValue};
-use jni::sys::{jlong, jstring};
+use jni::sys::{jboolean, jlong, jstring};
 use jni::JNIEnv;
 use uuid::Uuid;
 
+use crate::json_interface;
 use crate::json_interface::translate;
 use crate::model::state::Config;
 use crate::service::drawing_service::SupportedImageFormats;
+use crate::service::event_service::ChangeEvent;
+use crate::service::sync_scheduler_service::SyncSchedulerEvent;
 use crate::service::sync_service::SyncProgress;
 use crate::{
-    calculate_work, create_account, create_file, delete_file, export_account, export_drawing,
-    export_drawing_to_disk, get_account, get_all_error_variants, get_children, get_db_state,
-    get_file_by_id, get_root, get_uncompressed_usage, get_usage, import_account, init_logger,
-    migrate_db, move_file, read_document, rename_file, save_document_to_disk, set_last_synced,
-    sync_all, write_document, Error,
+    calculate_work, cancel_sync, create_account, create_file, delete_file, export_account,
+    export_drawing, export_drawing_to_disk, get_account, get_all_error_variants, get_children,
+    get_db_state, get_file_by_id, get_lazy_sync, get_pinned_files, get_root,
+    get_uncompressed_usage, get_usage, import_account, init_logger, migrate_db, move_file,
+    pin_file, read_document, rename_file, save_document_to_disk, set_last_synced, set_lazy_sync,
+    start_sync_scheduler, stop_sync_scheduler, subscribe, sync_all, sync_now, unpin_file,
+    unsubscribe, wait_for_remote_changes, write_document, Error,
 };
 use basic_human_duration::ChronoHumanDuration;
 use chrono::Duration;
@@ -580,6 +585,24 @@ pub extern "system" fn Java_app_lockbook_core_CoreKt_syncAll(
                 args.as_slice(),
             )
             .unwrap();
+
+        let phase = env_c
+            .new_string(format!("{:?}", sync_progress.phase))
+            .unwrap();
+        let args = [
+            JValue::Object(phase.into()),
+            JValue::Long(sync_progress.bytes_transferred as jlong),
+            JValue::Long(sync_progress.total_bytes as jlong),
+        ]
+        .to_vec();
+        env_c
+            .call_method(
+                jsyncmodel,
+                "updateSyncPhaseAndBytes",
+                "(Ljava/lang/String;JJ)V",
+                args.as_slice(),
+            )
+            .unwrap();
     };
 
     string_to_jstring(&env, translate(sync_all(&config, Some(Box::new(closure)))))
@@ -599,6 +622,20 @@ pub extern "system" fn Java_app_lockbook_core_CoreKt_backgroundSync(
     string_to_jstring(&env, translate(sync_all(&config, None)))
 }
 
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_cancelSync(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(&env, translate(cancel_sync(&config)))
+}
+
 #[no_mangle]
 pub extern "system" fn Java_app_lockbook_core_CoreKt_calculateWork(
     env: JNIEnv,
@@ -613,6 +650,267 @@ pub extern "system" fn Java_app_lockbook_core_CoreKt_calculateWork(
     string_to_jstring(&env, translate(calculate_work(&config)))
 }
 
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_waitForRemoteChanges(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+    jtimeout: jlong,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(
+        &env,
+        translate(wait_for_remote_changes(&config, jtimeout as u64)),
+    )
+}
+
+/// `jlistener` is called back through `onCoreEvent(String)` with each event as JSON.
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_subscribe(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+    jlistener: JObject,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+    let (vm, listener) = match env
+        .get_java_vm()
+        .and_then(|vm| env.new_global_ref(jlistener).map(|listener| (vm, listener)))
+    {
+        Ok(ok) => ok,
+        Err(err) => {
+            return string_to_jstring(
+                &env,
+                translate::<(), Error<()>>(Err(Error::<()>::Unexpected(format!(
+                    "Couldn't hold on to listener: {:?}",
+                    err
+                )))),
+            )
+        }
+    };
+
+    // Events fire on whichever thread made the change, which the JVM may not know about yet
+    let callback = move |event: &ChangeEvent| {
+        let result = vm.attach_current_thread_permanently().and_then(|env| {
+            let event = env.new_string(
+                serde_json::to_string(event).expect("Couldn't serialize event into string!"),
+            )?;
+            env.call_method(
+                listener.as_obj(),
+                "onCoreEvent",
+                "(Ljava/lang/String;)V",
+                &[JValue::Object(event.into())],
+            )
+            .map(|_| ())
+        });
+        if let Err(err) = result {
+            error!("Could not deliver {:?} to the JVM: {:?}", event, err);
+        }
+    };
+
+    string_to_jstring(&env, translate(subscribe(&config, Box::new(callback))))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_unsubscribe(
+    env: JNIEnv,
+    _: JClass,
+    jsubscriptionid: jlong,
+) -> jstring {
+    string_to_jstring(&env, translate(unsubscribe(jsubscriptionid as u64)))
+}
+
+/// `jlistener` is called back through `onSyncSchedulerEvent(String)` with each event as JSON.
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_startSyncScheduler(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+    jlistener: JObject,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+    let (vm, listener) = match env
+        .get_java_vm()
+        .and_then(|vm| env.new_global_ref(jlistener).map(|listener| (vm, listener)))
+    {
+        Ok(ok) => ok,
+        Err(err) => {
+            return string_to_jstring(
+                &env,
+                translate::<(), Error<()>>(Err(Error::<()>::Unexpected(format!(
+                    "Couldn't hold on to listener: {:?}",
+                    err
+                )))),
+            )
+        }
+    };
+
+    // Events fire on the scheduler's thread, which the JVM doesn't know about yet
+    let callback = move |event: SyncSchedulerEvent| {
+        let result = vm.attach_current_thread_permanently().and_then(|env| {
+            let event = env.new_string(
+                serde_json::to_string(&event).expect("Couldn't serialize event into string!"),
+            )?;
+            env.call_method(
+                listener.as_obj(),
+                "onSyncSchedulerEvent",
+                "(Ljava/lang/String;)V",
+                &[JValue::Object(event.into())],
+            )
+            .map(|_| ())
+        });
+        if let Err(err) = result {
+            error!("Could not deliver {:?} to the JVM: {:?}", event, err);
+        }
+    };
+
+    string_to_jstring(
+        &env,
+        translate(start_sync_scheduler(&config, Box::new(callback))),
+    )
+}
+
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_syncNow(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(&env, translate(sync_now(&config)))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_stopSyncScheduler(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(&env, translate(stop_sync_scheduler(&config)))
+}
+
+/// Runs any core function by name, see `json_interface::call`.
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_call(
+    env: JNIEnv,
+    _: JClass,
+    jmethod: JString,
+    jargs: JString,
+) -> jstring {
+    let method = match jstring_to_string(&env, jmethod, "method") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+    let args = match jstring_to_string(&env, jargs, "args") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(&env, json_interface::call(&method, &args))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_setLazySync(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+    jlazy: jboolean,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(&env, translate(set_lazy_sync(&config, jlazy != 0)))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_getLazySync(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(&env, translate(get_lazy_sync(&config)))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_pinFile(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+    jid: JString,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    let id = match deserialize_id(&env, jid) {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(&env, translate(pin_file(&config, id)))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_unpinFile(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+    jid: JString,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    let id = match deserialize_id(&env, jid) {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(&env, translate(unpin_file(&config, id)))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_app_lockbook_core_CoreKt_getPinnedFiles(
+    env: JNIEnv,
+    _: JClass,
+    jconfig: JString,
+) -> jstring {
+    let config = match deserialize::<Config>(&env, jconfig, "config") {
+        Ok(ok) => ok,
+        Err(err) => return err,
+    };
+
+    string_to_jstring(&env, translate(get_pinned_files(&config)))
+}
+
 #[no_mangle]
 pub extern "system" fn Java_app_lockbook_core_CoreKt_getAllErrorVariants(
     env: JNIEnv,
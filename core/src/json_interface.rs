@@ -1,5 +1,19 @@
+use serde::de::DeserializeOwned;
 use serde::Serialize;
-use serde_json::json;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::model::state::Config;
+use crate::repo::padding_repo::PaddingScheme;
+use crate::service::drawing_service::SupportedImageFormats;
+use crate::service::integrity_service::RepairPolicy;
+use crate::service::path_service::filter_from_str;
+use crate::Error;
+use lockbook_models::drawing::{ColorAlias, ColorRGB};
+use lockbook_models::file_metadata::FileType;
+use lockbook_models::local_changes::ChangeField;
 
 pub fn translate<T, E>(intermediate: Result<T, E>) -> String
 where
@@ -18,3 +32,208 @@ where
     }
     .to_string()
 }
+
+fn unexpected(msg: String) -> String {
+    translate::<(), Error<()>>(Err(Error::<()>::Unexpected(msg)))
+}
+
+fn arg<T: DeserializeOwned>(args: &Map<String, Value>, name: &str) -> Result<T, String> {
+    // Missing arguments are null, so that optional ones can be left out
+    serde_json::from_value(args.get(name).cloned().unwrap_or(Value::Null))
+        .map_err(|err| format!("Couldn't deserialize {}: {:?}", name, err))
+}
+
+// Generates `call`, which runs the function named `method` with arguments taken from `json_args`,
+// a JSON object keyed by parameter name. Every binding goes through this, so a function listed
+// here is available everywhere at once.
+macro_rules! dispatch {
+    ( $( $method:ident ( $( $arg:ident : $ty:ty ),* ) => $call:expr ,)* ) => {
+        pub const METHODS: &[&str] = &[ $( stringify!($method), )* ];
+
+        pub fn call(method: &str, json_args: &str) -> String {
+            let args = match serde_json::from_str::<Map<String, Value>>(json_args) {
+                Ok(args) => args,
+                Err(err) => return unexpected(format!("Couldn't deserialize args: {:?}", err)),
+            };
+
+            match method {
+                $(
+                    stringify!($method) => {
+                        $(
+                            let $arg: $ty = match arg(&args, stringify!($arg)) {
+                                Ok(value) => value,
+                                Err(msg) => return unexpected(msg),
+                            };
+                        )*
+                        translate($call)
+                    }
+                )*
+                _ => unexpected(format!("Unknown method: {}", method)),
+            }
+        }
+    };
+}
+
+/// `pub fn`s in lib.rs that can't be called with JSON, and why.
+pub const NOT_DISPATCHED: &[(&str, &str)] = &[
+    (
+        "write_document_from",
+        "reads from a Read, use write_document",
+    ),
+    ("read_document_to", "writes to a Write, use read_document"),
+    (
+        "subscribe",
+        "takes a callback, each binding bridges it itself",
+    ),
+    (
+        "start_sync_scheduler",
+        "takes a callback, each binding bridges it itself",
+    ),
+];
+
+dispatch! {
+    init_logger(log_path: String) => crate::init_logger(Path::new(&log_path)),
+    get_db_state(config: Config) => crate::get_db_state(&config),
+    migrate_db(config: Config) => crate::migrate_db(&config),
+    create_account(config: Config, username: String, api_url: String) =>
+        crate::create_account(&config, &username, &api_url),
+    import_account(config: Config, account_string: String) =>
+        crate::import_account(&config, &account_string),
+    export_account(config: Config) => crate::export_account(&config),
+    get_account(config: Config) => crate::get_account(&config),
+    list_profiles(config: Config) => crate::list_profiles(&config),
+    create_profile(config: Config, name: String) => crate::create_profile(&config, &name),
+    switch_profile(config: Config, name: String) => crate::switch_profile(&config, &name),
+    remove_profile(config: Config, name: String) => crate::remove_profile(&config, &name),
+    get_active_profile(config: Config) => crate::get_active_profile(&config),
+    get_profile_config(config: Config, name: Option<String>) =>
+        crate::get_profile_config(&config, name.as_deref()),
+    create_file_at_path(config: Config, path_and_name: String) =>
+        crate::create_file_at_path(&config, &path_and_name),
+    write_document(config: Config, id: Uuid, content: Vec<u8>) =>
+        crate::write_document(&config, id, &content),
+    create_file(config: Config, name: String, parent: Uuid, file_type: FileType) =>
+        crate::create_file(&config, &name, parent, file_type),
+    get_root(config: Config) => crate::get_root(&config),
+    get_children(config: Config, id: Uuid) => crate::get_children(&config, id),
+    get_and_get_children_recursively(config: Config, id: Uuid) =>
+        crate::get_and_get_children_recursively(&config, id),
+    get_file_by_id(config: Config, id: Uuid) => crate::get_file_by_id(&config, id),
+    get_file_by_path(config: Config, path: String) => crate::get_file_by_path(&config, &path),
+    delete_file(config: Config, id: Uuid) => crate::delete_file(&config, id),
+    read_document(config: Config, id: Uuid) => crate::read_document(&config, id),
+    save_document_to_disk(config: Config, id: Uuid, location: String) =>
+        crate::save_document_to_disk(&config, id, location),
+    list_paths(config: Config, filter: Option<String>) => match filter.as_deref().map(filter_from_str) {
+        Some(Err(err)) => return unexpected(format!("Couldn't deserialize filter: {:?}", err)),
+        Some(Ok(filter)) => crate::list_paths(&config, filter),
+        None => crate::list_paths(&config, None),
+    },
+    list_metadatas(config: Config) => crate::list_metadatas(&config),
+    rename_file(config: Config, id: Uuid, new_name: String) =>
+        crate::rename_file(&config, id, &new_name),
+    move_file(config: Config, id: Uuid, new_parent: Uuid) =>
+        crate::move_file(&config, id, new_parent),
+    revert_local_change(config: Config, id: Uuid, fields: Vec<ChangeField>) =>
+        crate::revert_local_change(&config, id, &fields),
+    revert_all_local_changes(config: Config) => crate::revert_all_local_changes(&config),
+    sync_all(config: Config) => crate::sync_all(&config, None),
+    cancel_sync(config: Config) => crate::cancel_sync(&config),
+    unsubscribe(subscription_id: u64) => crate::unsubscribe(subscription_id),
+    sync_now(config: Config) => crate::sync_now(&config),
+    stop_sync_scheduler(config: Config) => crate::stop_sync_scheduler(&config),
+    wait_for_remote_changes(config: Config, timeout_ms: u64) =>
+        crate::wait_for_remote_changes(&config, timeout_ms),
+    set_lazy_sync(config: Config, lazy: bool) => crate::set_lazy_sync(&config, lazy),
+    get_lazy_sync(config: Config) => crate::get_lazy_sync(&config),
+    set_padding_scheme(config: Config, scheme: PaddingScheme) =>
+        crate::set_padding_scheme(&config, scheme),
+    get_padding_scheme(config: Config) => crate::get_padding_scheme(&config),
+    set_case_insensitive_names(config: Config, case_insensitive: bool) =>
+        crate::set_case_insensitive_names(&config, case_insensitive),
+    get_case_insensitive_names(config: Config) => crate::get_case_insensitive_names(&config),
+    pin_file(config: Config, id: Uuid) => crate::pin_file(&config, id),
+    unpin_file(config: Config, id: Uuid) => crate::unpin_file(&config, id),
+    get_pinned_files(config: Config) => crate::get_pinned_files(&config),
+    set_sync_exclusions(config: Config, ids: Vec<Uuid>) =>
+        crate::set_sync_exclusions(&config, ids),
+    get_sync_exclusions(config: Config) => crate::get_sync_exclusions(&config),
+    get_local_changes(config: Config) => crate::get_local_changes(&config),
+    get_local_change_details(config: Config) => crate::get_local_change_details(&config),
+    diff_local_document(config: Config, id: Uuid) => crate::diff_local_document(&config, id),
+    calculate_work(config: Config) => crate::calculate_work(&config),
+    set_last_synced(config: Config, last_sync: u64) => crate::set_last_synced(&config, last_sync),
+    get_last_synced(config: Config) => crate::get_last_synced(&config),
+    get_last_synced_human_string(config: Config) => crate::get_last_synced_human_string(&config),
+    get_usage(config: Config) => crate::get_usage(&config),
+    get_uncompressed_usage(config: Config) => crate::get_uncompressed_usage(&config),
+    get_drawing(config: Config, id: Uuid) => crate::get_drawing(&config, id),
+    save_drawing(config: Config, id: Uuid, drawing_bytes: Vec<u8>) =>
+        crate::save_drawing(&config, id, &drawing_bytes),
+    export_drawing(
+        config: Config,
+        id: Uuid,
+        format: SupportedImageFormats,
+        render_theme: Option<HashMap<ColorAlias, ColorRGB>>
+    ) => crate::export_drawing(&config, id, format, render_theme),
+    export_drawing_to_disk(
+        config: Config,
+        id: Uuid,
+        format: SupportedImageFormats,
+        render_theme: Option<HashMap<ColorAlias, ColorRGB>>,
+        location: String
+    ) => crate::export_drawing_to_disk(&config, id, format, render_theme, location),
+    test_repo_integrity(config: Config) => crate::test_repo_integrity(&config),
+    repair_repo(config: Config, policy: RepairPolicy) => crate::repair_repo(&config, policy),
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::json_interface::{call, METHODS, NOT_DISPATCHED};
+    use crate::model::state::temp_config;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn every_pub_fn_is_dispatched() {
+        let unregistered = include_str!("lib.rs")
+            .lines()
+            .filter_map(|line| line.strip_prefix("pub fn "))
+            .map(|rest| rest.split(|c| c == '(' || c == '<').next().unwrap())
+            .filter(|name| !METHODS.contains(name))
+            .filter(|name| !NOT_DISPATCHED.iter().any(|(skipped, _)| skipped == name))
+            .collect::<Vec<&str>>();
+
+        assert_eq!(unregistered, Vec::<&str>::new());
+    }
+
+    #[test]
+    fn call_round_trip() {
+        let config = temp_config();
+        let args = json!({ "config": { "writeable_path": config.writeable_path } }).to_string();
+
+        let result: Value = serde_json::from_str(&call("get_lazy_sync", &args)).unwrap();
+        assert_eq!(result, json!({ "tag": "Ok", "content": false }));
+
+        let result: Value = serde_json::from_str(&call("get_account", &args)).unwrap();
+        assert_eq!(
+            result,
+            json!({
+                "tag": "Err",
+                "content": {
+                    "tag": "UiError",
+                    "content": "NoAccount",
+                    "code": 100,
+                    "category": "UserFixable",
+                    "message": "There is no account on this device, create or import one."
+                }
+            })
+        );
+
+        let result: Value = serde_json::from_str(&call("no_such_method", &args)).unwrap();
+        assert_eq!(result["content"]["tag"], "Unexpected");
+        assert_eq!(result["content"]["code"], 1);
+
+        let result: Value = serde_json::from_str(&call("get_file_by_id", &args)).unwrap();
+        assert_eq!(result["content"]["tag"], "Unexpected");
+    }
+}
@@ -6,19 +6,27 @@ extern crate reqwest;
 
 use crate::client::ApiError;
 use crate::model::client_conversion::{
-    generate_client_file_metadata, generate_client_work_calculated, ClientFileMetadata,
-    ClientWorkCalculated,
+    generate_client_file_metadata, generate_client_local_change, generate_client_work_calculated,
+    ClientFileMetadata, ClientLocalChange, ClientWorkCalculated,
 };
+use crate::model::error_catalog;
+use crate::model::error_catalog::ErrorInfo;
 use crate::model::state::Config;
 use crate::repo::local_changes_repo;
-use crate::repo::{account_repo, file_metadata_repo};
+use crate::repo::padding_repo::PaddingScheme;
+use crate::repo::{account_repo, file_metadata_repo, file_name_repo, offline_repo, padding_repo};
 use crate::service::db_state_service::State;
 use crate::service::drawing_service::SupportedImageFormats;
+use crate::service::event_service::ChangeEvent;
+use crate::service::integrity_service::{RepairPolicy, RepairReport, TestRepoError};
+use crate::service::lock_service::DataDirLock;
+use crate::service::sync_scheduler_service::SyncSchedulerEvent;
 use crate::service::sync_service::SyncProgress;
-use crate::service::usage_service::{UsageItemMetric, UsageMetrics};
+use crate::service::usage_service::{UncompressedUsage, UsageMetrics};
 use crate::service::{
-    account_service, db_state_service, drawing_service, file_service, path_service, sync_service,
-    usage_service,
+    account_service, db_state_service, drawing_service, event_service, file_name_service,
+    file_service, integrity_service, lock_service, offline_service, path_service, profile_service,
+    sync_scheduler_service, sync_service, usage_service,
 };
 use basic_human_duration::ChronoHumanDuration;
 use chrono::Duration;
@@ -27,25 +35,69 @@ use lockbook_models::account::Account;
 use lockbook_models::crypto::DecryptedDocument;
 use lockbook_models::drawing::{ColorAlias, ColorRGB, Drawing};
 use lockbook_models::file_metadata::{FileMetadata, FileType};
-use serde::Serialize;
+use lockbook_models::local_changes::ChangeField;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 use serde_json::{json, value::Value};
 use std::collections::HashMap;
 use std::env;
-use std::io::ErrorKind;
+use std::io::{ErrorKind, Read, Write};
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use uuid::Uuid;
 use Error::UiError;
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "tag", content = "content")]
+#[derive(Debug)]
 pub enum Error<U: Serialize> {
     UiError(U),
     Unexpected(String),
 }
 
+impl<U: Serialize> Error<U> {
+    /// This error's entry in the error catalog.
+    pub fn info(&self) -> &'static ErrorInfo {
+        match self {
+            UiError(err) => match serde_json::to_value(err) {
+                Ok(Value::String(variant)) => error_catalog::lookup(&variant),
+                _ => None,
+            }
+            .unwrap_or_else(error_catalog::unexpected),
+            Error::Unexpected(_) => error_catalog::unexpected(),
+        }
+    }
+}
+
+// Serialized as `{ "tag", "content" }`, plus the catalog's `code`, `category` and `message` so
+// that clients can handle errors without matching on names.
+impl<U: Serialize> Serialize for Error<U> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let info = self.info();
+        let mut state = serializer.serialize_struct("Error", 5)?;
+        match self {
+            UiError(err) => {
+                state.serialize_field("tag", "UiError")?;
+                state.serialize_field("content", err)?;
+            }
+            Error::Unexpected(msg) => {
+                state.serialize_field("tag", "Unexpected")?;
+                state.serialize_field("content", msg)?;
+            }
+        }
+        state.serialize_field("code", &info.code)?;
+        state.serialize_field("category", &info.category)?;
+        state.serialize_field("message", &info.message)?;
+        state.end()
+    }
+}
+
+/// The `UiError` of functions that can only fail unexpectedly. It has no variants, so these
+/// functions only ever return `Error::Unexpected`.
+#[derive(Debug, Serialize)]
+pub enum NoUiError {}
+
 macro_rules! unexpected {
     ($base:literal $(, $args:tt )*) => {
         Error::Unexpected(format!($base $(, $args )*))
@@ -59,13 +111,18 @@ pub enum CoreError {
     AccountStringCorrupted,
     ClientUpdateRequired,
     ClientWipeRequired,
+    DataDirLocked,
     DiskPathInvalid,
     DiskPathTaken,
+    DocumentCorrupted,
+    DocumentNotText,
     DrawingInvalid,
     FileExists,
     FileNameContainsSlash,
     FileNameEmpty,
+    FileNameInvalid,
     FileNonexistent,
+    FileNotAvailableOffline,
     FileNotDocument,
     FileNotFolder,
     FileParentNonexistent,
@@ -74,9 +131,16 @@ pub enum CoreError {
     PathNonexistent,
     PathStartsWithNonRoot,
     PathTaken,
+    ProfileExists,
+    ProfileIsActive,
+    ProfileIsDefault,
+    ProfileNameInvalid,
+    ProfileNonexistent,
     RootModificationInvalid,
     RootNonexistent,
+    ServerTampering,
     ServerUnreachable,
+    SyncCancelled,
     UsernameInvalid,
     UsernamePublicKeyMismatch,
     UsernameTaken,
@@ -87,6 +151,15 @@ fn core_err_unexpected<T: std::fmt::Debug>(err: T) -> CoreError {
     CoreError::Unexpected(format!("{:#?}", err))
 }
 
+// Held until the end of the calling function, so that other processes can't change the data
+// directory in the meantime. Functions that only read don't take it.
+fn lock_data_dir<U: Serialize>(config: &Config, locked: U) -> Result<DataDirLock, Error<U>> {
+    lock_service::lock(config).map_err(|e| match e {
+        CoreError::DataDirLocked => UiError(locked),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
 impl From<std::io::Error> for CoreError {
     fn from(e: std::io::Error) -> Self {
         match e.kind() {
@@ -104,6 +177,7 @@ impl<T: std::fmt::Debug> From<ApiError<T>> for CoreError {
         match e {
             ApiError::SendFailed(_) => CoreError::ServerUnreachable,
             ApiError::ClientUpdateRequired => CoreError::ClientUpdateRequired,
+            ApiError::Cancelled => CoreError::SyncCancelled,
             e => core_err_unexpected(e),
         }
     }
@@ -138,9 +212,11 @@ pub fn get_db_state(config: &Config) -> Result<State, Error<GetStateError>> {
 #[derive(Debug, Serialize, EnumIter)]
 pub enum MigrationError {
     StateRequiresCleaning,
+    DataDirLocked,
 }
 
 pub fn migrate_db(config: &Config) -> Result<(), Error<MigrationError>> {
+    let _lock = lock_data_dir(config, MigrationError::DataDirLocked)?;
     db_state_service::perform_migration(&config).map_err(|e| match e {
         CoreError::ClientWipeRequired => UiError(MigrationError::StateRequiresCleaning),
         _ => unexpected!("{:#?}", e),
@@ -154,6 +230,7 @@ pub enum CreateAccountError {
     CouldNotReachServer,
     AccountExistsAlready,
     ClientUpdateRequired,
+    DataDirLocked,
 }
 
 pub fn create_account(
@@ -161,6 +238,7 @@ pub fn create_account(
     username: &str,
     api_url: &str,
 ) -> Result<Account, Error<CreateAccountError>> {
+    let _lock = lock_data_dir(config, CreateAccountError::DataDirLocked)?;
     account_service::create_account(&config, username, api_url).map_err(|e| match e {
         CoreError::AccountExists => UiError(CreateAccountError::AccountExistsAlready),
         CoreError::UsernameTaken => UiError(CreateAccountError::UsernameTaken),
@@ -179,12 +257,14 @@ pub enum ImportError {
     UsernamePKMismatch,
     CouldNotReachServer,
     ClientUpdateRequired,
+    DataDirLocked,
 }
 
 pub fn import_account(
     config: &Config,
     account_string: &str,
 ) -> Result<Account, Error<ImportError>> {
+    let _lock = lock_data_dir(config, ImportError::DataDirLocked)?;
     account_service::import_account(&config, account_string).map_err(|e| match e {
         CoreError::AccountStringCorrupted => UiError(ImportError::AccountStringCorrupted),
         CoreError::AccountExists => UiError(ImportError::AccountExistsAlready),
@@ -220,6 +300,80 @@ pub fn get_account(config: &Config) -> Result<Account, Error<GetAccountError>> {
     })
 }
 
+/// `config` here, and in the other profile functions, is the installation's data directory rather
+/// than a profile's, see `get_profile_config`.
+pub fn list_profiles(config: &Config) -> Result<Vec<String>, Error<NoUiError>> {
+    profile_service::list(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum CreateProfileError {
+    ProfileNameInvalid,
+    ProfileExists,
+}
+
+pub fn create_profile(config: &Config, name: &str) -> Result<(), Error<CreateProfileError>> {
+    profile_service::create(&config, name).map_err(|e| match e {
+        CoreError::ProfileNameInvalid => UiError(CreateProfileError::ProfileNameInvalid),
+        CoreError::ProfileExists => UiError(CreateProfileError::ProfileExists),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum SwitchProfileError {
+    ProfileDoesNotExist,
+}
+
+pub fn switch_profile(config: &Config, name: &str) -> Result<(), Error<SwitchProfileError>> {
+    profile_service::switch(&config, name).map_err(|e| match e {
+        CoreError::ProfileNonexistent => UiError(SwitchProfileError::ProfileDoesNotExist),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum RemoveProfileError {
+    ProfileDoesNotExist,
+    CannotRemoveDefaultProfile,
+    CannotRemoveActiveProfile,
+    DataDirLocked,
+}
+
+pub fn remove_profile(config: &Config, name: &str) -> Result<(), Error<RemoveProfileError>> {
+    profile_service::remove(&config, name).map_err(|e| match e {
+        CoreError::ProfileNonexistent => UiError(RemoveProfileError::ProfileDoesNotExist),
+        CoreError::ProfileIsDefault => UiError(RemoveProfileError::CannotRemoveDefaultProfile),
+        CoreError::ProfileIsActive => UiError(RemoveProfileError::CannotRemoveActiveProfile),
+        CoreError::DataDirLocked => UiError(RemoveProfileError::DataDirLocked),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+pub fn get_active_profile(config: &Config) -> Result<String, Error<NoUiError>> {
+    profile_service::get_active(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum GetProfileConfigError {
+    ProfileDoesNotExist,
+}
+
+/// The config for a profile's data, `None` for the active profile.
+pub fn get_profile_config(
+    config: &Config,
+    name: Option<&str>,
+) -> Result<Config, Error<GetProfileConfigError>> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => profile_service::get_active(&config).map_err(|e| unexpected!("{:#?}", e))?,
+    };
+    profile_service::config_for(&config, &name).map_err(|e| match e {
+        CoreError::ProfileNonexistent => UiError(GetProfileConfigError::ProfileDoesNotExist),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
 #[derive(Debug, Serialize, EnumIter)]
 pub enum CreateFileAtPathError {
     FileAlreadyExists,
@@ -228,12 +382,15 @@ pub enum CreateFileAtPathError {
     PathDoesntStartWithRoot,
     PathContainsEmptyFile,
     DocumentTreatedAsFolder,
+    FileNameInvalid,
+    DataDirLocked,
 }
 
 pub fn create_file_at_path(
     config: &Config,
     path_and_name: &str,
 ) -> Result<ClientFileMetadata, Error<CreateFileAtPathError>> {
+    let _lock = lock_data_dir(config, CreateFileAtPathError::DataDirLocked)?;
     path_service::create_at_path(&config, path_and_name)
         .map_err(|e| match e {
             CoreError::PathStartsWithNonRoot => {
@@ -246,6 +403,7 @@ pub fn create_file_at_path(
             CoreError::AccountNonexistent => UiError(CreateFileAtPathError::NoAccount),
             CoreError::PathTaken => UiError(CreateFileAtPathError::FileAlreadyExists),
             CoreError::FileNotFolder => UiError(CreateFileAtPathError::DocumentTreatedAsFolder),
+            CoreError::FileNameInvalid => UiError(CreateFileAtPathError::FileNameInvalid),
             _ => unexpected!("{:#?}", e),
         })
         .and_then(|file_metadata| {
@@ -259,6 +417,8 @@ pub enum WriteToDocumentError {
     NoAccount,
     FileDoesNotExist,
     FolderTreatedAsDocument,
+    NotAvailableOffline,
+    DataDirLocked,
 }
 
 pub fn write_document(
@@ -266,9 +426,28 @@ pub fn write_document(
     id: Uuid,
     content: &[u8],
 ) -> Result<(), Error<WriteToDocumentError>> {
+    let _lock = lock_data_dir(config, WriteToDocumentError::DataDirLocked)?;
     file_service::write_document(&config, id, content).map_err(|e| match e {
         CoreError::AccountNonexistent => UiError(WriteToDocumentError::NoAccount),
         CoreError::FileNonexistent => UiError(WriteToDocumentError::FileDoesNotExist),
+        CoreError::FileNotAvailableOffline => UiError(WriteToDocumentError::NotAvailableOffline),
+        CoreError::FileNotDocument => UiError(WriteToDocumentError::FolderTreatedAsDocument),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+/// Like `write_document`, but `content` is read, compressed and encrypted a segment at a time so
+/// documents larger than memory can be written.
+pub fn write_document_from(
+    config: &Config,
+    id: Uuid,
+    content: &mut dyn Read,
+) -> Result<(), Error<WriteToDocumentError>> {
+    let _lock = lock_data_dir(config, WriteToDocumentError::DataDirLocked)?;
+    file_service::write_document_from(&config, id, content).map_err(|e| match e {
+        CoreError::AccountNonexistent => UiError(WriteToDocumentError::NoAccount),
+        CoreError::FileNonexistent => UiError(WriteToDocumentError::FileDoesNotExist),
+        CoreError::FileNotAvailableOffline => UiError(WriteToDocumentError::NotAvailableOffline),
         CoreError::FileNotDocument => UiError(WriteToDocumentError::FolderTreatedAsDocument),
         _ => unexpected!("{:#?}", e),
     })
@@ -282,6 +461,8 @@ pub enum CreateFileError {
     FileNameNotAvailable,
     FileNameEmpty,
     FileNameContainsSlash,
+    FileNameInvalid,
+    DataDirLocked,
 }
 
 pub fn create_file(
@@ -290,7 +471,9 @@ pub fn create_file(
     parent: Uuid,
     file_type: FileType,
 ) -> Result<ClientFileMetadata, Error<CreateFileError>> {
-    file_service::create(&config, name, parent, file_type)
+    let _lock = lock_data_dir(config, CreateFileError::DataDirLocked)?;
+    file_name_service::validate(name)
+        .and_then(|name| file_service::create(&config, &name, parent, file_type))
         .map_err(|e| match e {
             CoreError::AccountNonexistent => UiError(CreateFileError::NoAccount),
             CoreError::FileNotFolder => UiError(CreateFileError::DocumentTreatedAsFolder),
@@ -298,6 +481,7 @@ pub fn create_file(
             CoreError::PathTaken => UiError(CreateFileError::FileNameNotAvailable),
             CoreError::FileNameEmpty => UiError(CreateFileError::FileNameEmpty),
             CoreError::FileNameContainsSlash => UiError(CreateFileError::FileNameContainsSlash),
+            CoreError::FileNameInvalid => UiError(CreateFileError::FileNameInvalid),
             _ => unexpected!("{:#?}", e),
         })
         .and_then(|file_metadata| {
@@ -408,9 +592,11 @@ pub fn get_file_by_path(
 pub enum FileDeleteError {
     CannotDeleteRoot,
     FileDoesNotExist,
+    DataDirLocked,
 }
 
 pub fn delete_file(config: &Config, id: Uuid) -> Result<(), Error<FileDeleteError>> {
+    let _lock = lock_data_dir(config, FileDeleteError::DataDirLocked)?;
     match file_metadata_repo::get(&config, id) {
         Ok(meta) => match meta.file_type {
             FileType::Document => file_service::delete_document(&config, id),
@@ -430,6 +616,8 @@ pub enum ReadDocumentError {
     TreatedFolderAsDocument,
     NoAccount,
     FileDoesNotExist,
+    NotAvailableOffline,
+    DocumentCorrupted,
 }
 
 pub fn read_document(
@@ -440,6 +628,25 @@ pub fn read_document(
         CoreError::FileNotDocument => UiError(ReadDocumentError::TreatedFolderAsDocument),
         CoreError::AccountNonexistent => UiError(ReadDocumentError::NoAccount),
         CoreError::FileNonexistent => UiError(ReadDocumentError::FileDoesNotExist),
+        CoreError::FileNotAvailableOffline => UiError(ReadDocumentError::NotAvailableOffline),
+        CoreError::DocumentCorrupted => UiError(ReadDocumentError::DocumentCorrupted),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+/// Like `read_document`, but the content is written to `destination` as it's decrypted. Returns
+/// the number of bytes written.
+pub fn read_document_to(
+    config: &Config,
+    id: Uuid,
+    destination: &mut dyn Write,
+) -> Result<u64, Error<ReadDocumentError>> {
+    file_service::read_document_to(&config, id, destination).map_err(|e| match e {
+        CoreError::FileNotDocument => UiError(ReadDocumentError::TreatedFolderAsDocument),
+        CoreError::AccountNonexistent => UiError(ReadDocumentError::NoAccount),
+        CoreError::FileNonexistent => UiError(ReadDocumentError::FileDoesNotExist),
+        CoreError::FileNotAvailableOffline => UiError(ReadDocumentError::NotAvailableOffline),
+        CoreError::DocumentCorrupted => UiError(ReadDocumentError::DocumentCorrupted),
         _ => unexpected!("{:#?}", e),
     })
 }
@@ -451,6 +658,8 @@ pub enum SaveDocumentToDiskError {
     FileDoesNotExist,
     BadPath,
     FileAlreadyExistsInDisk,
+    NotAvailableOffline,
+    DocumentCorrupted,
 }
 
 pub fn save_document_to_disk(
@@ -462,8 +671,10 @@ pub fn save_document_to_disk(
         CoreError::FileNotDocument => UiError(SaveDocumentToDiskError::TreatedFolderAsDocument),
         CoreError::AccountNonexistent => UiError(SaveDocumentToDiskError::NoAccount),
         CoreError::FileNonexistent => UiError(SaveDocumentToDiskError::FileDoesNotExist),
+        CoreError::FileNotAvailableOffline => UiError(SaveDocumentToDiskError::NotAvailableOffline),
         CoreError::DiskPathInvalid => UiError(SaveDocumentToDiskError::BadPath),
         CoreError::DiskPathTaken => UiError(SaveDocumentToDiskError::FileAlreadyExistsInDisk),
+        CoreError::DocumentCorrupted => UiError(SaveDocumentToDiskError::DocumentCorrupted),
         _ => unexpected!("{:#?}", e),
     })
 }
@@ -505,8 +716,10 @@ pub enum RenameFileError {
     FileDoesNotExist,
     NewNameEmpty,
     NewNameContainsSlash,
+    NewNameInvalid,
     FileNameNotAvailable,
     CannotRenameRoot,
+    DataDirLocked,
 }
 
 pub fn rename_file(
@@ -514,14 +727,18 @@ pub fn rename_file(
     id: Uuid,
     new_name: &str,
 ) -> Result<(), Error<RenameFileError>> {
-    file_service::rename_file(&config, id, new_name).map_err(|e| match e {
-        CoreError::FileNonexistent => UiError(RenameFileError::FileDoesNotExist),
-        CoreError::FileNameEmpty => UiError(RenameFileError::NewNameEmpty),
-        CoreError::FileNameContainsSlash => UiError(RenameFileError::NewNameContainsSlash),
-        CoreError::PathTaken => UiError(RenameFileError::FileNameNotAvailable),
-        CoreError::RootModificationInvalid => UiError(RenameFileError::CannotRenameRoot),
-        _ => unexpected!("{:#?}", e),
-    })
+    let _lock = lock_data_dir(config, RenameFileError::DataDirLocked)?;
+    file_name_service::validate(new_name)
+        .and_then(|new_name| file_service::rename_file(&config, id, &new_name))
+        .map_err(|e| match e {
+            CoreError::FileNonexistent => UiError(RenameFileError::FileDoesNotExist),
+            CoreError::FileNameEmpty => UiError(RenameFileError::NewNameEmpty),
+            CoreError::FileNameContainsSlash => UiError(RenameFileError::NewNameContainsSlash),
+            CoreError::FileNameInvalid => UiError(RenameFileError::NewNameInvalid),
+            CoreError::PathTaken => UiError(RenameFileError::FileNameNotAvailable),
+            CoreError::RootModificationInvalid => UiError(RenameFileError::CannotRenameRoot),
+            _ => unexpected!("{:#?}", e),
+        })
 }
 
 #[derive(Debug, Serialize, EnumIter)]
@@ -533,9 +750,11 @@ pub enum MoveFileError {
     NoAccount,
     TargetParentDoesNotExist,
     TargetParentHasChildNamedThat,
+    DataDirLocked,
 }
 
 pub fn move_file(config: &Config, id: Uuid, new_parent: Uuid) -> Result<(), Error<MoveFileError>> {
+    let _lock = lock_data_dir(config, MoveFileError::DataDirLocked)?;
     file_service::move_file(&config, id, new_parent).map_err(|e| match e {
         CoreError::RootModificationInvalid => UiError(MoveFileError::CannotMoveRoot),
         CoreError::FileNotFolder => UiError(MoveFileError::DocumentTreatedAsFolder),
@@ -548,11 +767,61 @@ pub fn move_file(config: &Config, id: Uuid, new_parent: Uuid) -> Result<(), Erro
     })
 }
 
+#[derive(Debug, Serialize, EnumIter)]
+pub enum RevertLocalChangeError {
+    CouldNotReachServer,
+    FileDoesNotExist,
+    FileNameNotAvailable,
+    NoAccount,
+    ParentDoesNotExist,
+    DataDirLocked,
+}
+
+pub fn revert_local_change(
+    config: &Config,
+    id: Uuid,
+    fields: &[ChangeField],
+) -> Result<(), Error<RevertLocalChangeError>> {
+    let _lock = lock_data_dir(config, RevertLocalChangeError::DataDirLocked)?;
+    file_service::revert_local_change(&config, id, fields).map_err(|e| match e {
+        CoreError::ServerUnreachable => UiError(RevertLocalChangeError::CouldNotReachServer),
+        CoreError::FileNonexistent => UiError(RevertLocalChangeError::FileDoesNotExist),
+        CoreError::PathTaken => UiError(RevertLocalChangeError::FileNameNotAvailable),
+        CoreError::AccountNonexistent => UiError(RevertLocalChangeError::NoAccount),
+        CoreError::FileParentNonexistent => UiError(RevertLocalChangeError::ParentDoesNotExist),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum RevertAllLocalChangesError {
+    CouldNotReachServer,
+    FileNameNotAvailable,
+    NoAccount,
+    ParentDoesNotExist,
+    DataDirLocked,
+}
+
+pub fn revert_all_local_changes(config: &Config) -> Result<(), Error<RevertAllLocalChangesError>> {
+    let _lock = lock_data_dir(config, RevertAllLocalChangesError::DataDirLocked)?;
+    file_service::revert_all_local_changes(&config).map_err(|e| match e {
+        CoreError::ServerUnreachable => UiError(RevertAllLocalChangesError::CouldNotReachServer),
+        CoreError::PathTaken => UiError(RevertAllLocalChangesError::FileNameNotAvailable),
+        CoreError::AccountNonexistent => UiError(RevertAllLocalChangesError::NoAccount),
+        CoreError::FileParentNonexistent => UiError(RevertAllLocalChangesError::ParentDoesNotExist),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
 #[derive(Debug, Serialize, EnumIter)]
 pub enum SyncAllError {
     NoAccount,
     ClientUpdateRequired,
     CouldNotReachServer,
+    Cancelled,
+    ServerTampering,
+    DocumentCorrupted,
+    DataDirLocked,
 }
 
 pub fn sync_all(
@@ -561,12 +830,202 @@ pub fn sync_all(
 ) -> Result<(), Error<SyncAllError>> {
     sync_service::sync(&config, f).map_err(|e| match e {
         CoreError::AccountNonexistent => UiError(SyncAllError::NoAccount),
+        CoreError::DataDirLocked => UiError(SyncAllError::DataDirLocked),
         CoreError::ServerUnreachable => UiError(SyncAllError::CouldNotReachServer),
         CoreError::ClientUpdateRequired => UiError(SyncAllError::ClientUpdateRequired),
+        CoreError::SyncCancelled => UiError(SyncAllError::Cancelled),
+        CoreError::ServerTampering => UiError(SyncAllError::ServerTampering),
+        CoreError::DocumentCorrupted => UiError(SyncAllError::DocumentCorrupted),
         _ => unexpected!("{:#?}", e),
     })
 }
 
+/// Asks any sync of this data directory that is running on another thread to stop, returns
+/// whether one was running. The cancelled `sync_all` returns `SyncAllError::Cancelled`.
+pub fn cancel_sync(config: &Config) -> Result<bool, Error<NoUiError>> {
+    sync_service::cancel_syncs(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
+/// Calls `callback` with every change made to this data directory from now on, whether by this
+/// client or by sync. Returns an id for `unsubscribe`.
+pub fn subscribe(
+    config: &Config,
+    callback: Box<dyn Fn(&ChangeEvent) + Send + Sync>,
+) -> Result<u64, Error<NoUiError>> {
+    event_service::subscribe(&config, Arc::from(callback)).map_err(|e| unexpected!("{:#?}", e))
+}
+
+pub fn unsubscribe(subscription_id: u64) -> Result<bool, Error<NoUiError>> {
+    event_service::unsubscribe(subscription_id).map_err(|e| unexpected!("{:#?}", e))
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum StartSyncSchedulerError {
+    NoAccount,
+    SchedulerAlreadyRunning,
+}
+
+/// Syncs this data directory in the background until `stop_sync_scheduler`, see
+/// `SyncScheduler`. `callback` is called with each `SyncSchedulerEvent`, on the scheduler's thread.
+pub fn start_sync_scheduler(
+    config: &Config,
+    callback: Box<dyn Fn(SyncSchedulerEvent) + Send>,
+) -> Result<(), Error<StartSyncSchedulerError>> {
+    account_repo::get_account(&config)
+        .and_then(|_| sync_scheduler_service::start_for(&config, callback))
+        .map_err(|e| match e {
+            CoreError::AccountNonexistent => UiError(StartSyncSchedulerError::NoAccount),
+            _ => unexpected!("{:#?}", e),
+        })
+        .and_then(|started| {
+            if started {
+                Ok(())
+            } else {
+                Err(UiError(StartSyncSchedulerError::SchedulerAlreadyRunning))
+            }
+        })
+}
+
+/// Asks this data directory's background sync to sync right away, returns whether it's running.
+pub fn sync_now(config: &Config) -> Result<bool, Error<NoUiError>> {
+    sync_scheduler_service::sync_now_for(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
+/// Stops this data directory's background sync once any sync in progress finishes, returns
+/// whether it was running.
+pub fn stop_sync_scheduler(config: &Config) -> Result<bool, Error<NoUiError>> {
+    sync_scheduler_service::stop_for(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum WaitForRemoteChangesError {
+    NoAccount,
+    CouldNotReachServer,
+    ClientUpdateRequired,
+}
+
+pub fn wait_for_remote_changes(
+    config: &Config,
+    timeout_ms: u64,
+) -> Result<bool, Error<WaitForRemoteChangesError>> {
+    sync_service::wait_for_remote_changes(&config, timeout_ms).map_err(|e| match e {
+        CoreError::AccountNonexistent => UiError(WaitForRemoteChangesError::NoAccount),
+        CoreError::ServerUnreachable => UiError(WaitForRemoteChangesError::CouldNotReachServer),
+        CoreError::ClientUpdateRequired => UiError(WaitForRemoteChangesError::ClientUpdateRequired),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum SetLazySyncError {
+    DataDirLocked,
+}
+
+pub fn set_lazy_sync(config: &Config, lazy: bool) -> Result<(), Error<SetLazySyncError>> {
+    let _lock = lock_data_dir(config, SetLazySyncError::DataDirLocked)?;
+    offline_service::set_lazy(&config, lazy).map_err(|e| unexpected!("{:#?}", e))
+}
+
+pub fn get_lazy_sync(config: &Config) -> Result<bool, Error<NoUiError>> {
+    offline_repo::is_lazy(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum SetPaddingSchemeError {
+    DataDirLocked,
+}
+
+pub fn set_padding_scheme(
+    config: &Config,
+    scheme: PaddingScheme,
+) -> Result<(), Error<SetPaddingSchemeError>> {
+    let _lock = lock_data_dir(config, SetPaddingSchemeError::DataDirLocked)?;
+    padding_repo::set_scheme(&config, scheme).map_err(|e| unexpected!("{:#?}", e))
+}
+
+pub fn get_padding_scheme(config: &Config) -> Result<PaddingScheme, Error<NoUiError>> {
+    padding_repo::get_scheme(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum SetCaseInsensitiveNamesError {
+    DataDirLocked,
+}
+
+/// Once on, new names on this device must differ from their siblings' even when case is ignored.
+/// Names that already differ only in case are left alone.
+pub fn set_case_insensitive_names(
+    config: &Config,
+    case_insensitive: bool,
+) -> Result<(), Error<SetCaseInsensitiveNamesError>> {
+    let _lock = lock_data_dir(config, SetCaseInsensitiveNamesError::DataDirLocked)?;
+    file_name_repo::set_case_insensitive(&config, case_insensitive)
+        .map_err(|e| unexpected!("{:#?}", e))
+}
+
+pub fn get_case_insensitive_names(config: &Config) -> Result<bool, Error<NoUiError>> {
+    file_name_repo::is_case_insensitive(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum PinFileError {
+    FileDoesNotExist,
+    CouldNotReachServer,
+    ClientUpdateRequired,
+    DataDirLocked,
+}
+
+pub fn pin_file(config: &Config, id: Uuid) -> Result<(), Error<PinFileError>> {
+    let _lock = lock_data_dir(config, PinFileError::DataDirLocked)?;
+    offline_service::pin(&config, id).map_err(|e| match e {
+        CoreError::FileNonexistent => UiError(PinFileError::FileDoesNotExist),
+        CoreError::FileNotAvailableOffline => UiError(PinFileError::CouldNotReachServer),
+        CoreError::ClientUpdateRequired => UiError(PinFileError::ClientUpdateRequired),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum UnpinFileError {
+    FileDoesNotExist,
+    DataDirLocked,
+}
+
+pub fn unpin_file(config: &Config, id: Uuid) -> Result<(), Error<UnpinFileError>> {
+    let _lock = lock_data_dir(config, UnpinFileError::DataDirLocked)?;
+    offline_service::unpin(&config, id).map_err(|e| match e {
+        CoreError::FileNonexistent => UiError(UnpinFileError::FileDoesNotExist),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+pub fn get_pinned_files(config: &Config) -> Result<Vec<Uuid>, Error<NoUiError>> {
+    offline_repo::get_all_pinned(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum SetSyncExclusionsError {
+    FileDoesNotExist,
+    CannotExcludeRoot,
+    DataDirLocked,
+}
+
+pub fn set_sync_exclusions(
+    config: &Config,
+    ids: Vec<Uuid>,
+) -> Result<(), Error<SetSyncExclusionsError>> {
+    let _lock = lock_data_dir(config, SetSyncExclusionsError::DataDirLocked)?;
+    offline_service::set_sync_exclusions(&config, ids).map_err(|e| match e {
+        CoreError::FileNonexistent => UiError(SetSyncExclusionsError::FileDoesNotExist),
+        CoreError::RootModificationInvalid => UiError(SetSyncExclusionsError::CannotExcludeRoot),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
+pub fn get_sync_exclusions(config: &Config) -> Result<Vec<Uuid>, Error<NoUiError>> {
+    offline_repo::get_all_excluded(&config).map_err(|e| unexpected!("{:#?}", e))
+}
+
 #[derive(Debug, Serialize, EnumIter)]
 pub enum GetLocalChangesError {
     Stub,
@@ -580,11 +1039,63 @@ pub fn get_local_changes(config: &Config) -> Result<Vec<Uuid>, Error<GetLocalCha
         .collect())
 }
 
+#[derive(Debug, Serialize, EnumIter)]
+pub enum GetLocalChangeDetailsError {
+    NoAccount,
+}
+
+pub fn get_local_change_details(
+    config: &Config,
+) -> Result<Vec<ClientLocalChange>, Error<GetLocalChangeDetailsError>> {
+    let _account = account_repo::get_account(&config).map_err(|e| match e {
+        CoreError::AccountNonexistent => UiError(GetLocalChangeDetailsError::NoAccount),
+        _ => unexpected!("{:#?}", e),
+    })?;
+
+    local_changes_repo::get_all_local_changes(&config)
+        .and_then(|changes| {
+            changes
+                .iter()
+                .map(|change| generate_client_local_change(&config, change))
+                .collect()
+        })
+        .map_err(|e| unexpected!("{:#?}", e))
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum DiffLocalDocumentError {
+    NoAccount,
+    FileDoesNotExist,
+    TreatedFolderAsDocument,
+    DocumentNotText,
+    OldVersionNotAvailable,
+    DocumentCorrupted,
+}
+
+/// A unified diff of a document's unsynced edits, empty if there are none.
+pub fn diff_local_document(
+    config: &Config,
+    id: Uuid,
+) -> Result<String, Error<DiffLocalDocumentError>> {
+    file_service::diff_local_document(&config, id).map_err(|e| match e {
+        CoreError::AccountNonexistent => UiError(DiffLocalDocumentError::NoAccount),
+        CoreError::FileNonexistent => UiError(DiffLocalDocumentError::FileDoesNotExist),
+        CoreError::FileNotDocument => UiError(DiffLocalDocumentError::TreatedFolderAsDocument),
+        CoreError::DocumentNotText => UiError(DiffLocalDocumentError::DocumentNotText),
+        CoreError::FileNotAvailableOffline => {
+            UiError(DiffLocalDocumentError::OldVersionNotAvailable)
+        }
+        CoreError::DocumentCorrupted => UiError(DiffLocalDocumentError::DocumentCorrupted),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
 #[derive(Debug, Serialize, EnumIter)]
 pub enum CalculateWorkError {
     NoAccount,
     CouldNotReachServer,
     ClientUpdateRequired,
+    ServerTampering,
 }
 
 pub fn calculate_work(config: &Config) -> Result<ClientWorkCalculated, Error<CalculateWorkError>> {
@@ -593,6 +1104,7 @@ pub fn calculate_work(config: &Config) -> Result<ClientWorkCalculated, Error<Cal
             CoreError::AccountNonexistent => UiError(CalculateWorkError::NoAccount),
             CoreError::ServerUnreachable => UiError(CalculateWorkError::CouldNotReachServer),
             CoreError::ClientUpdateRequired => UiError(CalculateWorkError::ClientUpdateRequired),
+            CoreError::ServerTampering => UiError(CalculateWorkError::ServerTampering),
             _ => unexpected!("{:#?}", e),
         })
         .and_then(|work_calculated| {
@@ -603,10 +1115,11 @@ pub fn calculate_work(config: &Config) -> Result<ClientWorkCalculated, Error<Cal
 
 #[derive(Debug, Serialize, EnumIter)]
 pub enum SetLastSyncedError {
-    Stub, // TODO: Enums should not be empty
+    DataDirLocked,
 }
 
 pub fn set_last_synced(config: &Config, last_sync: u64) -> Result<(), Error<SetLastSyncedError>> {
+    let _lock = lock_data_dir(config, SetLastSyncedError::DataDirLocked)?;
     file_metadata_repo::set_last_synced(&config, last_sync).map_err(|e| unexpected!("{:#?}", e))
 }
 
@@ -649,10 +1162,11 @@ pub fn get_usage(config: &Config) -> Result<UsageMetrics, Error<GetUsageError>>
     })
 }
 
-pub fn get_uncompressed_usage(config: &Config) -> Result<UsageItemMetric, Error<GetUsageError>> {
+pub fn get_uncompressed_usage(config: &Config) -> Result<UncompressedUsage, Error<GetUsageError>> {
     usage_service::get_uncompressed_usage(&config).map_err(|e| match e {
         CoreError::AccountNonexistent => UiError(GetUsageError::NoAccount),
         CoreError::ServerUnreachable => UiError(GetUsageError::CouldNotReachServer),
+        CoreError::FileNotAvailableOffline => UiError(GetUsageError::CouldNotReachServer),
         CoreError::ClientUpdateRequired => UiError(GetUsageError::ClientUpdateRequired),
         _ => unexpected!("{:#?}", e),
     })
@@ -664,6 +1178,7 @@ pub enum GetDrawingError {
     FolderTreatedAsDrawing,
     InvalidDrawing,
     FileDoesNotExist,
+    NotAvailableOffline,
 }
 
 pub fn get_drawing(config: &Config, id: Uuid) -> Result<Drawing, Error<GetDrawingError>> {
@@ -672,6 +1187,7 @@ pub fn get_drawing(config: &Config, id: Uuid) -> Result<Drawing, Error<GetDrawin
         CoreError::FileNotDocument => UiError(GetDrawingError::FolderTreatedAsDrawing),
         CoreError::AccountNonexistent => UiError(GetDrawingError::NoAccount),
         CoreError::FileNonexistent => UiError(GetDrawingError::FileDoesNotExist),
+        CoreError::FileNotAvailableOffline => UiError(GetDrawingError::NotAvailableOffline),
         _ => unexpected!("{:#?}", e),
     })
 }
@@ -682,6 +1198,8 @@ pub enum SaveDrawingError {
     FileDoesNotExist,
     FolderTreatedAsDrawing,
     InvalidDrawing,
+    NotAvailableOffline,
+    DataDirLocked,
 }
 
 pub fn save_drawing(
@@ -689,10 +1207,12 @@ pub fn save_drawing(
     id: Uuid,
     drawing_bytes: &[u8],
 ) -> Result<(), Error<SaveDrawingError>> {
+    let _lock = lock_data_dir(config, SaveDrawingError::DataDirLocked)?;
     drawing_service::save_drawing(&config, id, drawing_bytes).map_err(|e| match e {
         CoreError::DrawingInvalid => UiError(SaveDrawingError::InvalidDrawing),
         CoreError::AccountNonexistent => UiError(SaveDrawingError::NoAccount),
         CoreError::FileNonexistent => UiError(SaveDrawingError::FileDoesNotExist),
+        CoreError::FileNotAvailableOffline => UiError(SaveDrawingError::NotAvailableOffline),
         CoreError::FileNotDocument => UiError(SaveDrawingError::FolderTreatedAsDrawing),
         _ => unexpected!("{:#?}", e),
     })
@@ -704,6 +1224,7 @@ pub enum ExportDrawingError {
     FileDoesNotExist,
     NoAccount,
     InvalidDrawing,
+    NotAvailableOffline,
 }
 
 pub fn export_drawing(
@@ -716,6 +1237,7 @@ pub fn export_drawing(
         CoreError::DrawingInvalid => UiError(ExportDrawingError::InvalidDrawing),
         CoreError::AccountNonexistent => UiError(ExportDrawingError::NoAccount),
         CoreError::FileNonexistent => UiError(ExportDrawingError::FileDoesNotExist),
+        CoreError::FileNotAvailableOffline => UiError(ExportDrawingError::NotAvailableOffline),
         CoreError::FileNotDocument => UiError(ExportDrawingError::FolderTreatedAsDrawing),
         _ => unexpected!("{:#?}", e),
     })
@@ -729,6 +1251,7 @@ pub enum ExportDrawingToDiskError {
     InvalidDrawing,
     BadPath,
     FileAlreadyExistsInDisk,
+    NotAvailableOffline,
 }
 
 pub fn export_drawing_to_disk(
@@ -743,6 +1266,9 @@ pub fn export_drawing_to_disk(
             CoreError::DrawingInvalid => UiError(ExportDrawingToDiskError::InvalidDrawing),
             CoreError::AccountNonexistent => UiError(ExportDrawingToDiskError::NoAccount),
             CoreError::FileNonexistent => UiError(ExportDrawingToDiskError::FileDoesNotExist),
+            CoreError::FileNotAvailableOffline => {
+                UiError(ExportDrawingToDiskError::NotAvailableOffline)
+            }
             CoreError::FileNotDocument => UiError(ExportDrawingToDiskError::FolderTreatedAsDrawing),
             CoreError::DiskPathInvalid => UiError(ExportDrawingToDiskError::BadPath),
             CoreError::DiskPathTaken => UiError(ExportDrawingToDiskError::FileAlreadyExistsInDisk),
@@ -751,15 +1277,46 @@ pub fn export_drawing_to_disk(
     )
 }
 
+pub fn test_repo_integrity(config: &Config) -> Result<Vec<TestRepoError>, Error<()>> {
+    let problems = integrity_service::test_repo_integrity(&config)
+        .err()
+        .unwrap_or_default();
+    match problems
+        .iter()
+        .find(|problem| matches!(problem, TestRepoError::Core(_)))
+    {
+        Some(err) => Err(unexpected!("{:#?}", err)),
+        None => Ok(problems),
+    }
+}
+
+#[derive(Debug, Serialize, EnumIter)]
+pub enum RepairRepoError {
+    NoAccount,
+    DataDirLocked,
+}
+
+pub fn repair_repo(
+    config: &Config,
+    policy: RepairPolicy,
+) -> Result<RepairReport, Error<RepairRepoError>> {
+    let _lock = lock_data_dir(config, RepairRepoError::DataDirLocked)?;
+    integrity_service::repair_repo(&config, policy).map_err(|e| match e {
+        CoreError::AccountNonexistent => UiError(RepairRepoError::NoAccount),
+        _ => unexpected!("{:#?}", e),
+    })
+}
+
 // This basically generates a function called `get_all_error_variants`,
-// which will produce a big json dict of { "Error": ["Values"] }.
-// Clients can consume this and attempt deserializing each array of errors to see
+// which will produce a big json dict of { "Error": ["Values"] }, plus the error catalog under
+// "ErrorCatalog". Clients can consume this and attempt deserializing each array of errors to see
 // if they are handling all cases
 macro_rules! impl_get_variants {
     ( $( $name:ty,)* ) => {
         fn get_all_error_variants() -> Value {
             json!({
                 $(stringify!($name): <$name>::iter().collect::<Vec<_>>(),)*
+                "ErrorCatalog": error_catalog::CATALOG,
             })
         }
     };
@@ -773,6 +1330,10 @@ impl_get_variants!(
     ImportError,
     AccountExportError,
     GetAccountError,
+    CreateProfileError,
+    SwitchProfileError,
+    RemoveProfileError,
+    GetProfileConfigError,
     CreateFileAtPathError,
     WriteToDocumentError,
     CreateFileError,
@@ -786,7 +1347,11 @@ impl_get_variants!(
     ListMetadatasError,
     RenameFileError,
     MoveFileError,
+    RevertLocalChangeError,
+    RevertAllLocalChangesError,
     SyncAllError,
+    GetLocalChangeDetailsError,
+    DiffLocalDocumentError,
     CalculateWorkError,
     SetLastSyncedError,
     GetLastSyncedError,
@@ -796,6 +1361,15 @@ impl_get_variants!(
     ExportDrawingError,
     ExportDrawingToDiskError,
     SaveDocumentToDiskError,
+    SetLazySyncError,
+    SetPaddingSchemeError,
+    SetCaseInsensitiveNamesError,
+    PinFileError,
+    UnpinFileError,
+    SetSyncExclusionsError,
+    StartSyncSchedulerError,
+    WaitForRemoteChangesError,
+    RepairRepoError,
 );
 
 pub mod c_interface;
@@ -1,9 +1,13 @@
 use crate::model::state::Config;
+use crate::repo::file_metadata_repo;
 use crate::service::file_encryption_service::get_name;
+use crate::service::offline_service::is_remote_only;
+use crate::service::path_service::get_path_by_id;
 use crate::service::sync_service::WorkCalculated;
 use crate::CoreError;
 use lockbook_models::account::Username;
 use lockbook_models::file_metadata::{FileMetadata, FileType};
+use lockbook_models::local_changes::LocalChange;
 use lockbook_models::work_unit::WorkUnit;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -19,6 +23,7 @@ pub struct ClientFileMetadata {
     pub content_version: u64,
     pub deleted: bool,
     pub users_with_access: Vec<Username>,
+    pub remote_only: bool,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -29,6 +34,20 @@ pub struct ClientWorkCalculated {
     pub most_recent_update_from_server: u64,
 }
 
+/// An unsynced change to a file. `renamed_from` is the old name, `moved_from` the old path.
+#[derive(Debug, Serialize, PartialEq, Clone)]
+pub struct ClientLocalChange {
+    pub id: Uuid,
+    pub file_type: FileType,
+    pub path: String,
+    pub timestamp: i64,
+    pub new: bool,
+    pub renamed_from: Option<String>,
+    pub moved_from: Option<String>,
+    pub edited: bool,
+    pub deleted: bool,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub enum ClientWorkUnit {
     ServerUnknownName(Uuid),
@@ -56,6 +75,40 @@ pub fn generate_client_file_metadata(
             .iter()
             .map(|(username, _access_info)| username.clone())
             .collect(),
+        remote_only: is_remote_only(config, meta)?,
+    })
+}
+
+pub fn generate_client_local_change(
+    config: &Config,
+    change: &LocalChange,
+) -> Result<ClientLocalChange, CoreError> {
+    let meta = file_metadata_repo::get(config, change.id)?;
+    let old_name = match &change.renamed {
+        Some(renamed) => renamed.old_value.clone(),
+        None => get_name(config, &meta)?,
+    };
+    let moved_from = match &change.moved {
+        Some(moved) => {
+            let old_parent_path = get_path_by_id(config, moved.old_value)?;
+            Some(match meta.file_type {
+                FileType::Document => format!("{}{}", old_parent_path, old_name),
+                FileType::Folder => format!("{}{}/", old_parent_path, old_name),
+            })
+        }
+        None => None,
+    };
+
+    Ok(ClientLocalChange {
+        id: change.id,
+        file_type: meta.file_type,
+        path: get_path_by_id(config, change.id)?,
+        timestamp: change.timestamp,
+        new: change.new,
+        renamed_from: change.renamed.as_ref().map(|_| old_name),
+        moved_from,
+        edited: change.content_edited.is_some(),
+        deleted: change.deleted,
     })
 }
 
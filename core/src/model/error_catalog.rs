@@ -0,0 +1,157 @@
+use serde::Serialize;
+
+/// What a client can do about an error.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ErrorCategory {
+    /// Trying again later may work, e.g. once the network is back.
+    Retryable,
+    /// The user has to change something first, e.g. pick another name.
+    UserFixable,
+    /// Neither retrying nor the user can fix this.
+    Fatal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ErrorInfo {
+    pub code: u32,
+    pub variant: &'static str,
+    pub category: ErrorCategory,
+    pub message: &'static str,
+}
+
+macro_rules! catalog {
+    ( $( $code:literal => $variant:ident, $category:ident, $message:literal ,)* ) => {
+        /// Every UI error variant, keyed by name since the same variant means the same thing in
+        /// every error enum. Codes are part of the public API: never change or reuse one, only
+        /// add new ones.
+        pub const CATALOG: &[ErrorInfo] = &[
+            $(
+                ErrorInfo {
+                    code: $code,
+                    variant: stringify!($variant),
+                    category: ErrorCategory::$category,
+                    message: $message,
+                },
+            )*
+        ];
+    };
+}
+
+catalog!(
+    // General (1-99)
+    1 => Unexpected, Fatal, "Something unexpected happened.",
+    3 => DataDirLocked, Retryable, "Another app is changing this data, try again in a moment.",
+
+    // Account (100s)
+    100 => NoAccount, UserFixable, "There is no account on this device, create or import one.",
+    101 => AccountExistsAlready, UserFixable, "There is already an account on this device.",
+    102 => AccountDoesNotExist, UserFixable, "This account does not exist on the server.",
+    103 => AccountStringCorrupted, UserFixable, "This account string is corrupted.",
+    104 => InvalidUsername, UserFixable, "Usernames may only contain a-z and 0-9.",
+    105 => UsernameTaken, UserFixable, "This username is already taken.",
+    106 => UsernamePKMismatch, UserFixable, "This account's key does not match the one on the server.",
+
+    // Server and sync (200s)
+    200 => CouldNotReachServer, Retryable, "Could not reach the server.",
+    201 => ClientUpdateRequired, UserFixable, "This app needs to be updated to do this.",
+    202 => ServerTampering, Fatal, "The server sent changes that weren't made by this account.",
+    203 => Cancelled, Retryable, "The sync was cancelled.",
+    204 => StateRequiresCleaning, Fatal, "Local data cannot be migrated, sync again from a fresh install.",
+    205 => SchedulerAlreadyRunning, UserFixable, "Background sync is already running.",
+
+    // Files (300s)
+    300 => FileDoesNotExist, UserFixable, "The file does not exist.",
+    301 => NoFileWithThatId, UserFixable, "There is no file with that id.",
+    302 => NoFileAtThatPath, UserFixable, "There is no file at that path.",
+    303 => NoRoot, Retryable, "There is no root folder yet, sync first.",
+    304 => ParentDoesNotExist, UserFixable, "The parent folder does not exist.",
+    305 => TargetParentDoesNotExist, UserFixable, "The destination folder does not exist.",
+    306 => CouldNotFindAParent, UserFixable, "One of the folders on this path does not exist.",
+    307 => FileAlreadyExists, UserFixable, "A file already exists at that path.",
+    308 => FileNameNotAvailable, UserFixable, "A file with this name already exists in the folder.",
+    309 => TargetParentHasChildNamedThat, UserFixable, "The destination folder already has a file with this name.",
+    310 => FileNameEmpty, UserFixable, "File names cannot be empty.",
+    311 => NewNameEmpty, UserFixable, "File names cannot be empty.",
+    312 => FileNameContainsSlash, UserFixable, "File names cannot contain a slash.",
+    313 => NewNameContainsSlash, UserFixable, "File names cannot contain a slash.",
+    314 => PathContainsEmptyFile, UserFixable, "The path contains an empty file name.",
+    315 => PathDoesntStartWithRoot, UserFixable, "The path does not start with the root folder.",
+    316 => DocumentTreatedAsFolder, UserFixable, "A document was used as a folder.",
+    317 => FolderTreatedAsDocument, UserFixable, "A folder was used as a document.",
+    318 => TreatedFolderAsDocument, UserFixable, "A folder was used as a document.",
+    319 => FolderTreatedAsDrawing, UserFixable, "A folder was used as a drawing.",
+    320 => FolderMovedIntoItself, UserFixable, "A folder cannot be moved into itself.",
+    321 => CannotDeleteRoot, UserFixable, "The root folder cannot be deleted.",
+    322 => CannotMoveRoot, UserFixable, "The root folder cannot be moved.",
+    323 => CannotRenameRoot, UserFixable, "The root folder cannot be renamed.",
+    324 => CannotExcludeRoot, UserFixable, "The root folder cannot be excluded from sync.",
+    325 => FileNameInvalid, UserFixable, "File names cannot contain control characters or be a name the operating system reserves.",
+    326 => NewNameInvalid, UserFixable, "File names cannot contain control characters or be a name the operating system reserves.",
+
+    // Content (400s)
+    400 => DocumentCorrupted, Fatal, "The document is corrupted.",
+    401 => DocumentNotText, UserFixable, "The document is not text.",
+    402 => InvalidDrawing, UserFixable, "The document is not a valid drawing.",
+    403 => NotAvailableOffline, Retryable, "The document has not been downloaded yet, sync while online.",
+    404 => OldVersionNotAvailable, Fatal, "The previous version of this document is not available.",
+
+    // Disk (500s)
+    500 => BadPath, UserFixable, "The location on disk is invalid.",
+    501 => FileAlreadyExistsInDisk, UserFixable, "A file already exists at that location on disk.",
+
+    // Profiles (600s)
+    600 => ProfileNameInvalid, UserFixable, "Profile names may only contain a-z, A-Z, 0-9, - and _.",
+    601 => ProfileExists, UserFixable, "A profile with this name already exists.",
+    602 => ProfileDoesNotExist, UserFixable, "There is no profile with this name.",
+    603 => CannotRemoveDefaultProfile, UserFixable, "The default profile cannot be removed.",
+    604 => CannotRemoveActiveProfile, UserFixable, "The active profile cannot be removed, switch to another one first.",
+);
+
+pub fn lookup(variant: &str) -> Option<&'static ErrorInfo> {
+    CATALOG.iter().find(|info| info.variant == variant)
+}
+
+/// The entry for `Error::Unexpected`, also used for anything missing from the catalog.
+pub fn unexpected() -> &'static ErrorInfo {
+    &CATALOG[0]
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::get_all_error_variants;
+    use crate::model::error_catalog::{lookup, CATALOG};
+    use std::collections::HashSet;
+
+    #[test]
+    fn every_variant_is_cataloged() {
+        let variants = get_all_error_variants();
+        let missing = variants
+            .as_object()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| *name != "ErrorCatalog")
+            .flat_map(|(_, variants)| variants.as_array().unwrap())
+            .map(|variant| variant.as_str().unwrap())
+            // The placeholder of enums that have no errors yet, never returned
+            .filter(|variant| *variant != "Stub")
+            .filter(|variant| lookup(variant).is_none())
+            .collect::<Vec<&str>>();
+
+        assert_eq!(missing, Vec::<&str>::new());
+    }
+
+    #[test]
+    fn codes_and_variants_are_unique() {
+        let codes = CATALOG
+            .iter()
+            .map(|info| info.code)
+            .collect::<HashSet<u32>>();
+        let variants = CATALOG
+            .iter()
+            .map(|info| info.variant)
+            .collect::<HashSet<&str>>();
+
+        assert_eq!(codes.len(), CATALOG.len());
+        assert_eq!(variants.len(), CATALOG.len());
+    }
+}
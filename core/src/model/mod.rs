@@ -1,2 +1,3 @@
 pub mod client_conversion;
+pub mod error_catalog;
 pub mod state;
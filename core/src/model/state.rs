@@ -1,20 +1,44 @@
-use serde::Deserialize;
+use crate::repo::local_storage::{FileBackend, MemoryBackend, StorageBackend};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub writeable_path: String,
+    /// Where local state is kept, a directory at `writeable_path` if this is `None`. Configs with
+    /// their own backend still need a `writeable_path` unique to their data, which identifies it
+    /// within the process.
+    #[serde(skip)]
+    pub storage: Option<Arc<dyn StorageBackend>>,
 }
 
 impl Config {
     pub fn path(&self) -> &Path {
         Path::new(&self.writeable_path)
     }
+
+    pub fn storage(&self) -> Arc<dyn StorageBackend> {
+        match &self.storage {
+            Some(storage) => storage.clone(),
+            None => Arc::new(FileBackend::new(&self.writeable_path)),
+        }
+    }
 }
 
 pub fn temp_config() -> Config {
     Config {
         writeable_path: String::from(tempfile::tempdir().unwrap().path().to_str().unwrap()),
+        storage: None,
+    }
+}
+
+/// A config that keeps its data in memory, and nothing on disk.
+pub fn memory_config() -> Config {
+    Config {
+        writeable_path: format!("memory-{}", Uuid::new_v4()),
+        storage: Some(Arc::new(MemoryBackend::default())),
     }
 }
 
@@ -50,7 +50,7 @@ mod unit_tests {
         let test_account = Account {
             username: "parth".to_string(),
             api_url: "ftp://uranus.net".to_string(),
-            private_key: pubkey::generate_key(),
+            private_key: pubkey::generate_key().into(),
         };
 
         let config = temp_config();
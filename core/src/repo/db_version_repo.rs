@@ -4,6 +4,7 @@ use crate::repo::local_storage;
 use crate::CoreError;
 
 static DB_VERSION: &str = "DB_VERSION";
+static MIGRATIONS: &str = "MIGRATIONS";
 
 pub fn set(config: &Config, version: &str) -> Result<(), CoreError> {
     local_storage::write(
@@ -28,6 +29,25 @@ pub fn get(config: &Config) -> Result<Option<String>, CoreError> {
     }
 }
 
+/// Records that a migration has been applied to this data, for migrations that don't come with
+/// a new db version.
+pub fn set_applied(config: &Config, migration: &str) -> Result<(), CoreError> {
+    local_storage::write(
+        config,
+        MIGRATIONS,
+        migration,
+        serde_json::to_vec(&true).map_err(core_err_unexpected)?,
+    )
+}
+
+pub fn is_applied(config: &Config, migration: &str) -> Result<bool, CoreError> {
+    let maybe_value: Option<Vec<u8>> = local_storage::read(config, MIGRATIONS, migration)?;
+    match maybe_value {
+        None => Ok(false),
+        Some(value) => Ok(serde_json::from_slice(value.as_ref()).map_err(core_err_unexpected)?),
+    }
+}
+
 #[cfg(test)]
 mod unit_tests {
     use crate::model::state::temp_config;
@@ -43,4 +63,14 @@ mod unit_tests {
         db_version_repo::set(&config, "version 2").unwrap();
         assert_eq!(db_version_repo::get(&config).unwrap().unwrap(), "version 2");
     }
+
+    #[test]
+    fn migrations_sanity_check() {
+        let config = temp_config();
+
+        assert!(!db_version_repo::is_applied(&config, "migration").unwrap());
+        db_version_repo::set_applied(&config, "migration").unwrap();
+        assert!(db_version_repo::is_applied(&config, "migration").unwrap());
+        assert!(!db_version_repo::is_applied(&config, "other migration").unwrap());
+    }
 }
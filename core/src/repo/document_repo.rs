@@ -1,11 +1,22 @@
 use crate::core_err_unexpected;
 use crate::model::state::Config;
 use crate::repo::local_storage;
+use crate::repo::local_storage::{Stream, TempStream};
 use crate::CoreError;
 use lockbook_models::crypto::*;
 use uuid::Uuid;
 
 pub const NAMESPACE: &[u8; 9] = b"documents";
+/// Documents written as a stream, see `lockbook_crypto::stream`. A document is stored either here
+/// or in `NAMESPACE`, never both.
+pub const STREAM_NAMESPACE: &[u8; 16] = b"document_streams";
+
+/// A document's content on this device. Streamed documents may not fit in memory and are read
+/// from storage as needed.
+pub enum LocalDocument {
+    Whole(EncryptedDocument),
+    Streamed(Stream),
+}
 
 pub fn insert(config: &Config, id: Uuid, document: &EncryptedDocument) -> Result<(), CoreError> {
     local_storage::write(
@@ -13,7 +24,31 @@ pub fn insert(config: &Config, id: Uuid, document: &EncryptedDocument) -> Result
         NAMESPACE,
         id.to_string().as_str(),
         serde_json::to_vec(document).map_err(core_err_unexpected)?,
-    )
+    )?;
+    local_storage::delete(config, STREAM_NAMESPACE, id.to_string().as_str())
+}
+
+/// Moves a stream written to a `local_storage::temp_file` into place as the document's content.
+pub fn insert_stream(config: &Config, id: Uuid, stream: TempStream) -> Result<(), CoreError> {
+    local_storage::persist(config, STREAM_NAMESPACE, id.to_string().as_str(), stream)?;
+    local_storage::delete(config, NAMESPACE, id.to_string().as_str())
+}
+
+pub fn maybe_get_local(config: &Config, id: Uuid) -> Result<Option<LocalDocument>, CoreError> {
+    match local_storage::open(config, STREAM_NAMESPACE, id.to_string().as_str())? {
+        Some(stream) => Ok(Some(LocalDocument::Streamed(stream))),
+        None => Ok(maybe_get(config, id)?.map(LocalDocument::Whole)),
+    }
+}
+
+pub fn is_streamed(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    Ok(local_storage::open(config, STREAM_NAMESPACE, id.to_string().as_str())?.is_some())
+}
+
+/// Whether this device holds the document's content, in either form.
+pub fn exists(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    Ok(is_streamed(config, id)?
+        || local_storage::open(config, NAMESPACE, id.to_string().as_str())?.is_some())
 }
 
 pub fn get(config: &Config, id: Uuid) -> Result<EncryptedDocument, CoreError> {
@@ -37,7 +72,8 @@ pub fn maybe_get(config: &Config, id: Uuid) -> Result<Option<EncryptedDocument>,
 }
 
 pub fn delete(config: &Config, id: Uuid) -> Result<(), CoreError> {
-    local_storage::delete(config, NAMESPACE, id.to_string().as_str())
+    local_storage::delete(config, NAMESPACE, id.to_string().as_str())?;
+    local_storage::delete(config, STREAM_NAMESPACE, id.to_string().as_str())
 }
 
 #[cfg(test)]
@@ -10,6 +10,10 @@ use uuid::Uuid;
 pub static FILE_METADATA: &[u8; 13] = b"file_metadata";
 static ROOT: &[u8; 4] = b"ROOT";
 static LAST_UPDATED: &[u8; 12] = b"last_updated";
+/// Ids of files the server confirmed were deleted, so they can't be brought back.
+static TOMBSTONES: &[u8; 10] = b"tombstones";
+/// Set once the account's metadata is known to be signed, after which unsigned updates are refused.
+static SIGNATURES_REQUIRED: &[u8; 19] = b"signatures_required";
 
 pub fn insert(config: &Config, file: &FileMetadata) -> Result<(), CoreError> {
     local_storage::write(
@@ -148,6 +152,21 @@ pub fn non_recursive_delete(config: &Config, id: Uuid) -> Result<(), CoreError>
     local_storage::delete(config, FILE_METADATA, id.to_string().as_str())
 }
 
+pub fn add_tombstone(config: &Config, id: Uuid) -> Result<(), CoreError> {
+    local_storage::write(
+        config,
+        TOMBSTONES,
+        id.to_string().as_str(),
+        serde_json::to_vec(&id).map_err(core_err_unexpected)?,
+    )
+}
+
+pub fn is_tombstoned(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    let maybe_value: Option<Vec<u8>> =
+        local_storage::read(config, TOMBSTONES, id.to_string().as_str())?;
+    Ok(maybe_value.is_some())
+}
+
 pub fn get_children_non_recursively(
     config: &Config,
     id: Uuid,
@@ -176,6 +195,24 @@ pub fn get_last_updated(config: &Config) -> Result<u64, CoreError> {
     }
 }
 
+pub fn require_signatures(config: &Config) -> Result<(), CoreError> {
+    local_storage::write(
+        config,
+        SIGNATURES_REQUIRED,
+        SIGNATURES_REQUIRED,
+        serde_json::to_vec(&true).map_err(core_err_unexpected)?,
+    )
+}
+
+pub fn signatures_required(config: &Config) -> Result<bool, CoreError> {
+    let maybe_value: Option<Vec<u8>> =
+        local_storage::read(config, SIGNATURES_REQUIRED, SIGNATURES_REQUIRED)?;
+    match maybe_value {
+        None => Ok(false),
+        Some(value) => Ok(serde_json::from_slice(value.as_ref()).map_err(core_err_unexpected)?),
+    }
+}
+
 #[cfg(test)]
 mod unit_tests {
     use uuid::Uuid;
@@ -200,6 +237,8 @@ mod unit_tests {
             user_access_keys: Default::default(),
             folder_access_keys: EncryptedFolderAccessKey::new("", ""),
             deleted: false,
+            signature: None,
+            content_checksum: None,
         }
     }
 
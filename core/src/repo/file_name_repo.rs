@@ -0,0 +1,41 @@
+use crate::core_err_unexpected;
+use crate::model::state::Config;
+use crate::repo::local_storage;
+use crate::CoreError;
+
+static FILE_NAMES: &str = "file_names";
+static CASE_INSENSITIVE: &str = "case_insensitive";
+
+/// Whether new names must also differ from their siblings' when case is ignored, for accounts
+/// that are backed up or exported to case-insensitive filesystems.
+pub fn set_case_insensitive(config: &Config, case_insensitive: bool) -> Result<(), CoreError> {
+    local_storage::write(
+        config,
+        FILE_NAMES,
+        CASE_INSENSITIVE,
+        serde_json::to_vec(&case_insensitive).map_err(core_err_unexpected)?,
+    )
+}
+
+pub fn is_case_insensitive(config: &Config) -> Result<bool, CoreError> {
+    let maybe_value: Option<Vec<u8>> = local_storage::read(config, FILE_NAMES, CASE_INSENSITIVE)?;
+    match maybe_value {
+        None => Ok(false),
+        Some(value) => Ok(serde_json::from_slice(value.as_ref()).map_err(core_err_unexpected)?),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::model::state::temp_config;
+    use crate::repo::file_name_repo;
+
+    #[test]
+    fn case_insensitive_defaults_to_off() {
+        let config = temp_config();
+
+        assert!(!file_name_repo::is_case_insensitive(&config).unwrap());
+        file_name_repo::set_case_insensitive(&config, true).unwrap();
+        assert!(file_name_repo::is_case_insensitive(&config).unwrap());
+    }
+}
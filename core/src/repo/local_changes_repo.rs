@@ -10,6 +10,8 @@ use std::{thread, time};
 use uuid::Uuid;
 
 pub static LOCAL_CHANGES: &[u8; 13] = b"local_changes";
+static LAST_TRACKED: &str = "last_tracked";
+static TIMESTAMP: &str = "timestamp";
 
 pub fn get_all_local_changes(config: &Config) -> Result<Vec<LocalChange>, CoreError> {
     let mut value = local_storage::dump::<_, Vec<u8>>(config, LOCAL_CHANGES)?
@@ -22,6 +24,27 @@ pub fn get_all_local_changes(config: &Config) -> Result<Vec<LocalChange>, CoreEr
     Ok(value)
 }
 
+/// When a change was last tracked, including changes to files that already had some. Cheaper to
+/// poll than the changes themselves.
+pub fn get_last_tracked(config: &Config) -> Result<Option<i64>, CoreError> {
+    let maybe_value: Option<Vec<u8>> = local_storage::read(config, LAST_TRACKED, TIMESTAMP)?;
+    match maybe_value {
+        None => Ok(None),
+        Some(value) => Ok(Some(
+            serde_json::from_slice(value.as_ref()).map_err(core_err_unexpected)?,
+        )),
+    }
+}
+
+fn set_last_tracked(config: &Config, now: TimeGetter) -> Result<(), CoreError> {
+    local_storage::write(
+        config,
+        LAST_TRACKED,
+        TIMESTAMP,
+        serde_json::to_vec(&now().0).map_err(core_err_unexpected)?,
+    )
+}
+
 pub fn get_local_changes(config: &Config, id: Uuid) -> Result<Option<LocalChange>, CoreError> {
     let maybe_value: Option<Vec<u8>> =
         local_storage::read(config, LOCAL_CHANGES, id.to_string().as_str())?;
@@ -36,6 +59,7 @@ pub fn get_local_changes(config: &Config, id: Uuid) -> Result<Option<LocalChange
 }
 
 pub fn track_new_file(config: &Config, id: Uuid, now: TimeGetter) -> Result<(), CoreError> {
+    set_last_tracked(config, now)?;
     let new_local_change = LocalChange {
         timestamp: now().0,
         id,
@@ -65,6 +89,7 @@ pub fn track_rename(
     if old_name == new_name {
         return Ok(());
     }
+    set_last_tracked(config, now)?;
 
     match get_local_changes(config, id)? {
         None => {
@@ -118,6 +143,7 @@ pub fn track_move(
     if old_parent == new_parent {
         return Ok(());
     }
+    set_last_tracked(config, now)?;
 
     match get_local_changes(config, id)? {
         None => {
@@ -164,7 +190,7 @@ pub fn track_move(
 pub fn track_edit(
     config: &Config,
     id: Uuid,
-    old_version: &EncryptedDocument,
+    old_version: Option<&EncryptedDocument>,
     access_info_for_old_version: &UserAccessInfo,
     old_content_checksum: Vec<u8>,
     new_content_checksum: Vec<u8>,
@@ -173,6 +199,7 @@ pub fn track_edit(
     if old_content_checksum == new_content_checksum {
         return Ok(());
     }
+    set_last_tracked(config, now)?;
 
     match get_local_changes(config, id)? {
         None => {
@@ -183,7 +210,7 @@ pub fn track_edit(
                 moved: None,
                 new: false,
                 content_edited: Some(Edited {
-                    old_value: old_version.clone(),
+                    old_value: old_version.cloned(),
                     access_info: access_info_for_old_version.clone(),
                     old_content_checksum,
                 }),
@@ -200,7 +227,7 @@ pub fn track_edit(
         Some(mut change) => match change.content_edited {
             None => {
                 change.content_edited = Some(Edited {
-                    old_value: old_version.clone(),
+                    old_value: old_version.cloned(),
                     access_info: access_info_for_old_version.clone(),
                     old_content_checksum,
                 });
@@ -231,6 +258,7 @@ pub fn track_delete(
 ) -> Result<(), CoreError> {
     // Added to ensure that a prior move is at least 1ms older than this delete
     thread::sleep(time::Duration::from_millis(1));
+    set_last_tracked(config, now)?;
 
     match get_local_changes(config, id)? {
         None => {
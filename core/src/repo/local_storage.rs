@@ -1,8 +1,272 @@
 use crate::model::state::Config;
-use crate::CoreError;
+use crate::{core_err_unexpected, CoreError};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
 use std::fs::{create_dir_all, read_dir, remove_file, File, OpenOptions};
-use std::io::{ErrorKind, Read, Write};
+use std::io;
+use std::io::{Cursor, ErrorKind, Read, Write};
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use tempfile::NamedTempFile;
+
+const TEMP_NAMESPACE: &str = "tmp";
+
+type Namespaces = BTreeMap<String, BTreeMap<String, Vec<u8>>>;
+
+/// Where core keeps its local state: values under a key within a namespace. `Config::storage`
+/// picks the backend, and the functions in this module call whichever it is.
+///
+/// Only `read`, `write`, `delete` and `dump` have to be implemented. The stream functions default
+/// to holding streams in memory, which backends that can keep them elsewhere should override.
+pub trait StorageBackend: Debug + Send + Sync {
+    fn read(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, CoreError>;
+
+    fn write(&self, namespace: &str, key: &str, value: Vec<u8>) -> Result<(), CoreError>;
+
+    /// Deleting a key that isn't there is not an error.
+    fn delete(&self, namespace: &str, key: &str) -> Result<(), CoreError>;
+
+    /// Every value in a namespace, in no particular order.
+    fn dump(&self, namespace: &str) -> Result<Vec<Vec<u8>>, CoreError>;
+
+    fn open(&self, namespace: &str, key: &str) -> Result<Option<Stream>, CoreError> {
+        Ok(self
+            .read(namespace, key)?
+            .map(|value| Stream::Memory(Cursor::new(value))))
+    }
+
+    fn temp_file(&self) -> Result<TempStream, CoreError> {
+        Ok(TempStream::Memory(Vec::new()))
+    }
+
+    fn persist(&self, namespace: &str, key: &str, stream: TempStream) -> Result<(), CoreError> {
+        self.write(namespace, key, stream.into_bytes()?)
+    }
+}
+
+/// Content opened with `open`, read as it's needed rather than all at once.
+pub enum Stream {
+    File(File),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Stream {
+    pub fn size(&self) -> Result<u64, CoreError> {
+        match self {
+            Stream::File(file) => Ok(file.metadata()?.len()),
+            Stream::Memory(cursor) => Ok(cursor.get_ref().len() as u64),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::File(file) => file.read(buf),
+            Stream::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+/// A stream being written, which `persist` moves into place once it's complete so that readers
+/// never see it half written.
+pub enum TempStream {
+    File(NamedTempFile),
+    Memory(Vec<u8>),
+}
+
+impl TempStream {
+    /// Reads what's been written so far, from the start.
+    pub fn reopen(&self) -> Result<Stream, CoreError> {
+        match self {
+            TempStream::File(file) => Ok(Stream::File(file.reopen()?)),
+            TempStream::Memory(bytes) => Ok(Stream::Memory(Cursor::new(bytes.clone()))),
+        }
+    }
+
+    fn into_bytes(self) -> Result<Vec<u8>, CoreError> {
+        match self {
+            TempStream::File(file) => {
+                let mut bytes = Vec::new();
+                file.reopen()?.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+            TempStream::Memory(bytes) => Ok(bytes),
+        }
+    }
+}
+
+impl Write for TempStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            TempStream::File(file) => file.write(buf),
+            TempStream::Memory(bytes) => bytes.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            TempStream::File(file) => file.flush(),
+            TempStream::Memory(_) => Ok(()),
+        }
+    }
+}
+
+/// Keeps each namespace in a directory and each key in a file, under `Config::writeable_path`.
+/// This is the backend when a config doesn't name one.
+#[derive(Debug)]
+pub struct FileBackend {
+    path: String,
+}
+
+impl FileBackend {
+    pub fn new(path: &str) -> FileBackend {
+        FileBackend {
+            path: path.to_string(),
+        }
+    }
+
+    fn namespace_path(&self, namespace: &str) -> String {
+        format!("{}/{}", self.path, namespace)
+    }
+
+    fn key_path(&self, namespace: &str, key: &str) -> String {
+        format!("{}/{}", self.namespace_path(namespace), key)
+    }
+}
+
+impl StorageBackend for FileBackend {
+    fn read(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, CoreError> {
+        let path_str = self.key_path(namespace, key);
+        trace!("read\t{}", &path_str);
+        match File::open(Path::new(&path_str)) {
+            Ok(mut f) => {
+                let mut buffer: Vec<u8> = Vec::new();
+                f.read_to_end(&mut buffer)?;
+                Ok(Some(buffer))
+            }
+            Err(err) => match err.kind() {
+                ErrorKind::NotFound => Ok(None),
+                _ => Err(err.into()),
+            },
+        }
+    }
+
+    fn write(&self, namespace: &str, key: &str, value: Vec<u8>) -> Result<(), CoreError> {
+        let path_str = self.key_path(namespace, key);
+        let path = Path::new(&path_str);
+        trace!("write\t{} {:?} bytes", &path_str, value.len());
+        create_dir_all(path.parent().unwrap())?;
+        let mut f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        f.write_all(&value).map_err(CoreError::from)
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<(), CoreError> {
+        let path_str = self.key_path(namespace, key);
+        let path = Path::new(&path_str);
+        trace!("delete\t{}", &path_str);
+        if path.exists() {
+            remove_file(path).map_err(CoreError::from)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn dump(&self, namespace: &str) -> Result<Vec<Vec<u8>>, CoreError> {
+        match read_dir(Path::new(&self.namespace_path(namespace))) {
+            Ok(rd) => rd
+                .map(|dir_entry| {
+                    let de = dir_entry.map_err(CoreError::from)?;
+                    self.read(namespace, &de.file_name().into_string().unwrap())
+                        .map(|r| r.unwrap())
+                })
+                .collect(),
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    fn open(&self, namespace: &str, key: &str) -> Result<Option<Stream>, CoreError> {
+        let path_str = self.key_path(namespace, key);
+        trace!("open\t{}", &path_str);
+        match File::open(Path::new(&path_str)) {
+            Ok(f) => Ok(Some(Stream::File(f))),
+            Err(err) => match err.kind() {
+                ErrorKind::NotFound => Ok(None),
+                _ => Err(err.into()),
+            },
+        }
+    }
+
+    fn temp_file(&self) -> Result<TempStream, CoreError> {
+        let path_str = self.namespace_path(TEMP_NAMESPACE);
+        create_dir_all(&path_str)?;
+        Ok(TempStream::File(NamedTempFile::new_in(&path_str)?))
+    }
+
+    fn persist(&self, namespace: &str, key: &str, stream: TempStream) -> Result<(), CoreError> {
+        let path_str = self.key_path(namespace, key);
+        let path = Path::new(&path_str);
+        trace!("persist\t{}", &path_str);
+        create_dir_all(path.parent().unwrap())?;
+        match stream {
+            TempStream::File(file) => file
+                .persist(path)
+                .map(|_| ())
+                .map_err(|err| CoreError::from(err.error)),
+            stream => self.write(namespace, key, stream.into_bytes()?),
+        }
+    }
+}
+
+/// Keeps everything in memory, for embedders without a writable directory and for tests. Nothing
+/// outlives the backend, and configs share data only if they share the backend.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    namespaces: Mutex<Namespaces>,
+}
+
+impl MemoryBackend {
+    fn namespaces(&self) -> Result<MutexGuard<Namespaces>, CoreError> {
+        self.namespaces.lock().map_err(core_err_unexpected)
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn read(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, CoreError> {
+        Ok(self
+            .namespaces()?
+            .get(namespace)
+            .and_then(|values| values.get(key))
+            .cloned())
+    }
+
+    fn write(&self, namespace: &str, key: &str, value: Vec<u8>) -> Result<(), CoreError> {
+        self.namespaces()?
+            .entry(namespace.to_string())
+            .or_default()
+            .insert(key.to_string(), value);
+        Ok(())
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<(), CoreError> {
+        if let Some(values) = self.namespaces()?.get_mut(namespace) {
+            values.remove(key);
+        }
+        Ok(())
+    }
+
+    fn dump(&self, namespace: &str) -> Result<Vec<Vec<u8>>, CoreError> {
+        Ok(self
+            .namespaces()?
+            .get(namespace)
+            .map(|values| values.values().cloned().collect())
+            .unwrap_or_default())
+    }
+}
 
 pub fn write<N, K, V>(db: &Config, namespace: N, key: K, value: V) -> Result<(), CoreError>
 where
@@ -10,17 +274,8 @@ where
     K: AsRef<[u8]>,
     V: Into<Vec<u8>>,
 {
-    let path_str = key_path(db, namespace, key);
-    let path = Path::new(&path_str);
-    let data = &value.into();
-    trace!("write\t{} {:?} bytes", &path_str, data.len());
-    create_dir_all(path.parent().unwrap())?;
-    let mut f = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(path)?;
-    f.write_all(data).map_err(CoreError::from)
+    db.storage()
+        .write(&to_str(namespace), &to_str(key), value.into())
 }
 
 pub fn read<N, K, V>(db: &Config, namespace: N, key: K) -> Result<Option<V>, CoreError>
@@ -29,70 +284,58 @@ where
     K: AsRef<[u8]>,
     V: From<Vec<u8>>,
 {
-    let path_str = key_path(db, namespace, key);
-    let path = Path::new(&path_str);
-    trace!("read\t{}", &path_str);
-    match File::open(path) {
-        Ok(mut f) => {
-            let mut buffer: Vec<u8> = Vec::new();
-            f.read_to_end(&mut buffer)?;
-            Ok(Some(From::from(buffer)))
-        }
-        Err(err) => match err.kind() {
-            ErrorKind::NotFound => Ok(None),
-            _ => Err(err.into()),
-        },
-    }
+    Ok(db
+        .storage()
+        .read(&to_str(namespace), &to_str(key))?
+        .map(From::from))
 }
 
-pub fn delete<N, K>(db: &Config, namespace: N, key: K) -> Result<(), CoreError>
+/// Opens the value behind a key so it can be read without holding it all in memory.
+pub fn open<N, K>(db: &Config, namespace: N, key: K) -> Result<Option<Stream>, CoreError>
 where
     N: AsRef<[u8]>,
     K: AsRef<[u8]>,
 {
-    let path_str = key_path(db, namespace, key);
-    let path = Path::new(&path_str);
-    trace!("delete\t{}", &path_str);
-    if path.exists() {
-        remove_file(path).map_err(CoreError::from)
-    } else {
-        Ok(())
-    }
+    db.storage().open(&to_str(namespace), &to_str(key))
 }
 
-pub fn dump<N, V>(db: &Config, namespace: N) -> Result<Vec<V>, CoreError>
-where
-    N: AsRef<[u8]> + Copy,
-    V: From<Vec<u8>>,
-{
-    let path_str = namespace_path(db, namespace);
-    let path = Path::new(&path_str);
-
-    match read_dir(path) {
-        Ok(rd) => rd
-            .map(|dir_entry| {
-                let de = dir_entry.map_err(CoreError::from)?;
-                read(db, namespace, de.file_name().into_string().unwrap()).map(|r| r.unwrap())
-            })
-            .collect::<Result<Vec<V>, CoreError>>()
-            .map_err(CoreError::from),
-        Err(_) => Ok(Vec::new()),
-    }
+/// A stream to be written gradually and then moved into place with `persist`.
+pub fn temp_file(db: &Config) -> Result<TempStream, CoreError> {
+    db.storage().temp_file()
 }
 
-fn namespace_path<N>(db: &Config, namespace: N) -> String
+pub fn persist<N, K>(db: &Config, namespace: N, key: K, file: TempStream) -> Result<(), CoreError>
 where
     N: AsRef<[u8]>,
+    K: AsRef<[u8]>,
 {
-    let n = String::from_utf8_lossy(namespace.as_ref()).to_string();
-    format!("{}/{}", db.writeable_path, n)
+    db.storage().persist(&to_str(namespace), &to_str(key), file)
 }
 
-fn key_path<N, K>(db: &Config, namespace: N, key: K) -> String
+pub fn delete<N, K>(db: &Config, namespace: N, key: K) -> Result<(), CoreError>
 where
     N: AsRef<[u8]>,
     K: AsRef<[u8]>,
 {
-    let k = String::from_utf8_lossy(key.as_ref()).to_string();
-    format!("{}/{}", namespace_path(db, namespace), k)
+    db.storage().delete(&to_str(namespace), &to_str(key))
+}
+
+pub fn dump<N, V>(db: &Config, namespace: N) -> Result<Vec<V>, CoreError>
+where
+    N: AsRef<[u8]> + Copy,
+    V: From<Vec<u8>>,
+{
+    Ok(db
+        .storage()
+        .dump(&to_str(namespace))?
+        .into_iter()
+        .map(From::from)
+        .collect())
+}
+
+fn to_str<S>(name: S) -> String
+where
+    S: AsRef<[u8]>,
+{
+    String::from_utf8_lossy(name.as_ref()).to_string()
 }
@@ -2,5 +2,9 @@ pub mod account_repo;
 pub mod db_version_repo;
 pub mod document_repo;
 pub mod file_metadata_repo;
+pub mod file_name_repo;
 pub mod local_changes_repo;
 pub mod local_storage;
+pub mod offline_repo;
+pub mod padding_repo;
+pub mod profile_repo;
@@ -0,0 +1,172 @@
+use crate::core_err_unexpected;
+use crate::model::state::Config;
+use crate::repo::local_storage;
+use crate::CoreError;
+use uuid::Uuid;
+
+static SYNC_MODE: &str = "sync_mode";
+static LAZY: &str = "lazy";
+static PINNED: &str = "pinned";
+static SYNC_EXCLUSIONS: &str = "sync_exclusions";
+static PENDING_DOWNLOADS: &str = "pending_downloads";
+
+pub fn set_lazy(config: &Config, lazy: bool) -> Result<(), CoreError> {
+    local_storage::write(
+        config,
+        SYNC_MODE,
+        LAZY,
+        serde_json::to_vec(&lazy).map_err(core_err_unexpected)?,
+    )
+}
+
+pub fn is_lazy(config: &Config) -> Result<bool, CoreError> {
+    let maybe_value: Option<Vec<u8>> = local_storage::read(config, SYNC_MODE, LAZY)?;
+    match maybe_value {
+        None => Ok(false),
+        Some(value) => Ok(serde_json::from_slice(value.as_ref()).map_err(core_err_unexpected)?),
+    }
+}
+
+pub fn pin(config: &Config, id: Uuid) -> Result<(), CoreError> {
+    local_storage::write(
+        config,
+        PINNED,
+        id.to_string().as_str(),
+        serde_json::to_vec(&id).map_err(core_err_unexpected)?,
+    )
+}
+
+pub fn unpin(config: &Config, id: Uuid) -> Result<(), CoreError> {
+    local_storage::delete(config, PINNED, id.to_string().as_str())
+}
+
+pub fn is_pinned(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    let maybe_value: Option<Vec<u8>> =
+        local_storage::read(config, PINNED, id.to_string().as_str())?;
+    Ok(maybe_value.is_some())
+}
+
+pub fn get_all_pinned(config: &Config) -> Result<Vec<Uuid>, CoreError> {
+    local_storage::dump::<_, Vec<u8>>(config, PINNED)?
+        .into_iter()
+        .map(|s| serde_json::from_slice(s.as_ref()).map_err(core_err_unexpected))
+        .collect()
+}
+
+pub fn set_exclusions(config: &Config, ids: &[Uuid]) -> Result<(), CoreError> {
+    for id in get_all_excluded(config)? {
+        local_storage::delete(config, SYNC_EXCLUSIONS, id.to_string().as_str())?;
+    }
+
+    for id in ids {
+        local_storage::write(
+            config,
+            SYNC_EXCLUSIONS,
+            id.to_string().as_str(),
+            serde_json::to_vec(id).map_err(core_err_unexpected)?,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn is_excluded(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    let maybe_value: Option<Vec<u8>> =
+        local_storage::read(config, SYNC_EXCLUSIONS, id.to_string().as_str())?;
+    Ok(maybe_value.is_some())
+}
+
+pub fn get_all_excluded(config: &Config) -> Result<Vec<Uuid>, CoreError> {
+    local_storage::dump::<_, Vec<u8>>(config, SYNC_EXCLUSIONS)?
+        .into_iter()
+        .map(|s| serde_json::from_slice(s.as_ref()).map_err(core_err_unexpected))
+        .collect()
+}
+
+/// Files whose content, and their children's, this device may now need to hold but hasn't
+/// downloaded yet, e.g. a folder whose exclusion was lifted while offline.
+pub fn add_pending_download(config: &Config, id: Uuid) -> Result<(), CoreError> {
+    local_storage::write(
+        config,
+        PENDING_DOWNLOADS,
+        id.to_string().as_str(),
+        serde_json::to_vec(&id).map_err(core_err_unexpected)?,
+    )
+}
+
+pub fn remove_pending_download(config: &Config, id: Uuid) -> Result<(), CoreError> {
+    local_storage::delete(config, PENDING_DOWNLOADS, id.to_string().as_str())
+}
+
+pub fn get_all_pending_downloads(config: &Config) -> Result<Vec<Uuid>, CoreError> {
+    local_storage::dump::<_, Vec<u8>>(config, PENDING_DOWNLOADS)?
+        .into_iter()
+        .map(|s| serde_json::from_slice(s.as_ref()).map_err(core_err_unexpected))
+        .collect()
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::model::state::temp_config;
+    use crate::repo::offline_repo;
+    use uuid::Uuid;
+
+    #[test]
+    fn lazy_defaults_to_false() {
+        let config = temp_config();
+
+        assert!(!offline_repo::is_lazy(&config).unwrap());
+        offline_repo::set_lazy(&config, true).unwrap();
+        assert!(offline_repo::is_lazy(&config).unwrap());
+        offline_repo::set_lazy(&config, false).unwrap();
+        assert!(!offline_repo::is_lazy(&config).unwrap());
+    }
+
+    #[test]
+    fn pin_and_unpin() {
+        let config = temp_config();
+        let id = Uuid::new_v4();
+
+        assert!(!offline_repo::is_pinned(&config, id).unwrap());
+        offline_repo::pin(&config, id).unwrap();
+        assert!(offline_repo::is_pinned(&config, id).unwrap());
+        assert_eq!(offline_repo::get_all_pinned(&config).unwrap(), vec![id]);
+        offline_repo::unpin(&config, id).unwrap();
+        assert!(!offline_repo::is_pinned(&config, id).unwrap());
+        assert!(offline_repo::get_all_pinned(&config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn set_exclusions_replaces_previous() {
+        let config = temp_config();
+        let id1 = Uuid::new_v4();
+        let id2 = Uuid::new_v4();
+
+        offline_repo::set_exclusions(&config, &[id1]).unwrap();
+        assert!(offline_repo::is_excluded(&config, id1).unwrap());
+
+        offline_repo::set_exclusions(&config, &[id2]).unwrap();
+        assert!(!offline_repo::is_excluded(&config, id1).unwrap());
+        assert!(offline_repo::is_excluded(&config, id2).unwrap());
+        assert_eq!(offline_repo::get_all_excluded(&config).unwrap(), vec![id2]);
+    }
+
+    #[test]
+    fn pending_downloads() {
+        let config = temp_config();
+        let id = Uuid::new_v4();
+
+        assert!(offline_repo::get_all_pending_downloads(&config)
+            .unwrap()
+            .is_empty());
+        offline_repo::add_pending_download(&config, id).unwrap();
+        assert_eq!(
+            offline_repo::get_all_pending_downloads(&config).unwrap(),
+            vec![id]
+        );
+        offline_repo::remove_pending_download(&config, id).unwrap();
+        assert!(offline_repo::get_all_pending_downloads(&config)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+use crate::core_err_unexpected;
+use crate::model::state::Config;
+use crate::repo::local_storage;
+use crate::CoreError;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+static PADDING: &str = "padding";
+static SCHEME: &str = "scheme";
+
+/// How documents written on this device are padded before they're encrypted, so the server can't
+/// tell a document's length from the size of its ciphertext. Streamed documents are not padded.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PaddingScheme {
+    Off,
+    /// Pads to the next power of two, hides the most but can double the space a document takes
+    PowerOfTwo,
+    /// Padmé, see "Reducing Metadata Leakage from Encrypted Files and Communication with PURBs".
+    /// Costs at most 12% more space.
+    Padme,
+}
+
+impl FromStr for PaddingScheme {
+    type Err = ();
+    fn from_str(input: &str) -> Result<PaddingScheme, Self::Err> {
+        match input {
+            "Off" => Ok(PaddingScheme::Off),
+            "PowerOfTwo" => Ok(PaddingScheme::PowerOfTwo),
+            "Padme" => Ok(PaddingScheme::Padme),
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn set_scheme(config: &Config, scheme: PaddingScheme) -> Result<(), CoreError> {
+    local_storage::write(
+        config,
+        PADDING,
+        SCHEME,
+        serde_json::to_vec(&scheme).map_err(core_err_unexpected)?,
+    )
+}
+
+pub fn get_scheme(config: &Config) -> Result<PaddingScheme, CoreError> {
+    let maybe_value: Option<Vec<u8>> = local_storage::read(config, PADDING, SCHEME)?;
+    match maybe_value {
+        None => Ok(PaddingScheme::Off),
+        Some(value) => Ok(serde_json::from_slice(value.as_ref()).map_err(core_err_unexpected)?),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::model::state::temp_config;
+    use crate::repo::padding_repo;
+    use crate::repo::padding_repo::PaddingScheme;
+
+    #[test]
+    fn padding_defaults_to_off() {
+        let config = temp_config();
+
+        assert_eq!(
+            padding_repo::get_scheme(&config).unwrap(),
+            PaddingScheme::Off
+        );
+        padding_repo::set_scheme(&config, PaddingScheme::Padme).unwrap();
+        assert_eq!(
+            padding_repo::get_scheme(&config).unwrap(),
+            PaddingScheme::Padme
+        );
+    }
+}
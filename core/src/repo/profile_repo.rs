@@ -0,0 +1,92 @@
+use crate::model::state::Config;
+use crate::repo::local_storage;
+use crate::{core_err_unexpected, CoreError};
+use std::fs::{create_dir_all, read_dir, remove_dir_all};
+use std::io::ErrorKind;
+
+static PROFILE: &str = "profile";
+static ACTIVE: &str = "active";
+static PROFILES: &str = "profiles";
+
+/// Where a named profile keeps its data, inside the installation's data directory. Profiles are
+/// directories, so they're always on the filesystem backend.
+pub fn config_for(base: &Config, name: &str) -> Config {
+    Config {
+        writeable_path: format!("{}/{}/{}", base.writeable_path, PROFILES, name),
+        storage: None,
+    }
+}
+
+pub fn set_active(base: &Config, name: &str) -> Result<(), CoreError> {
+    local_storage::write(base, PROFILE, ACTIVE, name)
+}
+
+pub fn get_active(base: &Config) -> Result<Option<String>, CoreError> {
+    let maybe_value: Option<Vec<u8>> = local_storage::read(base, PROFILE, ACTIVE)?;
+    match maybe_value {
+        None => Ok(None),
+        Some(value) => Ok(Some(String::from_utf8(value).map_err(core_err_unexpected)?)),
+    }
+}
+
+pub fn exists(base: &Config, name: &str) -> bool {
+    config_for(base, name).path().is_dir()
+}
+
+/// Names of the profiles created with `create`, sorted.
+pub fn list(base: &Config) -> Result<Vec<String>, CoreError> {
+    let entries = match read_dir(format!("{}/{}", base.writeable_path, PROFILES)) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(core_err_unexpected(err)),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(core_err_unexpected)?;
+        if entry.path().is_dir() {
+            if let Ok(name) = entry.file_name().into_string() {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+pub fn create(base: &Config, name: &str) -> Result<(), CoreError> {
+    if base.storage.is_some() {
+        return Err(CoreError::Unexpected(String::from(
+            "profiles need the filesystem storage backend",
+        )));
+    }
+    create_dir_all(config_for(base, name).path()).map_err(core_err_unexpected)
+}
+
+pub fn remove(base: &Config, name: &str) -> Result<(), CoreError> {
+    remove_dir_all(config_for(base, name).path()).map_err(core_err_unexpected)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::model::state::temp_config;
+    use crate::repo::profile_repo;
+
+    #[test]
+    fn create_list_remove() {
+        let config = temp_config();
+
+        assert_eq!(profile_repo::list(&config).unwrap(), Vec::<String>::new());
+        profile_repo::create(&config, "work").unwrap();
+        profile_repo::create(&config, "personal").unwrap();
+        assert_eq!(
+            profile_repo::list(&config).unwrap(),
+            vec!["personal", "work"]
+        );
+        assert!(profile_repo::exists(&config, "work"));
+
+        profile_repo::remove(&config, "work").unwrap();
+        assert_eq!(profile_repo::list(&config).unwrap(), vec!["personal"]);
+        assert!(!profile_repo::exists(&config, "work"));
+    }
+}
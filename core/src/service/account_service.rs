@@ -11,6 +11,7 @@ use lockbook_models::account::Account;
 use lockbook_models::api::{
     GetPublicKeyError, GetPublicKeyRequest, NewAccountError, NewAccountRequest,
 };
+use lockbook_models::crypto::Secret;
 
 pub fn create_account(
     config: &Config,
@@ -30,7 +31,7 @@ pub fn create_account(
     let account = Account {
         username: String::from(username),
         api_url: api_url.to_string(),
-        private_key: keys,
+        private_key: keys.into(),
     };
 
     info!("Generating Root Folder");
@@ -63,10 +64,7 @@ pub fn create_account(
 
     file_metadata_repo::insert(config, &file_metadata)?;
 
-    debug!(
-        "{}",
-        serde_json::to_string(&account).map_err(core_err_unexpected)?
-    );
+    debug!("{:?}", account);
 
     info!("Saving account locally");
     account_repo::insert_account(config, &account)?;
@@ -80,10 +78,10 @@ pub fn import_account(config: &Config, account_string: &str) -> Result<Account,
         return Err(CoreError::AccountExists);
     }
 
-    info!("Importing account string: {}", &account_string);
+    info!("Importing account string");
 
     let decoded = match base64::decode(&account_string) {
-        Ok(d) => d,
+        Ok(d) => Secret::new(d),
         Err(_) => {
             return Err(CoreError::AccountStringCorrupted);
         }
@@ -136,6 +134,6 @@ pub fn import_account(config: &Config, account_string: &str) -> Result<Account,
 
 pub fn export_account(config: &Config) -> Result<String, CoreError> {
     let account = account_repo::get_account(config)?;
-    let encoded: Vec<u8> = bincode::serialize(&account).map_err(core_err_unexpected)?;
-    Ok(base64::encode(&encoded))
+    let encoded = Secret::new(bincode::serialize(&account).map_err(core_err_unexpected)?);
+    Ok(base64::encode(&*encoded))
 }
@@ -1,10 +1,21 @@
 use crate::model::state::Config;
 use crate::repo::{account_repo, db_version_repo};
 use crate::service::db_state_service;
-use crate::service::db_state_service::State::{Empty, ReadyToUse, StateRequiresClearing};
+use crate::service::db_state_service::State::{
+    Empty, MigrationRequired, ReadyToUse, StateRequiresClearing,
+};
+use crate::service::file_name_service;
 use crate::CoreError;
 use serde::Serialize;
 
+type Migration = fn(&Config) -> Result<(), CoreError>;
+
+/// Migrations that don't change the db version, applied in order by `perform_migration`.
+static MIGRATIONS: [(&str, Migration); 1] = [(
+    "normalize_file_names",
+    file_name_service::normalize_existing,
+)];
+
 pub fn get_code_version() -> &'static str {
     env!("CARGO_PKG_VERSION")
 }
@@ -20,6 +31,9 @@ pub enum State {
 pub fn get_state(config: &Config) -> Result<State, CoreError> {
     if account_repo::maybe_get_account(config)?.is_none() {
         db_version_repo::set(config, db_state_service::get_code_version())?;
+        for (migration, _) in MIGRATIONS.iter() {
+            db_version_repo::set_applied(config, migration)?;
+        }
         return Ok(Empty);
     }
 
@@ -27,10 +41,10 @@ pub fn get_state(config: &Config) -> Result<State, CoreError> {
         None => Ok(StateRequiresClearing),
         Some(state_version) => {
             if state_version == db_state_service::get_code_version() {
-                Ok(ReadyToUse)
+                pending_migrations_state(config)
             } else {
                 match state_version.as_str() {
-                    "0.1.4" => Ok(ReadyToUse),
+                    "0.1.4" => pending_migrations_state(config),
                     _ => Ok(StateRequiresClearing),
                 }
             }
@@ -38,20 +52,36 @@ pub fn get_state(config: &Config) -> Result<State, CoreError> {
     }
 }
 
+fn pending_migrations_state(config: &Config) -> Result<State, CoreError> {
+    for (migration, _) in MIGRATIONS.iter() {
+        if !db_version_repo::is_applied(config, migration)? {
+            return Ok(MigrationRequired);
+        }
+    }
+    Ok(ReadyToUse)
+}
+
 pub fn perform_migration(config: &Config) -> Result<(), CoreError> {
     let db_version = match db_version_repo::get(config)? {
         None => return Err(CoreError::ClientWipeRequired),
         Some(version) => version,
     };
 
-    if db_version == db_state_service::get_code_version() {
-        return Ok(());
+    if db_version != db_state_service::get_code_version() {
+        match db_version.as_str() {
+            "0.1.4" => {}
+            _ => return Err(CoreError::ClientWipeRequired),
+        }
     }
 
-    match db_version.as_str() {
-        "0.1.4" => Ok(()),
-        _ => Err(CoreError::ClientWipeRequired),
+    for (migration, migrate) in MIGRATIONS.iter() {
+        if !db_version_repo::is_applied(config, migration)? {
+            migrate(config)?;
+            db_version_repo::set_applied(config, migration)?;
+        }
     }
+
+    db_version_repo::set(config, db_state_service::get_code_version())
 }
 
 #[cfg(test)]
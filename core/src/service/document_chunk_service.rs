@@ -0,0 +1,365 @@
+use crate::client;
+use crate::client::ApiError;
+use crate::model::state::Config;
+use crate::repo::local_storage::TempStream;
+use crate::repo::padding_repo::PaddingScheme;
+use crate::repo::{document_repo, padding_repo};
+use crate::service::file_compression_service::Codec;
+use crate::service::{document_stream_service, file_compression_service, file_encryption_service};
+use crate::{core_err_unexpected, CoreError};
+use lockbook_crypto::symkey;
+use lockbook_models::account::Account;
+use lockbook_models::api::{
+    ChangeDocumentContentChunksError, ChangeDocumentContentChunksRequest,
+    ChangeDocumentContentRequest, DocumentContent, GetDocumentChunksRequest,
+};
+use lockbook_models::crypto::{AESKey, EncryptedChunk, EncryptedDocument};
+use lockbook_models::file_metadata::FileMetadata;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Documents smaller than this are uploaded whole, chunking only pays off once edits touch a
+/// small part of the document.
+pub const CHUNKED_UPLOAD_THRESHOLD: usize = 64 * 1024;
+
+const MIN_CHUNK_SIZE: usize = 2 * 1024;
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+/// Cuts land on average every 8KiB past the minimum chunk size
+const BOUNDARY_MASK: u64 = (1 << 13) - 1;
+
+/// Pseudo-random value for each byte, rolled into the gear hash.
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut i = 0;
+    while i < 256 {
+        // splitmix64
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+static GEAR: [u64; 256] = gear_table();
+
+/// Splits content at boundaries picked from the content itself, so an edit only changes the chunks
+/// around it and the rest keep the same id.
+pub fn split(content: &[u8]) -> Vec<&[u8]> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    let mut hash = 0u64;
+
+    for (i, byte) in content.iter().enumerate() {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        let len = i + 1 - start;
+        if (len >= MIN_CHUNK_SIZE && hash & BOUNDARY_MASK == 0) || len >= MAX_CHUNK_SIZE {
+            chunks.push(&content[start..=i]);
+            start = i + 1;
+            hash = 0;
+        }
+    }
+
+    if start < content.len() {
+        chunks.push(&content[start..]);
+    }
+
+    chunks
+}
+
+pub fn chunk_id(key: &AESKey, chunk: &[u8]) -> Result<String, CoreError> {
+    let hmac = symkey::hmac(key, chunk).map_err(core_err_unexpected)?;
+    Ok(hmac.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Chunks are padded like documents, their sizes would otherwise give away the document's.
+fn encrypt_chunk(
+    key: &AESKey,
+    padding: PaddingScheme,
+    codec: Codec,
+    chunk: &[u8],
+) -> Result<EncryptedChunk, CoreError> {
+    let compressed = file_compression_service::compress_with(codec, chunk)?;
+    file_encryption_service::encrypt_padded(key, padding, &compressed)
+}
+
+fn decrypt_chunk(key: &AESKey, chunk: &EncryptedChunk) -> Result<Vec<u8>, CoreError> {
+    let compressed = file_encryption_service::decrypt_padded(key, chunk)?;
+    file_compression_service::decompress(&compressed)
+}
+
+fn decrypt_document(key: &AESKey, document: &EncryptedDocument) -> Result<Vec<u8>, CoreError> {
+    let compressed = file_encryption_service::decrypt_padded(key, document)?;
+    file_compression_service::decompress(&compressed)
+}
+
+/// Chunks of a version of the document this device already holds, by id.
+fn known_chunks(key: &AESKey, content: &[u8]) -> Result<HashMap<String, Vec<u8>>, CoreError> {
+    let mut chunks = HashMap::new();
+    for chunk in split(content) {
+        chunks.insert(chunk_id(key, chunk)?, chunk.to_vec());
+    }
+    Ok(chunks)
+}
+
+pub enum DownloadedDocument {
+    Whole(EncryptedDocument),
+    /// Streamed versions are written to storage as they download rather than held in memory
+    Streamed(TempStream),
+}
+
+impl DownloadedDocument {
+    pub fn insert(self, config: &Config, id: Uuid) -> Result<(), CoreError> {
+        match self {
+            DownloadedDocument::Whole(document) => document_repo::insert(config, id, &document),
+            DownloadedDocument::Streamed(stream) => {
+                document_repo::insert_stream(config, id, stream)
+            }
+        }
+    }
+
+    /// Fails with `CoreError::DocumentCorrupted` if the server sent something other than the
+    /// version `metadata` describes.
+    pub fn verify(&self, config: &Config, metadata: &FileMetadata) -> Result<(), CoreError> {
+        match self {
+            DownloadedDocument::Whole(document) => {
+                let key = file_encryption_service::decrypt_key_for_file(config, metadata.id)?;
+                file_encryption_service::read_verified_document(&key, metadata, document)
+                    .map(|_| ())
+            }
+            DownloadedDocument::Streamed(stream) => {
+                document_stream_service::verify(config, metadata, stream.reopen()?)
+            }
+        }
+    }
+}
+
+/// Fetches `metadata.content_version` of a document. Chunks shared with the copy this device
+/// already holds are not downloaded again. `on_progress` behaves as it does for
+/// `client::request_with_progress`, across both requests when the version was streamed.
+pub fn download(
+    config: &Config,
+    account: &Account,
+    metadata: &FileMetadata,
+    on_progress: &mut dyn FnMut(u64, u64) -> bool,
+) -> Result<DownloadedDocument, CoreError> {
+    let key = file_encryption_service::decrypt_key_for_file(config, metadata.id)?;
+    let mut known = match document_repo::maybe_get(config, metadata.id)? {
+        Some(cached) => match decrypt_document(&key, &cached) {
+            Ok(content) => known_chunks(&key, &content)?,
+            Err(_) => HashMap::new(),
+        },
+        None => HashMap::new(),
+    };
+
+    let mut received_before_stream = 0;
+    let content = client::request_with_progress(
+        account,
+        GetDocumentChunksRequest {
+            id: metadata.id,
+            content_version: metadata.content_version,
+            known_chunks: known.keys().cloned().collect(),
+        },
+        &mut |received, total| {
+            received_before_stream = received;
+            on_progress(received, total)
+        },
+    )
+    .map_err(CoreError::from)?
+    .content;
+
+    match content {
+        DocumentContent::Whole(document) => Ok(DownloadedDocument::Whole(document)),
+        DocumentContent::Streamed { size } => {
+            let stream = document_stream_service::download(
+                config,
+                account,
+                metadata,
+                &mut |received, _| {
+                    on_progress(
+                        received_before_stream + received,
+                        received_before_stream + size,
+                    )
+                },
+            )?;
+            Ok(DownloadedDocument::Streamed(stream))
+        }
+        DocumentContent::Chunked { manifest, chunks } => {
+            for (id, chunk) in chunks {
+                known.insert(id, decrypt_chunk(&key, &chunk)?);
+            }
+
+            let mut content = vec![];
+            for chunk in manifest.chunks {
+                let plaintext = known.get(&chunk.id).ok_or_else(|| {
+                    CoreError::Unexpected(format!("server did not send chunk {}", chunk.id))
+                })?;
+                content.extend_from_slice(plaintext);
+            }
+
+            let name = file_encryption_service::get_name(config, metadata)?;
+            let compressed = file_compression_service::compress_for(&name, &content)?;
+            let document = file_encryption_service::encrypt_padded(
+                &key,
+                padding_repo::get_scheme(config)?,
+                &compressed,
+            )?;
+            Ok(DownloadedDocument::Whole(document))
+        }
+    }
+}
+
+/// Uploads `new_content` as the next version of a document and returns the new version along with
+/// the number of bytes of content sent. Large documents only send the chunks that are not part of
+/// `old_content`, the version the server holds.
+pub fn upload(
+    config: &Config,
+    account: &Account,
+    metadata: &FileMetadata,
+    old_content: Option<&EncryptedDocument>,
+    new_content: &EncryptedDocument,
+) -> Result<(u64, u64), CoreError> {
+    let key = file_encryption_service::decrypt_key_for_file(config, metadata.id)?;
+    let compressed = file_encryption_service::decrypt_padded(&key, new_content)?;
+    // Chunks are compressed the way the document is, except that they're too small for zstd
+    let chunk_codec = match file_compression_service::codec_of(&compressed)? {
+        Codec::Store => Codec::Store,
+        _ => Codec::Zlib,
+    };
+    let content = file_compression_service::decompress(&compressed)?;
+
+    if content.len() < CHUNKED_UPLOAD_THRESHOLD {
+        let version = client::request(
+            account,
+            ChangeDocumentContentRequest {
+                id: metadata.id,
+                old_metadata_version: metadata.metadata_version,
+                new_content: new_content.clone(),
+                content_checksum: metadata.content_checksum,
+            },
+        )
+        .map_err(CoreError::from)?
+        .new_metadata_and_content_version;
+        return Ok((version, new_content.value.len() as u64));
+    }
+
+    let mut skip: HashSet<String> = match old_content.map(|old| decrypt_document(&key, old)) {
+        Some(Ok(old)) => known_chunks(&key, &old)?.keys().cloned().collect(),
+        Some(Err(_)) | None => HashSet::new(),
+    };
+
+    let mut ids = vec![];
+    let mut chunks = HashMap::new();
+    for chunk in split(&content) {
+        let id = chunk_id(&key, chunk)?;
+        chunks.entry(id.clone()).or_insert(chunk);
+        ids.push(id);
+    }
+
+    let padding = padding_repo::get_scheme(config)?;
+    loop {
+        let mut new_chunks = HashMap::new();
+        for (id, chunk) in &chunks {
+            if !skip.contains(id) {
+                new_chunks.insert(
+                    id.clone(),
+                    encrypt_chunk(&key, padding, chunk_codec, chunk)?,
+                );
+            }
+        }
+        let bytes_sent = new_chunks
+            .values()
+            .map(|chunk: &EncryptedChunk| chunk.value.len() as u64)
+            .sum();
+
+        let result = client::request(
+            account,
+            ChangeDocumentContentChunksRequest {
+                id: metadata.id,
+                old_metadata_version: metadata.metadata_version,
+                chunks: ids.clone(),
+                new_chunks,
+                content_checksum: metadata.content_checksum,
+            },
+        );
+
+        match result {
+            Ok(response) => return Ok((response.new_metadata_and_content_version, bytes_sent)),
+            // The server may not have kept the old version in chunks, send what it asks for
+            Err(ApiError::Endpoint(ChangeDocumentContentChunksError::ChunksMissing(missing)))
+                if missing.iter().any(|id| skip.contains(id)) =>
+            {
+                for id in missing {
+                    skip.remove(&id);
+                }
+            }
+            Err(err) => return Err(CoreError::from(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::service::document_chunk_service::{chunk_id, split, MAX_CHUNK_SIZE};
+    use lockbook_crypto::symkey;
+    use std::collections::HashSet;
+
+    fn content(len: usize) -> Vec<u8> {
+        let mut state = 7u64;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 33) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_reassembles() {
+        let content = content(500 * 1024);
+        let chunks = split(&content);
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= MAX_CHUNK_SIZE));
+        assert_eq!(chunks.concat(), content);
+    }
+
+    #[test]
+    fn split_empty() {
+        assert!(split(&[]).is_empty());
+    }
+
+    #[test]
+    fn edit_changes_few_chunks() {
+        let key = symkey::generate_key();
+        let before = content(500 * 1024);
+        let mut after = before.clone();
+        after.splice(
+            250 * 1024..250 * 1024,
+            b"an edit in the middle".iter().cloned(),
+        );
+
+        let before_ids: HashSet<String> = split(&before)
+            .into_iter()
+            .map(|chunk| chunk_id(&key, chunk).unwrap())
+            .collect();
+        let after_ids: Vec<String> = split(&after)
+            .into_iter()
+            .map(|chunk| chunk_id(&key, chunk).unwrap())
+            .collect();
+
+        let changed = after_ids
+            .iter()
+            .filter(|id| !before_ids.contains(*id))
+            .count();
+        assert!(
+            changed <= 2,
+            "{} of {} chunks changed",
+            changed,
+            after_ids.len()
+        );
+    }
+}
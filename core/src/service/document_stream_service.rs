@@ -0,0 +1,251 @@
+use crate::client;
+use crate::model::state::Config;
+use crate::repo::local_storage;
+use crate::repo::local_storage::{Stream, TempStream};
+use crate::service::file_compression_service::{Codec, Encoder};
+use crate::service::{file_compression_service, file_encryption_service};
+use crate::{core_err_unexpected, CoreError};
+use lockbook_crypto::stream;
+use lockbook_crypto::stream::{DecryptingReader, EncryptingWriter, SEGMENT_SIZE};
+use lockbook_crypto::symkey::HmacWriter;
+use lockbook_models::account::Account;
+use lockbook_models::api::{ChangeDocumentContentStreamRequest, GetDocumentStreamRequest};
+use lockbook_models::file_metadata::FileMetadata;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+
+/// Content smaller than this is better written whole, streamed documents aren't merged, padded or
+/// uploaded as chunks. Callers that know the size of what they're writing can use it to choose.
+pub const STREAMING_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Compresses and encrypts `content` segment by segment so that it never has to fit in memory.
+/// Returns the stream, ready for `document_repo::insert_stream`, along with a checksum of `content`
+/// and its keyed checksum for `FileMetadata::content_checksum`.
+pub fn encrypt(
+    config: &Config,
+    metadata: &FileMetadata,
+    content: &mut dyn Read,
+) -> Result<(TempStream, Vec<u8>, [u8; 32]), CoreError> {
+    let key = file_encryption_service::decrypt_key_for_file(config, metadata.id)?;
+    let name = file_encryption_service::get_name(config, metadata)?;
+    let mut stream = local_storage::temp_file(config)?;
+    let mut hasher = Sha256::new();
+    let mut content_checksum = HmacWriter::new(&key).map_err(core_err_unexpected)?;
+
+    let mut buffer = vec![0u8; SEGMENT_SIZE];
+    let mut read = read_some(content, &mut buffer)?;
+    // Streamed documents are large, so zstd unless the content is already compressed
+    let codec = if file_compression_service::is_precompressed(&name, &buffer[..read]) {
+        Codec::Store
+    } else {
+        Codec::Zstd
+    };
+
+    let writer = EncryptingWriter::new(&key, BufWriter::new(&mut stream))?;
+    let mut encoder = Encoder::new(codec, writer)?;
+    while read > 0 {
+        hasher.input(&buffer[..read]);
+        content_checksum.write_all(&buffer[..read])?;
+        encoder.write_all(&buffer[..read])?;
+        read = read_some(content, &mut buffer)?;
+    }
+    encoder.finish()?.finish()?.flush()?;
+
+    Ok((stream, hasher.result().to_vec(), content_checksum.finish()))
+}
+
+fn read_some(content: &mut dyn Read, buffer: &mut [u8]) -> Result<usize, CoreError> {
+    loop {
+        match content.read(buffer) {
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            result => return result.map_err(CoreError::from),
+        }
+    }
+}
+
+/// The content of a streamed document.
+pub fn open(
+    config: &Config,
+    metadata: &FileMetadata,
+    stream: Stream,
+) -> Result<impl Read, CoreError> {
+    open_with_codec(config, metadata, stream).map(|(_, content)| content)
+}
+
+fn open_with_codec(
+    config: &Config,
+    metadata: &FileMetadata,
+    stream: Stream,
+) -> Result<(Codec, impl Read), CoreError> {
+    let key = file_encryption_service::decrypt_key_for_file(config, metadata.id)?;
+    let reader =
+        DecryptingReader::new(&key, BufReader::new(stream)).map_err(core_err_unexpected)?;
+    file_compression_service::decoder_with_codec(reader)
+}
+
+/// Writes the content of a streamed document to `destination` and returns its length.
+pub fn decrypt(
+    config: &Config,
+    metadata: &FileMetadata,
+    stream: Stream,
+    destination: &mut dyn Write,
+) -> Result<u64, CoreError> {
+    copy_content(config, metadata, stream, destination).map_err(|err| match err {
+        CopyError::Reading(err) | CopyError::Writing(err) => err,
+    })
+}
+
+/// Like `decrypt`, but fails with `CoreError::DocumentCorrupted` if the stream doesn't decrypt or its
+/// content doesn't match `metadata.content_checksum`. By then the content has been written to
+/// `destination`. Failures to write to `destination` are returned as they are.
+pub fn decrypt_verified(
+    config: &Config,
+    metadata: &FileMetadata,
+    stream: Stream,
+    destination: &mut dyn Write,
+) -> Result<u64, CoreError> {
+    let key = file_encryption_service::decrypt_key_for_file(config, metadata.id)?;
+    let mut checksumming = ChecksummingWriter {
+        destination,
+        checksum: HmacWriter::new(&key).map_err(core_err_unexpected)?,
+    };
+
+    let written = match copy_content(config, metadata, stream, &mut checksumming) {
+        Ok(written) => written,
+        Err(CopyError::Reading(CoreError::Unexpected(msg))) => {
+            error!("Could not read content of {}: {}", metadata.id, msg);
+            return Err(CoreError::DocumentCorrupted);
+        }
+        Err(CopyError::Reading(err)) | Err(CopyError::Writing(err)) => return Err(err),
+    };
+
+    file_encryption_service::verify_content_checksum(metadata, checksumming.checksum.finish())?;
+    Ok(written)
+}
+
+/// Which side of a copy failed, so that a destination that can't be written to (a closed pipe, a
+/// full disk) isn't mistaken for content that can't be decrypted.
+enum CopyError {
+    Reading(CoreError),
+    Writing(CoreError),
+}
+
+fn copy_content(
+    config: &Config,
+    metadata: &FileMetadata,
+    stream: Stream,
+    destination: &mut dyn Write,
+) -> Result<u64, CopyError> {
+    let mut content = open(config, metadata, stream).map_err(CopyError::Reading)?;
+    let mut buffer = vec![0u8; SEGMENT_SIZE];
+    let mut written = 0;
+    loop {
+        let read = match content.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(CopyError::Reading(core_err_unexpected(err))),
+        };
+        destination
+            .write_all(&buffer[..read])
+            .map_err(|err| CopyError::Writing(CoreError::from(err)))?;
+        written += read as u64;
+    }
+    Ok(written)
+}
+
+/// Checks a streamed document the way `decrypt_verified` does, without keeping the content.
+pub fn verify(config: &Config, metadata: &FileMetadata, stream: Stream) -> Result<(), CoreError> {
+    decrypt_verified(config, metadata, stream, &mut io::sink()).map(|_| ())
+}
+
+struct ChecksummingWriter<'a> {
+    destination: &'a mut dyn Write,
+    checksum: HmacWriter,
+}
+
+impl Write for ChecksummingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.destination.write(buf)?;
+        self.checksum.write_all(&buf[..written])?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.destination.flush()
+    }
+}
+
+/// The codec a streamed document was compressed with, its compressed size and its size.
+pub fn measure(
+    config: &Config,
+    metadata: &FileMetadata,
+    stream: Stream,
+) -> Result<(Codec, u64, u64), CoreError> {
+    let compressed_size = stream::plaintext_len(stream.size()?);
+    let (codec, mut content) = open_with_codec(config, metadata, stream)?;
+    let size = io::copy(&mut content, &mut io::sink()).map_err(core_err_unexpected)?;
+    Ok((codec, compressed_size, size))
+}
+
+/// A checksum of a streamed document's content, comparable to those taken of whole documents.
+pub fn checksum(
+    config: &Config,
+    metadata: &FileMetadata,
+    stream: Stream,
+) -> Result<Vec<u8>, CoreError> {
+    let mut hasher = Sha256::new();
+    decrypt(config, metadata, stream, &mut hasher)?;
+    Ok(hasher.result().to_vec())
+}
+
+/// Uploads a streamed document as its next version. Returns the new version and the number of
+/// bytes sent.
+pub fn upload(
+    account: &Account,
+    metadata: &FileMetadata,
+    stream: Stream,
+) -> Result<(u64, u64), CoreError> {
+    let size = stream.size()?;
+    let version = client::upload_stream(
+        account,
+        ChangeDocumentContentStreamRequest {
+            id: metadata.id,
+            old_metadata_version: metadata.metadata_version,
+            size,
+            content_checksum: metadata.content_checksum,
+        },
+        stream,
+        size,
+    )
+    .map_err(CoreError::from)?
+    .new_metadata_and_content_version;
+
+    Ok((version, size))
+}
+
+/// Downloads a streamed version of a document, ready for `document_repo::insert_stream`.
+pub fn download(
+    config: &Config,
+    account: &Account,
+    metadata: &FileMetadata,
+    on_progress: &mut dyn FnMut(u64, u64) -> bool,
+) -> Result<TempStream, CoreError> {
+    let mut stream = local_storage::temp_file(config)?;
+    {
+        let mut destination = BufWriter::new(&mut stream);
+        client::download_stream(
+            account,
+            GetDocumentStreamRequest {
+                id: metadata.id,
+                content_version: metadata.content_version,
+            },
+            &mut destination,
+            on_progress,
+        )
+        .map_err(CoreError::from)?;
+        destination.flush()?;
+    }
+    Ok(stream)
+}
@@ -379,7 +379,7 @@ mod unit_tests {
         let account = Account {
             username: String::from("username"),
             api_url: "ftp://uranus.net".to_string(),
-            private_key: keys,
+            private_key: keys.into(),
         };
 
         account_repo::insert_account(config, &account).unwrap();
@@ -422,7 +422,7 @@ mod unit_tests {
         let account = Account {
             username: String::from("username"),
             api_url: "ftp://uranus.net".to_string(),
-            private_key: keys,
+            private_key: keys.into(),
         };
 
         account_repo::insert_account(config, &account).unwrap();
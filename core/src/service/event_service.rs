@@ -0,0 +1,122 @@
+use crate::model::state::Config;
+use crate::{core_err_unexpected, CoreError};
+use lockbook_models::file_metadata::FileMetadata;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use uuid::Uuid;
+
+/// Something that changed in a data directory, so that UIs can update what they show in place
+/// instead of reloading everything. The same change may occasionally be reported twice, e.g. when
+/// sync merges a document and then records the server's version of it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ChangeEvent {
+    FileCreated {
+        id: Uuid,
+        parent: Uuid,
+    },
+    Renamed {
+        id: Uuid,
+    },
+    Moved {
+        id: Uuid,
+        old_parent: Uuid,
+        new_parent: Uuid,
+    },
+    ContentChanged {
+        id: Uuid,
+    },
+    Deleted {
+        id: Uuid,
+    },
+    SyncStateChanged(SyncState),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum SyncState {
+    Started,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+pub type Subscriber = Arc<dyn Fn(&ChangeEvent) + Send + Sync>;
+
+/// Callbacks registered through `subscribe`, keyed by the data directory they are watching.
+static SUBSCRIBERS: Mutex<Vec<(String, u64, Subscriber)>> = Mutex::new(Vec::new());
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Calls `callback` with every change made to this data directory from now on, on the thread that
+/// made it. Returns an id for `unsubscribe`.
+pub fn subscribe(config: &Config, callback: Subscriber) -> Result<u64, CoreError> {
+    let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::SeqCst);
+    subscribers()?.push((config.writeable_path.clone(), id, callback));
+    Ok(id)
+}
+
+/// Returns whether there was a subscription with this id.
+pub fn unsubscribe(id: u64) -> Result<bool, CoreError> {
+    let mut subscribers = subscribers()?;
+    let before = subscribers.len();
+    subscribers.retain(|(_, subscription_id, _)| *subscription_id != id);
+    Ok(subscribers.len() != before)
+}
+
+/// Tells this data directory's subscribers about `event`. A change has already been made by the
+/// time it's emitted, so failing to report it is logged rather than returned.
+pub fn emit(config: &Config, event: ChangeEvent) {
+    // Callbacks run without the lock held so that they can subscribe and unsubscribe
+    let interested = match subscribers() {
+        Ok(subscribers) => subscribers
+            .iter()
+            .filter(|(path, _, _)| *path == config.writeable_path)
+            .map(|(_, _, callback)| Arc::clone(callback))
+            .collect::<Vec<Subscriber>>(),
+        Err(err) => {
+            error!("Could not emit {:?}: {:#?}", event, err);
+            return;
+        }
+    };
+
+    for callback in interested {
+        callback(&event);
+    }
+}
+
+/// The events for a file going from `before` to `after`, for changes that come from the server.
+pub fn emit_file_changes(config: &Config, before: Option<&FileMetadata>, after: &FileMetadata) {
+    let id = after.id;
+    match before {
+        None if !after.deleted => emit(
+            config,
+            ChangeEvent::FileCreated {
+                id,
+                parent: after.parent,
+            },
+        ),
+        None => {}
+        Some(_) if after.deleted => emit(config, ChangeEvent::Deleted { id }),
+        Some(before) => {
+            if before.name != after.name {
+                emit(config, ChangeEvent::Renamed { id });
+            }
+            if before.parent != after.parent {
+                emit(
+                    config,
+                    ChangeEvent::Moved {
+                        id,
+                        old_parent: before.parent,
+                        new_parent: after.parent,
+                    },
+                );
+            }
+            if before.content_version != after.content_version {
+                emit(config, ChangeEvent::ContentChanged { id });
+            }
+        }
+    }
+}
+
+fn subscribers() -> Result<MutexGuard<'static, Vec<(String, u64, Subscriber)>>, CoreError> {
+    SUBSCRIBERS.lock().map_err(core_err_unexpected)
+}
@@ -1,26 +1,230 @@
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
-use std::io::{Read, Write};
+use serde::Serialize;
+use std::io;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
 
 use crate::CoreError;
 
+/// Compressed content starts with `MAGIC` followed by a byte identifying the codec. Content without
+/// it was compressed before codecs were recorded, and is zlib. A zlib stream can't start with `L`:
+/// the low bits of its first byte name the compression method, which must be 8.
+const MAGIC: &[u8; 3] = b"LBC";
+pub const HEADER_LEN: usize = MAGIC.len() + 1;
+
+/// Content at least this large is compressed with zstd, which is faster and compresses better than
+/// zlib once there's enough content for it to find patterns in.
+pub const ZSTD_THRESHOLD: usize = 64 * 1024;
+/// zstd's default level.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Extensions of files whose content is already compressed, compressing them again only costs time.
+const PRECOMPRESSED_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "heic", "mp3", "m4a", "ogg", "flac", "mp4", "m4v", "mov",
+    "mkv", "webm", "zip", "gz", "tgz", "bz2", "xz", "7z", "rar", "zst", "jar", "apk", "docx",
+    "xlsx", "pptx", "epub",
+];
+
+/// Magic numbers of already compressed formats, for files whose extension doesn't say.
+const PRECOMPRESSED_SIGNATURES: &[&[u8]] = &[
+    b"\x89PNG",
+    b"\xFF\xD8\xFF",
+    b"GIF8",
+    b"PK\x03\x04",
+    b"\x1F\x8B",
+    b"BZh",
+    b"\xFD7zXZ\x00",
+    b"7z\xBC\xAF\x27\x1C",
+    b"Rar!",
+    b"\x28\xB5\x2F\xFD",
+];
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Codec {
+    /// zlib without a header, as written before codecs were recorded
+    LegacyZlib,
+    Zlib,
+    Zstd,
+    Store,
+}
+
+impl Codec {
+    fn id(self) -> Option<u8> {
+        match self {
+            Codec::LegacyZlib => None,
+            Codec::Zlib => Some(1),
+            Codec::Zstd => Some(2),
+            Codec::Store => Some(3),
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Codec> {
+        match id {
+            1 => Some(Codec::Zlib),
+            2 => Some(Codec::Zstd),
+            3 => Some(Codec::Store),
+            _ => None,
+        }
+    }
+
+    fn header(self) -> Vec<u8> {
+        match self.id() {
+            Some(id) => [&MAGIC[..], &[id]].concat(),
+            None => vec![],
+        }
+    }
+}
+
+pub fn is_precompressed(name: &str, content_start: &[u8]) -> bool {
+    let extension_matches = match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some(extension) => PRECOMPRESSED_EXTENSIONS
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(extension)),
+        None => false,
+    };
+
+    extension_matches
+        || PRECOMPRESSED_SIGNATURES
+            .iter()
+            .any(|signature| content_start.starts_with(signature))
+}
+
+/// Picks a codec for a document from its name and content.
+pub fn choose_codec(name: &str, content: &[u8]) -> Codec {
+    if is_precompressed(name, content) {
+        Codec::Store
+    } else if content.len() >= ZSTD_THRESHOLD {
+        Codec::Zstd
+    } else {
+        Codec::Zlib
+    }
+}
+
+/// Compresses content without a name, the codec is picked from the content alone.
 pub fn compress(content: &[u8]) -> Result<Vec<u8>, CoreError> {
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    compress_with(choose_codec("", content), content)
+}
+
+pub fn compress_for(name: &str, content: &[u8]) -> Result<Vec<u8>, CoreError> {
+    compress_with(choose_codec(name, content), content)
+}
+
+pub fn compress_with(codec: Codec, content: &[u8]) -> Result<Vec<u8>, CoreError> {
+    let mut encoder = Encoder::new(codec, Vec::new())?;
     encoder.write_all(content).map_err(CoreError::from)?;
     encoder.finish().map_err(CoreError::from)
 }
 
 pub fn decompress(content: &[u8]) -> Result<Vec<u8>, CoreError> {
-    let mut decoder = ZlibDecoder::new(content);
     let mut result = Vec::<u8>::new();
-    decoder.read_to_end(&mut result).map_err(CoreError::from)?;
+    decoder(content)?
+        .read_to_end(&mut result)
+        .map_err(CoreError::from)?;
     Ok(result)
 }
 
+/// The codec `content` was compressed with.
+pub fn codec_of(content: &[u8]) -> Result<Codec, CoreError> {
+    if !content.starts_with(MAGIC) {
+        return Ok(Codec::LegacyZlib);
+    }
+    match content.get(MAGIC.len()) {
+        Some(id) => Codec::from_id(*id)
+            .ok_or_else(|| CoreError::Unexpected(format!("unknown compression codec {}", id))),
+        None => Err(CoreError::Unexpected(String::from(
+            "compression header cut short",
+        ))),
+    }
+}
+
+/// Compresses everything written to it into `inner`, after the codec's header. `finish` must be
+/// called to complete the content.
+pub enum Encoder<W: Write> {
+    Zlib(ZlibEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Store(W),
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(codec: Codec, mut inner: W) -> Result<Encoder<W>, CoreError> {
+        inner.write_all(&codec.header())?;
+        Ok(match codec {
+            Codec::LegacyZlib | Codec::Zlib => {
+                Encoder::Zlib(ZlibEncoder::new(inner, Compression::default()))
+            }
+            Codec::Zstd => Encoder::Zstd(zstd::stream::write::Encoder::new(inner, ZSTD_LEVEL)?),
+            Codec::Store => Encoder::Store(inner),
+        })
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Zlib(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+            Encoder::Store(inner) => Ok(inner),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Zlib(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+            Encoder::Store(inner) => inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Zlib(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+            Encoder::Store(inner) => inner.flush(),
+        }
+    }
+}
+
+/// Decompresses content read from `inner`, whichever codec it was written with.
+pub fn decoder<'a, R: Read + 'a>(inner: R) -> Result<Box<dyn Read + 'a>, CoreError> {
+    decoder_with_codec(inner).map(|(_, decoder)| decoder)
+}
+
+pub fn decoder_with_codec<'a, R: Read + 'a>(
+    mut inner: R,
+) -> Result<(Codec, Box<dyn Read + 'a>), CoreError> {
+    let mut header = [0u8; HEADER_LEN];
+    let mut read = 0;
+    while read < HEADER_LEN {
+        match inner.read(&mut header[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(CoreError::from(err)),
+        }
+    }
+
+    let codec = codec_of(&header[..read])?;
+    let decoder: Box<dyn Read + 'a> = match codec {
+        // What was read belongs to the content
+        Codec::LegacyZlib => Box::new(ZlibDecoder::new(
+            Cursor::new(header[..read].to_vec()).chain(inner),
+        )),
+        Codec::Zlib => Box::new(ZlibDecoder::new(inner)),
+        Codec::Zstd => Box::new(zstd::stream::read::Decoder::new(inner)?),
+        Codec::Store => Box::new(inner),
+    };
+    Ok((codec, decoder))
+}
+
 #[cfg(test)]
 mod unit_tests {
     use crate::service::file_compression_service;
+    use crate::service::file_compression_service::{Codec, ZSTD_THRESHOLD};
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
 
     #[test]
     fn compress_decompress() {
@@ -29,4 +233,53 @@ mod unit_tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn every_codec_round_trips() {
+        let content = b"hello hello hello hello".repeat(100);
+        for codec in &[Codec::LegacyZlib, Codec::Zlib, Codec::Zstd, Codec::Store] {
+            let compressed = file_compression_service::compress_with(*codec, &content).unwrap();
+            assert_eq!(
+                file_compression_service::codec_of(&compressed).unwrap(),
+                *codec
+            );
+            assert_eq!(
+                file_compression_service::decompress(&compressed).unwrap(),
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn decompress_legacy_zlib() {
+        // As written before codecs were recorded
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"legacy content").unwrap();
+        let legacy = encoder.finish().unwrap();
+
+        assert_eq!(
+            file_compression_service::decompress(&legacy).unwrap(),
+            b"legacy content"
+        );
+    }
+
+    #[test]
+    fn codec_choice() {
+        assert_eq!(
+            file_compression_service::choose_codec("notes.md", b"# notes"),
+            Codec::Zlib
+        );
+        assert_eq!(
+            file_compression_service::choose_codec("notes.md", &vec![b'a'; ZSTD_THRESHOLD]),
+            Codec::Zstd
+        );
+        assert_eq!(
+            file_compression_service::choose_codec("photo.JPG", b"anything"),
+            Codec::Store
+        );
+        assert_eq!(
+            file_compression_service::choose_codec("untitled", b"\x89PNG\r\n\x1a\n"),
+            Codec::Store
+        );
+    }
 }
@@ -1,15 +1,26 @@
 use crate::model::state::Config;
-use crate::repo::{account_repo, file_metadata_repo};
+use crate::repo::padding_repo::PaddingScheme;
+use crate::repo::{account_repo, file_metadata_repo, padding_repo};
+use crate::service::file_compression_service;
 use crate::{core_err_unexpected, CoreError};
-use lockbook_crypto::{pubkey, symkey};
+use lockbook_crypto::{clock_service, pubkey, symkey};
 use lockbook_models::account::Account;
 use lockbook_models::crypto::*;
 use lockbook_models::file_metadata::FileType::Folder;
-use lockbook_models::file_metadata::{FileMetadata, FileType};
+use lockbook_models::file_metadata::{FileMetadata, FileType, MetadataSignature, SignedFields};
 use std::collections::HashMap;
+use std::convert::TryInto;
 use uuid::Uuid;
 
-pub fn decrypt_key_for_file(config: &Config, id: Uuid) -> Result<AESKey, CoreError> {
+/// Padded content starts with `PADDING_MAGIC` and the length of the content, followed by the
+/// content and zeros up to the padded length. Content without it was written unpadded. Compressed
+/// content starts with `LBC` or a zlib header, so it can't be mistaken for padded content.
+const PADDING_MAGIC: &[u8; 3] = b"LBP";
+const PADDING_HEADER_LEN: usize = PADDING_MAGIC.len() + 8;
+/// Padded documents smaller than this are all padded to this length.
+const MIN_PADDED_LEN: u64 = 256;
+
+pub fn decrypt_key_for_file(config: &Config, id: Uuid) -> Result<SecretAESKey, CoreError> {
     let account = account_repo::get_account(&config)?;
     let parents = file_metadata_repo::get_with_all_parents(&config, id)?;
     let access_key = parents
@@ -20,15 +31,15 @@ pub fn decrypt_key_for_file(config: &Config, id: Uuid) -> Result<AESKey, CoreErr
         None => {
             let folder_access = access_key.folder_access_keys.clone();
             let decrypted_parent = decrypt_key_for_file(&config, access_key.parent)?;
-            let key =
-                symkey::decrypt(&decrypted_parent, &folder_access).map_err(core_err_unexpected)?;
+            let key = symkey::decrypt_key(&decrypted_parent, &folder_access)
+                .map_err(core_err_unexpected)?;
             Ok(key)
         }
         Some(user_access) => {
             let access_key_key =
                 pubkey::get_aes_key(&account.private_key, &user_access.encrypted_by)
                     .map_err(core_err_unexpected)?;
-            let key = symkey::decrypt(&access_key_key, &user_access.access_key)
+            let key = symkey::decrypt_key(&access_key_key, &user_access.access_key)
                 .map_err(core_err_unexpected)?;
             Ok(key)
         }
@@ -37,21 +48,37 @@ pub fn decrypt_key_for_file(config: &Config, id: Uuid) -> Result<AESKey, CoreErr
 
 pub fn re_encrypt_key_for_file(
     config: &Config,
-    file_key: AESKey,
+    file_key: &AESKey,
     new_parent_id: Uuid,
 ) -> Result<EncryptedFolderAccessKey, CoreError> {
     let parent_key = decrypt_key_for_file(&config, new_parent_id)?;
-    let access_key = symkey::encrypt(&parent_key, &file_key).map_err(core_err_unexpected)?;
+    let access_key = symkey::encrypt(&parent_key, file_key).map_err(core_err_unexpected)?;
     Ok(access_key)
 }
 
+/// Finds which of `candidates` a file's key was encrypted with, for files whose recorded parent
+/// is missing or wrong. Returns that file's id and key.
+pub fn find_parent_key(
+    config: &Config,
+    meta: &FileMetadata,
+    candidates: &[Uuid],
+) -> Result<Option<(Uuid, SecretAESKey)>, CoreError> {
+    for candidate in candidates.iter().filter(|candidate| **candidate != meta.id) {
+        let key = decrypt_key_for_file(&config, *candidate)?;
+        if symkey::decrypt_key(&key, &meta.folder_access_keys).is_ok() {
+            return Ok(Some((*candidate, key)));
+        }
+    }
+    Ok(None)
+}
+
 pub fn get_key_for_user(config: &Config, id: Uuid) -> Result<UserAccessInfo, CoreError> {
     let account = account_repo::get_account(&config)?;
     let key = decrypt_key_for_file(&config, id)?;
     let public_key = account.public_key();
     let key_encryption_key = pubkey::get_aes_key(&account.private_key, &account.public_key())
         .map_err(core_err_unexpected)?;
-    let access_key = symkey::encrypt(&key_encryption_key, &key).map_err(core_err_unexpected)?;
+    let access_key = symkey::encrypt(&key_encryption_key, &*key).map_err(core_err_unexpected)?;
 
     Ok(UserAccessInfo {
         username: account.username,
@@ -68,8 +95,8 @@ pub fn create_file_metadata(
 ) -> Result<FileMetadata, CoreError> {
     let account = account_repo::get_account(&config)?;
     let parent_key = decrypt_key_for_file(&config, parent)?;
-    let folder_access_keys =
-        symkey::encrypt(&parent_key, &symkey::generate_key()).map_err(core_err_unexpected)?;
+    let key = Secret::new(symkey::generate_key());
+    let folder_access_keys = symkey::encrypt(&parent_key, &*key).map_err(core_err_unexpected)?;
     let id = Uuid::new_v4();
     let name = symkey::encrypt_and_hmac(&parent_key, name).map_err(core_err_unexpected)?;
 
@@ -84,18 +111,20 @@ pub fn create_file_metadata(
         deleted: false,
         user_access_keys: Default::default(),
         folder_access_keys,
+        signature: None,
+        content_checksum: None,
     })
 }
 
 pub fn create_metadata_for_root_folder(account: &Account) -> Result<FileMetadata, CoreError> {
     let id = Uuid::new_v4();
-    let key = symkey::generate_key();
+    let key = Secret::new(symkey::generate_key());
     let name =
         symkey::encrypt_and_hmac(&key, &account.username.clone()).map_err(core_err_unexpected)?;
     let key_encryption_key = pubkey::get_aes_key(&account.private_key, &account.public_key())
         .map_err(core_err_unexpected)?;
     let encrypted_access_key =
-        symkey::encrypt(&key_encryption_key, &key).map_err(core_err_unexpected)?;
+        symkey::encrypt(&key_encryption_key, &*key).map_err(core_err_unexpected)?;
     let use_access_key = UserAccessInfo {
         username: account.username.clone(),
         encrypted_by: account.public_key(),
@@ -115,42 +144,190 @@ pub fn create_metadata_for_root_folder(account: &Account) -> Result<FileMetadata
         metadata_version: 0,
         deleted: false,
         user_access_keys,
-        folder_access_keys: symkey::encrypt(&symkey::generate_key(), &key)
+        folder_access_keys: symkey::encrypt(&symkey::generate_key(), &*key)
             .map_err(core_err_unexpected)?,
+        signature: None,
+        content_checksum: None,
     })
 }
 
+/// Signs `metadata` as the server will hold it once the change it's sent with is applied.
+pub fn sign_metadata(
+    account: &Account,
+    metadata: &FileMetadata,
+) -> Result<MetadataSignature, CoreError> {
+    pubkey::sign(
+        &account.private_key,
+        metadata.signed_fields(),
+        clock_service::get_time,
+    )
+    .map(MetadataSignature::new)
+    .map_err(core_err_unexpected)
+}
+
+pub fn verify_metadata(signature: &MetadataSignature, fields: SignedFields) -> bool {
+    pubkey::verify_signature(&signature.over(fields)).is_ok()
+}
+
 pub fn write_to_document(
     config: &Config,
     content: &[u8],
     metadata: &FileMetadata,
 ) -> Result<EncryptedDocument, CoreError> {
     let key = decrypt_key_for_file(&config, metadata.id)?;
-    symkey::encrypt(&key, &content.to_vec()).map_err(core_err_unexpected)
+    encrypt_padded(&key, padding_repo::get_scheme(config)?, content)
 }
 
 pub fn read_document(
     config: &Config,
     file: &EncryptedDocument,
     metadata: &FileMetadata,
-) -> Result<DecryptedDocument, CoreError> {
+) -> Result<SecretDocument, CoreError> {
+    let key = decrypt_key_for_file(&config, metadata.id)?;
+    decrypt_padded(&key, file)
+}
+
+/// The keyed checksum of a document's content, for `FileMetadata::content_checksum`.
+pub fn content_checksum(
+    config: &Config,
+    metadata: &FileMetadata,
+    content: &[u8],
+) -> Result<[u8; 32], CoreError> {
     let key = decrypt_key_for_file(&config, metadata.id)?;
-    symkey::decrypt(&key, file).map_err(core_err_unexpected)
+    symkey::hmac(&key, content).map_err(core_err_unexpected)
+}
+
+/// Fails with `CoreError::DocumentCorrupted` if `checksum`, taken of a document's content, isn't
+/// the one in its metadata. Documents without one can't be checked.
+pub fn verify_content_checksum(
+    metadata: &FileMetadata,
+    checksum: [u8; 32],
+) -> Result<(), CoreError> {
+    match metadata.content_checksum {
+        Some(expected) if expected != checksum => {
+            error!("Content of {} does not match its checksum", metadata.id);
+            Err(CoreError::DocumentCorrupted)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Decrypts and decompresses a whole document, then checks it against its checksum. Content that
+/// was corrupted in storage or in transit fails one of these with `CoreError::DocumentCorrupted`.
+pub fn read_verified_document(
+    key: &AESKey,
+    metadata: &FileMetadata,
+    document: &EncryptedDocument,
+) -> Result<SecretDocument, CoreError> {
+    let content = decrypt_padded(key, document)
+        .and_then(|compressed| file_compression_service::decompress(&compressed))
+        .map(SecretDocument::new)
+        .map_err(|err| {
+            error!("Could not read content of {}: {:?}", metadata.id, err);
+            CoreError::DocumentCorrupted
+        })?;
+
+    if metadata.content_checksum.is_some() {
+        verify_content_checksum(
+            metadata,
+            symkey::hmac(key, &content).map_err(core_err_unexpected)?,
+        )?;
+    }
+
+    Ok(content)
+}
+
+/// Bytes of padding in a document, what the server stores beyond the document's content.
+pub fn padding_of(
+    config: &Config,
+    file: &EncryptedDocument,
+    metadata: &FileMetadata,
+) -> Result<u64, CoreError> {
+    let key = decrypt_key_for_file(&config, metadata.id)?;
+    let padded = symkey::decrypt(&key, file).map_err(core_err_unexpected)?;
+    let len = padded.len();
+    Ok((len - unpad(padded)?.len()) as u64)
 }
 
 pub fn user_read_document(
     account: &Account,
     file: &EncryptedDocument,
     user_access_info: &UserAccessInfo,
-) -> Result<DecryptedDocument, CoreError> {
+) -> Result<SecretDocument, CoreError> {
     let key_decryption_key =
         pubkey::get_aes_key(&account.private_key, &user_access_info.encrypted_by)
             .map_err(core_err_unexpected)?;
-    let key = symkey::decrypt(&key_decryption_key, &user_access_info.access_key)
+    let key = symkey::decrypt_key(&key_decryption_key, &user_access_info.access_key)
         .map_err(core_err_unexpected)?;
 
-    let content = symkey::decrypt(&key, file).map_err(core_err_unexpected)?;
-    Ok(content)
+    decrypt_padded(&key, file)
+}
+
+pub fn encrypt_padded(
+    key: &AESKey,
+    scheme: PaddingScheme,
+    content: &[u8],
+) -> Result<EncryptedDocument, CoreError> {
+    let padded = SecretDocument::new(pad(scheme, content));
+    symkey::encrypt(key, &*padded).map_err(core_err_unexpected)
+}
+
+pub fn decrypt_padded(
+    key: &AESKey,
+    encrypted: &EncryptedDocument,
+) -> Result<SecretDocument, CoreError> {
+    unpad(symkey::decrypt(key, encrypted).map_err(core_err_unexpected)?)
+}
+
+/// The length `len` bytes are padded to, header included.
+pub fn padded_len(scheme: PaddingScheme, len: u64) -> u64 {
+    match scheme {
+        PaddingScheme::Off => len,
+        PaddingScheme::PowerOfTwo => len.max(MIN_PADDED_LEN).next_power_of_two(),
+        PaddingScheme::Padme => padme(len.max(MIN_PADDED_LEN)),
+    }
+}
+
+/// Rounds `len` up so that only its top `log2(log2(len)) + 1` bits can be set, which leaves an
+/// observer `O(log log len)` bits of information about it.
+fn padme(len: u64) -> u64 {
+    let exponent = 63 - len.leading_zeros() as u64;
+    let exponent_bits = 64 - exponent.leading_zeros() as u64;
+    let mask = (1u64 << (exponent - exponent_bits)) - 1;
+    (len + mask) & !mask
+}
+
+fn pad(scheme: PaddingScheme, content: &[u8]) -> Vec<u8> {
+    if scheme == PaddingScheme::Off {
+        return content.to_vec();
+    }
+
+    let len = padded_len(scheme, (PADDING_HEADER_LEN + content.len()) as u64) as usize;
+    let mut padded = Vec::with_capacity(len);
+    padded.extend_from_slice(PADDING_MAGIC);
+    padded.extend_from_slice(&(content.len() as u64).to_le_bytes());
+    padded.extend_from_slice(content);
+    padded.resize(len, 0);
+    padded
+}
+
+/// The content of a padded document. The padded copy is zeroed rather than truncated in place,
+/// which would leave the padding and a copy of the content's tail in the spare capacity.
+fn unpad(content: Vec<u8>) -> Result<SecretDocument, CoreError> {
+    let content = SecretDocument::new(content);
+    if !content.starts_with(PADDING_MAGIC) {
+        return Ok(content);
+    }
+
+    let len = content
+        .get(PADDING_MAGIC.len()..PADDING_HEADER_LEN)
+        .and_then(|len| len.try_into().ok())
+        .map(|len| u64::from_le_bytes(len) as usize)
+        .filter(|len| PADDING_HEADER_LEN + len <= content.len())
+        .ok_or_else(|| CoreError::Unexpected(String::from("padding header is corrupt")))?;
+    Ok(SecretDocument::new(
+        content[PADDING_HEADER_LEN..PADDING_HEADER_LEN + len].to_vec(),
+    ))
 }
 
 pub fn get_name(config: &Config, meta: &FileMetadata) -> Result<String, CoreError> {
@@ -176,3 +353,91 @@ pub fn rekey_secret_filename(
     let new_parent_key = decrypt_key_for_file(&config, new_parent.id)?;
     symkey::encrypt_and_hmac(&new_parent_key, &old_name).map_err(core_err_unexpected)
 }
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::assert_matches;
+    use crate::repo::padding_repo::PaddingScheme;
+    use crate::service::file_compression_service;
+    use crate::service::file_encryption_service::{
+        encrypt_padded, pad, padded_len, read_verified_document, unpad, MIN_PADDED_LEN,
+    };
+    use crate::service::test_utils::{generate_account, generate_root_metadata};
+    use crate::CoreError;
+    use lockbook_crypto::symkey;
+
+    #[test]
+    fn padding_round_trips() {
+        let content = b"LBC\x01 some compressed content".to_vec();
+        for scheme in &[
+            PaddingScheme::Off,
+            PaddingScheme::PowerOfTwo,
+            PaddingScheme::Padme,
+        ] {
+            assert_eq!(*unpad(pad(*scheme, &content)).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn padding_hides_small_differences() {
+        let short = pad(PaddingScheme::Padme, &[1; 1000]);
+        let long = pad(PaddingScheme::Padme, &[1; 1010]);
+        assert_eq!(short.len(), long.len());
+
+        assert_eq!(pad(PaddingScheme::Padme, b"").len() as u64, MIN_PADDED_LEN);
+        assert_eq!(pad(PaddingScheme::Off, b"").len(), 0);
+    }
+
+    #[test]
+    fn padded_lengths() {
+        assert_eq!(padded_len(PaddingScheme::PowerOfTwo, 1000), 1024);
+        assert_eq!(padded_len(PaddingScheme::PowerOfTwo, 1025), 2048);
+        assert_eq!(padded_len(PaddingScheme::Padme, 1000), 1024);
+        assert_eq!(padded_len(PaddingScheme::Padme, 1025), 1088);
+        assert_eq!(padded_len(PaddingScheme::Padme, 1 << 20), 1 << 20);
+
+        for len in (MIN_PADDED_LEN..1 << 24).step_by(9973) {
+            let padded = padded_len(PaddingScheme::Padme, len);
+            assert!(padded >= len);
+            assert!(padded as f64 <= len as f64 * 1.12);
+        }
+    }
+
+    #[test]
+    fn unpadded_content_is_left_alone() {
+        assert_eq!(*unpad(b"\x78\x9c".to_vec()).unwrap(), b"\x78\x9c");
+        assert!(unpad(b"LBP\xff".to_vec()).is_err());
+    }
+
+    #[test]
+    fn verified_reads_catch_corruption() {
+        let (mut metadata, key) = generate_root_metadata(&generate_account());
+        let content = b"some document content".to_vec();
+        let document = encrypt_padded(
+            &key,
+            PaddingScheme::Padme,
+            &file_compression_service::compress(&content).unwrap(),
+        )
+        .unwrap();
+
+        metadata.content_checksum = Some(symkey::hmac(&key, &content).unwrap());
+        assert_eq!(
+            *read_verified_document(&key, &metadata, &document).unwrap(),
+            content
+        );
+
+        metadata.content_checksum = Some(symkey::hmac(&key, b"other content").unwrap());
+        assert_matches!(
+            read_verified_document(&key, &metadata, &document),
+            Err(CoreError::DocumentCorrupted)
+        );
+
+        let mut damaged = document.clone();
+        damaged.value[0] ^= 1;
+        metadata.content_checksum = None;
+        assert_matches!(
+            read_verified_document(&key, &metadata, &damaged),
+            Err(CoreError::DocumentCorrupted)
+        );
+    }
+}
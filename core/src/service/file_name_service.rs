@@ -0,0 +1,149 @@
+use crate::model::state::Config;
+use crate::repo::{file_metadata_repo, file_name_repo, local_changes_repo};
+use crate::service::file_encryption_service;
+use crate::CoreError;
+use lockbook_crypto::clock_service;
+use lockbook_models::file_metadata::FileMetadata;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use uuid::Uuid;
+
+/// Names Windows won't create a file with, whatever the extension.
+static RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Names are kept in NFC, so that the same name typed on different platforms is the same name
+/// (and the same hmac) once it's encrypted.
+pub fn normalize(name: &str) -> String {
+    name.nfc().collect()
+}
+
+/// Checks that a name can be stored at all, and returns it normalized. Names core comes up with
+/// itself, like the conflict copies sync makes of names from other platforms, only need this.
+pub fn check(name: &str) -> Result<String, CoreError> {
+    if name.is_empty() {
+        return Err(CoreError::FileNameEmpty);
+    }
+    if name.contains('/') {
+        return Err(CoreError::FileNameContainsSlash);
+    }
+
+    Ok(normalize(name))
+}
+
+/// Checks that a name someone chose can be given to a file on every platform, and returns it
+/// normalized.
+pub fn validate(name: &str) -> Result<String, CoreError> {
+    let name = check(name)?;
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if name.chars().any(char::is_control)
+        || name == "."
+        || name == ".."
+        || RESERVED_NAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Err(CoreError::FileNameInvalid);
+    }
+
+    Ok(name)
+}
+
+/// Checks that no child of `parent` is already called `name`. If names are case-insensitive,
+/// names that differ only in case are taken too, except `renamed`'s own.
+pub fn check_available(
+    config: &Config,
+    parent: Uuid,
+    name: &str,
+    renamed: Option<Uuid>,
+) -> Result<(), CoreError> {
+    let case_insensitive = file_name_repo::is_case_insensitive(config)?;
+    let name = normalize(name);
+    let lowercase = name.to_lowercase();
+
+    for child in file_metadata_repo::get_children_non_recursively(config, parent)? {
+        let child_name = get_name(config, &child)?;
+        if child_name == name
+            || (case_insensitive
+                && Some(child.id) != renamed
+                && child_name.to_lowercase() == lowercase)
+        {
+            return Err(CoreError::PathTaken);
+        }
+    }
+
+    Ok(())
+}
+
+/// A file's name, normalized in case it was named before names were or on a client that doesn't
+/// normalize them.
+pub fn get_name(config: &Config, file: &FileMetadata) -> Result<String, CoreError> {
+    Ok(normalize(&file_encryption_service::get_name(config, file)?))
+}
+
+/// Re-encrypts every name that isn't normalized, for files named before names were. A name that
+/// normalizes to a sibling's is renamed the way sync renames conflicting files.
+pub fn normalize_existing(config: &Config) -> Result<(), CoreError> {
+    for mut file in file_metadata_repo::get_all(config)? {
+        if file.id == file.parent {
+            continue;
+        }
+
+        let old_name = file_encryption_service::get_name(&config, &file)?;
+        if is_nfc(&old_name) {
+            continue;
+        }
+
+        let mut new_name = normalize(&old_name);
+        for sibling in file_metadata_repo::get_children_non_recursively(config, file.parent)? {
+            if sibling.id != file.id && get_name(config, &sibling)? == new_name {
+                new_name = format!("{}-NAME-CONFLICT-{}", new_name, file.id);
+                break;
+            }
+        }
+
+        local_changes_repo::track_rename(
+            config,
+            file.id,
+            &old_name,
+            &new_name,
+            clock_service::get_time,
+        )?;
+        file.name = file_encryption_service::create_name(&config, &file, &new_name)?;
+        file_metadata_repo::insert(config, &file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::service::file_name_service;
+    use crate::CoreError;
+
+    #[test]
+    fn names_are_normalized() {
+        assert_eq!(
+            file_name_service::validate("cafe\u{301}.md").unwrap(),
+            "caf\u{e9}.md"
+        );
+    }
+
+    #[test]
+    fn invalid_names() {
+        for name in &["tab\there", "..", "CON", "con.txt", "Lpt1 .md", "\u{7f}"] {
+            match file_name_service::validate(name) {
+                Err(CoreError::FileNameInvalid) => {}
+                result => panic!("{:?} was allowed: {:?}", name, result),
+            }
+        }
+    }
+
+    #[test]
+    fn valid_names() {
+        for name in &["console.md", "COM10", ".hidden", "notes.con", "..."] {
+            assert_eq!(&file_name_service::validate(name).unwrap(), name);
+        }
+    }
+}
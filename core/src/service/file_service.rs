@@ -1,20 +1,29 @@
 use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
+use crate::client;
 use crate::model::state::Config;
 use crate::repo::document_repo;
+use crate::repo::document_repo::LocalDocument;
 use crate::repo::file_metadata_repo;
 use crate::repo::{account_repo, local_changes_repo};
+use crate::service::document_stream_service;
+use crate::service::event_service;
+use crate::service::event_service::ChangeEvent;
 use crate::service::file_compression_service;
 use crate::service::file_encryption_service;
+use crate::service::file_name_service;
+use crate::service::offline_service;
 use crate::CoreError;
 use lockbook_crypto::clock_service;
-use lockbook_models::crypto::DecryptedDocument;
+use lockbook_models::api::GetUpdatesRequest;
+use lockbook_models::crypto::{DecryptedDocument, EncryptedDocument, SecretDocument};
 use lockbook_models::file_metadata::FileType::{Document, Folder};
 use lockbook_models::file_metadata::{FileMetadata, FileType};
-use std::fs::OpenOptions;
-use std::io::Write;
+use lockbook_models::local_changes::{ChangeField, Edited, LocalChange};
+use std::io::{Read, Write};
 use std::path::Path;
+use tempfile::NamedTempFile;
 
 pub fn create(
     config: &Config,
@@ -22,12 +31,7 @@ pub fn create(
     parent: Uuid,
     file_type: FileType,
 ) -> Result<FileMetadata, CoreError> {
-    if name.is_empty() {
-        return Err(CoreError::FileNameEmpty);
-    }
-    if name.contains('/') {
-        return Err(CoreError::FileNameContainsSlash);
-    }
+    let name = &file_name_service::check(name)?;
 
     let _account = account_repo::get_account(config)?;
 
@@ -39,18 +43,20 @@ pub fn create(
         return Err(CoreError::FileNotFolder);
     }
 
-    // Check that this file name is available
-    for child in file_metadata_repo::get_children_non_recursively(config, parent.id)? {
-        if file_encryption_service::get_name(&config, &child)? == name {
-            return Err(CoreError::PathTaken);
-        }
-    }
+    file_name_service::check_available(config, parent.id, name, None)?;
 
     let new_metadata =
         file_encryption_service::create_file_metadata(&config, name, file_type, parent.id)?;
 
     file_metadata_repo::insert(config, &new_metadata)?;
     local_changes_repo::track_new_file(config, new_metadata.id, clock_service::get_time)?;
+    event_service::emit(
+        config,
+        ChangeEvent::FileCreated {
+            id: new_metadata.id,
+            parent: new_metadata.parent,
+        },
+    );
 
     if file_type == Document {
         write_document(config, new_metadata.id, &[])?;
@@ -61,48 +67,110 @@ pub fn create(
 pub fn write_document(config: &Config, id: Uuid, content: &[u8]) -> Result<(), CoreError> {
     let _account = account_repo::get_account(config)?;
 
-    let file_metadata =
+    let mut file_metadata =
         file_metadata_repo::maybe_get(config, id)?.ok_or(CoreError::FileNonexistent)?;
 
     if file_metadata.file_type == Folder {
         return Err(CoreError::FileNotDocument);
     }
 
-    let compressed_content = file_compression_service::compress(content)?;
+    let name = file_encryption_service::get_name(config, &file_metadata)?;
+    let compressed_content =
+        SecretDocument::new(file_compression_service::compress_for(&name, content)?);
     let new_file =
         file_encryption_service::write_to_document(&config, &compressed_content, &file_metadata)?;
-    file_metadata_repo::insert(config, &file_metadata)?;
 
-    if let Some(old_encrypted) = document_repo::maybe_get(config, id)? {
-        let decrypted =
-            file_encryption_service::read_document(&config, &old_encrypted, &file_metadata)?;
-        let decompressed = file_compression_service::decompress(&decrypted)?;
-        let permanent_access_info = file_encryption_service::get_key_for_user(&config, id)?;
-
-        local_changes_repo::track_edit(
-            config,
-            file_metadata.id,
-            &old_encrypted,
-            &permanent_access_info,
-            Sha256::digest(&decompressed).to_vec(),
-            Sha256::digest(&content).to_vec(),
-            clock_service::get_time,
-        )?;
-    };
+    track_edit(config, &file_metadata, Sha256::digest(&content).to_vec())?;
 
     document_repo::insert(config, file_metadata.id, &new_file)?;
 
+    file_metadata.content_checksum = Some(file_encryption_service::content_checksum(
+        config,
+        &file_metadata,
+        content,
+    )?);
+    file_metadata_repo::insert(config, &file_metadata)?;
+
+    event_service::emit(config, ChangeEvent::ContentChanged { id });
     Ok(())
 }
 
-pub fn rename_file(config: &Config, id: Uuid, new_name: &str) -> Result<(), CoreError> {
-    if new_name.is_empty() {
-        return Err(CoreError::FileNameEmpty);
-    }
-    if new_name.contains('/') {
-        return Err(CoreError::FileNameContainsSlash);
+/// Like `write_document`, but reads the content from `content` as it's encrypted so that it never
+/// has to fit in memory.
+pub fn write_document_from(
+    config: &Config,
+    id: Uuid,
+    content: &mut dyn Read,
+) -> Result<(), CoreError> {
+    let _account = account_repo::get_account(config)?;
+
+    let mut file_metadata =
+        file_metadata_repo::maybe_get(config, id)?.ok_or(CoreError::FileNonexistent)?;
+
+    if file_metadata.file_type == Folder {
+        return Err(CoreError::FileNotDocument);
     }
 
+    let (stream, checksum, content_checksum) =
+        document_stream_service::encrypt(config, &file_metadata, content)?;
+
+    track_edit(config, &file_metadata, checksum)?;
+
+    document_repo::insert_stream(config, file_metadata.id, stream)?;
+
+    file_metadata.content_checksum = Some(content_checksum);
+    file_metadata_repo::insert(config, &file_metadata)?;
+
+    event_service::emit(config, ChangeEvent::ContentChanged { id });
+    Ok(())
+}
+
+/// Records that the content of a document is about to change, unless it's new.
+fn track_edit(
+    config: &Config,
+    file_metadata: &FileMetadata,
+    new_content_checksum: Vec<u8>,
+) -> Result<(), CoreError> {
+    let (old_encrypted, old_content_checksum) =
+        match offline_service::maybe_get_document(config, &file_metadata) {
+            Ok(None) => return Ok(()),
+            Ok(Some(LocalDocument::Whole(old_encrypted))) => {
+                let decrypted = file_encryption_service::read_document(
+                    &config,
+                    &old_encrypted,
+                    &file_metadata,
+                )?;
+                let decompressed =
+                    SecretDocument::new(file_compression_service::decompress(&decrypted)?);
+                (Some(old_encrypted), Sha256::digest(&decompressed).to_vec())
+            }
+            // Merges aren't attempted for streamed documents, so the old version isn't kept
+            Ok(Some(LocalDocument::Streamed(old_stream))) => (
+                None,
+                document_stream_service::checksum(config, &file_metadata, old_stream)?,
+            ),
+            // Without the old version sync can't merge, so it keeps this one as a conflict copy.
+            // No content has an empty checksum, so the edit is never mistaken for a revert.
+            Err(CoreError::FileNotAvailableOffline) => (None, vec![]),
+            Err(err) => return Err(err),
+        };
+    let permanent_access_info =
+        file_encryption_service::get_key_for_user(&config, file_metadata.id)?;
+
+    local_changes_repo::track_edit(
+        config,
+        file_metadata.id,
+        old_encrypted.as_ref(),
+        &permanent_access_info,
+        old_content_checksum,
+        new_content_checksum,
+        clock_service::get_time,
+    )
+}
+
+pub fn rename_file(config: &Config, id: Uuid, new_name: &str) -> Result<(), CoreError> {
+    let new_name = &file_name_service::check(new_name)?;
+
     match file_metadata_repo::maybe_get(config, id)? {
         None => Err(CoreError::FileNonexistent),
         Some(mut file) => {
@@ -110,14 +178,7 @@ pub fn rename_file(config: &Config, id: Uuid, new_name: &str) -> Result<(), Core
                 return Err(CoreError::RootModificationInvalid);
             }
 
-            let siblings = file_metadata_repo::get_children_non_recursively(config, file.parent)?;
-
-            // Check that this file name is available
-            for child in siblings {
-                if file_encryption_service::get_name(&config, &child)? == new_name {
-                    return Err(CoreError::PathTaken);
-                }
-            }
+            file_name_service::check_available(config, file.parent, new_name, Some(file.id))?;
 
             let old_file_name = file_encryption_service::get_name(&config, &file)?;
 
@@ -132,6 +193,7 @@ pub fn rename_file(config: &Config, id: Uuid, new_name: &str) -> Result<(), Core
             file.name = file_encryption_service::create_name(&config, &file, new_name)?;
             file_metadata_repo::insert(config, &file)?;
 
+            event_service::emit(config, ChangeEvent::Renamed { id });
             Ok(())
         }
     }
@@ -151,17 +213,11 @@ pub fn move_file(config: &Config, id: Uuid, new_parent: Uuid) -> Result<(), Core
         return Err(CoreError::FileNotFolder);
     }
 
-    let siblings = file_metadata_repo::get_children_non_recursively(config, parent_metadata.id)?;
+    let name = file_encryption_service::get_name(&config, &file)?;
+    file_name_service::check_available(config, parent_metadata.id, &name, Some(file.id))?;
     let new_name =
         file_encryption_service::rekey_secret_filename(&config, &file, &parent_metadata)?;
 
-    // Check that this file name is available
-    for child in siblings {
-        if child.name == new_name {
-            return Err(CoreError::PathTaken);
-        }
-    }
-
     // Checking if a folder is being moved into itself or its children
     if file.file_type == FileType::Folder {
         let children = file_metadata_repo::get_and_get_children_recursively(config, id)?;
@@ -174,7 +230,7 @@ pub fn move_file(config: &Config, id: Uuid, new_parent: Uuid) -> Result<(), Core
 
     let access_key = file_encryption_service::decrypt_key_for_file(&config, file.id)?;
     let new_access_info =
-        file_encryption_service::re_encrypt_key_for_file(&config, access_key, parent_metadata.id)?;
+        file_encryption_service::re_encrypt_key_for_file(&config, &access_key, parent_metadata.id)?;
 
     local_changes_repo::track_move(
         config,
@@ -184,11 +240,21 @@ pub fn move_file(config: &Config, id: Uuid, new_parent: Uuid) -> Result<(), Core
         clock_service::get_time,
     )?;
 
+    let old_parent = file.parent;
     file.parent = parent_metadata.id;
     file.folder_access_keys = new_access_info;
     file.name = new_name;
 
     file_metadata_repo::insert(config, &file)?;
+
+    event_service::emit(
+        config,
+        ChangeEvent::Moved {
+            id,
+            old_parent,
+            new_parent: parent_metadata.id,
+        },
+    );
     Ok(())
 }
 
@@ -202,24 +268,67 @@ pub fn read_document(config: &Config, id: Uuid) -> Result<DecryptedDocument, Cor
         return Err(CoreError::FileNotDocument);
     }
 
-    let document = document_repo::get(config, id)?;
-    let compressed_content =
-        file_encryption_service::read_document(&config, &document, &file_metadata)?;
-    let content = file_compression_service::decompress(&compressed_content)?;
+    let mut content = vec![];
+    read_document_from(config, &file_metadata, &mut content)?;
 
     Ok(content)
 }
 
+/// Like `read_document`, but writes the content to `destination`. Documents written with
+/// `write_document_from` are never held in memory whole. Returns the length of the content.
+pub fn read_document_to(
+    config: &Config,
+    id: Uuid,
+    destination: &mut dyn Write,
+) -> Result<u64, CoreError> {
+    let _account = account_repo::get_account(config)?;
+
+    let file_metadata =
+        file_metadata_repo::maybe_get(config, id)?.ok_or(CoreError::FileNonexistent)?;
+
+    if file_metadata.file_type == Folder {
+        return Err(CoreError::FileNotDocument);
+    }
+
+    read_document_from(config, &file_metadata, destination)
+}
+
+fn read_document_from(
+    config: &Config,
+    file_metadata: &FileMetadata,
+    destination: &mut dyn Write,
+) -> Result<u64, CoreError> {
+    match offline_service::get_document(config, &file_metadata)? {
+        LocalDocument::Whole(document) => {
+            let key = file_encryption_service::decrypt_key_for_file(config, file_metadata.id)?;
+            let content =
+                file_encryption_service::read_verified_document(&key, file_metadata, &document)?;
+            destination.write_all(&content)?;
+            Ok(content.len() as u64)
+        }
+        LocalDocument::Streamed(stream) => {
+            document_stream_service::decrypt_verified(config, &file_metadata, stream, destination)
+        }
+    }
+}
+
+/// Writes the document beside `location` and moves it there once it has all been read, so that
+/// nothing is left behind if reading fails.
 pub fn save_document_to_disk(config: &Config, id: Uuid, location: String) -> Result<(), CoreError> {
-    let document_content = read_document(config, id)?;
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(Path::new(&location))
-        .map_err(CoreError::from)?;
-
-    file.write_all(document_content.as_slice())
-        .map_err(CoreError::from)
+    let path = Path::new(&location);
+    if path.exists() {
+        return Err(CoreError::DiskPathTaken);
+    }
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut file = NamedTempFile::new_in(directory)?;
+    read_document_to(config, id, &mut file)?;
+    file.persist_noclobber(path)
+        .map(|_| ())
+        .map_err(|err| CoreError::from(err.error))
 }
 
 pub fn delete_document(config: &Config, id: Uuid) -> Result<(), CoreError> {
@@ -246,6 +355,7 @@ pub fn delete_document(config: &Config, id: Uuid) -> Result<(), CoreError> {
     document_repo::delete(config, id)?;
     local_changes_repo::track_delete(config, id, file_metadata.file_type, clock_service::get_time)?;
 
+    event_service::emit(config, ChangeEvent::Deleted { id });
     Ok(())
 }
 
@@ -286,5 +396,161 @@ pub fn delete_folder(config: &Config, id: Uuid) -> Result<(), CoreError> {
         }
     }
 
+    event_service::emit(config, ChangeEvent::Deleted { id });
+    Ok(())
+}
+
+/// Undoes the unsynced changes to a file's name, parent or content, restoring the last synced
+/// version of each. A new file has no synced version, so it's dropped entirely, along with the new
+/// files inside it. Deletions can't be undone.
+pub fn revert_local_change(
+    config: &Config,
+    id: Uuid,
+    fields: &[ChangeField],
+) -> Result<(), CoreError> {
+    let file = file_metadata_repo::maybe_get(config, id)?.ok_or(CoreError::FileNonexistent)?;
+    let change = match local_changes_repo::get_local_changes(config, id)? {
+        Some(change) => change,
+        None => return Ok(()),
+    };
+
+    if change.new {
+        return drop_new_file(config, &file);
+    }
+
+    if fields.contains(&ChangeField::Name) {
+        if let Some(renamed) = &change.renamed {
+            rename_file(config, id, &renamed.old_value)?;
+        }
+    }
+    if fields.contains(&ChangeField::Parent) {
+        if let Some(moved) = &change.moved {
+            move_file(config, id, moved.old_value)?;
+        }
+    }
+    if fields.contains(&ChangeField::Content) {
+        if let Some(edited) = &change.content_edited {
+            revert_content(config, &file, edited)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reverts every unsynced change, newest first, except deletions.
+pub fn revert_all_local_changes(config: &Config) -> Result<(), CoreError> {
+    let mut changes = local_changes_repo::get_all_local_changes(config)?;
+    changes.reverse();
+
+    for change in changes {
+        // Dropping a new folder drops the new files inside it too
+        if file_metadata_repo::maybe_get(config, change.id)?.is_some() {
+            revert_local_change(config, change.id, &ChangeField::ALL)?;
+        }
+    }
+
     Ok(())
 }
+
+fn drop_new_file(config: &Config, file: &FileMetadata) -> Result<(), CoreError> {
+    for child in file_metadata_repo::get_children_non_recursively(config, file.id)? {
+        let change = local_changes_repo::get_local_changes(config, child.id)?;
+        match change.as_ref().map(|change| (change.new, &change.moved)) {
+            Some((true, _)) => drop_new_file(config, &child)?,
+            // Files moved into a new folder go back to where they were
+            Some((false, Some(moved))) => move_file(config, child.id, moved.old_value)?,
+            _ => {
+                return Err(CoreError::Unexpected(String::from(
+                    "new folder has a child that is neither new nor moved",
+                )))
+            }
+        }
+    }
+
+    if file.file_type == Document {
+        document_repo::delete(config, file.id)?;
+    }
+    file_metadata_repo::non_recursive_delete(config, file.id)?;
+    local_changes_repo::delete(config, file.id)?;
+
+    event_service::emit(config, ChangeEvent::Deleted { id: file.id });
+    Ok(())
+}
+
+fn revert_content(config: &Config, file: &FileMetadata, edited: &Edited) -> Result<(), CoreError> {
+    match &edited.old_value {
+        Some(old_value) => {
+            let content = read_old_version(config, old_value, edited)?;
+
+            // Writing the old content back untracks the edit
+            write_document(config, file.id, &content)
+        }
+        None => {
+            // The old version was streamed and isn't kept locally, so it comes back from the
+            // server, checked against the checksum the server has for it
+            let account = account_repo::get_account(config)?;
+            let server_file = client::request(
+                &account,
+                GetUpdatesRequest {
+                    since_metadata_version: file.metadata_version.saturating_sub(1),
+                },
+            )
+            .map_err(CoreError::from)?
+            .file_metadata
+            .into_iter()
+            .find(|server_file| server_file.id == file.id)
+            .ok_or(CoreError::FileNonexistent)?;
+
+            local_changes_repo::untrack_edit(config, file.id)?;
+            document_repo::delete(config, file.id)?;
+
+            // If the server has moved on, the next sync downloads its version as usual
+            let mut file = file.clone();
+            if server_file.content_version == file.content_version {
+                file.content_checksum = server_file.content_checksum;
+            }
+            file_metadata_repo::insert(config, &file)?;
+
+            if server_file.content_version == file.content_version
+                && offline_service::should_download(config, &file)?
+            {
+                offline_service::get_document(config, &file)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// A unified diff of a document's unsynced edits, empty if it has none. Only text documents can be
+/// diffed, and only if the synced version was kept locally, which it isn't for streamed documents.
+pub fn diff_local_document(config: &Config, id: Uuid) -> Result<String, CoreError> {
+    let new_content = read_document(config, id)?;
+    let old_content = match local_changes_repo::get_local_changes(config, id)? {
+        Some(LocalChange {
+            content_edited: Some(edited),
+            ..
+        }) => match &edited.old_value {
+            Some(old_value) => read_old_version(config, old_value, &edited)?,
+            None => return Err(CoreError::FileNotAvailableOffline),
+        },
+        _ => return Ok(String::new()),
+    };
+
+    let old_text = std::str::from_utf8(&old_content).map_err(|_| CoreError::DocumentNotText)?;
+    let new_text = std::str::from_utf8(&new_content).map_err(|_| CoreError::DocumentNotText)?;
+
+    Ok(diffy::create_patch(old_text, new_text).to_string())
+}
+
+fn read_old_version(
+    config: &Config,
+    old_value: &EncryptedDocument,
+    edited: &Edited,
+) -> Result<SecretDocument, CoreError> {
+    let account = account_repo::get_account(config)?;
+    let compressed =
+        file_encryption_service::user_read_document(&account, old_value, &edited.access_info)?;
+    Ok(SecretDocument::new(file_compression_service::decompress(
+        &compressed,
+    )?))
+}
@@ -1,18 +1,26 @@
 use crate::model::state::Config;
-use crate::repo::file_metadata_repo;
+use crate::repo::document_repo::LocalDocument;
+use crate::repo::{document_repo, file_metadata_repo, local_changes_repo};
 use crate::service::integrity_service::TestRepoError::{
-    Core, CycleDetected, DocumentTreatedAsFolder, FileNameContainsSlash, FileNameEmpty,
-    FileOrphaned, NameConflictDetected, NoRootFolder,
+    Core, CycleDetected, DanglingLocalChange, DocumentMissing, DocumentTreatedAsFolder,
+    DocumentUnreadable, FileNameContainsSlash, FileNameEmpty, FileOrphaned, NameConflictDetected,
+    NoRootFolder,
+};
+use crate::service::{
+    document_stream_service, file_encryption_service, file_service, offline_service,
 };
-use crate::CoreError;
+use crate::{core_err_unexpected, CoreError};
+use lockbook_crypto::{clock_service, symkey};
 use lockbook_models::file_metadata::FileMetadata;
 use lockbook_models::file_metadata::FileType::{Document, Folder};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
-use super::file_encryption_service;
+/// Name of the folder, under root, that `repair_repo` moves misplaced files into.
+pub const LOST_AND_FOUND: &str = "lost+found";
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub enum TestRepoError {
     NoRootFolder,
     DocumentTreatedAsFolder(Uuid),
@@ -21,92 +29,378 @@ pub enum TestRepoError {
     FileNameEmpty(Uuid),
     FileNameContainsSlash(Uuid),
     NameConflictDetected(Uuid),
+    /// The local content of a document fails to decrypt, decompress or match its checksum.
+    DocumentUnreadable(Uuid),
+    /// A document has unsynced edits, or should be kept on this device, but has no local content.
+    DocumentMissing(Uuid),
+    /// A local change is tracked for a file that doesn't exist.
+    DanglingLocalChange(Uuid),
+    #[serde(skip_serializing)]
     Core(CoreError),
 }
 
-pub fn test_repo_integrity(config: &Config) -> Result<(), TestRepoError> {
-    let root = file_metadata_repo::get_root(&config)
-        .map_err(Core)?
-        .ok_or(NoRootFolder)?;
+/// How far `repair_repo` may go to fix a problem.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RepairPolicy {
+    /// Only make fixes that lose nothing: move misplaced files to `LOST_AND_FOUND`, rename
+    /// files, forget dangling changes and re-download content the server has.
+    Safe,
+    /// Also drop unsynced edits whose content can't be read or is missing.
+    DiscardUnrecoverable,
+}
 
-    let all = file_metadata_repo::get_all(config).map_err(Core)?;
+#[derive(Debug, Default, Serialize)]
+pub struct RepairReport {
+    pub repaired: Vec<TestRepoError>,
+    pub unrepaired: Vec<TestRepoError>,
+}
 
-    {
-        let document_with_children = all
-            .clone()
-            .into_iter()
-            .filter(|f| f.file_type == Document)
-            .filter(|doc| all.clone().into_iter().any(|child| child.parent == doc.id))
-            .last();
+/// Returns every problem found, not just the first. Files that don't descend from root are only
+/// reported as `FileOrphaned` or `CycleDetected`, their names and content can't be checked.
+pub fn test_repo_integrity(config: &Config) -> Result<(), Vec<TestRepoError>> {
+    let mut problems = vec![];
+    if let Err(err) = find_problems(config, &mut problems) {
+        problems.push(Core(err));
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+fn find_problems(config: &Config, problems: &mut Vec<TestRepoError>) -> Result<(), CoreError> {
+    let root = match file_metadata_repo::get_root(&config)? {
+        Some(root) => root,
+        None => {
+            problems.push(NoRootFolder);
+            return Ok(());
+        }
+    };
 
-        if let Some(file) = document_with_children {
-            return Err(DocumentTreatedAsFolder(file.id));
+    let all = file_metadata_repo::get_all(config)?;
+
+    for doc in all.iter().filter(|f| f.file_type == Document) {
+        if all
+            .iter()
+            .any(|child| child.parent == doc.id && child.id != doc.id)
+        {
+            problems.push(DocumentTreatedAsFolder(doc.id));
         }
     }
 
     // Find files that don't descend from root
-    {
-        let mut not_orphaned = HashMap::new();
-        not_orphaned.insert(root.id, root);
-
-        for file in all.clone() {
-            let mut visited: HashMap<Uuid, FileMetadata> = HashMap::new();
-            let mut current = file.clone();
-            'parent_finder: loop {
-                if visited.contains_key(&current.id) {
-                    return Err(CycleDetected(current.id));
+    let reachable = {
+        let by_id: HashMap<Uuid, &FileMetadata> = all.iter().map(|f| (f.id, f)).collect();
+        let mut reachable = HashSet::new();
+        reachable.insert(root.id);
+        let mut reported = HashSet::new();
+
+        for file in &all {
+            let mut path = vec![];
+            let mut current = file;
+            loop {
+                if reachable.contains(&current.id) {
+                    reachable.extend(path);
+                    break;
                 }
-                visited.insert(current.id, current.clone());
+                if let Some(start) = path.iter().position(|id| *id == current.id) {
+                    // Every file above a cycle reports it by the same member
+                    let id = *path[start..].iter().min().unwrap_or(&current.id);
+                    if reported.insert(id) {
+                        problems.push(CycleDetected(id));
+                    }
+                    break;
+                }
+                path.push(current.id);
 
-                match file_metadata_repo::maybe_get(&config, current.parent).map_err(Core)? {
+                match by_id.get(&current.parent) {
+                    Some(parent) => current = *parent,
                     None => {
-                        return Err(FileOrphaned(current.id));
-                    }
-                    Some(parent) => {
-                        // No Problems
-                        if not_orphaned.contains_key(&parent.id) {
-                            for node in visited.values() {
-                                not_orphaned.insert(node.id, node.clone());
-                            }
-
-                            break 'parent_finder;
-                        } else {
-                            current = parent.clone();
+                        if reported.insert(current.id) {
+                            problems.push(FileOrphaned(current.id));
                         }
+                        break;
                     }
                 }
             }
         }
-    }
+
+        reachable
+    };
 
     // Find files with invalid names
-    for file in all.clone() {
-        let name = file_encryption_service::get_name(&config, &file).map_err(Core)?;
+    let mut names = HashMap::new();
+    for file in all.iter().filter(|f| reachable.contains(&f.id)) {
+        let name = file_encryption_service::get_name(&config, &file)?;
         if name.is_empty() {
-            return Err(FileNameEmpty(file.id));
+            problems.push(FileNameEmpty(file.id));
         }
 
         if name.contains('/') {
-            return Err(FileNameContainsSlash(file.id));
+            problems.push(FileNameContainsSlash(file.id));
         }
+        names.insert(file.id, name);
     }
 
     // Find naming conflicts
+    for folder in all
+        .iter()
+        .filter(|f| f.file_type == Folder && reachable.contains(&f.id))
     {
-        for file in all.iter().filter(|f| f.file_type == Folder) {
-            let children =
-                file_metadata_repo::get_children_non_recursively(&config, file.id).map_err(Core)?;
-            let mut children_set = HashSet::new();
-            for child in children {
-                let name = file_encryption_service::get_name(&config, &child).map_err(Core)?;
-                if children_set.contains(&name) {
-                    return Err(NameConflictDetected(child.id));
+        let mut children_set = HashSet::new();
+        for child in all
+            .iter()
+            .filter(|f| f.parent == folder.id && f.id != folder.id)
+        {
+            if let Some(name) = names.get(&child.id) {
+                if !children_set.insert(name) {
+                    problems.push(NameConflictDetected(child.id));
                 }
+            }
+        }
+    }
 
-                children_set.insert(name);
+    // Find documents whose content is damaged or missing
+    for doc in all
+        .iter()
+        .filter(|f| f.file_type == Document && !f.deleted && reachable.contains(&f.id))
+    {
+        match document_repo::maybe_get_local(config, doc.id)? {
+            Some(document) => match verify_document(config, doc, document) {
+                Ok(()) => {}
+                Err(CoreError::DocumentCorrupted) => problems.push(DocumentUnreadable(doc.id)),
+                Err(err) => return Err(err),
+            },
+            None => {
+                let edited = local_changes_repo::get_local_changes(config, doc.id)?
+                    .map(|change| change.content_edited.is_some())
+                    .unwrap_or(false);
+                if edited
+                    || (doc.content_version != 0 && offline_service::should_download(config, doc)?)
+                {
+                    problems.push(DocumentMissing(doc.id));
+                }
             }
         }
     }
 
+    // Find changes to files that are gone
+    let ids: HashSet<Uuid> = all.iter().map(|f| f.id).collect();
+    for change in local_changes_repo::get_all_local_changes(config)? {
+        if !ids.contains(&change.id) {
+            problems.push(DanglingLocalChange(change.id));
+        }
+    }
+
     Ok(())
 }
+
+fn verify_document(
+    config: &Config,
+    metadata: &FileMetadata,
+    document: LocalDocument,
+) -> Result<(), CoreError> {
+    match document {
+        LocalDocument::Whole(document) => {
+            let key = file_encryption_service::decrypt_key_for_file(config, metadata.id)?;
+            file_encryption_service::read_verified_document(&key, metadata, &document).map(|_| ())
+        }
+        LocalDocument::Streamed(stream) => {
+            document_stream_service::verify(config, metadata, stream)
+        }
+    }
+}
+
+/// Fixes what can be fixed without the server's help, apart from re-downloading content. Files
+/// that don't descend from root are moved to `LOST_AND_FOUND`, files with bad or conflicting names
+/// are renamed, and damaged or missing content is fetched again. Problems that are left are
+/// reported as `unrepaired`.
+pub fn repair_repo(config: &Config, policy: RepairPolicy) -> Result<RepairReport, CoreError> {
+    let mut report = RepairReport::default();
+
+    for problem in test_repo_integrity(config).err().unwrap_or_default() {
+        let repaired = match &problem {
+            NoRootFolder | Core(_) => false,
+            FileOrphaned(id) => move_to_lost_and_found(config, *id)?,
+            CycleDetected(id) => break_cycle(config, *id)?,
+            DocumentTreatedAsFolder(id) => {
+                let mut repaired = true;
+                for child in file_metadata_repo::get_children_non_recursively(config, *id)? {
+                    repaired &= move_to_lost_and_found(config, child.id)?;
+                }
+                repaired
+            }
+            FileNameEmpty(id) => {
+                rename(config, *id, &id.to_string())?;
+                true
+            }
+            FileNameContainsSlash(id) => {
+                let name = get_name(config, *id)?;
+                rename(config, *id, &name.replace('/', "-"))?;
+                true
+            }
+            NameConflictDetected(id) => {
+                let name = get_name(config, *id)?;
+                rename(config, *id, &conflict_name(&name, *id))?;
+                true
+            }
+            DocumentUnreadable(id) | DocumentMissing(id) => restore_document(config, *id, policy)?,
+            DanglingLocalChange(id) => {
+                local_changes_repo::delete(config, *id)?;
+                true
+            }
+        };
+
+        if repaired {
+            report.repaired.push(problem);
+        } else {
+            report.unrepaired.push(problem);
+        }
+    }
+
+    Ok(report)
+}
+
+fn get_name(config: &Config, id: Uuid) -> Result<String, CoreError> {
+    file_encryption_service::get_name(config, &file_metadata_repo::get(config, id)?)
+}
+
+fn conflict_name(name: &str, id: Uuid) -> String {
+    format!("{}-NAME-CONFLICT-{}", name, id)
+}
+
+fn rename(config: &Config, id: Uuid, name: &str) -> Result<(), CoreError> {
+    match file_service::rename_file(config, id, name) {
+        Err(CoreError::PathTaken) => {
+            file_service::rename_file(config, id, &conflict_name(name, id))
+        }
+        result => result,
+    }
+}
+
+/// Ids of the files that descend from root, the only ones whose keys can be worked out safely.
+fn reachable_files(config: &Config) -> Result<Vec<Uuid>, CoreError> {
+    let all = file_metadata_repo::get_all(config)?;
+    let by_id: HashMap<Uuid, &FileMetadata> = all.iter().map(|f| (f.id, f)).collect();
+
+    Ok(all
+        .iter()
+        .filter(|file| {
+            let mut visited = HashSet::new();
+            let mut current = *file;
+            loop {
+                if current.id == current.parent {
+                    return true;
+                }
+                if !visited.insert(current.id) {
+                    return false;
+                }
+                match by_id.get(&current.parent) {
+                    Some(parent) => current = *parent,
+                    None => return false,
+                }
+            }
+        })
+        .map(|file| file.id)
+        .collect())
+}
+
+fn break_cycle(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    let mut current = file_metadata_repo::get(config, id)?;
+    loop {
+        if move_to_lost_and_found(config, current.id)? {
+            return Ok(true);
+        }
+        current = file_metadata_repo::get(config, current.parent)?;
+        if current.id == id {
+            return Ok(false);
+        }
+    }
+}
+
+fn lost_and_found(config: &Config) -> Result<FileMetadata, CoreError> {
+    let root = file_metadata_repo::get_root(config)?.ok_or(CoreError::RootNonexistent)?;
+    for child in file_metadata_repo::get_children_non_recursively(config, root.id)? {
+        if child.file_type == Folder
+            && !child.deleted
+            && file_encryption_service::get_name(config, &child)? == LOST_AND_FOUND
+        {
+            return Ok(child);
+        }
+    }
+
+    file_service::create(config, LOST_AND_FOUND, root.id, Folder)
+}
+
+/// Moves a file whose parent is missing, or not where it should be, into `LOST_AND_FOUND`. Its
+/// key is encrypted with the key of the folder it was created or last moved in, so this only
+/// works if that folder descends from root.
+fn move_to_lost_and_found(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    let mut file = file_metadata_repo::get(config, id)?;
+    let (old_parent, old_parent_key) =
+        match file_encryption_service::find_parent_key(config, &file, &reachable_files(config)?)? {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+    let key = symkey::decrypt_key(&old_parent_key, &file.folder_access_keys)
+        .map_err(core_err_unexpected)?;
+    let name =
+        symkey::decrypt_and_verify(&old_parent_key, &file.name).map_err(core_err_unexpected)?;
+
+    let lost_and_found = lost_and_found(config)?;
+    let mut new_name = name.clone();
+    for sibling in file_metadata_repo::get_children_non_recursively(config, lost_and_found.id)? {
+        if file_encryption_service::get_name(config, &sibling)? == name {
+            new_name = conflict_name(&name, id);
+        }
+    }
+
+    local_changes_repo::track_move(
+        config,
+        id,
+        old_parent,
+        lost_and_found.id,
+        clock_service::get_time,
+    )?;
+    if new_name != name {
+        local_changes_repo::track_rename(config, id, &name, &new_name, clock_service::get_time)?;
+    }
+
+    file.parent = lost_and_found.id;
+    file.folder_access_keys =
+        file_encryption_service::re_encrypt_key_for_file(config, &key, lost_and_found.id)?;
+    file.name = file_encryption_service::create_name(config, &file, &new_name)?;
+    file_metadata_repo::insert(config, &file)?;
+
+    Ok(true)
+}
+
+/// Replaces a document's local content with the server's. Unsynced edits are only given up under
+/// `RepairPolicy::DiscardUnrecoverable`, documents the server has no content for are emptied.
+fn restore_document(config: &Config, id: Uuid, policy: RepairPolicy) -> Result<bool, CoreError> {
+    let file = file_metadata_repo::get(config, id)?;
+    let edited = local_changes_repo::get_local_changes(config, id)?
+        .map(|change| change.content_edited.is_some())
+        .unwrap_or(false);
+
+    if edited {
+        if policy != RepairPolicy::DiscardUnrecoverable {
+            return Ok(false);
+        }
+        local_changes_repo::untrack_edit(config, id)?;
+    }
+    document_repo::delete(config, id)?;
+
+    if file.content_version == 0 {
+        file_service::write_document(config, id, &[])?;
+        return Ok(true);
+    }
+
+    match offline_service::maybe_get_document(config, &file) {
+        Ok(_) => Ok(true),
+        Err(CoreError::FileNotAvailableOffline) | Err(CoreError::DocumentCorrupted) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,117 @@
+use crate::model::state::Config;
+use crate::{core_err_unexpected, CoreError};
+use fs2::FileExt;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::marker::PhantomData;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+static LOCK_FILE: &str = "lock";
+
+/// How long a change waits for another process or thread to finish with the data directory. Syncs
+/// hold it for as long as they run, network round-trips included, so changes can still fail with
+/// `CoreError::DataDirLocked` and clients retry or queue them.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The data directories this process has locked, the thread holding each, how many `DataDirLock`s
+/// that thread has out and the file the OS lock is held through. Configs with their own storage
+/// backend aren't directories, so they're only locked within the process.
+static HELD: Mutex<Held> = Mutex::new(Vec::new());
+
+/// Notified whenever a data directory is released by this process.
+static RELEASED: Condvar = Condvar::new();
+
+type Held = Vec<(String, ThreadId, usize, Option<File>)>;
+
+/// Keeps other processes and threads from changing a data directory until it's dropped. The
+/// thread holding it can take it any number of times, so that operations holding it can call each
+/// other. It's released by the thread that took it, so it can't be sent to another.
+#[must_use]
+pub struct DataDirLock {
+    path: String,
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Takes the data directory's advisory lock, waiting up to `LOCK_TIMEOUT` for another process or
+/// thread to release it before returning `CoreError::DataDirLocked`. Only changes take it: reads
+/// keep working while it's held.
+pub fn lock(config: &Config) -> Result<DataDirLock, CoreError> {
+    let started = Instant::now();
+    let mut held = held()?;
+    loop {
+        match try_lock(config, &mut held) {
+            Err(CoreError::DataDirLocked) if started.elapsed() < LOCK_TIMEOUT => {
+                // Woken early by releases within the process, other processes are polled
+                held = RELEASED
+                    .wait_timeout(held, LOCK_RETRY_INTERVAL)
+                    .map_err(core_err_unexpected)?
+                    .0;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn try_lock(config: &Config, held: &mut Held) -> Result<DataDirLock, CoreError> {
+    let current = thread::current().id();
+    match held
+        .iter_mut()
+        .find(|(path, _, _, _)| *path == config.writeable_path)
+    {
+        Some((_, owner, count, _)) if *owner == current => *count += 1,
+        Some(_) => return Err(CoreError::DataDirLocked),
+        None if config.storage.is_some() => {
+            held.push((config.writeable_path.clone(), current, 1, None))
+        }
+        None => {
+            create_dir_all(config.path())?;
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open(config.path().join(LOCK_FILE))?;
+            file.try_lock_exclusive().map_err(|err| {
+                if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() {
+                    CoreError::DataDirLocked
+                } else {
+                    core_err_unexpected(err)
+                }
+            })?;
+            held.push((config.writeable_path.clone(), current, 1, Some(file)));
+        }
+    }
+
+    Ok(DataDirLock {
+        path: config.writeable_path.clone(),
+        _not_send: PhantomData,
+    })
+}
+
+impl Drop for DataDirLock {
+    fn drop(&mut self) {
+        let mut held = match held() {
+            Ok(held) => held,
+            Err(err) => {
+                error!("Could not release the lock on {}: {:#?}", self.path, err);
+                return;
+            }
+        };
+
+        if let Some(index) = held.iter().position(|(path, _, _, _)| *path == self.path) {
+            held[index].2 -= 1;
+            if held[index].2 == 0 {
+                let (_, _, _, file) = held.remove(index);
+                if let Some(Err(err)) = file.map(|file| file.unlock()) {
+                    error!("Could not release the lock on {}: {:#?}", self.path, err);
+                }
+                RELEASED.notify_all();
+            }
+        }
+    }
+}
+
+fn held() -> Result<MutexGuard<'static, Held>, CoreError> {
+    HELD.lock().map_err(core_err_unexpected)
+}
@@ -1,11 +1,19 @@
 pub mod account_service;
 pub mod db_state_service;
+pub mod document_chunk_service;
+pub mod document_stream_service;
 pub mod drawing_service;
+pub mod event_service;
 pub mod file_compression_service;
 pub mod file_encryption_service;
+pub mod file_name_service;
 pub mod file_service;
 pub mod integrity_service;
+pub mod lock_service;
+pub mod offline_service;
 pub mod path_service;
+pub mod profile_service;
+pub mod sync_scheduler_service;
 pub mod sync_service;
 pub mod test_utils;
 pub mod usage_service;
@@ -0,0 +1,194 @@
+use crate::model::state::Config;
+use crate::repo::document_repo::LocalDocument;
+use crate::repo::{
+    account_repo, document_repo, file_metadata_repo, local_changes_repo, offline_repo,
+};
+use crate::service::document_chunk_service;
+use crate::CoreError;
+use lockbook_models::file_metadata::FileMetadata;
+use lockbook_models::file_metadata::FileType::{Document, Folder};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Walks from `id` up to the root and reports whether any file along the way matches `check`.
+/// Parents that have not been pulled yet (mid-sync) end the walk.
+fn any_parent(
+    config: &Config,
+    id: Uuid,
+    check: fn(&Config, Uuid) -> Result<bool, CoreError>,
+) -> Result<bool, CoreError> {
+    let mut current_id = id;
+    loop {
+        if check(config, current_id)? {
+            return Ok(true);
+        }
+        match file_metadata_repo::maybe_get(config, current_id)? {
+            Some(file) if file.id != file.parent => current_id = file.parent,
+            _ => return Ok(false),
+        }
+    }
+}
+
+/// A file is kept offline if it, or any of its parents, has been pinned.
+pub fn is_kept_offline(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    any_parent(config, id, offline_repo::is_pinned)
+}
+
+/// A file is excluded from this device if it, or any of its parents, is in the sync exclusions.
+pub fn is_excluded(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    any_parent(config, id, offline_repo::is_excluded)
+}
+
+/// Whether this device should hold the content of a document without it being asked for.
+/// Exclusions win over pins.
+fn should_keep(config: &Config, id: Uuid) -> Result<bool, CoreError> {
+    Ok(!is_excluded(config, id)?
+        && (!offline_repo::is_lazy(config)? || is_kept_offline(config, id)?))
+}
+
+/// Whether sync should pull the content of this document along with its metadata. Documents we
+/// already have a copy of are refreshed so that the cache never goes stale, unless excluded.
+pub fn should_download(config: &Config, metadata: &FileMetadata) -> Result<bool, CoreError> {
+    Ok(should_keep(config, metadata.id)?
+        || (document_repo::exists(config, metadata.id)? && !is_excluded(config, metadata.id)?))
+}
+
+/// A document is remote-only when the server has content for it that this device does not hold.
+/// A folder is remote-only when it's excluded from this device.
+pub fn is_remote_only(config: &Config, metadata: &FileMetadata) -> Result<bool, CoreError> {
+    match metadata.file_type {
+        Document => {
+            Ok(metadata.content_version != 0 && !document_repo::exists(config, metadata.id)?)
+        }
+        Folder => is_excluded(config, metadata.id),
+    }
+}
+
+/// Returns the local copy of a document, fetching and caching it from the server if sync skipped
+/// it. Returns `None` only for documents the server has never seen.
+pub fn maybe_get_document(
+    config: &Config,
+    metadata: &FileMetadata,
+) -> Result<Option<LocalDocument>, CoreError> {
+    if metadata.content_version != 0 && !document_repo::exists(config, metadata.id)? {
+        fetch_document(config, metadata)?;
+    }
+    document_repo::maybe_get_local(config, metadata.id)
+}
+
+pub fn get_document(config: &Config, metadata: &FileMetadata) -> Result<LocalDocument, CoreError> {
+    maybe_get_document(config, metadata)?.ok_or(CoreError::FileNonexistent)
+}
+
+fn fetch_document(config: &Config, metadata: &FileMetadata) -> Result<(), CoreError> {
+    let account = account_repo::get_account(config)?;
+
+    let download = document_chunk_service::download(config, &account, metadata, &mut |_, _| true);
+    let document = download.map_err(|err| match err {
+        CoreError::ServerUnreachable => CoreError::FileNotAvailableOffline,
+        err => err,
+    })?;
+    document.verify(config, metadata)?;
+
+    document.insert(config, metadata.id)
+}
+
+/// Downloads documents this device should hold but doesn't, among `changed` and the files pending
+/// a download, and their children. Sync passes the files it changed, since a folder moved into a
+/// pinned one brings its documents along. Pins and lifted exclusions leave pending downloads.
+pub fn download_missing(config: &Config, changed: &[Uuid]) -> Result<(), CoreError> {
+    let pending = offline_repo::get_all_pending_downloads(config)?;
+    let mut to_check: Vec<Uuid> = changed.iter().chain(&pending).copied().collect();
+    if to_check.is_empty() {
+        return Ok(());
+    }
+
+    let all = file_metadata_repo::get_all(config)?;
+    let mut children: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for file in all.iter().filter(|file| file.id != file.parent) {
+        children.entry(file.parent).or_default().push(file.id);
+    }
+    let files: HashMap<Uuid, FileMetadata> = all.into_iter().map(|file| (file.id, file)).collect();
+
+    let mut checked = HashSet::new();
+    while let Some(id) = to_check.pop() {
+        if !checked.insert(id) {
+            continue;
+        }
+        if let Some(file) = files.get(&id) {
+            if file.file_type == Document && should_keep(config, id)? {
+                maybe_get_document(config, file)?;
+            }
+        }
+        to_check.extend(children.get(&id).into_iter().flatten());
+    }
+
+    for id in pending {
+        offline_repo::remove_pending_download(config, id)?;
+    }
+    Ok(())
+}
+
+/// Drops cached content under `id` that this device no longer needs. Documents with local edits
+/// that have not been synced yet are never evicted.
+fn evict_unneeded(config: &Config, id: Uuid) -> Result<(), CoreError> {
+    for file in file_metadata_repo::get_and_get_children_recursively(config, id)? {
+        if file.file_type != Document || should_keep(config, file.id)? {
+            continue;
+        }
+
+        let dirty = local_changes_repo::get_local_changes(config, file.id)?
+            .map(|change| change.new || change.content_edited.is_some())
+            .unwrap_or(false);
+
+        if !dirty {
+            document_repo::delete(config, file.id)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn pin(config: &Config, id: Uuid) -> Result<(), CoreError> {
+    file_metadata_repo::maybe_get(config, id)?.ok_or(CoreError::FileNonexistent)?;
+    offline_repo::pin(config, id)?;
+    offline_repo::add_pending_download(config, id)?;
+    download_missing(config, &[])
+}
+
+pub fn unpin(config: &Config, id: Uuid) -> Result<(), CoreError> {
+    file_metadata_repo::maybe_get(config, id)?.ok_or(CoreError::FileNonexistent)?;
+    offline_repo::unpin(config, id)?;
+    evict_unneeded(config, id)
+}
+
+/// Turning lazy sync off means every document should be held, which the next sync downloads.
+pub fn set_lazy(config: &Config, lazy: bool) -> Result<(), CoreError> {
+    offline_repo::set_lazy(config, lazy)?;
+    if let (false, Some(root)) = (lazy, file_metadata_repo::get_root(config)?) {
+        offline_repo::add_pending_download(config, root.id)?;
+    }
+    Ok(())
+}
+
+pub fn set_sync_exclusions(config: &Config, ids: Vec<Uuid>) -> Result<(), CoreError> {
+    for id in &ids {
+        let file = file_metadata_repo::maybe_get(config, *id)?.ok_or(CoreError::FileNonexistent)?;
+        if file.id == file.parent {
+            return Err(CoreError::RootModificationInvalid);
+        }
+    }
+
+    for lifted in offline_repo::get_all_excluded(config)? {
+        if !ids.contains(&lifted) {
+            offline_repo::add_pending_download(config, lifted)?;
+        }
+    }
+    offline_repo::set_exclusions(config, &ids)?;
+
+    for id in ids {
+        evict_unneeded(config, id)?;
+    }
+
+    Ok(())
+}
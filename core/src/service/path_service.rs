@@ -1,15 +1,17 @@
 use crate::model::state::Config;
 use crate::repo::file_metadata_repo;
-use crate::service::{file_encryption_service, file_service};
+use crate::service::{file_encryption_service, file_name_service, file_service};
 use crate::CoreError;
 use lockbook_models::file_metadata::FileMetadata;
 use lockbook_models::file_metadata::FileType::{Document, Folder};
+use uuid::Uuid;
 
 pub fn create_at_path(config: &Config, path_and_name: &str) -> Result<FileMetadata, CoreError> {
     if path_and_name.contains("//") {
         return Err(CoreError::PathContainsEmptyFileName);
     }
 
+    let path_and_name = &file_name_service::normalize(path_and_name);
     debug!("Creating path at: {}", path_and_name);
     let path_components = split_path(path_and_name);
 
@@ -17,7 +19,7 @@ pub fn create_at_path(config: &Config, path_and_name: &str) -> Result<FileMetada
 
     let mut current = file_metadata_repo::get_root(config)?.ok_or(CoreError::RootNonexistent)?;
 
-    if file_encryption_service::get_name(&config, &current)? != path_components[0] {
+    if file_name_service::get_name(&config, &current)? != path_components[0] {
         return Err(CoreError::PathStartsWithNonRoot);
     }
 
@@ -33,7 +35,7 @@ pub fn create_at_path(config: &Config, path_and_name: &str) -> Result<FileMetada
         debug!("child we're searching for: {}", next_name);
 
         for child in children {
-            if file_encryption_service::get_name(&config, &child)? == next_name {
+            if file_name_service::get_name(&config, &child)? == next_name {
                 // If we're at the end and we find this child, that means this path already exists
                 if index == path_components.len() - 2 {
                     return Err(CoreError::PathTaken);
@@ -53,6 +55,8 @@ pub fn create_at_path(config: &Config, path_and_name: &str) -> Result<FileMetada
             Document
         };
 
+        // Paths come from users, so the names in them are held to what every platform allows
+        file_name_service::validate(next_name)?;
         current = file_service::create(config, next_name, current.id, file_type)?;
     }
 
@@ -63,11 +67,12 @@ pub fn get_by_path(config: &Config, path: &str) -> Result<FileMetadata, CoreErro
     let root = file_metadata_repo::get_root(&config)?
         .ok_or_else(|| CoreError::Unexpected(String::from("no root")))?;
 
+    let path = &file_name_service::normalize(path);
     let paths = split_path(path);
     let mut current = root;
 
     for (i, value) in paths.iter().enumerate() {
-        if *value != file_encryption_service::get_name(&config, &current)? {
+        if *value != file_name_service::get_name(&config, &current)? {
             return Err(CoreError::FileNonexistent);
         }
 
@@ -79,7 +84,7 @@ pub fn get_by_path(config: &Config, path: &str) -> Result<FileMetadata, CoreErro
         let mut found_child = false;
 
         for child in children {
-            let child_name = file_encryption_service::get_name(&config, &child)?;
+            let child_name = file_name_service::get_name(&config, &child)?;
 
             if child_name == paths[i + 1] {
                 current = child;
@@ -126,26 +131,32 @@ pub fn get_all_paths(config: &Config, filter: Option<Filter>) -> Result<Vec<Stri
         }
     }
 
-    let mut paths: Vec<String> = vec![];
-    for file in filtered_files {
-        let mut current = file.clone();
-        let mut current_path = String::from("");
-        while current.id != current.parent {
-            let current_name = file_encryption_service::get_name(&config, &current)?;
-            if current.file_type == Document {
-                current_path = current_name;
-            } else {
-                current_path = format!("{}/{}", current_name, current_path);
-            }
-            current = file_metadata_repo::get(&config, current.parent)?;
-        }
+    filtered_files
+        .iter()
+        .map(|file| get_path_of(config, file))
+        .collect()
+}
 
-        let root_name = file_encryption_service::get_name(&config, &current)?;
-        current_path = format!("{}/{}", root_name, current_path);
-        paths.push(current_path.to_string());
+pub fn get_path_by_id(config: &Config, id: Uuid) -> Result<String, CoreError> {
+    let file = file_metadata_repo::maybe_get(config, id)?.ok_or(CoreError::FileNonexistent)?;
+    get_path_of(config, &file)
+}
+
+fn get_path_of(config: &Config, file: &FileMetadata) -> Result<String, CoreError> {
+    let mut current = file.clone();
+    let mut current_path = String::from("");
+    while current.id != current.parent {
+        let current_name = file_encryption_service::get_name(&config, &current)?;
+        if current.file_type == Document {
+            current_path = current_name;
+        } else {
+            current_path = format!("{}/{}", current_name, current_path);
+        }
+        current = file_metadata_repo::get(&config, current.parent)?;
     }
 
-    Ok(paths)
+    let root_name = file_encryption_service::get_name(&config, &current)?;
+    Ok(format!("{}/{}", root_name, current_path))
 }
 
 fn split_path(path: &str) -> Vec<&str> {
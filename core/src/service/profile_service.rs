@@ -0,0 +1,82 @@
+use crate::model::state::Config;
+use crate::repo::profile_repo;
+use crate::service::lock_service;
+use crate::CoreError;
+
+/// The profile whose data lives directly in the installation's data directory, which is where
+/// everything lived before there were profiles.
+pub const DEFAULT_PROFILE: &str = "default";
+
+// Profiles are directories, keep their names boring
+fn validate_name(name: &str) -> Result<(), CoreError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(CoreError::ProfileNameInvalid)
+    }
+}
+
+fn exists(base: &Config, name: &str) -> bool {
+    name == DEFAULT_PROFILE || profile_repo::exists(base, name)
+}
+
+/// Every profile in this installation, starting with the default one.
+pub fn list(base: &Config) -> Result<Vec<String>, CoreError> {
+    let mut profiles = vec![DEFAULT_PROFILE.to_string()];
+    profiles.extend(profile_repo::list(base)?);
+    Ok(profiles)
+}
+
+pub fn create(base: &Config, name: &str) -> Result<(), CoreError> {
+    validate_name(name)?;
+    if exists(base, name) {
+        return Err(CoreError::ProfileExists);
+    }
+    profile_repo::create(base, name)
+}
+
+pub fn switch(base: &Config, name: &str) -> Result<(), CoreError> {
+    if !exists(base, name) {
+        return Err(CoreError::ProfileNonexistent);
+    }
+    profile_repo::set_active(base, name)
+}
+
+/// Deletes everything stored for this profile, including unsynced changes.
+pub fn remove(base: &Config, name: &str) -> Result<(), CoreError> {
+    if name == DEFAULT_PROFILE {
+        return Err(CoreError::ProfileIsDefault);
+    }
+    if !exists(base, name) {
+        return Err(CoreError::ProfileNonexistent);
+    }
+    if get_active(base)? == name {
+        return Err(CoreError::ProfileIsActive);
+    }
+    // Another process could be syncing or saving into it
+    let _lock = lock_service::lock(&config_for(base, name)?)?;
+    profile_repo::remove(base, name)
+}
+
+/// The profile last switched to, or the default one if it has since been removed by hand.
+pub fn get_active(base: &Config) -> Result<String, CoreError> {
+    Ok(match profile_repo::get_active(base)? {
+        Some(name) if exists(base, &name) => name,
+        _ => DEFAULT_PROFILE.to_string(),
+    })
+}
+
+/// The config to pass to every other function to work with this profile's data.
+pub fn config_for(base: &Config, name: &str) -> Result<Config, CoreError> {
+    if name == DEFAULT_PROFILE {
+        Ok(base.clone())
+    } else if exists(base, name) {
+        Ok(profile_repo::config_for(base, name))
+    } else {
+        Err(CoreError::ProfileNonexistent)
+    }
+}
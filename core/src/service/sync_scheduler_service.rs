@@ -0,0 +1,286 @@
+use crate::model::state::Config;
+use crate::repo::local_changes_repo;
+use crate::service::sync_service;
+use crate::{core_err_unexpected, CoreError};
+use serde::Serialize;
+use std::cmp;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize)]
+pub enum SyncSchedulerEvent {
+    SyncStarted,
+    SyncCompleted,
+    /// The server could not be reached, the next attempt happens after `retry_in_ms`
+    Offline {
+        retry_in_ms: u64,
+    },
+    /// Another process is changing the data directory, the next attempt happens after `retry_in_ms`
+    DataDirLocked {
+        retry_in_ms: u64,
+    },
+    /// The scheduler has stopped itself, the client must be updated before syncing again
+    ClientUpdateRequired,
+    SyncFailed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncSchedulerSettings {
+    /// How long local changes must be quiet before they are pushed
+    pub debounce: Duration,
+    /// How often to sync when nothing has changed locally
+    pub idle_interval: Duration,
+    /// How often to look for local changes made by other processes
+    pub poll_interval: Duration,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for SyncSchedulerSettings {
+    fn default() -> Self {
+        SyncSchedulerSettings {
+            debounce: Duration::from_secs(5),
+            idle_interval: Duration::from_secs(30 * 60),
+            poll_interval: Duration::from_secs(1),
+            min_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+struct SchedulerState {
+    stopped: bool,
+    sync_requested: bool,
+    last_local_change: Option<Instant>,
+}
+
+/// Runs sync on a background thread. Local changes are pushed once they have been quiet for
+/// `debounce`, otherwise sync runs every `idle_interval`. While the server is unreachable
+/// attempts back off exponentially, and `ClientUpdateRequired` stops the scheduler for good.
+pub struct SyncScheduler {
+    state: Arc<(Mutex<SchedulerState>, Condvar)>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SyncScheduler {
+    pub fn start(
+        config: &Config,
+        settings: SyncSchedulerSettings,
+        on_event: Box<dyn Fn(SyncSchedulerEvent) + Send>,
+    ) -> SyncScheduler {
+        let state = Arc::new((
+            Mutex::new(SchedulerState {
+                stopped: false,
+                sync_requested: true,
+                last_local_change: None,
+            }),
+            Condvar::new(),
+        ));
+
+        let thread_state = Arc::clone(&state);
+        let config = config.clone();
+        let handle = thread::spawn(move || run(&config, &settings, &thread_state, on_event));
+
+        SyncScheduler {
+            state,
+            handle: Some(handle),
+        }
+    }
+
+    /// Restarts the debounce window, call this after writing to a document.
+    pub fn notify_local_change(&self) {
+        self.update(|state| state.last_local_change = Some(Instant::now()));
+    }
+
+    pub fn sync_now(&self) {
+        self.update(|state| state.sync_requested = true);
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.state.0.lock().unwrap().stopped
+    }
+
+    /// Stops the scheduler, waiting for a sync in progress to finish.
+    pub fn stop(mut self) {
+        self.update(|state| state.stopped = true);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                error!("Sync scheduler thread panicked");
+            }
+        }
+    }
+
+    fn update<F: FnOnce(&mut SchedulerState)>(&self, f: F) {
+        let (lock, condvar) = &*self.state;
+        f(&mut lock.lock().unwrap());
+        condvar.notify_all();
+    }
+}
+
+impl Drop for SyncScheduler {
+    fn drop(&mut self) {
+        self.update(|state| state.stopped = true);
+    }
+}
+
+/// Schedulers started with `start_for`, one per data directory, for clients that can't hold on to
+/// a `SyncScheduler` themselves.
+static SCHEDULERS: Mutex<Vec<(String, SyncScheduler)>> = Mutex::new(Vec::new());
+
+/// Starts a scheduler for this data directory with the default settings, unless one is already
+/// running. Returns whether it started one.
+pub fn start_for(
+    config: &Config,
+    on_event: Box<dyn Fn(SyncSchedulerEvent) + Send>,
+) -> Result<bool, CoreError> {
+    let mut schedulers = schedulers()?;
+    // One that stopped itself is replaced
+    if let Some(index) = schedulers
+        .iter()
+        .position(|(path, _)| *path == config.writeable_path)
+    {
+        if schedulers[index].1.is_running() {
+            return Ok(false);
+        }
+        schedulers.remove(index);
+    }
+
+    let scheduler = SyncScheduler::start(config, SyncSchedulerSettings::default(), on_event);
+    schedulers.push((config.writeable_path.clone(), scheduler));
+    Ok(true)
+}
+
+/// Asks this data directory's scheduler to sync right away. Returns whether one was running.
+pub fn sync_now_for(config: &Config) -> Result<bool, CoreError> {
+    let schedulers = schedulers()?;
+    match schedulers
+        .iter()
+        .find(|(path, _)| *path == config.writeable_path)
+    {
+        Some((_, scheduler)) => {
+            scheduler.sync_now();
+            Ok(scheduler.is_running())
+        }
+        None => Ok(false),
+    }
+}
+
+/// Stops this data directory's scheduler, waiting for a sync in progress to finish. Returns
+/// whether one was running.
+pub fn stop_for(config: &Config) -> Result<bool, CoreError> {
+    let removed = {
+        let mut schedulers = schedulers()?;
+        schedulers
+            .iter()
+            .position(|(path, _)| *path == config.writeable_path)
+            .map(|index| schedulers.remove(index).1)
+    };
+
+    // Stopped without the registry held, so that its callback can still use it meanwhile
+    Ok(match removed {
+        Some(scheduler) => {
+            let running = scheduler.is_running();
+            scheduler.stop();
+            running
+        }
+        None => false,
+    })
+}
+
+fn schedulers() -> Result<MutexGuard<'static, Vec<(String, SyncScheduler)>>, CoreError> {
+    SCHEDULERS.lock().map_err(core_err_unexpected)
+}
+
+fn latest_local_change(config: &Config) -> Option<i64> {
+    match local_changes_repo::get_last_tracked(config) {
+        Ok(last_tracked) => last_tracked,
+        Err(err) => {
+            error!("Sync scheduler could not read local changes: {:#?}", err);
+            None
+        }
+    }
+}
+
+fn run(
+    config: &Config,
+    settings: &SyncSchedulerSettings,
+    state: &Arc<(Mutex<SchedulerState>, Condvar)>,
+    on_event: Box<dyn Fn(SyncSchedulerEvent) + Send>,
+) {
+    let (lock, condvar) = &**state;
+    let mut next_idle_sync = Instant::now() + settings.idle_interval;
+    let mut backoff: Option<Duration> = None;
+    let mut seen_local_change = latest_local_change(config);
+
+    loop {
+        loop {
+            // Writes made by other processes only show up in local changes. Read outside the lock
+            // so that notifying the scheduler never waits on storage
+            let latest = latest_local_change(config);
+
+            let mut state = lock.lock().unwrap();
+            if state.stopped {
+                return;
+            }
+
+            if latest != seen_local_change {
+                seen_local_change = latest;
+                state.last_local_change = Some(Instant::now());
+            }
+
+            let due = match (backoff, state.last_local_change) {
+                (Some(_), _) => next_idle_sync,
+                (None, Some(changed)) => cmp::min(changed + settings.debounce, next_idle_sync),
+                (None, None) => next_idle_sync,
+            };
+
+            // Changes made from here on, even while syncing, are noticed after the sync
+            let now = Instant::now();
+            if state.sync_requested || now >= due {
+                state.sync_requested = false;
+                state.last_local_change = None;
+                break;
+            }
+
+            let wait = cmp::min(due - now, settings.poll_interval);
+            drop(condvar.wait_timeout(state, wait).unwrap());
+        }
+
+        on_event(SyncSchedulerEvent::SyncStarted);
+        match sync_service::sync(config, None) {
+            Ok(_) => {
+                backoff = None;
+                next_idle_sync = Instant::now() + settings.idle_interval;
+                on_event(SyncSchedulerEvent::SyncCompleted);
+            }
+            Err(CoreError::ServerUnreachable) => {
+                let delay = match backoff {
+                    Some(previous) => cmp::min(previous * 2, settings.max_backoff),
+                    None => settings.min_backoff,
+                };
+                backoff = Some(delay);
+                next_idle_sync = Instant::now() + delay;
+                on_event(SyncSchedulerEvent::Offline {
+                    retry_in_ms: delay.as_millis() as u64,
+                });
+            }
+            Err(CoreError::DataDirLocked) => {
+                next_idle_sync = Instant::now() + settings.min_backoff;
+                on_event(SyncSchedulerEvent::DataDirLocked {
+                    retry_in_ms: settings.min_backoff.as_millis() as u64,
+                });
+            }
+            Err(CoreError::ClientUpdateRequired) => {
+                lock.lock().unwrap().stopped = true;
+                on_event(SyncSchedulerEvent::ClientUpdateRequired);
+                return;
+            }
+            Err(err) => {
+                error!("Scheduled sync failed: {:#?}", err);
+                next_idle_sync = Instant::now() + settings.idle_interval;
+                on_event(SyncSchedulerEvent::SyncFailed(format!("{:#?}", err)));
+            }
+        }
+    }
+}
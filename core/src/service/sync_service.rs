@@ -1,22 +1,35 @@
 use crate::model::client_conversion::{generate_client_work_unit, ClientWorkUnit};
 use crate::model::state::Config;
-use crate::repo::{account_repo, document_repo, file_metadata_repo, local_changes_repo};
+use crate::repo::document_repo::LocalDocument;
+use crate::repo::{
+    account_repo, document_repo, file_metadata_repo, local_changes_repo, offline_repo,
+};
+use crate::service::document_chunk_service::DownloadedDocument;
+use crate::service::event_service::{ChangeEvent, SyncState};
 use crate::service::file_compression_service;
-use crate::service::{file_encryption_service, file_service};
-use crate::{client, CoreError};
+use crate::service::{
+    document_chunk_service, document_stream_service, event_service, file_encryption_service,
+    file_service, lock_service, offline_service,
+};
+use crate::{client, core_err_unexpected, CoreError};
 use lockbook_models::account::Account;
 use lockbook_models::api::{
-    ChangeDocumentContentRequest, CreateDocumentRequest, CreateFolderRequest,
-    DeleteDocumentRequest, DeleteFolderRequest, GetDocumentRequest, GetUpdatesRequest,
-    MoveDocumentRequest, MoveFolderRequest, RenameDocumentRequest, RenameFolderRequest,
+    CreateDocumentRequest, CreateFolderRequest, DeleteDocumentRequest, DeleteFolderRequest,
+    GetUpdatesRequest, MoveDocumentRequest, MoveFolderRequest, RenameDocumentRequest,
+    RenameFolderRequest, WaitForUpdatesRequest,
 };
-use lockbook_models::file_metadata::FileMetadata;
+use lockbook_models::crypto::SecretDocument;
+use lockbook_models::drawing::{Drawing, Stroke};
 use lockbook_models::file_metadata::FileType::{Document, Folder};
+use lockbook_models::file_metadata::{FileMetadata, SignedFields};
 use lockbook_models::local_changes::{Edited, LocalChange as LocalChangeRepoLocalChange};
 use lockbook_models::work_unit::WorkUnit;
 use lockbook_models::work_unit::WorkUnit::{LocalChange, ServerChange};
 use serde::Serialize;
-use std::collections::HashMap;
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Clone)]
@@ -25,10 +38,450 @@ pub struct WorkCalculated {
     pub most_recent_update_from_server: u64,
 }
 
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum SyncPhase {
+    Calculating,
+    Pushing,
+    Pulling,
+    Merging,
+}
+
+#[derive(Debug, Serialize, Clone)]
 pub struct SyncProgress {
     pub total: usize,
     pub progress: usize,
-    pub current_work_unit: ClientWorkUnit,
+    /// `None` while work is being calculated
+    pub current_work_unit: Option<ClientWorkUnit>,
+    pub phase: SyncPhase,
+    pub bytes_transferred: u64,
+    /// Grows as sync discovers how large the documents it's pulling are
+    pub total_bytes: u64,
+}
+
+/// Lets another thread stop a sync in progress. Cancellation is checked between work units and
+/// while document content is being downloaded, so a sync never stops halfway through a work unit.
+#[derive(Debug, Clone, Default)]
+pub struct SyncCancellationToken(Arc<AtomicBool>);
+
+impl SyncCancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Syncs started through `sync`, keyed by the data directory they are syncing, so that
+/// `cancel_syncs` can reach them from another thread.
+static RUNNING_SYNCS: Mutex<Vec<(String, SyncCancellationToken)>> = Mutex::new(Vec::new());
+
+/// Keeps track of how far along a sync is and reports it to the caller.
+struct SyncTracker<'a> {
+    f: Option<&'a dyn Fn(SyncProgress)>,
+    cancellation: &'a SyncCancellationToken,
+    total: usize,
+    progress: usize,
+    current_work_unit: Option<ClientWorkUnit>,
+    phase: SyncPhase,
+    bytes_transferred: u64,
+    total_bytes: u64,
+    /// Bytes counted towards `total_bytes` for documents that haven't been pushed yet
+    counted_uploads: HashMap<Uuid, u64>,
+}
+
+impl<'a> SyncTracker<'a> {
+    fn new(
+        f: Option<&'a dyn Fn(SyncProgress)>,
+        cancellation: &'a SyncCancellationToken,
+    ) -> SyncTracker<'a> {
+        SyncTracker {
+            f,
+            cancellation,
+            total: 0,
+            progress: 0,
+            current_work_unit: None,
+            phase: SyncPhase::Calculating,
+            bytes_transferred: 0,
+            total_bytes: 0,
+            counted_uploads: HashMap::new(),
+        }
+    }
+
+    fn report(&self) {
+        if let Some(func) = self.f {
+            func(SyncProgress {
+                total: self.total,
+                progress: self.progress,
+                current_work_unit: self.current_work_unit.clone(),
+                phase: self.phase,
+                bytes_transferred: self.bytes_transferred,
+                total_bytes: self.total_bytes,
+            })
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<(), CoreError> {
+        if self.cancellation.is_cancelled() {
+            Err(CoreError::SyncCancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn calculating(&mut self) -> Result<(), CoreError> {
+        self.check_cancelled()?;
+        self.phase = SyncPhase::Calculating;
+        self.current_work_unit = None;
+        self.report();
+        Ok(())
+    }
+
+    fn merging(&mut self) {
+        self.phase = SyncPhase::Merging;
+        self.report();
+    }
+
+    /// Counts the content of documents this pass will push, pulled content is counted as each
+    /// download starts since only the server knows how large it is. Documents a previous pass
+    /// failed to push are already counted.
+    fn start_pass(&mut self, config: &Config, work_units: &[WorkUnit]) -> Result<(), CoreError> {
+        self.total = work_units.len();
+        self.progress = 0;
+        for work_unit in work_units {
+            if let LocalChange { metadata } = work_unit {
+                if let Some(change) = local_changes_repo::get_local_changes(config, metadata.id)? {
+                    if metadata.file_type == Document
+                        && (change.new || change.content_edited.is_some())
+                        && !self.counted_uploads.contains_key(&metadata.id)
+                    {
+                        if let Some(document) = document_repo::maybe_get_local(config, metadata.id)?
+                        {
+                            let size = local_document_size(&document)?;
+                            self.total_bytes += size;
+                            self.counted_uploads.insert(metadata.id, size);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Documents are counted whole when the pass starts, but chunked uploads may send less, so the
+    /// count is replaced with what was actually sent.
+    fn uploaded(&mut self, id: Uuid, bytes_sent: u64) {
+        let counted_bytes = self.counted_uploads.remove(&id).unwrap_or(0);
+        self.total_bytes = self.total_bytes - counted_bytes + bytes_sent;
+        self.bytes_transferred += bytes_sent;
+        self.report();
+    }
+
+    fn download_document(
+        &mut self,
+        config: &Config,
+        account: &Account,
+        metadata: &FileMetadata,
+    ) -> Result<DownloadedDocument, CoreError> {
+        let transferred_before = self.bytes_transferred;
+        let mut counted_bytes = 0;
+
+        document_chunk_service::download(config, &account, &metadata, &mut |received, total| {
+            // Without a content length we only learn the size as the document arrives
+            let total = total.max(received);
+            if total > counted_bytes {
+                self.total_bytes += total - counted_bytes;
+                counted_bytes = total;
+            }
+            self.bytes_transferred = transferred_before + received;
+            self.report();
+            !self.cancellation.is_cancelled()
+        })
+    }
+}
+
+fn local_document_size(document: &LocalDocument) -> Result<u64, CoreError> {
+    match document {
+        LocalDocument::Whole(document) => Ok(document.value.len() as u64),
+        LocalDocument::Streamed(stream) => stream.size(),
+    }
+}
+
+/// Combines edits made to a document on this device with edits made elsewhere since the last sync.
+/// Gets the plaintext of the version both sides started from, this device's version and the
+/// server's version. Returning `None` means the versions can't be combined, and sync keeps this
+/// device's version as a `-CONTENT-CONFLICT-` copy next to the server's.
+pub trait DocumentMerger: Send + Sync {
+    fn merge(&self, common_ancestor: &[u8], local: &[u8], server: &[u8]) -> Option<Vec<u8>>;
+}
+
+#[derive(Debug, Clone)]
+pub enum MergerKey {
+    /// A file extension without the leading dot, matched case-insensitively
+    Extension(String),
+    /// Used when no merger handles the extension, if a `ContentSniffer` gives both versions this
+    /// type. Only `application/json` is sniffed unless embedders register sniffers of their own.
+    ContentType(String),
+}
+
+impl MergerKey {
+    fn matches(&self, other: &MergerKey) -> bool {
+        match (self, other) {
+            (MergerKey::Extension(a), MergerKey::Extension(b)) => a.eq_ignore_ascii_case(b),
+            (MergerKey::ContentType(a), MergerKey::ContentType(b)) => a.eq_ignore_ascii_case(b),
+            _ => false,
+        }
+    }
+}
+
+/// Guesses the content type of a document's plaintext, for finding a `MergerKey::ContentType`
+/// merger for documents whose extension has none.
+pub trait ContentSniffer: Send + Sync {
+    fn sniff(&self, content: &[u8]) -> Option<String>;
+}
+
+pub static JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Mergers and sniffers registered by embedders. Later registrations take precedence over earlier
+/// ones and over the built-in ones.
+#[derive(Default)]
+struct Mergers {
+    mergers: Vec<(MergerKey, Arc<dyn DocumentMerger>)>,
+    sniffers: Vec<Arc<dyn ContentSniffer>>,
+}
+
+static REGISTERED_MERGERS: RwLock<Mergers> = RwLock::new(Mergers {
+    mergers: Vec::new(),
+    sniffers: Vec::new(),
+});
+
+pub fn register_merger(key: MergerKey, merger: Arc<dyn DocumentMerger>) -> Result<(), CoreError> {
+    REGISTERED_MERGERS
+        .write()
+        .map_err(core_err_unexpected)?
+        .mergers
+        .push((key, merger));
+    Ok(())
+}
+
+pub fn register_sniffer(sniffer: Arc<dyn ContentSniffer>) -> Result<(), CoreError> {
+    REGISTERED_MERGERS
+        .write()
+        .map_err(core_err_unexpected)?
+        .sniffers
+        .push(sniffer);
+    Ok(())
+}
+
+pub fn find_merger(
+    name: &str,
+    local: &[u8],
+    server: &[u8],
+) -> Result<Option<Arc<dyn DocumentMerger>>, CoreError> {
+    Ok(REGISTERED_MERGERS
+        .read()
+        .map_err(core_err_unexpected)?
+        .find(name, local, server))
+}
+
+impl Mergers {
+    fn find(&self, name: &str, local: &[u8], server: &[u8]) -> Option<Arc<dyn DocumentMerger>> {
+        let mut keys = vec![];
+        if let Some((_, extension)) = name.rsplit_once('.') {
+            keys.push(MergerKey::Extension(extension.to_string()));
+        }
+        keys.extend(self.sniff(local, server).map(MergerKey::ContentType));
+
+        keys.iter().find_map(|key| {
+            self.mergers
+                .iter()
+                .rev()
+                .find(|(registered_key, _)| registered_key.matches(key))
+                .map(|(_, merger)| Arc::clone(merger))
+                .or_else(|| builtin_merger(key))
+        })
+    }
+
+    /// Content types that both versions sniff as, most recently registered sniffer first.
+    fn sniff(&self, local: &[u8], server: &[u8]) -> Vec<String> {
+        let builtin: Arc<dyn ContentSniffer> = Arc::new(JsonSniffer);
+        self.sniffers
+            .iter()
+            .rev()
+            .chain(std::iter::once(&builtin))
+            .filter_map(
+                |sniffer| match (sniffer.sniff(local), sniffer.sniff(server)) {
+                    (Some(a), Some(b)) if a.eq_ignore_ascii_case(&b) => Some(a),
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+}
+
+fn builtin_merger(key: &MergerKey) -> Option<Arc<dyn DocumentMerger>> {
+    match key {
+        MergerKey::Extension(extension) => match extension.to_ascii_lowercase().as_str() {
+            "md" | "txt" => Some(Arc::new(TextMerger)),
+            "json" => Some(Arc::new(JsonMerger)),
+            "draw" => Some(Arc::new(DrawingMerger)),
+            _ => None,
+        },
+        MergerKey::ContentType(content_type) if content_type == JSON_CONTENT_TYPE => {
+            Some(Arc::new(JsonMerger))
+        }
+        MergerKey::ContentType(_) => None,
+    }
+}
+
+/// Sniffs JSON objects and arrays as `application/json`.
+pub struct JsonSniffer;
+
+impl ContentSniffer for JsonSniffer {
+    fn sniff(&self, content: &[u8]) -> Option<String> {
+        match serde_json::from_slice::<Value>(content) {
+            Ok(Value::Object(_)) | Ok(Value::Array(_)) => Some(JSON_CONTENT_TYPE.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Line based merge, conflicting lines are kept with git style conflict markers.
+pub struct TextMerger;
+
+impl DocumentMerger for TextMerger {
+    fn merge(&self, common_ancestor: &[u8], local: &[u8], server: &[u8]) -> Option<Vec<u8>> {
+        Some(match diffy::merge_bytes(common_ancestor, local, server) {
+            Ok(no_conflicts) => no_conflicts,
+            Err(conflicts) => conflicts,
+        })
+    }
+}
+
+/// Structural merge of JSON documents. Object keys are merged independently; any other value
+/// changed differently on both sides is a conflict.
+pub struct JsonMerger;
+
+impl JsonMerger {
+    fn merge_values(
+        common_ancestor: Option<&Value>,
+        local: Option<&Value>,
+        server: Option<&Value>,
+    ) -> Option<Option<Value>> {
+        if local == server || server == common_ancestor {
+            return Some(local.cloned());
+        }
+        if local == common_ancestor {
+            return Some(server.cloned());
+        }
+
+        let empty = Value::Object(Map::new());
+        match (common_ancestor.unwrap_or(&empty), local, server) {
+            (Value::Object(ancestor), Some(Value::Object(local)), Some(Value::Object(server))) => {
+                let keys: BTreeSet<&String> = ancestor
+                    .keys()
+                    .chain(local.keys())
+                    .chain(server.keys())
+                    .collect();
+
+                let mut merged = Map::new();
+                for key in keys {
+                    if let Some(value) =
+                        Self::merge_values(ancestor.get(key), local.get(key), server.get(key))?
+                    {
+                        merged.insert(key.clone(), value);
+                    }
+                }
+                Some(Some(Value::Object(merged)))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl DocumentMerger for JsonMerger {
+    fn merge(&self, common_ancestor: &[u8], local: &[u8], server: &[u8]) -> Option<Vec<u8>> {
+        let common_ancestor = serde_json::from_slice::<Value>(common_ancestor).ok();
+        let local = serde_json::from_slice::<Value>(local).ok()?;
+        let server = serde_json::from_slice::<Value>(server).ok()?;
+
+        let merged = Self::merge_values(common_ancestor.as_ref(), Some(&local), Some(&server))??;
+        serde_json::to_vec(&merged).ok()
+    }
+}
+
+/// Three way merge of lockbook drawings. Strokes added on either side are kept and strokes
+/// deleted on either side are dropped. `scale`, `translation_*` and `theme` go to whichever side
+/// changed them; if both did, this device wins since it pushes the merged drawing last.
+pub struct DrawingMerger;
+
+impl DrawingMerger {
+    /// Strokes have no ids, so identical strokes are told apart by how often they occur.
+    fn remove_one(strokes: &mut Vec<Stroke>, stroke: &Stroke) -> bool {
+        match strokes.iter().position(|candidate| candidate == stroke) {
+            Some(index) => {
+                strokes.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn merge_strokes(ancestor: &[Stroke], local: &[Stroke], server: &[Stroke]) -> Vec<Stroke> {
+        let mut added_by_server = server.to_vec();
+        let mut deleted_by_server = vec![];
+        for stroke in ancestor {
+            if !Self::remove_one(&mut added_by_server, stroke) {
+                deleted_by_server.push(stroke);
+            }
+        }
+
+        let mut merged = local.to_vec();
+        for stroke in deleted_by_server {
+            Self::remove_one(&mut merged, stroke);
+        }
+        merged.extend(added_by_server);
+        merged
+    }
+
+    fn last_writer<T: PartialEq + Clone>(ancestor: &T, local: &T, server: &T) -> T {
+        if local == ancestor {
+            server.clone()
+        } else {
+            local.clone()
+        }
+    }
+}
+
+impl DocumentMerger for DrawingMerger {
+    fn merge(&self, common_ancestor: &[u8], local: &[u8], server: &[u8]) -> Option<Vec<u8>> {
+        let ancestor = serde_json::from_slice::<Drawing>(common_ancestor).ok()?;
+        let local = serde_json::from_slice::<Drawing>(local).ok()?;
+        let server = serde_json::from_slice::<Drawing>(server).ok()?;
+
+        let merged = Drawing {
+            scale: Self::last_writer(&ancestor.scale, &local.scale, &server.scale),
+            translation_x: Self::last_writer(
+                &ancestor.translation_x,
+                &local.translation_x,
+                &server.translation_x,
+            ),
+            translation_y: Self::last_writer(
+                &ancestor.translation_y,
+                &local.translation_y,
+                &server.translation_y,
+            ),
+            strokes: Self::merge_strokes(&ancestor.strokes, &local.strokes, &server.strokes),
+            theme: Self::last_writer(&ancestor.theme, &local.theme, &server.theme),
+        };
+
+        serde_json::to_vec(&merged).ok()
+    }
 }
 
 pub fn calculate_work(config: &Config) -> Result<WorkCalculated, CoreError> {
@@ -47,13 +500,27 @@ pub fn calculate_work(config: &Config) -> Result<WorkCalculated, CoreError> {
     .map_err(CoreError::from)?
     .file_metadata;
 
+    let updates_by_id: HashMap<Uuid, FileMetadata> = server_updates
+        .iter()
+        .map(|metadata| (metadata.id, metadata.clone()))
+        .collect();
+
     let mut most_recent_update_from_server: u64 = last_sync;
     for metadata in server_updates {
         if metadata.metadata_version > most_recent_update_from_server {
             most_recent_update_from_server = metadata.metadata_version;
         }
 
-        match file_metadata_repo::maybe_get(config, metadata.id)? {
+        let local_metadata = file_metadata_repo::maybe_get(config, metadata.id)?;
+        verify_server_update(
+            config,
+            &account,
+            &metadata,
+            local_metadata.as_ref(),
+            &updates_by_id,
+        )?;
+
+        match local_metadata {
             None => {
                 if !metadata.deleted {
                     // no work for files we don't have that have been deleted
@@ -89,39 +556,257 @@ pub fn calculate_work(config: &Config) -> Result<WorkCalculated, CoreError> {
     })
 }
 
+/// Signatures are made on each device's clock, which the server lets be off by some seconds, so two
+/// changes can carry signatures slightly out of order.
+const SIGNATURE_REORDER_TOLERANCE_MS: i64 = 60 * 1000;
+
+/// Checks that an update from the server was signed by the account and doesn't take the file back
+/// to an older state, see `SignedFields`. Files the server deleted along with their folder keep
+/// the signature from before they were deleted, so those are checked against the folder, which
+/// is either part of `updates` or was already deleted here.
+fn verify_server_update(
+    config: &Config,
+    account: &Account,
+    metadata: &FileMetadata,
+    local_metadata: Option<&FileMetadata>,
+    updates: &HashMap<Uuid, FileMetadata>,
+) -> Result<(), CoreError> {
+    let tampering = |reason: &str| {
+        error!(
+            "Rejecting update of {} from server: {}",
+            metadata.id, reason
+        );
+        Err(CoreError::ServerTampering)
+    };
+
+    if !metadata.deleted && file_metadata_repo::is_tombstoned(config, metadata.id)? {
+        return tampering("file was deleted");
+    }
+
+    match &metadata.signature {
+        Some(signature) => {
+            if signature.public_key != account.public_key() {
+                return tampering("not signed by this account");
+            }
+
+            let verified =
+                file_encryption_service::verify_metadata(signature, metadata.signed_fields());
+            if !verified {
+                let deleted_with_parent = metadata.deleted
+                    && file_encryption_service::verify_metadata(
+                        signature,
+                        SignedFields {
+                            deleted: false,
+                            ..metadata.signed_fields()
+                        },
+                    )
+                    && match updates.get(&metadata.parent) {
+                        Some(parent) => parent.deleted,
+                        None => file_metadata_repo::is_tombstoned(config, metadata.parent)?,
+                    };
+                if !deleted_with_parent {
+                    return tampering("signature does not match");
+                }
+            }
+
+            // Only the account can sign, so its metadata is signed from now on
+            if !file_metadata_repo::signatures_required(config)? {
+                file_metadata_repo::require_signatures(config)?;
+            }
+        }
+        None => {
+            // Files created before metadata was signed have no signature until a device signs
+            // them, see `sign_unsigned_files`. Clients that don't sign are refused by the server
+            // since 0.1.5. The root is never signed, but can't be changed once the account exists.
+            if metadata.id != metadata.parent
+                && (file_metadata_repo::signatures_required(config)?
+                    || local_metadata.map_or(false, |local| local.signature.is_some()))
+            {
+                return tampering("not signed");
+            }
+        }
+    }
+
+    if let Some(local) = local_metadata {
+        if metadata.metadata_version < local.metadata_version
+            || metadata.content_version < local.content_version
+        {
+            return tampering("version went backwards");
+        }
+
+        if let (Some(signature), Some(local_signature)) = (&metadata.signature, &local.signature) {
+            if signature.timestamp + SIGNATURE_REORDER_TOLERANCE_MS < local_signature.timestamp {
+                return tampering("signature is older than ours");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// How many files from before metadata was signed one sync signs, so that a large account is
+/// signed over several syncs rather than holding the data directory for one request per file.
+const SIGN_BATCH_SIZE: usize = 50;
+
+/// Signs the files created before metadata was signed, by renaming each to its current name with a
+/// signature, `SIGN_BATCH_SIZE` at a time. Once none are left, or a signed update has come in,
+/// every update from the server has to be signed apart from the root's, and this does nothing.
+fn sign_unsigned_files(config: &Config, account: &Account) -> Result<(), CoreError> {
+    if file_metadata_repo::signatures_required(config)? {
+        return Ok(());
+    }
+
+    let mut unsigned = vec![];
+    for metadata in file_metadata_repo::get_all(config)? {
+        if metadata.signature.is_none()
+            && metadata.id != metadata.parent
+            && local_changes_repo::get_local_changes(config, metadata.id)?.is_none()
+        {
+            unsigned.push(metadata);
+        }
+    }
+
+    let remaining = unsigned.len().saturating_sub(SIGN_BATCH_SIZE);
+    for mut metadata in unsigned.into_iter().take(SIGN_BATCH_SIZE) {
+        metadata.signature = Some(file_encryption_service::sign_metadata(account, &metadata)?);
+        metadata.metadata_version = if metadata.file_type == Document {
+            client::request(account, RenameDocumentRequest::new(&metadata))
+                .map_err(CoreError::from)?
+                .new_metadata_version
+        } else {
+            client::request(account, RenameFolderRequest::new(&metadata))
+                .map_err(CoreError::from)?
+                .new_metadata_version
+        };
+        file_metadata_repo::insert(config, &metadata)?;
+    }
+
+    if remaining == 0 {
+        file_metadata_repo::require_signatures(config)?;
+    }
+    Ok(())
+}
+
+/// Blocks until the server has metadata newer than our last sync or `timeout_ms` elapses, without
+/// pulling anything. Returns whether there are remote changes to sync. The server caps how long it
+/// will hold a request, so callers should loop rather than pass very large timeouts.
+pub fn wait_for_remote_changes(config: &Config, timeout_ms: u64) -> Result<bool, CoreError> {
+    let account = account_repo::get_account(config)?;
+    let last_sync = file_metadata_repo::get_last_updated(config)?;
+
+    let most_recent_metadata_version = client::request(
+        &account,
+        WaitForUpdatesRequest {
+            since_metadata_version: last_sync,
+            timeout_ms,
+        },
+    )
+    .map_err(CoreError::from)?
+    .most_recent_metadata_version;
+
+    Ok(most_recent_metadata_version > last_sync)
+}
+
 pub fn execute_work(config: &Config, account: &Account, work: WorkUnit) -> Result<(), CoreError> {
+    let cancellation = SyncCancellationToken::new();
+    execute_tracked_work(
+        config,
+        account,
+        work,
+        &mut SyncTracker::new(None, &cancellation),
+    )
+}
+
+fn execute_tracked_work(
+    config: &Config,
+    account: &Account,
+    work: WorkUnit,
+    tracker: &mut SyncTracker,
+) -> Result<(), CoreError> {
     match work {
         WorkUnit::LocalChange { mut metadata } => {
-            handle_local_change(config, &account, &mut metadata)
+            handle_local_change(config, &account, &mut metadata, tracker)
         }
         WorkUnit::ServerChange { mut metadata } => {
-            handle_server_change(config, &account, &mut metadata)
+            handle_server_change(config, &account, &mut metadata, tracker)
         }
     }
 }
 
 pub fn sync(config: &Config, f: Option<Box<dyn Fn(SyncProgress)>>) -> Result<(), CoreError> {
+    let _lock = lock_service::lock(config)?;
+    let cancellation = SyncCancellationToken::new();
+    running_syncs()?.push((config.writeable_path.clone(), cancellation.clone()));
+
+    event_service::emit(config, ChangeEvent::SyncStateChanged(SyncState::Started));
+    let result = sync_with_cancellation(config, f, &cancellation);
+
+    running_syncs()?.retain(|(_, token)| !Arc::ptr_eq(&token.0, &cancellation.0));
+    let state = match result {
+        Ok(_) => SyncState::Completed,
+        Err(CoreError::SyncCancelled) => SyncState::Cancelled,
+        Err(_) => SyncState::Failed,
+    };
+    event_service::emit(config, ChangeEvent::SyncStateChanged(state));
+    result
+}
+
+/// Cancels every sync of this data directory that is currently running. Returns whether there
+/// was anything to cancel.
+pub fn cancel_syncs(config: &Config) -> Result<bool, CoreError> {
+    let running = running_syncs()?;
+    let mut cancelled = false;
+    for (path, token) in running.iter() {
+        if *path == config.writeable_path {
+            token.cancel();
+            cancelled = true;
+        }
+    }
+    Ok(cancelled)
+}
+
+fn running_syncs() -> Result<MutexGuard<'static, Vec<(String, SyncCancellationToken)>>, CoreError> {
+    RUNNING_SYNCS.lock().map_err(core_err_unexpected)
+}
+
+/// Like `sync`, but returns `CoreError::SyncCancelled` as soon as `cancellation` is cancelled.
+/// Work units that completed before then stay synced.
+pub fn sync_with_cancellation(
+    config: &Config,
+    f: Option<Box<dyn Fn(SyncProgress)>>,
+    cancellation: &SyncCancellationToken,
+) -> Result<(), CoreError> {
     let account = account_repo::get_account(config)?;
+    let mut tracker = SyncTracker::new(f.as_deref(), cancellation);
     let mut sync_errors: HashMap<Uuid, CoreError> = HashMap::new();
+    let mut synced = vec![];
+    tracker.calculating()?;
     let mut work_calculated = calculate_work(config)?;
 
     // Retry sync n times
     for _ in 0..10 {
         info!("Syncing");
+        tracker.start_pass(config, &work_calculated.work_units)?;
 
         for (progress, work_unit) in work_calculated.work_units.iter().enumerate() {
-            if let Some(ref func) = f {
-                func(SyncProgress {
-                    total: work_calculated.work_units.len(),
-                    progress,
-                    current_work_unit: generate_client_work_unit(config, &work_unit)?,
-                })
-            }
-
-            match execute_work(config, &account, work_unit.clone()) {
+            tracker.check_cancelled()?;
+            tracker.progress = progress;
+            tracker.current_work_unit = Some(generate_client_work_unit(config, &work_unit)?);
+            tracker.phase = match work_unit {
+                LocalChange { .. } => SyncPhase::Pushing,
+                ServerChange { .. } => SyncPhase::Pulling,
+            };
+            tracker.report();
+
+            match execute_tracked_work(config, &account, work_unit.clone(), &mut tracker) {
                 Ok(_) => {
                     debug!("{:#?} executed successfully", work_unit);
                     sync_errors.remove(&work_unit.get_metadata().id);
+                    synced.push(work_unit.get_metadata().id);
+                }
+                Err(CoreError::SyncCancelled) => {
+                    info!("Sync cancelled during {:#?}", work_unit);
+                    return Err(CoreError::SyncCancelled);
                 }
                 Err(err) => {
                     error!("Sync error detected: {:#?} {:#?}", work_unit, err);
@@ -137,6 +822,7 @@ pub fn sync(config: &Config, f: Option<Box<dyn Fn(SyncProgress)>>) -> Result<(),
             )?;
         }
 
+        tracker.calculating()?;
         work_calculated = calculate_work(config)?;
         if work_calculated.work_units.is_empty() {
             break;
@@ -148,7 +834,11 @@ pub fn sync(config: &Config, f: Option<Box<dyn Fn(SyncProgress)>>) -> Result<(),
             config,
             work_calculated.most_recent_update_from_server,
         )?;
-        Ok(())
+        sign_unsigned_files(config, &account)?;
+        offline_service::download_missing(config, &synced).map_err(|err| match err {
+            CoreError::FileNotAvailableOffline => CoreError::ServerUnreachable,
+            err => err,
+        })
     } else {
         error!("We finished everything calculate work told us about, but still have errors, this is concerning, the errors are: {:#?}", sync_errors);
         Err(CoreError::Unexpected(format!(
@@ -190,27 +880,36 @@ fn save_file_locally(
     config: &Config,
     account: &Account,
     metadata: &FileMetadata,
+    tracker: &mut SyncTracker,
 ) -> Result<(), CoreError> {
     file_metadata_repo::insert(config, &metadata)?;
 
-    if metadata.file_type == Document {
-        let document = client::request(
-            &account,
-            GetDocumentRequest {
-                id: metadata.id,
-                content_version: metadata.content_version,
-            },
-        )
-        .map_err(CoreError::from)?
-        .content;
+    if metadata.file_type == Document && offline_service::should_download(config, &metadata)? {
+        let document = match tracker
+            .download_document(config, &account, &metadata)
+            .and_then(|document| document.verify(config, &metadata).map(|_| document))
+        {
+            Ok(document) => document,
+            Err(err) => {
+                // Don't leave stale content behind, it's fetched again on read or next sync
+                document_repo::delete(config, metadata.id)?;
+                offline_repo::add_pending_download(config, metadata.id)?;
+                return Err(err);
+            }
+        };
 
-        document_repo::insert(config, metadata.id, &document)?;
+        document.insert(config, metadata.id)?;
+    } else if metadata.file_type == Document {
+        // Whatever we have cached is now stale
+        document_repo::delete(config, metadata.id)?;
     }
 
     Ok(())
 }
 
 fn delete_file_locally(config: &Config, metadata: &FileMetadata) -> Result<(), CoreError> {
+    file_metadata_repo::add_tombstone(config, metadata.id)?;
+
     if metadata.file_type == Document {
         // A deleted document
         file_metadata_repo::non_recursive_delete(config, metadata.id)?;
@@ -254,80 +953,87 @@ fn merge_documents(
     account: &Account,
     metadata: &mut FileMetadata,
     local_metadata: &FileMetadata,
-    local_changes: &LocalChangeRepoLocalChange,
     edited_locally: &Edited,
+    tracker: &mut SyncTracker,
 ) -> Result<(), CoreError> {
+    tracker.merging();
     let local_name = file_encryption_service::get_name(&config, &local_metadata)?;
-    if local_name.ends_with(".md") || local_name.ends_with(".txt") {
-        let common_ancestor = {
-            let compressed_common_ancestor = file_encryption_service::user_read_document(
-                &account,
-                &edited_locally.old_value,
-                &edited_locally.access_info,
-            )?;
-
-            file_compression_service::decompress(&compressed_common_ancestor)?
-        };
-
-        let current_version = file_service::read_document(config, metadata.id)?;
 
-        let server_version = {
-            let server_document = client::request(
-                &account,
-                GetDocumentRequest {
-                    id: metadata.id,
-                    content_version: metadata.content_version,
-                },
-            )?
-            .content;
-
-            let compressed_server_version = file_encryption_service::user_read_document(
-                &account,
-                &server_document,
-                &edited_locally.access_info,
+    let local_document =
+        document_repo::maybe_get_local(config, metadata.id)?.ok_or(CoreError::FileNonexistent)?;
+    let server_document = tracker.download_document(config, &account, &metadata)?;
+    server_document.verify(config, &metadata)?;
+
+    let merged = match (&edited_locally.old_value, &local_document, &server_document) {
+        (Some(old_value), LocalDocument::Whole(_), DownloadedDocument::Whole(server_document)) => {
+            let common_ancestor = {
+                let compressed_common_ancestor = file_encryption_service::user_read_document(
+                    &account,
+                    old_value,
+                    &edited_locally.access_info,
+                )?;
+
+                SecretDocument::new(file_compression_service::decompress(
+                    &compressed_common_ancestor,
+                )?)
+            };
+
+            let current_version =
+                SecretDocument::new(file_service::read_document(config, metadata.id)?);
+
+            let server_version = {
+                let compressed_server_version = file_encryption_service::user_read_document(
+                    &account,
+                    server_document,
+                    &edited_locally.access_info,
+                )?;
+                // This assumes that a file is never re-keyed.
+
+                SecretDocument::new(file_compression_service::decompress(
+                    &compressed_server_version,
+                )?)
+            };
+
+            find_merger(&local_name, &current_version, &server_version)?.and_then(|merger| {
+                merger.merge(&common_ancestor, &current_version, &server_version)
+            })
+        }
+        // Streamed documents may not fit in memory, so they're never merged
+        _ => None,
+    };
+
+    match merged {
+        Some(result) => {
+            file_service::write_document(config, metadata.id, &result)?;
+            metadata.content_checksum =
+                file_metadata_repo::get(config, metadata.id)?.content_checksum;
+        }
+        None => {
+            // Keep our version in a new file
+            let new_file = file_service::create(
+                config,
+                &format!("{}-CONTENT-CONFLICT-{}", &local_name, local_metadata.id),
+                local_metadata.parent,
+                Document,
             )?;
-            // This assumes that a file is never re-keyed.
-
-            file_compression_service::decompress(&compressed_server_version)?
-        };
-
-        let result = match diffy::merge_bytes(&common_ancestor, &current_version, &server_version) {
-            Ok(no_conflicts) => no_conflicts,
-            Err(conflicts) => conflicts,
-        };
-
-        file_service::write_document(config, metadata.id, &result)?;
-    } else {
-        // Create a new file
-        let new_file = file_service::create(
-            config,
-            &format!("{}-CONTENT-CONFLICT-{}", &local_name, local_metadata.id),
-            local_metadata.parent,
-            Document,
-        )?;
-
-        // Copy the local copy over
-        document_repo::insert(
-            config,
-            new_file.id,
-            &document_repo::get(config, local_changes.id)?,
-        )?;
-
-        // Overwrite local file with server copy
-        let new_content = client::request(
-            &account,
-            GetDocumentRequest {
-                id: metadata.id,
-                content_version: metadata.content_version,
-            },
-        )
-        .map_err(CoreError::from)?
-        .content;
+            match local_document {
+                LocalDocument::Whole(_) => {
+                    let current_version = file_service::read_document(config, metadata.id)?;
+                    file_service::write_document(config, new_file.id, &current_version)?;
+                }
+                LocalDocument::Streamed(stream) => {
+                    let mut current_version =
+                        document_stream_service::open(config, &local_metadata, stream)?;
+                    file_service::write_document_from(config, new_file.id, &mut current_version)?;
+                }
+            }
 
-        document_repo::insert(config, metadata.id, &new_content)?;
+            // Overwrite local file with server copy
+            server_document.insert(config, metadata.id)?;
 
-        // Mark content as synced
-        local_changes_repo::untrack_edit(config, metadata.id)?;
+            // Mark content as synced
+            local_changes_repo::untrack_edit(config, metadata.id)?;
+        }
     }
 
     Ok(())
@@ -339,6 +1045,7 @@ fn merge_files(
     metadata: &mut FileMetadata,
     local_metadata: &FileMetadata,
     local_changes: &LocalChangeRepoLocalChange,
+    tracker: &mut SyncTracker,
 ) -> Result<(), CoreError> {
     if let Some(renamed_locally) = &local_changes.renamed {
         // Check if both renamed, if so, server wins
@@ -374,9 +1081,12 @@ fn merge_files(
                 &account,
                 metadata,
                 &local_metadata,
-                &local_changes,
                 edited_locally,
+                tracker,
             )?;
+        } else {
+            // The server's checksum is of the content we're about to replace
+            metadata.content_checksum = local_metadata.content_checksum;
         }
     }
 
@@ -387,13 +1097,15 @@ fn handle_server_change(
     config: &Config,
     account: &Account,
     metadata: &mut FileMetadata,
+    tracker: &mut SyncTracker,
 ) -> Result<(), CoreError> {
     rename_local_conflicting_files(&config, &metadata)?;
 
-    match file_metadata_repo::maybe_get(config, metadata.id)? {
+    let before = file_metadata_repo::maybe_get(config, metadata.id)?;
+    match before.clone() {
         None => {
             if !metadata.deleted {
-                save_file_locally(&config, &account, &metadata)?;
+                save_file_locally(&config, &account, &metadata, tracker)?;
             } else {
                 debug!(
                     "Server deleted a file we don't know about, ignored. id: {:?}",
@@ -407,12 +1119,19 @@ fn handle_server_change(
                     if metadata.deleted {
                         delete_file_locally(&config, &metadata)?;
                     } else {
-                        save_file_locally(&config, &account, &metadata)?;
+                        save_file_locally(&config, &account, &metadata, tracker)?;
                     }
                 }
                 Some(local_changes) => {
                     if !local_changes.deleted && !metadata.deleted {
-                        merge_files(&config, &account, metadata, &local_metadata, &local_changes)?;
+                        merge_files(
+                            &config,
+                            &account,
+                            metadata,
+                            &local_metadata,
+                            &local_changes,
+                            tracker,
+                        )?;
 
                         file_metadata_repo::insert(config, &metadata)?;
                     } else if metadata.deleted {
@@ -424,6 +1143,7 @@ fn handle_server_change(
         }
     }
 
+    event_service::emit_file_changes(config, before.as_ref(), metadata);
     Ok(())
 }
 
@@ -431,23 +1151,46 @@ fn handle_local_change(
     config: &Config,
     account: &Account,
     metadata: &mut FileMetadata,
+    tracker: &mut SyncTracker,
 ) -> Result<(), CoreError> {
     match local_changes_repo::get_local_changes(config, metadata.id)? {
                 None => debug!("Calculate work indicated there was work to be done, but local_changes_repo didn't give us anything. It must have been unset by a server change. id: {:?}", metadata.id),
                 Some(mut local_change) => {
                     if local_change.new {
                         if metadata.file_type == Document {
-                            let content = document_repo::get(config, metadata.id)?;
-                            let version = client::request(
-                                &account,
-                                CreateDocumentRequest::new(&metadata, content),
-                            )
-                                .map_err(CoreError::from)?
-                                .new_metadata_and_content_version;
-
-                            metadata.metadata_version = version;
-                            metadata.content_version = version;
+                            match document_repo::maybe_get_local(config, metadata.id)?.ok_or(CoreError::FileNonexistent)? {
+                                LocalDocument::Whole(content) => {
+                                    metadata.signature = Some(file_encryption_service::sign_metadata(&account, &metadata)?);
+                                    let version = client::request(
+                                        &account,
+                                        CreateDocumentRequest::new(&metadata, content.clone()),
+                                    )
+                                        .map_err(CoreError::from)?
+                                        .new_metadata_and_content_version;
+                                    tracker.uploaded(metadata.id, content.value.len() as u64);
+
+                                    metadata.metadata_version = version;
+                                    metadata.content_version = version;
+                                }
+                                LocalDocument::Streamed(stream) => {
+                                    // Documents are created empty, then their content is streamed
+                                    let empty = file_encryption_service::write_to_document(config, &file_compression_service::compress(&[])?, &metadata)?;
+                                    metadata.signature = Some(file_encryption_service::sign_metadata(&account, &metadata)?);
+                                    let mut create = CreateDocumentRequest::new(&metadata, empty);
+                                    create.content_checksum = Some(file_encryption_service::content_checksum(config, &metadata, &[])?);
+                                    metadata.metadata_version = client::request(&account, create)
+                                        .map_err(CoreError::from)?
+                                        .new_metadata_and_content_version;
+
+                                    let (version, bytes_sent) = document_stream_service::upload(&account, &metadata, stream)?;
+                                    tracker.uploaded(metadata.id, bytes_sent);
+
+                                    metadata.metadata_version = version;
+                                    metadata.content_version = version;
+                                }
+                            }
                         } else {
+                            metadata.signature = Some(file_encryption_service::sign_metadata(&account, &metadata)?);
                             let version = client::request(
                                 &account,
                                 CreateFolderRequest::new(&metadata),
@@ -475,29 +1218,42 @@ fn handle_local_change(
                     }
 
                     if local_change.renamed.is_some() {
+                        // A move is sent separately, until then the file is under its old parent
+                        let mut renamed = metadata.clone();
+                        if let Some(moved) = &local_change.moved {
+                            renamed.parent = moved.old_value;
+                        }
+                        renamed.signature = Some(file_encryption_service::sign_metadata(&account, &renamed)?);
+
                         let version = if metadata.file_type == Document {
-                            client::request(&account, RenameDocumentRequest::new(&metadata))
+                            client::request(&account, RenameDocumentRequest::new(&renamed))
                                 .map_err(CoreError::from)?.new_metadata_version
                         } else {
-                            client::request(&account, RenameFolderRequest::new(&metadata))
+                            client::request(&account, RenameFolderRequest::new(&renamed))
                                 .map_err(CoreError::from)?.new_metadata_version
                         };
                         metadata.metadata_version = version;
+                        metadata.signature = renamed.signature;
                         file_metadata_repo::insert(config, &metadata)?;
 
                         local_changes_repo::untrack_rename(config, metadata.id)?;
                         local_change.renamed = None;
                     }
 
-                    if local_change.moved.is_some() {
+                    if let Some(old_parent) = local_change.moved.as_ref().map(|moved| moved.old_value) {
+                        let mut renamed = metadata.clone();
+                        renamed.parent = old_parent;
+                        renamed.signature = Some(file_encryption_service::sign_metadata(&account, &renamed)?);
                         metadata.metadata_version = if metadata.file_type == Document {
-                            client::request(&account, RenameDocumentRequest::new(&metadata))
+                            client::request(&account, RenameDocumentRequest::new(&renamed))
                                 .map_err(CoreError::from)?.new_metadata_version
                         } else {
-                            client::request(&account, RenameFolderRequest::new(&metadata))
+                            client::request(&account, RenameFolderRequest::new(&renamed))
                                 .map_err(CoreError::from)?.new_metadata_version
                         };
 
+                        metadata.signature = Some(file_encryption_service::sign_metadata(&account, &metadata)?);
+
                         let version = if metadata.file_type == Document {
                             client::request(&account, MoveDocumentRequest::new(&metadata)).map_err(CoreError::from)?.new_metadata_version
                         } else {
@@ -512,11 +1268,15 @@ fn handle_local_change(
                     }
 
                     if local_change.content_edited.is_some() && metadata.file_type == Document {
-                        let version = client::request(&account, ChangeDocumentContentRequest{
-                            id: metadata.id,
-                            old_metadata_version: metadata.metadata_version,
-                            new_content: document_repo::get(config, metadata.id)?,
-                        }).map_err(CoreError::from)?.new_metadata_and_content_version;
+                        let new_content = document_repo::maybe_get_local(config, metadata.id)?.ok_or(CoreError::FileNonexistent)?;
+                        let (version, bytes_sent) = match new_content {
+                            LocalDocument::Whole(new_content) => {
+                                let old_content = local_change.content_edited.as_ref().and_then(|edited| edited.old_value.as_ref());
+                                document_chunk_service::upload(config, &account, &metadata, old_content, &new_content)?
+                            }
+                            LocalDocument::Streamed(stream) => document_stream_service::upload(&account, &metadata, stream)?,
+                        };
+                        tracker.uploaded(metadata.id, bytes_sent);
 
                         metadata.content_version = version;
                         metadata.metadata_version = version;
@@ -527,18 +1287,107 @@ fn handle_local_change(
                     }
 
                     if local_change.deleted {
+                        metadata.deleted = true;
+                        metadata.signature = Some(file_encryption_service::sign_metadata(&account, &metadata)?);
                         if metadata.file_type == Document {
-                            client::request(&account, DeleteDocumentRequest{ id: metadata.id }).map_err(CoreError::from)?;
+                            client::request(&account, DeleteDocumentRequest::new(&metadata)).map_err(CoreError::from)?;
                         } else {
-                            client::request(&account, DeleteFolderRequest{ id: metadata.id }).map_err(CoreError::from)?;
+                            client::request(&account, DeleteFolderRequest::new(&metadata)).map_err(CoreError::from)?;
                         }
 
                         local_changes_repo::delete(config, metadata.id)?;
                         local_change.deleted = false;
 
                         file_metadata_repo::non_recursive_delete(config, metadata.id)?; // Now it's safe to delete this locally
+                        file_metadata_repo::add_tombstone(config, metadata.id)?;
                     }
                 }
             }
     Ok(())
 }
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::service::sync_service::{
+        ContentSniffer, DocumentMerger, JsonMerger, MergerKey, Mergers,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn json_merge_combines_independent_keys() {
+        let merged = JsonMerger
+            .merge(
+                br#"{"a": 1, "b": {"c": 2, "d": 3}}"#,
+                br#"{"a": 5, "b": {"c": 2, "d": 3}}"#,
+                br#"{"a": 1, "b": {"c": 2, "d": 4}, "e": 6}"#,
+            )
+            .unwrap();
+
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&merged).unwrap(),
+            serde_json::json!({"a": 5, "b": {"c": 2, "d": 4}, "e": 6})
+        );
+    }
+
+    #[test]
+    fn json_merge_conflict() {
+        assert!(JsonMerger
+            .merge(br#"{"a": 1}"#, br#"{"a": 2}"#, br#"{"a": 3}"#)
+            .is_none());
+    }
+
+    struct KeepLocal;
+
+    impl DocumentMerger for KeepLocal {
+        fn merge(&self, _: &[u8], local: &[u8], _: &[u8]) -> Option<Vec<u8>> {
+            Some(local.to_vec())
+        }
+    }
+
+    #[test]
+    fn registered_merger_used_for_extension() {
+        let mut mergers = Mergers::default();
+        assert!(mergers.find("notes.unit-test", b"a", b"b").is_none());
+
+        mergers.mergers.push((
+            MergerKey::Extension("UNIT-TEST".to_string()),
+            Arc::new(KeepLocal),
+        ));
+
+        let merger = mergers.find("notes.unit-test", b"a", b"b").unwrap();
+        assert_eq!(merger.merge(b"", b"a", b"b").unwrap(), b"a".to_vec());
+    }
+
+    #[test]
+    fn json_content_sniffed_without_extension() {
+        let mergers = Mergers::default();
+        assert!(mergers.find("settings", b"{}", b"[]").is_some());
+        assert!(mergers.find("settings", b"{}", b"not json").is_none());
+    }
+
+    struct CsvSniffer;
+
+    impl ContentSniffer for CsvSniffer {
+        fn sniff(&self, content: &[u8]) -> Option<String> {
+            if content.contains(&b',') {
+                Some("text/csv".to_string())
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn registered_sniffer_finds_content_type_merger() {
+        let mut mergers = Mergers::default();
+        mergers.mergers.push((
+            MergerKey::ContentType("text/csv".to_string()),
+            Arc::new(KeepLocal),
+        ));
+        assert!(mergers.find("table", b"a,b", b"a,c").is_none());
+
+        mergers.sniffers.push(Arc::new(CsvSniffer));
+        assert!(mergers.find("table", b"a,b", b"a,c").is_some());
+        assert!(mergers.find("table", b"a,b", b"a c").is_none());
+    }
+}
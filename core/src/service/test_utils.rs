@@ -41,6 +41,7 @@ macro_rules! path {
 pub fn test_config() -> Config {
     Config {
         writeable_path: format!("/tmp/{}", Uuid::new_v4().to_string()),
+        storage: None,
     }
 }
 
@@ -70,7 +71,7 @@ pub fn generate_account() -> Account {
     Account {
         username: random_username(),
         api_url: url(),
-        private_key: pubkey::generate_key(),
+        private_key: pubkey::generate_key().into(),
     }
 }
 
@@ -102,6 +103,8 @@ pub fn generate_root_metadata(account: &Account) -> (FileMetadata, AESKey) {
             deleted: false,
             user_access_keys,
             folder_access_keys: symkey::encrypt(&symkey::generate_key(), &key).unwrap(),
+            signature: None,
+            content_checksum: None,
         },
         key,
     )
@@ -127,6 +130,8 @@ pub fn generate_file_metadata(
             deleted: false,
             user_access_keys: Default::default(),
             folder_access_keys: aes_encrypt(parent_key, &file_key),
+            signature: None,
+            content_checksum: None,
         },
         file_key,
     )
@@ -193,4 +198,9 @@ pub fn assert_dbs_eq(db1: &Config, db2: &Config) {
             .map(|s| serde_json::from_slice(s.as_ref()).unwrap())
             .collect();
     assert_eq!(value1, value2);
+
+    assert_eq!(
+        local_storage::dump::<_, Vec<u8>>(&db1, document_repo::STREAM_NAMESPACE).unwrap(),
+        local_storage::dump::<_, Vec<u8>>(&db2, document_repo::STREAM_NAMESPACE).unwrap()
+    );
 }
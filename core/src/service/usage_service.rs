@@ -1,11 +1,17 @@
 use crate::client;
 use crate::model::state::Config;
-use crate::repo::{account_repo, file_metadata_repo};
-use crate::service::file_service;
+use crate::repo::document_repo::LocalDocument;
+use crate::repo::{account_repo, document_repo, file_metadata_repo};
+use crate::service::file_compression_service::Codec;
+use crate::service::{
+    document_stream_service, file_compression_service, file_encryption_service, offline_service,
+};
 use crate::CoreError;
 use lockbook_models::api::{FileUsage, GetUsageRequest, GetUsageResponse};
+use lockbook_models::file_metadata::FileMetadata;
 use lockbook_models::file_metadata::FileType::Document;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 pub const BYTE: u64 = 1;
@@ -22,7 +28,10 @@ pub const TERABYTE_MINUS_ONE: u64 = TERABYTE - 1;
 #[derive(Serialize)]
 pub struct UsageMetrics {
     pub usages: Vec<FileUsage>,
+    /// What the server stores and bills for, padding included
     pub server_usage: UsageItemMetric,
+    /// `server_usage` without the padding of documents this device holds
+    pub logical_usage: UsageItemMetric,
     pub data_cap: UsageItemMetric,
 }
 
@@ -32,6 +41,24 @@ pub struct UsageItemMetric {
     pub readable: String,
 }
 
+/// The size of the documents on this device once decompressed.
+#[derive(Serialize)]
+pub struct UncompressedUsage {
+    pub exact: u64,
+    pub readable: String,
+    pub by_codec: Vec<CodecUsage>,
+}
+
+/// How much space a codec saved across the documents it compressed.
+#[derive(Serialize)]
+pub struct CodecUsage {
+    pub codec: Codec,
+    pub documents: u64,
+    pub uncompressed: UsageItemMetric,
+    pub compressed: UsageItemMetric,
+    pub savings: UsageItemMetric,
+}
+
 pub fn bytes_to_human(size: u64) -> String {
     let (unit, abbr) = match size {
         0..=KILOBYTE_MINUS_ONE => (BYTE, "B"),
@@ -59,7 +86,14 @@ pub fn get_usage(config: &Config) -> Result<UsageMetrics, CoreError> {
     let server_usage = server_usage_and_cap.sum_server_usage();
     let cap = server_usage_and_cap.cap;
 
+    let mut padding = 0;
+    for usage in &server_usage_and_cap.usages {
+        padding += padding_of(config, usage.file_id)?;
+    }
+    let logical_usage = server_usage.saturating_sub(padding);
+
     let readable_usage = bytes_to_human(server_usage);
+    let readable_logical_usage = bytes_to_human(logical_usage);
     let readable_cap = bytes_to_human(cap);
 
     Ok(UsageMetrics {
@@ -68,6 +102,10 @@ pub fn get_usage(config: &Config) -> Result<UsageMetrics, CoreError> {
             exact: server_usage,
             readable: readable_usage,
         },
+        logical_usage: UsageItemMetric {
+            exact: logical_usage,
+            readable: readable_logical_usage,
+        },
         data_cap: UsageItemMetric {
             exact: cap,
             readable: readable_cap,
@@ -75,23 +113,80 @@ pub fn get_usage(config: &Config) -> Result<UsageMetrics, CoreError> {
     })
 }
 
-pub fn get_uncompressed_usage(config: &Config) -> Result<UsageItemMetric, CoreError> {
-    let doc_ids: Vec<Uuid> = file_metadata_repo::get_all(&config)?
-        .into_iter()
-        .filter(|f| f.file_type == Document)
-        .map(|f| f.id)
-        .collect();
+/// Padding of the copy of a document this device holds. Documents that aren't held here, or that
+/// were streamed, are counted as unpadded.
+fn padding_of(config: &Config, id: Uuid) -> Result<u64, CoreError> {
+    let metadata = match file_metadata_repo::maybe_get(config, id)? {
+        Some(metadata) => metadata,
+        None => return Ok(0),
+    };
+    match document_repo::maybe_get_local(config, id)? {
+        Some(LocalDocument::Whole(document)) => {
+            file_encryption_service::padding_of(config, &document, &metadata)
+        }
+        Some(LocalDocument::Streamed(_)) | None => Ok(0),
+    }
+}
+
+pub fn get_uncompressed_usage(config: &Config) -> Result<UncompressedUsage, CoreError> {
+    let mut docs: Vec<FileMetadata> = vec![];
+    for file in file_metadata_repo::get_all(&config)? {
+        // Remote-only documents would be downloaded just to be measured
+        if file.file_type == Document && !offline_service::is_remote_only(&config, &file)? {
+            docs.push(file);
+        }
+    }
 
     let mut local_usage: u64 = 0;
-    for id in doc_ids {
-        local_usage += file_service::read_document(&config, id)?.len() as u64
+    let mut codecs: BTreeMap<Codec, (u64, u64, u64)> = BTreeMap::new();
+    for doc in docs {
+        let (codec, compressed_size, size) = match offline_service::get_document(config, &doc)? {
+            LocalDocument::Whole(document) => {
+                let compressed = file_encryption_service::read_document(config, &document, &doc)?;
+                let size = file_compression_service::decompress(&compressed)?.len() as u64;
+                (
+                    file_compression_service::codec_of(&compressed)?,
+                    compressed.len() as u64,
+                    size,
+                )
+            }
+            LocalDocument::Streamed(stream) => {
+                document_stream_service::measure(config, &doc, stream)?
+            }
+        };
+
+        local_usage += size;
+        let (documents, uncompressed, compressed) = codecs.entry(codec).or_default();
+        *documents += 1;
+        *uncompressed += size;
+        *compressed += compressed_size;
     }
 
-    let readable = bytes_to_human(local_usage);
-
-    Ok(UsageItemMetric {
+    Ok(UncompressedUsage {
         exact: local_usage,
-        readable,
+        readable: bytes_to_human(local_usage),
+        by_codec: codecs
+            .into_iter()
+            .map(|(codec, (documents, uncompressed, compressed))| {
+                let savings = uncompressed.saturating_sub(compressed);
+                CodecUsage {
+                    codec,
+                    documents,
+                    uncompressed: UsageItemMetric {
+                        exact: uncompressed,
+                        readable: bytes_to_human(uncompressed),
+                    },
+                    compressed: UsageItemMetric {
+                        exact: compressed,
+                        readable: bytes_to_human(compressed),
+                    },
+                    savings: UsageItemMetric {
+                        exact: savings,
+                        readable: bytes_to_human(savings),
+                    },
+                }
+            })
+            .collect(),
     })
 }
 
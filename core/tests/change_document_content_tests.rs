@@ -40,6 +40,7 @@ mod change_document_content_tests {
                 id: doc.id,
                 old_metadata_version: doc.metadata_version,
                 new_content: aes_encrypt(&doc_key, &String::from("new doc content").into_bytes()),
+                content_checksum: None,
             },
         )
         .unwrap();
@@ -62,6 +63,7 @@ mod change_document_content_tests {
                     &symkey::generate_key(),
                     &String::from("new doc content").into_bytes(),
                 ),
+                content_checksum: None,
             },
         );
         assert_matches!(
@@ -0,0 +1,92 @@
+mod integration_test;
+
+#[cfg(test)]
+mod data_dir_lock_tests {
+    use fs2::FileExt;
+    use lockbook_core::repo::padding_repo::PaddingScheme;
+    use lockbook_core::service::lock_service;
+    use lockbook_core::service::test_utils::test_config;
+    use lockbook_core::{get_padding_scheme, set_padding_scheme, Error, SetPaddingSchemeError};
+    use std::fs::{create_dir_all, File};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    // flock locks belong to open files, so this conflicts with core like another process would
+    fn lock_from_elsewhere(writeable_path: &str) -> File {
+        create_dir_all(writeable_path).unwrap();
+        let file = File::create(format!("{}/lock", writeable_path)).unwrap();
+        file.lock_exclusive().unwrap();
+        file
+    }
+
+    #[test]
+    fn changes_fail_while_locked_elsewhere() {
+        let config = test_config();
+        let other = lock_from_elsewhere(&config.writeable_path);
+
+        assert!(matches!(
+            set_padding_scheme(&config, PaddingScheme::Padme),
+            Err(Error::UiError(SetPaddingSchemeError::DataDirLocked))
+        ));
+        assert_eq!(get_padding_scheme(&config).unwrap(), PaddingScheme::Off);
+
+        other.unlock().unwrap();
+        set_padding_scheme(&config, PaddingScheme::Padme).unwrap();
+        assert_eq!(get_padding_scheme(&config).unwrap(), PaddingScheme::Padme);
+    }
+
+    #[test]
+    fn changes_wait_for_the_lock_to_be_released() {
+        let config = test_config();
+        let other = lock_from_elsewhere(&config.writeable_path);
+
+        let releasing = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            other.unlock().unwrap();
+        });
+        set_padding_scheme(&config, PaddingScheme::Padme).unwrap();
+        assert_eq!(get_padding_scheme(&config).unwrap(), PaddingScheme::Padme);
+        releasing.join().unwrap();
+    }
+
+    #[test]
+    fn lock_is_reentrant_and_released() {
+        let config = test_config();
+
+        let outer = lock_service::lock(&config).unwrap();
+        let inner = lock_service::lock(&config).unwrap();
+        drop(inner);
+        assert!(File::open(format!("{}/lock", config.writeable_path))
+            .unwrap()
+            .try_lock_exclusive()
+            .is_err());
+
+        drop(outer);
+        let other = File::open(format!("{}/lock", config.writeable_path)).unwrap();
+        other.try_lock_exclusive().unwrap();
+    }
+
+    #[test]
+    fn other_threads_wait_for_the_lock() {
+        let config = test_config();
+        let held = lock_service::lock(&config).unwrap();
+
+        let changed = Arc::new(AtomicBool::new(false));
+        let changing = {
+            let config = config.clone();
+            let changed = changed.clone();
+            thread::spawn(move || {
+                set_padding_scheme(&config, PaddingScheme::Padme).unwrap();
+                changed.store(true, Ordering::SeqCst);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(200));
+        assert!(!changed.load(Ordering::SeqCst));
+        drop(held);
+        changing.join().unwrap();
+        assert_eq!(get_padding_scheme(&config).unwrap(), PaddingScheme::Padme);
+    }
+}
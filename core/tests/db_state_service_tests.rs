@@ -39,4 +39,25 @@ mod db_state_service_tests {
             StateRequiresClearing
         );
     }
+
+    #[test]
+    fn data_from_the_previous_version_is_kept() {
+        let config = test_config();
+        let generated_account = generate_account();
+        create_account(
+            &config,
+            &generated_account.username,
+            &generated_account.api_url,
+        )
+        .unwrap();
+
+        db_version_repo::set(&config, "0.1.4").unwrap();
+        assert_eq!(db_state_service::get_state(&config).unwrap(), ReadyToUse);
+
+        db_state_service::perform_migration(&config).unwrap();
+        assert_eq!(
+            db_version_repo::get(&config).unwrap().unwrap(),
+            db_state_service::get_code_version()
+        );
+    }
 }
@@ -30,7 +30,7 @@ mod delete_document_tests {
         .unwrap();
 
         // delete document
-        client::request(&account, DeleteDocumentRequest { id: doc.id }).unwrap();
+        client::request(&account, DeleteDocumentRequest::new(&doc)).unwrap();
     }
 
     #[test]
@@ -42,7 +42,7 @@ mod delete_document_tests {
 
         // delete document that wasn't created
         let (doc, _) = generate_file_metadata(&account, &root, &root_key, FileType::Document);
-        let result = client::request(&account, DeleteDocumentRequest { id: doc.id });
+        let result = client::request(&account, DeleteDocumentRequest::new(&doc));
         assert_matches!(
             result,
             Err(ApiError::<DeleteDocumentError>::Endpoint(
@@ -70,10 +70,10 @@ mod delete_document_tests {
         .unwrap();
 
         // delete document
-        client::request(&account, DeleteDocumentRequest { id: doc.id }).unwrap();
+        client::request(&account, DeleteDocumentRequest::new(&doc)).unwrap();
 
         // delete document again
-        let result = client::request(&account, DeleteDocumentRequest { id: doc.id });
+        let result = client::request(&account, DeleteDocumentRequest::new(&doc));
         assert_matches!(
             result,
             Err(ApiError::<DeleteDocumentError>::Endpoint(
@@ -92,7 +92,7 @@ mod delete_document_tests {
             .folder_metadata_version;
 
         // delete root
-        let result = client::request(&account, DeleteFolderRequest { id: root.id });
+        let result = client::request(&account, DeleteFolderRequest::new(&root));
         assert_matches!(
             result,
             Err(ApiError::<DeleteFolderError>::Endpoint(
@@ -0,0 +1,176 @@
+mod integration_test;
+
+#[cfg(test)]
+mod unit_tests {
+    use libsecp256k1::SecretKey;
+    use lockbook_core::model::state::temp_config;
+    use lockbook_core::repo::{account_repo, db_version_repo, file_metadata_repo, file_name_repo};
+    use lockbook_core::service::db_state_service::State::{MigrationRequired, ReadyToUse};
+    use lockbook_core::service::{
+        db_state_service, file_encryption_service, file_service, integrity_service, path_service,
+    };
+    use lockbook_core::{
+        create_file, create_file_at_path, rename_file, CoreError, CreateFileAtPathError,
+        CreateFileError, Error, RenameFileError,
+    };
+    use lockbook_models::account::Account;
+    use lockbook_models::file_metadata::FileMetadata;
+    use lockbook_models::file_metadata::FileType::Document;
+    use rand::rngs::OsRng;
+
+    static NFC: &str = "caf\u{e9}.md";
+    static NFD: &str = "cafe\u{301}.md";
+
+    fn test_account() -> Account {
+        Account {
+            username: String::from("username"),
+            api_url: "ftp://uranus.net".to_string(),
+            private_key: SecretKey::random(&mut OsRng).into(),
+        }
+    }
+
+    fn setup() -> (lockbook_core::model::state::Config, FileMetadata) {
+        let config = temp_config();
+        let account = test_account();
+        account_repo::insert_account(&config, &account).unwrap();
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(&config, &root).unwrap();
+        (config, root)
+    }
+
+    #[test]
+    fn differently_normalized_names_are_the_same_name() {
+        let (config, root) = setup();
+
+        let file = file_service::create(&config, NFD, root.id, Document).unwrap();
+        assert_eq!(
+            file_encryption_service::get_name(&config, &file).unwrap(),
+            NFC
+        );
+        match file_service::create(&config, NFC, root.id, Document) {
+            Err(CoreError::PathTaken) => {}
+            result => panic!("created a second {}: {:?}", NFC, result),
+        }
+
+        assert_eq!(
+            path_service::get_by_path(&config, &format!("username/{}", NFD))
+                .unwrap()
+                .id,
+            file.id
+        );
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let (config, root) = setup();
+
+        match create_file(&config, "nul.txt", root.id, Document) {
+            Err(Error::UiError(CreateFileError::FileNameInvalid)) => {}
+            result => panic!("created a reserved name: {:?}", result),
+        }
+        match create_file_at_path(&config, "username/nul/notes.md") {
+            Err(Error::UiError(CreateFileAtPathError::FileNameInvalid)) => {}
+            result => panic!("created a reserved name: {:?}", result),
+        }
+
+        let file = create_file(&config, "notes.md", root.id, Document).unwrap();
+        match rename_file(&config, file.id, "new\nline.md") {
+            Err(Error::UiError(RenameFileError::NewNameInvalid)) => {}
+            result => panic!("renamed to a control character: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn derived_names_are_not_validated() {
+        let (config, root) = setup();
+
+        // Names from other platforms reach core through sync, which renames them on conflict
+        let file = file_service::create(&config, "nul.txt", root.id, Document).unwrap();
+        let conflict_name = format!("nul.txt-NAME-CONFLICT-{}", file.id);
+        file_service::rename_file(&config, file.id, &conflict_name).unwrap();
+        assert_eq!(
+            file_encryption_service::get_name(
+                &config,
+                &file_metadata_repo::get(&config, file.id).unwrap()
+            )
+            .unwrap(),
+            conflict_name
+        );
+    }
+
+    #[test]
+    fn stored_names_are_compared_normalized() {
+        let (config, root) = setup();
+
+        // Named by a client that doesn't normalize
+        let nfd =
+            file_encryption_service::create_file_metadata(&config, NFD, Document, root.id).unwrap();
+        file_metadata_repo::insert(&config, &nfd).unwrap();
+
+        match file_service::create(&config, NFC, root.id, Document) {
+            Err(CoreError::PathTaken) => {}
+            result => panic!("created a second {}: {:?}", NFC, result),
+        }
+        match path_service::create_at_path(&config, &format!("username/{}", NFC)) {
+            Err(CoreError::PathTaken) => {}
+            result => panic!("created a second {}: {:?}", NFC, result),
+        }
+        assert_eq!(
+            path_service::get_by_path(&config, &format!("username/{}", NFC))
+                .unwrap()
+                .id,
+            nfd.id
+        );
+    }
+
+    #[test]
+    fn case_insensitive_names_are_opt_in() {
+        let (config, root) = setup();
+
+        let file = file_service::create(&config, "notes.md", root.id, Document).unwrap();
+        file_service::create(&config, "Notes.md", root.id, Document).unwrap();
+
+        file_name_repo::set_case_insensitive(&config, true).unwrap();
+        match file_service::create(&config, "NOTES.md", root.id, Document) {
+            Err(CoreError::PathTaken) => {}
+            result => panic!("created a name taken ignoring case: {:?}", result),
+        }
+
+        let other = file_service::create(&config, "other.md", root.id, Document).unwrap();
+        file_service::rename_file(&config, other.id, "Other.md").unwrap();
+        match file_service::rename_file(&config, file.id, "NOTES.md") {
+            Err(CoreError::PathTaken) => {}
+            result => panic!("renamed to a name taken ignoring case: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn migration_normalizes_existing_names() {
+        let (config, root) = setup();
+
+        let nfc = file_service::create(&config, NFC, root.id, Document).unwrap();
+        let nfd =
+            file_encryption_service::create_file_metadata(&config, NFD, Document, root.id).unwrap();
+        file_metadata_repo::insert(&config, &nfd).unwrap();
+
+        db_version_repo::set(&config, db_state_service::get_code_version()).unwrap();
+        assert_eq!(
+            db_state_service::get_state(&config).unwrap(),
+            MigrationRequired
+        );
+        db_state_service::perform_migration(&config).unwrap();
+        assert_eq!(db_state_service::get_state(&config).unwrap(), ReadyToUse);
+
+        let nfc = file_metadata_repo::get(&config, nfc.id).unwrap();
+        let nfd = file_metadata_repo::get(&config, nfd.id).unwrap();
+        assert_eq!(
+            file_encryption_service::get_name(&config, &nfc).unwrap(),
+            NFC
+        );
+        assert_eq!(
+            file_encryption_service::get_name(&config, &nfd).unwrap(),
+            format!("{}-NAME-CONFLICT-{}", NFC, nfd.id)
+        );
+        integrity_service::test_repo_integrity(&config).unwrap();
+    }
+}
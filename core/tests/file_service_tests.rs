@@ -11,13 +11,18 @@ mod unit_tests {
     use lockbook_core::repo::{
         account_repo, document_repo, file_metadata_repo, local_changes_repo,
     };
+    use lockbook_core::service::event_service::ChangeEvent;
     use lockbook_core::service::{
         file_encryption_service, file_service, integrity_service, path_service,
     };
     use lockbook_core::CoreError;
     use lockbook_models::account::Account;
     use lockbook_models::file_metadata::FileType::{Document, Folder};
+    use lockbook_models::local_changes::ChangeField;
     use rand::rngs::OsRng;
+    use std::io;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
     use uuid::Uuid;
     macro_rules! assert_no_metadata_problems (
         ($db:expr) => {
@@ -51,7 +56,7 @@ mod unit_tests {
         Account {
             username: String::from("username"),
             api_url: "ftp://uranus.net".to_string(),
-            private_key: SecretKey::random(&mut OsRng),
+            private_key: SecretKey::random(&mut OsRng).into(),
         }
     }
 
@@ -586,6 +591,74 @@ mod unit_tests {
             .is_none());
     }
 
+    #[test]
+    fn test_streamed_document_round_trip() {
+        let config = &temp_config();
+
+        let account = test_account();
+        account_repo::insert_account(config, &account).unwrap();
+
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let file = path_service::create_at_path(config, "username/large.bin").unwrap();
+        let content: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        file_service::write_document_from(config, file.id, &mut content.as_slice()).unwrap();
+        assert!(document_repo::is_streamed(config, file.id).unwrap());
+
+        let mut read = vec![];
+        let len = file_service::read_document_to(config, file.id, &mut read).unwrap();
+        assert_eq!(len, content.len() as u64);
+        assert_eq!(read, content);
+        assert_eq!(
+            file_service::read_document(config, file.id).unwrap(),
+            content
+        );
+
+        local_changes_repo::untrack_new_file(config, file.id).unwrap();
+        assert_total_local_changes!(config, 0);
+
+        // Streamed and whole versions of the same content are not an edit
+        file_service::write_document(config, file.id, "small".as_bytes()).unwrap();
+        assert!(!document_repo::is_streamed(config, file.id).unwrap());
+        file_service::write_document_from(config, file.id, &mut content.as_slice()).unwrap();
+        assert!(local_changes_repo::get_local_changes(config, file.id)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_streamed_document_write_failure_is_not_corruption() {
+        struct BrokenPipe;
+
+        impl Write for BrokenPipe {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::from(io::ErrorKind::BrokenPipe))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let config = &temp_config();
+
+        let account = test_account();
+        account_repo::insert_account(config, &account).unwrap();
+
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let file = path_service::create_at_path(config, "username/large.bin").unwrap();
+        let content: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        file_service::write_document_from(config, file.id, &mut content.as_slice()).unwrap();
+
+        match file_service::read_document_to(config, file.id, &mut BrokenPipe) {
+            Err(CoreError::Unexpected(_)) => {}
+            result => panic!("a closed destination was reported as {:?}", result),
+        }
+    }
+
     #[test]
     fn test_document_delete_new_documents_no_trace_when_deleted() {
         let config = &temp_config();
@@ -774,4 +847,270 @@ mod unit_tests {
 
         assert_total_local_changes!(config, 0);
     }
+
+    #[test]
+    fn test_revert_local_changes() {
+        let config = &temp_config();
+
+        let account = test_account();
+        account_repo::insert_account(config, &account).unwrap();
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let folder1 = file_service::create(config, "folder1", root.id, Folder).unwrap();
+        let folder2 = file_service::create(config, "folder2", root.id, Folder).unwrap();
+        let doc = file_service::create(config, "doc.md", folder1.id, Document).unwrap();
+        file_service::write_document(config, doc.id, "synced".as_bytes()).unwrap();
+        for id in &[folder1.id, folder2.id, doc.id] {
+            local_changes_repo::untrack_new_file(config, *id).unwrap();
+        }
+        assert_total_local_changes!(config, 0);
+
+        file_service::rename_file(config, doc.id, "renamed.md").unwrap();
+        file_service::move_file(config, doc.id, folder2.id).unwrap();
+        file_service::write_document(config, doc.id, "edited".as_bytes()).unwrap();
+
+        file_service::revert_local_change(config, doc.id, &[ChangeField::Name]).unwrap();
+        let change = local_changes_repo::get_local_changes(config, doc.id)
+            .unwrap()
+            .unwrap();
+        assert!(change.renamed.is_none());
+        assert!(change.moved.is_some());
+        assert!(change.content_edited.is_some());
+        let metadata = file_metadata_repo::get(config, doc.id).unwrap();
+        assert_eq!(
+            file_encryption_service::get_name(config, &metadata).unwrap(),
+            "doc.md"
+        );
+
+        file_service::revert_local_change(
+            config,
+            doc.id,
+            &[ChangeField::Parent, ChangeField::Content],
+        )
+        .unwrap();
+        assert_total_local_changes!(config, 0);
+        assert_eq!(
+            file_metadata_repo::get(config, doc.id).unwrap().parent,
+            folder1.id
+        );
+        assert_eq!(
+            file_service::read_document(config, doc.id).unwrap(),
+            "synced".as_bytes()
+        );
+        assert_no_metadata_problems!(config);
+    }
+
+    #[test]
+    fn test_revert_new_folder() {
+        let config = &temp_config();
+
+        let account = test_account();
+        account_repo::insert_account(config, &account).unwrap();
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let synced = file_service::create(config, "synced.md", root.id, Document).unwrap();
+        local_changes_repo::untrack_new_file(config, synced.id).unwrap();
+
+        let folder = file_service::create(config, "folder", root.id, Folder).unwrap();
+        let new_doc = file_service::create(config, "new.md", folder.id, Document).unwrap();
+        file_service::move_file(config, synced.id, folder.id).unwrap();
+        assert_total_local_changes!(config, 3);
+
+        file_service::revert_local_change(config, folder.id, &[ChangeField::Name]).unwrap();
+        assert_total_local_changes!(config, 0);
+        assert!(file_metadata_repo::maybe_get(config, folder.id)
+            .unwrap()
+            .is_none());
+        assert!(file_metadata_repo::maybe_get(config, new_doc.id)
+            .unwrap()
+            .is_none());
+        assert!(document_repo::maybe_get(config, new_doc.id)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            file_metadata_repo::get(config, synced.id).unwrap().parent,
+            root.id
+        );
+        assert_no_metadata_problems!(config);
+    }
+
+    #[test]
+    fn test_revert_all_local_changes() {
+        let config = &temp_config();
+
+        let account = test_account();
+        account_repo::insert_account(config, &account).unwrap();
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let doc1 = file_service::create(config, "doc1.md", root.id, Document).unwrap();
+        let doc2 = file_service::create(config, "doc2.md", root.id, Document).unwrap();
+        file_service::write_document(config, doc2.id, "synced".as_bytes()).unwrap();
+        local_changes_repo::untrack_new_file(config, doc1.id).unwrap();
+        local_changes_repo::untrack_new_file(config, doc2.id).unwrap();
+
+        file_service::rename_file(config, doc1.id, "doc3.md").unwrap();
+        file_service::rename_file(config, doc2.id, "doc4.md").unwrap();
+        file_service::write_document(config, doc2.id, "edited".as_bytes()).unwrap();
+        let folder = file_service::create(config, "folder", root.id, Folder).unwrap();
+        file_service::create(config, "new.md", folder.id, Document).unwrap();
+
+        file_service::revert_all_local_changes(config).unwrap();
+        assert_total_local_changes!(config, 0);
+        assert_total_filtered_paths!(config, None, 3);
+        assert_eq!(
+            file_service::read_document(config, doc2.id).unwrap(),
+            "synced".as_bytes()
+        );
+        assert_no_metadata_problems!(config);
+    }
+
+    #[test]
+    fn test_local_change_details() {
+        let config = &temp_config();
+
+        let account = test_account();
+        account_repo::insert_account(config, &account).unwrap();
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let folder = file_service::create(config, "folder", root.id, Folder).unwrap();
+        let doc = file_service::create(config, "doc.md", root.id, Document).unwrap();
+        local_changes_repo::untrack_new_file(config, folder.id).unwrap();
+        local_changes_repo::untrack_new_file(config, doc.id).unwrap();
+
+        file_service::rename_file(config, doc.id, "renamed.md").unwrap();
+        file_service::move_file(config, doc.id, folder.id).unwrap();
+        file_service::write_document(config, doc.id, "edited".as_bytes()).unwrap();
+        let new = file_service::create(config, "new.md", root.id, Document).unwrap();
+
+        let details = lockbook_core::get_local_change_details(config).unwrap();
+        assert_eq!(details.len(), 2);
+
+        let moved = details.iter().find(|change| change.id == doc.id).unwrap();
+        assert_eq!(moved.path, "username/folder/renamed.md");
+        assert_eq!(moved.renamed_from, Some(String::from("doc.md")));
+        assert_eq!(moved.moved_from, Some(String::from("username/doc.md")));
+        assert!(moved.edited && !moved.new && !moved.deleted);
+
+        let created = details.iter().find(|change| change.id == new.id).unwrap();
+        assert_eq!(created.path, "username/new.md");
+        assert!(created.new && created.renamed_from.is_none() && created.moved_from.is_none());
+    }
+
+    #[test]
+    fn test_diff_local_document() {
+        let config = &temp_config();
+
+        let account = test_account();
+        account_repo::insert_account(config, &account).unwrap();
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let doc = file_service::create(config, "doc.md", root.id, Document).unwrap();
+        file_service::write_document(config, doc.id, "line 1\nline 2\n".as_bytes()).unwrap();
+        local_changes_repo::untrack_new_file(config, doc.id).unwrap();
+        assert_eq!(
+            file_service::diff_local_document(config, doc.id).unwrap(),
+            ""
+        );
+
+        file_service::write_document(config, doc.id, "line 1\nline two\n".as_bytes()).unwrap();
+        let diff = file_service::diff_local_document(config, doc.id).unwrap();
+        assert!(diff.contains("-line 2\n"));
+        assert!(diff.contains("+line two\n"));
+        assert!(diff.contains(" line 1\n"));
+
+        file_service::write_document(config, doc.id, &[0xff, 0xfe]).unwrap();
+        assert!(matches!(
+            file_service::diff_local_document(config, doc.id).unwrap_err(),
+            CoreError::DocumentNotText
+        ));
+    }
+
+    #[test]
+    fn test_change_events() {
+        let config = &temp_config();
+
+        let account = test_account();
+        account_repo::insert_account(config, &account).unwrap();
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::<ChangeEvent>::new()));
+        let callback_events = Arc::clone(&events);
+        let subscription = lockbook_core::subscribe(
+            config,
+            Box::new(move |event: &ChangeEvent| {
+                callback_events.lock().unwrap().push(event.clone())
+            }),
+        )
+        .unwrap();
+
+        let folder = file_service::create(config, "folder", root.id, Folder).unwrap();
+        let doc = file_service::create(config, "doc.md", root.id, Document).unwrap();
+        file_service::rename_file(config, doc.id, "renamed.md").unwrap();
+        file_service::move_file(config, doc.id, folder.id).unwrap();
+        file_service::write_document(config, doc.id, "content".as_bytes()).unwrap();
+        file_service::delete_document(config, doc.id).unwrap();
+
+        assert!(lockbook_core::unsubscribe(subscription).unwrap());
+        file_service::create(config, "unseen.md", root.id, Document).unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ChangeEvent::FileCreated {
+                    id: folder.id,
+                    parent: root.id
+                },
+                ChangeEvent::FileCreated {
+                    id: doc.id,
+                    parent: root.id
+                },
+                ChangeEvent::ContentChanged { id: doc.id },
+                ChangeEvent::Renamed { id: doc.id },
+                ChangeEvent::Moved {
+                    id: doc.id,
+                    old_parent: root.id,
+                    new_parent: folder.id
+                },
+                ChangeEvent::ContentChanged { id: doc.id },
+                ChangeEvent::Deleted { id: doc.id },
+            ]
+        );
+    }
+
+    #[test]
+    fn save_document_to_disk_leaves_nothing_on_failure() {
+        let config = &temp_config();
+        let account = test_account();
+        account_repo::insert_account(config, &account).unwrap();
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let doc = file_service::create(config, "doc.md", root.id, Document).unwrap();
+        file_service::write_document(config, doc.id, "content".as_bytes()).unwrap();
+        let folder = file_service::create(config, "folder", root.id, Folder).unwrap();
+
+        let directory = tempfile::tempdir().unwrap();
+        let location = directory.path().join("saved.md");
+        let location_string = location.to_str().unwrap().to_string();
+
+        assert!(matches!(
+            file_service::save_document_to_disk(config, folder.id, location_string.clone()),
+            Err(CoreError::FileNotDocument)
+        ));
+        assert_eq!(std::fs::read_dir(directory.path()).unwrap().count(), 0);
+
+        file_service::save_document_to_disk(config, doc.id, location_string.clone()).unwrap();
+        assert_eq!(std::fs::read(&location).unwrap(), "content".as_bytes());
+
+        assert!(matches!(
+            file_service::save_document_to_disk(config, doc.id, location_string),
+            Err(CoreError::DiskPathTaken)
+        ));
+    }
 }
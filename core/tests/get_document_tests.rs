@@ -10,6 +10,7 @@ mod get_document_tests {
     };
     use lockbook_models::api::*;
     use lockbook_models::file_metadata::FileType;
+    use std::collections::HashMap;
     use uuid::Uuid;
 
     #[test]
@@ -70,4 +71,58 @@ mod get_document_tests {
             ))
         );
     }
+
+    #[test]
+    fn get_chunked_document_requires_client_update() {
+        // new account
+        let account = generate_account();
+        let (root, root_key) = generate_root_metadata(&account);
+        client::request(&account, NewAccountRequest::new(&account, &root)).unwrap();
+
+        // create document
+        let (mut doc, doc_key) =
+            generate_file_metadata(&account, &root, &root_key, FileType::Document);
+        doc.metadata_version = client::request(
+            &account,
+            CreateDocumentRequest::new(
+                &doc,
+                aes_encrypt(&doc_key, &String::from("doc content").into_bytes()),
+            ),
+        )
+        .unwrap()
+        .new_metadata_and_content_version;
+
+        // change document content in chunks
+        let chunk = String::from("chunk");
+        let mut new_chunks = HashMap::new();
+        new_chunks.insert(
+            chunk.clone(),
+            aes_encrypt(&doc_key, &String::from("new doc content").into_bytes()),
+        );
+        doc.content_version = client::request(
+            &account,
+            ChangeDocumentContentChunksRequest {
+                id: doc.id,
+                old_metadata_version: doc.metadata_version,
+                chunks: vec![chunk],
+                new_chunks,
+                content_checksum: None,
+            },
+        )
+        .unwrap()
+        .new_metadata_and_content_version;
+
+        // clients that can't download chunks are told to update
+        let result = client::request(
+            &account,
+            GetDocumentRequest {
+                id: doc.id,
+                content_version: doc.content_version,
+            },
+        );
+        assert_matches!(
+            result,
+            Err(ApiError::<GetDocumentError>::ClientUpdateRequired)
+        );
+    }
 }
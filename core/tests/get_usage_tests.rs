@@ -3,10 +3,12 @@ mod integration_test;
 #[cfg(test)]
 mod get_usage_tests {
     use lockbook_core::repo::document_repo;
+    use lockbook_core::repo::padding_repo::PaddingScheme;
+    use lockbook_core::service::file_compression_service::Codec;
     use lockbook_core::service::test_utils::{generate_account, random_username, test_config};
     use lockbook_core::{
-        create_account, create_file, delete_file, get_root, get_usage, init_logger, sync_all,
-        write_document,
+        create_account, create_file, delete_file, get_root, get_uncompressed_usage, get_usage,
+        init_logger, read_document, set_padding_scheme, sync_all, write_document,
     };
     use lockbook_models::file_metadata::FileType;
     use lockbook_models::file_metadata::FileType::Folder;
@@ -55,6 +57,92 @@ mod get_usage_tests {
         )
     }
 
+    #[test]
+    fn padded_usage() {
+        let config = &test_config();
+        let generated_account = generate_account();
+        create_account(
+            config,
+            &generated_account.username,
+            &generated_account.api_url,
+        )
+        .unwrap();
+        let root = get_root(config).unwrap();
+        set_padding_scheme(config, PaddingScheme::Padme).unwrap();
+
+        let short = create_file(config, "short.md", root.id, FileType::Document).unwrap();
+        write_document(config, short.id, "short".as_bytes()).unwrap();
+        let long = create_file(config, "long.md", root.id, FileType::Document).unwrap();
+        write_document(config, long.id, "a little bit longer".as_bytes()).unwrap();
+
+        sync_all!(config).unwrap();
+
+        let usage = get_usage(config).unwrap();
+        assert_eq!(usage.usages.len(), 2);
+        assert_eq!(usage.usages[0].size_bytes, usage.usages[1].size_bytes);
+        assert!(usage.logical_usage.exact < usage.server_usage.exact);
+        assert_eq!(read_document(config, short.id).unwrap(), b"short");
+    }
+
+    #[test]
+    fn unpadded_usage() {
+        let config = &test_config();
+        let generated_account = generate_account();
+        create_account(
+            config,
+            &generated_account.username,
+            &generated_account.api_url,
+        )
+        .unwrap();
+        let root = get_root(config).unwrap();
+
+        let file = create_file(config, &random_username(), root.id, FileType::Document).unwrap();
+        write_document(config, file.id, "0000000000".as_bytes()).unwrap();
+
+        sync_all!(config).unwrap();
+
+        let usage = get_usage(config).unwrap();
+        assert_eq!(usage.logical_usage.exact, usage.server_usage.exact);
+    }
+
+    #[test]
+    fn uncompressed_usage_by_codec() {
+        let config = &test_config();
+        let generated_account = generate_account();
+        create_account(
+            config,
+            &generated_account.username,
+            &generated_account.api_url,
+        )
+        .unwrap();
+        let root = get_root(config).unwrap();
+
+        let small = create_file(config, "small.md", root.id, FileType::Document).unwrap();
+        write_document(config, small.id, "0000000000".as_bytes()).unwrap();
+        let large = create_file(config, "large.md", root.id, FileType::Document).unwrap();
+        write_document(config, large.id, "0000000000\n".repeat(10000).as_bytes()).unwrap();
+        let image = create_file(config, "image.png", root.id, FileType::Document).unwrap();
+        write_document(config, image.id, "not really a png".as_bytes()).unwrap();
+
+        let usage = get_uncompressed_usage(config).unwrap();
+        assert_eq!(usage.exact, 10 + 110000 + 16);
+
+        let codecs: Vec<(Codec, u64)> = usage
+            .by_codec
+            .iter()
+            .map(|codec_usage| (codec_usage.codec, codec_usage.documents))
+            .collect();
+        assert_eq!(
+            codecs,
+            vec![(Codec::Zlib, 1), (Codec::Zstd, 1), (Codec::Store, 1)]
+        );
+
+        let zstd = &usage.by_codec[1];
+        assert_eq!(zstd.uncompressed.exact, 110000);
+        assert!(zstd.savings.exact > 100000);
+        assert_eq!(usage.by_codec[2].savings.exact, 0);
+    }
+
     #[test]
     fn usage_go_back_down_after_delete() {
         let config = &test_config();
@@ -1,11 +1,15 @@
 #[cfg(test)]
 mod integrity_tests {
-    use lockbook_core::repo::file_metadata_repo;
+    use lockbook_core::repo::{document_repo, file_metadata_repo};
+    use lockbook_core::service::integrity_service::RepairPolicy;
     use lockbook_core::service::integrity_service::TestRepoError::*;
     use lockbook_core::service::test_utils::*;
     use lockbook_core::service::{file_encryption_service, integrity_service};
     use lockbook_core::{assert_matches, get_file_by_path, path};
-    use lockbook_core::{create_account, create_file_at_path};
+    use lockbook_core::{
+        create_account, create_file_at_path, read_document, sync_all, write_document,
+    };
+    use lockbook_models::crypto::EncryptedDocument;
     use lockbook_models::file_metadata::FileType::Document;
 
     #[test]
@@ -19,8 +23,8 @@ mod integrity_tests {
     fn test_no_root() {
         let cfg = test_config();
         assert_matches!(
-            integrity_service::test_repo_integrity(&cfg),
-            Err(NoRootFolder)
+            &integrity_service::test_repo_integrity(&cfg).unwrap_err()[..],
+            [NoRootFolder]
         );
     }
 
@@ -41,8 +45,8 @@ mod integrity_tests {
         .unwrap();
 
         assert_matches!(
-            integrity_service::test_repo_integrity(&cfg),
-            Err(FileOrphaned(_))
+            &integrity_service::test_repo_integrity(&cfg).unwrap_err()[..],
+            [FileOrphaned(_), DanglingLocalChange(_)]
         );
     }
 
@@ -56,8 +60,8 @@ mod integrity_tests {
         file_metadata_repo::insert(&cfg, &doc).unwrap();
 
         assert_matches!(
-            integrity_service::test_repo_integrity(&cfg),
-            Err(FileNameContainsSlash(_))
+            &integrity_service::test_repo_integrity(&cfg).unwrap_err()[..],
+            [FileNameContainsSlash(_)]
         );
     }
 
@@ -71,8 +75,8 @@ mod integrity_tests {
         file_metadata_repo::insert(&cfg, &doc).unwrap();
 
         assert_matches!(
-            integrity_service::test_repo_integrity(&cfg),
-            Err(FileNameEmpty(_))
+            &integrity_service::test_repo_integrity(&cfg).unwrap_err()[..],
+            [FileNameEmpty(_)]
         );
     }
 
@@ -93,8 +97,8 @@ mod integrity_tests {
         file_metadata_repo::insert(&cfg, &parent).unwrap();
 
         assert_matches!(
-            integrity_service::test_repo_integrity(&cfg),
-            Err(CycleDetected(_))
+            &integrity_service::test_repo_integrity(&cfg).unwrap_err()[..],
+            [CycleDetected(_)]
         );
     }
 
@@ -114,8 +118,8 @@ mod integrity_tests {
         file_metadata_repo::insert(&cfg, &parent).unwrap();
 
         assert_matches!(
-            integrity_service::test_repo_integrity(&cfg),
-            Err(DocumentTreatedAsFolder(_))
+            &integrity_service::test_repo_integrity(&cfg).unwrap_err()[..],
+            [DocumentTreatedAsFolder(_)]
         );
     }
 
@@ -130,8 +134,150 @@ mod integrity_tests {
         file_metadata_repo::insert(&cfg, &doc).unwrap();
 
         assert_matches!(
-            integrity_service::test_repo_integrity(&cfg),
-            Err(NameConflictDetected(_))
+            &integrity_service::test_repo_integrity(&cfg).unwrap_err()[..],
+            [NameConflictDetected(_)]
         );
     }
+
+    #[test]
+    fn test_all_problems_reported() {
+        let cfg = test_config();
+        let account = create_account(&cfg, &random_username(), &url()).unwrap();
+        for (path, name) in &[("document1.md", "na/me.md"), ("document2.md", "")] {
+            let doc = create_file_at_path(&cfg, path!(account, path)).unwrap();
+            let mut doc = file_metadata_repo::get(&cfg, doc.id).unwrap();
+            doc.name = file_encryption_service::create_name(&cfg, &doc, name).unwrap();
+            file_metadata_repo::insert(&cfg, &doc).unwrap();
+        }
+
+        let problems = integrity_service::test_repo_integrity(&cfg).unwrap_err();
+        assert_eq!(problems.len(), 2);
+        assert!(problems
+            .iter()
+            .any(|p| matches!(p, FileNameContainsSlash(_))));
+        assert!(problems.iter().any(|p| matches!(p, FileNameEmpty(_))));
+    }
+
+    #[test]
+    fn test_unreadable_document() {
+        let cfg = test_config();
+        let account = create_account(&cfg, &random_username(), &url()).unwrap();
+        let doc = create_file_at_path(&cfg, path!(account, "document1.md")).unwrap();
+        document_repo::insert(
+            &cfg,
+            doc.id,
+            &EncryptedDocument::new(vec![0; 48], vec![0; 12]),
+        )
+        .unwrap();
+
+        assert_matches!(
+            &integrity_service::test_repo_integrity(&cfg).unwrap_err()[..],
+            [DocumentUnreadable(_)]
+        );
+    }
+
+    #[test]
+    fn test_repair_cycle() {
+        let cfg = test_config();
+        let account = create_account(&cfg, &random_username(), &url()).unwrap();
+        create_file_at_path(&cfg, path!(account, "folder1/folder2/document1.md")).unwrap();
+        let mut parent = file_metadata_repo::get(
+            &cfg,
+            get_file_by_path(&cfg, path!(account, "folder1"))
+                .unwrap()
+                .id,
+        )
+        .unwrap();
+        let child = get_file_by_path(&cfg, path!(account, "folder1/folder2")).unwrap();
+        parent.parent = child.id;
+        file_metadata_repo::insert(&cfg, &parent).unwrap();
+
+        let report = integrity_service::repair_repo(&cfg, RepairPolicy::Safe).unwrap();
+        assert_matches!(&report.repaired[..], [CycleDetected(_)]);
+        assert!(report.unrepaired.is_empty());
+
+        integrity_service::test_repo_integrity(&cfg).unwrap();
+        get_file_by_path(
+            &cfg,
+            path!(account, "lost+found/folder1/folder2/document1.md"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_repair_name_conflict() {
+        let cfg = test_config();
+        let account = create_account(&cfg, &random_username(), &url()).unwrap();
+        let doc = create_file_at_path(&cfg, path!(account, "document1.md")).unwrap();
+        create_file_at_path(&cfg, path!(account, "document2.md")).unwrap();
+        let mut doc = file_metadata_repo::get(&cfg, doc.id).unwrap();
+        doc.name = file_encryption_service::create_name(&cfg, &doc, "document2.md").unwrap();
+        file_metadata_repo::insert(&cfg, &doc).unwrap();
+
+        let report = integrity_service::repair_repo(&cfg, RepairPolicy::Safe).unwrap();
+        assert_matches!(&report.repaired[..], [NameConflictDetected(_)]);
+
+        integrity_service::test_repo_integrity(&cfg).unwrap();
+    }
+
+    #[test]
+    fn test_repair_orphan_without_parent_key() {
+        let cfg = test_config();
+        let account = create_account(&cfg, &random_username(), &url()).unwrap();
+        create_file_at_path(&cfg, path!(account, "folder1/folder2/document1.md")).unwrap();
+        file_metadata_repo::non_recursive_delete(
+            &cfg,
+            get_file_by_path(&cfg, path!(account, "folder1"))
+                .unwrap()
+                .id,
+        )
+        .unwrap();
+
+        // folder2's key was encrypted with folder1's, which is gone
+        let report = integrity_service::repair_repo(&cfg, RepairPolicy::Safe).unwrap();
+        assert_matches!(&report.repaired[..], [DanglingLocalChange(_)]);
+        assert_matches!(&report.unrepaired[..], [FileOrphaned(_)]);
+    }
+
+    #[test]
+    fn test_repair_unreadable_document() {
+        let cfg = test_config();
+        let account = create_account(&cfg, &random_username(), &url()).unwrap();
+        let doc = create_file_at_path(&cfg, path!(account, "document1.md")).unwrap();
+        write_document(&cfg, doc.id, b"content").unwrap();
+        sync_all(&cfg, None).unwrap();
+        document_repo::insert(
+            &cfg,
+            doc.id,
+            &EncryptedDocument::new(vec![0; 48], vec![0; 12]),
+        )
+        .unwrap();
+
+        let report = integrity_service::repair_repo(&cfg, RepairPolicy::Safe).unwrap();
+        assert_matches!(&report.repaired[..], [DocumentUnreadable(_)]);
+        assert_eq!(read_document(&cfg, doc.id).unwrap(), b"content");
+    }
+
+    #[test]
+    fn test_repair_keeps_unsynced_edits_unless_told_otherwise() {
+        let cfg = test_config();
+        let account = create_account(&cfg, &random_username(), &url()).unwrap();
+        let doc = create_file_at_path(&cfg, path!(account, "document1.md")).unwrap();
+        write_document(&cfg, doc.id, b"unsynced").unwrap();
+        document_repo::insert(
+            &cfg,
+            doc.id,
+            &EncryptedDocument::new(vec![0; 48], vec![0; 12]),
+        )
+        .unwrap();
+
+        let report = integrity_service::repair_repo(&cfg, RepairPolicy::Safe).unwrap();
+        assert_matches!(&report.unrepaired[..], [DocumentUnreadable(_)]);
+
+        let report =
+            integrity_service::repair_repo(&cfg, RepairPolicy::DiscardUnrecoverable).unwrap();
+        assert_matches!(&report.repaired[..], [DocumentUnreadable(_)]);
+        assert_eq!(read_document(&cfg, doc.id).unwrap(), b"");
+        integrity_service::test_repo_integrity(&cfg).unwrap();
+    }
 }
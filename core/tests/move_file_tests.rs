@@ -146,7 +146,7 @@ mod move_document_tests {
         .unwrap();
 
         // delete document
-        client::request(&account, DeleteDocumentRequest { id: doc.id }).unwrap();
+        client::request(&account, DeleteDocumentRequest::new(&doc)).unwrap();
 
         // move deleted document
         doc.parent = folder.id;
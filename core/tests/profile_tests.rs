@@ -0,0 +1,79 @@
+mod integration_test;
+
+#[cfg(test)]
+mod profile_tests {
+    use lockbook_core::service::test_utils::{generate_account, test_config};
+    use lockbook_core::{
+        create_account, create_profile, get_account, get_active_profile, get_profile_config,
+        list_profiles, remove_profile, switch_profile, CreateProfileError, Error, GetAccountError,
+        RemoveProfileError, SwitchProfileError,
+    };
+
+    #[test]
+    fn profiles_keep_separate_accounts() {
+        let base = test_config();
+        let generated_account = generate_account();
+
+        create_profile(&base, "work").unwrap();
+        assert_eq!(list_profiles(&base).unwrap(), vec!["default", "work"]);
+
+        let work = get_profile_config(&base, Some("work")).unwrap();
+        create_account(
+            &work,
+            &generated_account.username,
+            &generated_account.api_url,
+        )
+        .unwrap();
+
+        assert!(get_account(&work).is_ok());
+        assert!(matches!(
+            get_account(&base),
+            Err(Error::UiError(GetAccountError::NoAccount))
+        ));
+
+        assert_eq!(get_active_profile(&base).unwrap(), "default");
+        switch_profile(&base, "work").unwrap();
+        assert_eq!(get_active_profile(&base).unwrap(), "work");
+        assert_eq!(
+            get_profile_config(&base, None).unwrap().writeable_path,
+            work.writeable_path
+        );
+    }
+
+    #[test]
+    fn profile_errors() {
+        let base = test_config();
+
+        assert!(matches!(
+            create_profile(&base, "a/b"),
+            Err(Error::UiError(CreateProfileError::ProfileNameInvalid))
+        ));
+        assert!(matches!(
+            create_profile(&base, "default"),
+            Err(Error::UiError(CreateProfileError::ProfileExists))
+        ));
+        assert!(matches!(
+            switch_profile(&base, "work"),
+            Err(Error::UiError(SwitchProfileError::ProfileDoesNotExist))
+        ));
+        assert!(matches!(
+            remove_profile(&base, "default"),
+            Err(Error::UiError(
+                RemoveProfileError::CannotRemoveDefaultProfile
+            ))
+        ));
+
+        create_profile(&base, "work").unwrap();
+        switch_profile(&base, "work").unwrap();
+        assert!(matches!(
+            remove_profile(&base, "work"),
+            Err(Error::UiError(
+                RemoveProfileError::CannotRemoveActiveProfile
+            ))
+        ));
+
+        switch_profile(&base, "default").unwrap();
+        remove_profile(&base, "work").unwrap();
+        assert_eq!(list_profiles(&base).unwrap(), vec!["default"]);
+    }
+}
@@ -76,7 +76,7 @@ mod rename_document_tests {
         .unwrap();
 
         // delete document
-        client::request(&account, DeleteDocumentRequest { id: doc.id }).unwrap();
+        client::request(&account, DeleteDocumentRequest::new(&doc)).unwrap();
 
         // rename document
         doc.name = random_filename();
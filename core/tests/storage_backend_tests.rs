@@ -1,77 +1,173 @@
 mod integration_test;
 
-/// We should figure out a way to not just copy-paste these tests!
-#[cfg(test)]
-mod unit_tests_file {
-    use lockbook_core::model::state::temp_config;
-    use lockbook_core::repo::local_storage;
-
-    #[test]
-    fn read() {
-        let config = &temp_config();
-
-        let result = local_storage::read::<_, _, Vec<u8>>(config, "files", "notes.txt").unwrap();
-
-        assert_eq!(result, None);
-    }
-
-    #[test]
-    fn write_and_read() {
-        let config = &temp_config();
-
-        let data = "noice";
-
-        local_storage::write(config, "files", "notes.txt", data).unwrap();
+macro_rules! storage_backend_tests {
+    ($module:ident, $config:expr) => {
+        #[cfg(test)]
+        mod $module {
+            use lockbook_core::repo::local_storage;
+            use std::io::{Read, Write};
+
+            #[test]
+            fn read() {
+                let config = &$config;
+
+                let result =
+                    local_storage::read::<_, _, Vec<u8>>(config, "files", "notes.txt").unwrap();
+
+                assert_eq!(result, None);
+            }
+
+            #[test]
+            fn write_and_read() {
+                let config = &$config;
+
+                let data = "noice";
+
+                local_storage::write(config, "files", "notes.txt", data).unwrap();
+
+                let result = local_storage::read::<_, _, Vec<u8>>(config, "files", "notes.txt")
+                    .unwrap()
+                    .unwrap();
+
+                assert_eq!(result, data.as_bytes());
+            }
+
+            #[test]
+            fn write_and_dump() {
+                let config = &$config;
+
+                println!("{:?}", config);
+
+                let data = "noice";
+
+                local_storage::write(config, "files", "a.txt", data).unwrap();
+                local_storage::write(config, "files", "b.txt", data).unwrap();
+                local_storage::write(config, "files", "c.txt", data).unwrap();
+
+                assert_eq!(
+                    vec![
+                        data.as_bytes().to_vec(),
+                        data.as_bytes().to_vec(),
+                        data.as_bytes().to_vec()
+                    ],
+                    local_storage::dump::<_, Vec<u8>>(config, "files").unwrap()
+                )
+            }
+
+            #[test]
+            fn write_read_delete() {
+                let config = &$config;
+
+                let data = "noice";
+
+                local_storage::write(config, "files", "notes.txt", data).unwrap();
+
+                assert_eq!(
+                    data.as_bytes().to_vec(),
+                    local_storage::read::<_, _, Vec<u8>>(config, "files", "notes.txt")
+                        .unwrap()
+                        .unwrap()
+                );
+
+                local_storage::delete(config, "files", "notes.txt").unwrap();
+
+                assert_eq!(
+                    None,
+                    local_storage::read::<_, _, Vec<u8>>(config, "files", "notes.txt").unwrap()
+                );
+            }
+
+            #[test]
+            fn persist_and_open() {
+                let config = &$config;
+
+                let mut stream = local_storage::temp_file(config).unwrap();
+                stream.write_all("noice".as_bytes()).unwrap();
+                stream.flush().unwrap();
+                assert!(local_storage::open(config, "files", "notes.txt")
+                    .unwrap()
+                    .is_none());
+
+                local_storage::persist(config, "files", "notes.txt", stream).unwrap();
+
+                let mut opened = local_storage::open(config, "files", "notes.txt")
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(opened.size().unwrap(), 5);
+                let mut result = String::new();
+                opened.read_to_string(&mut result).unwrap();
+                assert_eq!(result, "noice");
+            }
+        }
+    };
+}
 
-        let result = local_storage::read::<_, _, Vec<u8>>(config, "files", "notes.txt")
-            .unwrap()
-            .unwrap();
+storage_backend_tests!(unit_tests_file, lockbook_core::model::state::temp_config());
+storage_backend_tests!(
+    unit_tests_memory,
+    lockbook_core::model::state::memory_config()
+);
 
-        assert_eq!(result, data.as_bytes());
-    }
+#[cfg(test)]
+mod memory_backend_tests {
+    use libsecp256k1::SecretKey;
+    use lockbook_core::model::state::memory_config;
+    use lockbook_core::repo::{account_repo, document_repo, file_metadata_repo, local_storage};
+    use lockbook_core::service::{file_encryption_service, file_service, integrity_service};
+    use lockbook_models::account::Account;
+    use lockbook_models::file_metadata::FileType::Document;
+    use rand::rngs::OsRng;
 
     #[test]
-    fn write_and_dump() {
-        let config = &temp_config();
-
-        println!("{:?}", config);
-
-        let data = "noice";
-
-        local_storage::write(config, "files", "a.txt", data).unwrap();
-        local_storage::write(config, "files", "b.txt", data).unwrap();
-        local_storage::write(config, "files", "c.txt", data).unwrap();
+    fn core_runs_without_a_directory() {
+        let config = &memory_config();
+
+        let account = Account {
+            username: String::from("username"),
+            api_url: "ftp://uranus.net".to_string(),
+            private_key: SecretKey::random(&mut OsRng).into(),
+        };
+        account_repo::insert_account(config, &account).unwrap();
+        let root = file_encryption_service::create_metadata_for_root_folder(&account).unwrap();
+        file_metadata_repo::insert(config, &root).unwrap();
+
+        let small = file_service::create(config, "small.md", root.id, Document).unwrap();
+        file_service::write_document(config, small.id, "content".as_bytes()).unwrap();
+        assert_eq!(
+            file_service::read_document(config, small.id).unwrap(),
+            "content".as_bytes()
+        );
 
+        let large = file_service::create(config, "large.bin", root.id, Document).unwrap();
+        let content: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        file_service::write_document_from(config, large.id, &mut content.as_slice()).unwrap();
+        assert!(document_repo::is_streamed(config, large.id).unwrap());
         assert_eq!(
-            vec![
-                data.as_bytes().to_vec(),
-                data.as_bytes().to_vec(),
-                data.as_bytes().to_vec()
-            ],
-            local_storage::dump::<_, Vec<u8>>(config, "files").unwrap()
-        )
+            file_service::read_document(config, large.id).unwrap(),
+            content
+        );
+
+        integrity_service::test_repo_integrity(config).unwrap();
+        assert!(!config.path().exists());
     }
 
     #[test]
-    fn write_read_delete() {
-        let config = &temp_config();
-
-        let data = "noice";
+    fn configs_share_data_only_through_their_backend() {
+        let config = memory_config();
+        let same_backend = config.clone();
+        let other = memory_config();
 
-        local_storage::write(config, "files", "notes.txt", data).unwrap();
+        local_storage::write(&config, "files", "notes.txt", "noice").unwrap();
 
-        assert_eq!(
-            data.as_bytes().to_vec(),
-            local_storage::read::<_, _, Vec<u8>>(config, "files", "notes.txt")
-                .unwrap()
+        assert!(
+            local_storage::read::<_, _, Vec<u8>>(&same_backend, "files", "notes.txt")
                 .unwrap()
+                .is_some()
         );
-
-        local_storage::delete(config, "files", "notes.txt").unwrap();
-
-        assert_eq!(
-            None,
-            local_storage::read::<_, _, Vec<u8>>(config, "files", "notes.txt").unwrap()
+        assert!(
+            local_storage::read::<_, _, Vec<u8>>(&other, "files", "notes.txt")
+                .unwrap()
+                .is_none()
         );
     }
 }
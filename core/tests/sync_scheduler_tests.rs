@@ -0,0 +1,171 @@
+mod integration_test;
+
+#[cfg(test)]
+mod sync_scheduler_tests {
+    use lockbook_core::assert_matches;
+    use lockbook_core::path;
+    use lockbook_core::repo::account_repo;
+    use lockbook_core::service::sync_scheduler_service::{
+        SyncScheduler, SyncSchedulerEvent, SyncSchedulerSettings,
+    };
+    use lockbook_core::service::test_utils::{generate_account, test_config};
+    use lockbook_core::service::{account_service, file_service, path_service, sync_service};
+    use lockbook_core::{
+        start_sync_scheduler, stop_sync_scheduler, sync_now, Error, StartSyncSchedulerError,
+    };
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn fast_settings() -> SyncSchedulerSettings {
+        SyncSchedulerSettings {
+            debounce: Duration::from_millis(200),
+            idle_interval: Duration::from_secs(60),
+            poll_interval: Duration::from_millis(50),
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(400),
+        }
+    }
+
+    #[test]
+    fn pushes_local_changes_after_debounce() {
+        let db = test_config();
+        let generated_account = generate_account();
+        let account = account_service::create_account(
+            &db,
+            &generated_account.username,
+            &generated_account.api_url,
+        )
+        .unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let scheduler = SyncScheduler::start(
+            &db,
+            fast_settings(),
+            Box::new(move |event| sender.send(event).unwrap()),
+        );
+
+        // The scheduler syncs once on startup
+        assert_matches!(receiver.recv().unwrap(), SyncSchedulerEvent::SyncStarted);
+        assert_matches!(receiver.recv().unwrap(), SyncSchedulerEvent::SyncCompleted);
+
+        let file = path_service::create_at_path(&db, path!(account, "test.md")).unwrap();
+        file_service::write_document(&db, file.id, "scheduled".as_bytes()).unwrap();
+        scheduler.notify_local_change();
+
+        assert_matches!(
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap(),
+            SyncSchedulerEvent::SyncStarted
+        );
+        assert_matches!(
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap(),
+            SyncSchedulerEvent::SyncCompleted
+        );
+        scheduler.stop();
+
+        assert!(sync_service::calculate_work(&db)
+            .unwrap()
+            .work_units
+            .is_empty());
+    }
+
+    #[test]
+    fn backs_off_when_offline() {
+        let db = test_config();
+        let generated_account = generate_account();
+        let mut account = account_service::create_account(
+            &db,
+            &generated_account.username,
+            &generated_account.api_url,
+        )
+        .unwrap();
+
+        // Point the account at a server that isn't there
+        account.api_url = "http://localhost:1".to_string();
+        account_repo::insert_account(&db, &account).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let scheduler = SyncScheduler::start(
+            &db,
+            fast_settings(),
+            Box::new(move |event| sender.send(event).unwrap()),
+        );
+
+        let mut delays = vec![];
+        while delays.len() < 4 {
+            if let SyncSchedulerEvent::Offline { retry_in_ms } =
+                receiver.recv_timeout(Duration::from_secs(10)).unwrap()
+            {
+                delays.push(retry_in_ms);
+            }
+        }
+        scheduler.stop();
+
+        assert_eq!(delays, vec![100, 200, 400, 400]);
+    }
+
+    #[test]
+    fn notices_changes_without_being_notified() {
+        let db = test_config();
+        let generated_account = generate_account();
+        let account = account_service::create_account(
+            &db,
+            &generated_account.username,
+            &generated_account.api_url,
+        )
+        .unwrap();
+        let file = path_service::create_at_path(&db, path!(account, "test.md")).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let scheduler = SyncScheduler::start(
+            &db,
+            fast_settings(),
+            Box::new(move |event| sender.send(event).unwrap()),
+        );
+        assert_matches!(receiver.recv().unwrap(), SyncSchedulerEvent::SyncStarted);
+        assert_matches!(receiver.recv().unwrap(), SyncSchedulerEvent::SyncCompleted);
+
+        // Edits to a file that's already been edited still count, like another process would make
+        for content in &["first", "second"] {
+            file_service::write_document(&db, file.id, content.as_bytes()).unwrap();
+            assert_matches!(
+                receiver.recv_timeout(Duration::from_secs(10)).unwrap(),
+                SyncSchedulerEvent::SyncStarted
+            );
+            assert_matches!(
+                receiver.recv_timeout(Duration::from_secs(10)).unwrap(),
+                SyncSchedulerEvent::SyncCompleted
+            );
+        }
+        scheduler.stop();
+
+        assert!(sync_service::calculate_work(&db)
+            .unwrap()
+            .work_units
+            .is_empty());
+    }
+
+    #[test]
+    fn one_scheduler_per_data_directory() {
+        let db = test_config();
+        let generated_account = generate_account();
+        account_service::create_account(
+            &db,
+            &generated_account.username,
+            &generated_account.api_url,
+        )
+        .unwrap();
+
+        start_sync_scheduler(&db, Box::new(|_| {})).unwrap();
+        assert_matches!(
+            start_sync_scheduler(&db, Box::new(|_| {})),
+            Err(Error::UiError(
+                StartSyncSchedulerError::SchedulerAlreadyRunning
+            ))
+        );
+        assert!(sync_now(&db).unwrap());
+
+        assert!(stop_sync_scheduler(&db).unwrap());
+        assert!(!stop_sync_scheduler(&db).unwrap());
+        assert!(!sync_now(&db).unwrap());
+    }
+}
@@ -2,15 +2,31 @@ mod integration_test;
 
 #[cfg(test)]
 mod sync_tests {
+    use lockbook_core::client;
+    use lockbook_core::model::state::Config;
     use lockbook_core::path;
-    use lockbook_core::repo::{document_repo, file_metadata_repo, local_changes_repo};
+    use lockbook_core::repo::{
+        account_repo, document_repo, file_metadata_repo, local_changes_repo, offline_repo,
+    };
+    use lockbook_core::service::event_service::{ChangeEvent, SyncState};
+    use lockbook_core::service::sync_service::{SyncCancellationToken, SyncPhase, SyncProgress};
     use lockbook_core::service::test_utils::{assert_dbs_eq, generate_account, test_config};
     use lockbook_core::service::{
-        account_service, file_encryption_service, file_service, integrity_service, path_service,
+        account_service, drawing_service, event_service, file_compression_service,
+        file_encryption_service, file_service, integrity_service, offline_service, path_service,
         sync_service,
     };
-    use lockbook_models::file_metadata::FileType::Folder;
+    use lockbook_core::CoreError;
+    use lockbook_models::account::Account;
+    use lockbook_models::api::{
+        ChangeDocumentContentRequest, CreateDocumentRequest, RenameDocumentRequest,
+    };
+    use lockbook_models::drawing::{ColorAlias, ColorRGB, Drawing, Stroke};
+    use lockbook_models::file_metadata::FileType::{Document, Folder};
     use lockbook_models::work_unit::WorkUnit;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use uuid::Uuid;
 
     // TODO this can be moved to test_utils
     macro_rules! assert_no_metadata_problems (
@@ -314,6 +330,121 @@ mod sync_tests {
         assert_dbs_eq(&db1, &db2);
     }
 
+    #[test]
+    fn server_tampering_rejected() {
+        let db = test_config();
+        let account = make_account!(db);
+        let file = path_service::create_at_path(&db, path!(account, "test.md")).unwrap();
+        sync!(&db);
+
+        // the server renames the file, replaying the signature it was created with
+        let mut tampered = file_metadata_repo::get(&db, file.id).unwrap();
+        tampered.name =
+            file_encryption_service::create_name(&db, &tampered, "tampered.md").unwrap();
+        client::request(&account, RenameDocumentRequest::new(&tampered)).unwrap();
+
+        assert!(matches!(
+            sync_service::sync(&db, None),
+            Err(CoreError::ServerTampering)
+        ));
+        assert_eq!(
+            file_encryption_service::get_name(&db, &file_metadata_repo::get(&db, file.id).unwrap())
+                .unwrap(),
+            "test.md"
+        );
+    }
+
+    /// Creates a file on the server the way a client from before metadata was signed would.
+    fn create_unsigned_file(db: &Config, account: &Account, name: &str) -> Uuid {
+        let root = file_metadata_repo::get_root(db).unwrap().unwrap();
+        let metadata =
+            file_encryption_service::create_file_metadata(db, name, Document, root.id).unwrap();
+        let content = file_encryption_service::write_to_document(
+            db,
+            &file_compression_service::compress(&[]).unwrap(),
+            &metadata,
+        )
+        .unwrap();
+        client::request(account, CreateDocumentRequest::new(&metadata, content)).unwrap();
+        metadata.id
+    }
+
+    #[test]
+    fn unsigned_file_injected_by_server_rejected() {
+        let db = test_config();
+        let account = make_account!(db);
+        path_service::create_at_path(&db, path!(account, "test.md")).unwrap();
+        sync!(&db);
+
+        let injected = create_unsigned_file(&db, &account, "injected.md");
+
+        assert!(matches!(
+            sync_service::sync(&db, None),
+            Err(CoreError::ServerTampering)
+        ));
+        assert!(file_metadata_repo::maybe_get(&db, injected)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn unsigned_files_signed_on_next_sync() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+        let legacy = create_unsigned_file(&db1, &account, "legacy.md");
+
+        make_and_sync_new_client!(db2, db1);
+        assert!(file_metadata_repo::get(&db2, legacy)
+            .unwrap()
+            .signature
+            .is_some());
+
+        sync!(&db1);
+        assert!(file_metadata_repo::get(&db1, legacy)
+            .unwrap()
+            .signature
+            .is_some());
+        assert_dbs_eq(&db1, &db2);
+    }
+
+    #[test]
+    fn corrupted_download_rejected() {
+        let db = test_config();
+        let account = make_account!(db);
+        let file = path_service::create_at_path(&db, path!(account, "test.md")).unwrap();
+        file_service::write_document(&db, file.id, b"original content").unwrap();
+        sync!(&db);
+
+        // the server swaps the content but keeps the checksum of the original
+        let metadata = file_metadata_repo::get(&db, file.id).unwrap();
+        let swapped = file_encryption_service::write_to_document(
+            &db,
+            &file_compression_service::compress(b"swapped content").unwrap(),
+            &metadata,
+        )
+        .unwrap();
+        client::request(
+            &account,
+            ChangeDocumentContentRequest {
+                id: file.id,
+                old_metadata_version: metadata.metadata_version,
+                new_content: swapped,
+                content_checksum: metadata.content_checksum,
+            },
+        )
+        .unwrap();
+
+        make_new_client!(db2, db);
+        assert!(matches!(
+            sync_service::sync(&db2, None),
+            Err(CoreError::DocumentCorrupted)
+        ));
+        assert_eq!(
+            file_service::read_document(&db, file.id).unwrap(),
+            b"original content"
+        );
+    }
+
     #[test]
     fn move_then_edit() {
         let db1 = test_config();
@@ -506,6 +637,160 @@ mod sync_tests {
         assert_dbs_eq(&db1, &db2);
     }
 
+    #[test]
+    fn test_content_conflict_json_merged_structurally() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let file =
+            path_service::create_at_path(&db1, &format!("{}/settings.json", account.username))
+                .unwrap();
+
+        file_service::write_document(&db1, file.id, br#"{"font": "mono", "size": 12}"#).unwrap();
+
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+
+        file_service::write_document(&db1, file.id, br#"{"font": "serif", "size": 12}"#).unwrap();
+        sync!(&db1);
+        file_service::write_document(&db2, file.id, br#"{"font": "mono", "size": 14}"#).unwrap();
+
+        sync!(&db2);
+        sync!(&db1);
+
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(
+                &file_service::read_document(&db1, file.id).unwrap()
+            )
+            .unwrap(),
+            serde_json::json!({"font": "serif", "size": 14})
+        );
+        assert_n_work_units!(db1, 0);
+        assert_dbs_eq(&db1, &db2);
+    }
+
+    fn stroke(x: f32) -> Stroke {
+        Stroke {
+            points_x: vec![x, x + 1.0],
+            points_y: vec![x, x + 1.0],
+            points_girth: vec![1.0, 1.0],
+            color: ColorAlias::Black,
+            alpha: 1.0,
+        }
+    }
+
+    fn drawing(strokes: Vec<Stroke>) -> Drawing {
+        Drawing {
+            scale: 1.0,
+            translation_x: 0.0,
+            translation_y: 0.0,
+            strokes,
+            theme: None,
+        }
+    }
+
+    macro_rules! save_drawing {
+        ($db:expr, $id:expr, $drawing:expr) => {
+            drawing_service::save_drawing(&$db, $id, &serde_json::to_vec(&$drawing).unwrap())
+                .unwrap();
+        };
+    }
+
+    #[test]
+    fn test_content_conflict_drawing_strokes_added_on_both_sides() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let file = path_service::create_at_path(&db1, path!(account, "sketch.draw")).unwrap();
+        save_drawing!(db1, file.id, drawing(vec![stroke(0.0)]));
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+
+        save_drawing!(db1, file.id, drawing(vec![stroke(0.0), stroke(1.0)]));
+        sync!(&db1);
+        save_drawing!(db2, file.id, drawing(vec![stroke(0.0), stroke(2.0)]));
+
+        sync!(&db2);
+        sync!(&db1);
+
+        assert_eq!(
+            drawing_service::get_drawing(&db1, file.id).unwrap().strokes,
+            vec![stroke(0.0), stroke(2.0), stroke(1.0)]
+        );
+        assert_eq!(file_metadata_repo::get_all(&db1).unwrap().len(), 2);
+        assert_dbs_eq(&db1, &db2);
+    }
+
+    #[test]
+    fn test_content_conflict_drawing_honors_deletions() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let file = path_service::create_at_path(&db1, path!(account, "sketch.draw")).unwrap();
+        save_drawing!(
+            db1,
+            file.id,
+            drawing(vec![stroke(0.0), stroke(1.0), stroke(2.0)])
+        );
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+
+        save_drawing!(db1, file.id, drawing(vec![stroke(1.0), stroke(2.0)]));
+        sync!(&db1);
+        save_drawing!(
+            db2,
+            file.id,
+            drawing(vec![stroke(0.0), stroke(1.0), stroke(3.0)])
+        );
+
+        sync!(&db2);
+        sync!(&db1);
+
+        assert_eq!(
+            drawing_service::get_drawing(&db1, file.id).unwrap().strokes,
+            vec![stroke(1.0), stroke(3.0)]
+        );
+        assert_dbs_eq(&db1, &db2);
+    }
+
+    #[test]
+    fn test_content_conflict_drawing_view_last_writer_wins() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let file = path_service::create_at_path(&db1, path!(account, "sketch.draw")).unwrap();
+        save_drawing!(db1, file.id, drawing(vec![stroke(0.0)]));
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+
+        let mut server_drawing = drawing(vec![stroke(0.0)]);
+        server_drawing.scale = 2.0;
+        server_drawing.translation_x = 5.0;
+        save_drawing!(db1, file.id, server_drawing);
+        sync!(&db1);
+
+        let mut local_drawing = drawing(vec![stroke(0.0)]);
+        local_drawing.scale = 3.0;
+        let mut theme = HashMap::new();
+        theme.insert(ColorAlias::Black, ColorRGB { r: 1, g: 2, b: 3 });
+        local_drawing.theme = Some(theme.clone());
+        save_drawing!(db2, file.id, local_drawing);
+
+        sync!(&db2);
+        sync!(&db1);
+
+        let merged = drawing_service::get_drawing(&db1, file.id).unwrap();
+        assert_eq!(merged.scale, 3.0);
+        assert_eq!(merged.translation_x, 5.0);
+        assert_eq!(merged.theme, Some(theme));
+        assert_eq!(merged.strokes, vec![stroke(0.0)]);
+        assert_dbs_eq(&db1, &db2);
+    }
+
     #[test]
     fn test_content_conflict_local_move_before_mergable() {
         let db1 = test_config();
@@ -1216,4 +1501,408 @@ mod sync_tests {
 
         sync!(&db1);
     }
+
+    #[test]
+    fn lazy_sync_fetches_content_on_read() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let file = path_service::create_at_path(&db1, path!(account, "a/test.md")).unwrap();
+        file_service::write_document(&db1, file.id, "lazy content".as_bytes()).unwrap();
+        sync!(&db1);
+
+        make_new_client!(db2, db1);
+        offline_repo::set_lazy(&db2, true).unwrap();
+        sync!(&db2);
+
+        assert!(document_repo::maybe_get(&db2, file.id).unwrap().is_none());
+        assert_eq!(
+            file_service::read_document(&db2, file.id).unwrap(),
+            "lazy content".as_bytes()
+        );
+        assert!(document_repo::maybe_get(&db2, file.id).unwrap().is_some());
+    }
+
+    #[test]
+    fn lazy_sync_pinned_folder_downloaded() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let file = path_service::create_at_path(&db1, path!(account, "a/b/test.md")).unwrap();
+        let other = path_service::create_at_path(&db1, path!(account, "c/test.md")).unwrap();
+        sync!(&db1);
+
+        make_new_client!(db2, db1);
+        offline_repo::set_lazy(&db2, true).unwrap();
+        sync!(&db2);
+
+        let folder = path_service::get_by_path(&db2, path!(account, "a")).unwrap();
+        offline_service::pin(&db2, folder.id).unwrap();
+
+        assert!(document_repo::maybe_get(&db2, file.id).unwrap().is_some());
+        assert!(document_repo::maybe_get(&db2, other.id).unwrap().is_none());
+
+        offline_service::unpin(&db2, folder.id).unwrap();
+        assert!(document_repo::maybe_get(&db2, file.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn uncached_document_written_offline() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let file = path_service::create_at_path(&db1, path!(account, "test.md")).unwrap();
+        file_service::write_document(&db1, file.id, "online".as_bytes()).unwrap();
+        sync!(&db1);
+
+        make_new_client!(db2, db1);
+        offline_repo::set_lazy(&db2, true).unwrap();
+        sync!(&db2);
+        assert!(document_repo::maybe_get(&db2, file.id).unwrap().is_none());
+
+        let mut offline = account_repo::get_account(&db2).unwrap();
+        let api_url = offline.api_url.clone();
+        offline.api_url = String::from("http://127.0.0.1:1");
+        account_repo::insert_account(&db2, &offline).unwrap();
+
+        file_service::write_document(&db2, file.id, "offline".as_bytes()).unwrap();
+        let edited = local_changes_repo::get_local_changes(&db2, file.id)
+            .unwrap()
+            .unwrap()
+            .content_edited
+            .unwrap();
+        assert!(edited.old_value.is_none());
+
+        offline.api_url = api_url;
+        account_repo::insert_account(&db2, &offline).unwrap();
+        sync!(&db2);
+        sync!(&db1);
+        assert_eq!(
+            file_service::read_document(&db1, file.id).unwrap(),
+            "offline".as_bytes()
+        );
+    }
+
+    #[test]
+    fn excluded_folder_is_remote_only() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let archived =
+            path_service::create_at_path(&db1, path!(account, "archive/old.md")).unwrap();
+        let kept = path_service::create_at_path(&db1, path!(account, "work/new.md")).unwrap();
+        file_service::write_document(&db1, archived.id, "old".as_bytes()).unwrap();
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+        let archive = path_service::get_by_path(&db2, path!(account, "archive")).unwrap();
+        offline_service::set_sync_exclusions(&db2, vec![archive.id]).unwrap();
+
+        assert!(document_repo::maybe_get(&db2, archived.id)
+            .unwrap()
+            .is_none());
+        assert!(document_repo::maybe_get(&db2, kept.id).unwrap().is_some());
+        assert!(offline_service::is_remote_only(&db2, &archive).unwrap());
+
+        file_service::write_document(&db1, archived.id, "newer".as_bytes()).unwrap();
+        sync!(&db1);
+        sync!(&db2);
+
+        assert!(document_repo::maybe_get(&db2, archived.id)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            file_metadata_repo::get(&db2, archived.id)
+                .unwrap()
+                .content_version,
+            file_metadata_repo::get(&db1, archived.id)
+                .unwrap()
+                .content_version
+        );
+    }
+
+    #[test]
+    fn folder_moved_into_pinned_folder_downloaded() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let file = path_service::create_at_path(&db1, path!(account, "b/test.md")).unwrap();
+        file_service::write_document(&db1, file.id, "content".as_bytes()).unwrap();
+        path_service::create_at_path(&db1, path!(account, "a/")).unwrap();
+        sync!(&db1);
+
+        make_new_client!(db2, db1);
+        offline_repo::set_lazy(&db2, true).unwrap();
+        sync!(&db2);
+
+        let pinned = path_service::get_by_path(&db2, path!(account, "a")).unwrap();
+        offline_service::pin(&db2, pinned.id).unwrap();
+        assert!(document_repo::maybe_get(&db2, file.id).unwrap().is_none());
+
+        let moved = path_service::get_by_path(&db1, path!(account, "b")).unwrap();
+        file_service::move_file(&db1, moved.id, pinned.id).unwrap();
+        sync!(&db1);
+        sync!(&db2);
+
+        assert!(document_repo::maybe_get(&db2, file.id).unwrap().is_some());
+    }
+
+    #[test]
+    fn lifted_exclusion_downloaded_on_sync() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+
+        let archived =
+            path_service::create_at_path(&db1, path!(account, "archive/old.md")).unwrap();
+        file_service::write_document(&db1, archived.id, "old".as_bytes()).unwrap();
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+        let archive = path_service::get_by_path(&db2, path!(account, "archive")).unwrap();
+        offline_service::set_sync_exclusions(&db2, vec![archive.id]).unwrap();
+        assert!(document_repo::maybe_get(&db2, archived.id)
+            .unwrap()
+            .is_none());
+
+        offline_service::set_sync_exclusions(&db2, vec![]).unwrap();
+        sync!(&db2);
+
+        assert!(document_repo::maybe_get(&db2, archived.id)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn wait_for_remote_changes() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+        assert!(!sync_service::wait_for_remote_changes(&db2, 1000).unwrap());
+
+        path_service::create_at_path(&db1, path!(account, "test.md")).unwrap();
+        sync!(&db1);
+
+        assert!(sync_service::wait_for_remote_changes(&db2, 1000).unwrap());
+        sync!(&db2);
+        assert!(!sync_service::wait_for_remote_changes(&db2, 1000).unwrap());
+    }
+
+    #[test]
+    fn cancelled_sync_leaves_work_for_next_sync() {
+        let db = test_config();
+        let account = make_account!(db);
+        path_service::create_at_path(&db, path!(account, "test.md")).unwrap();
+
+        let cancellation = SyncCancellationToken::new();
+        cancellation.cancel();
+        assert!(matches!(
+            sync_service::sync_with_cancellation(&db, None, &cancellation),
+            Err(CoreError::SyncCancelled)
+        ));
+        assert_n_work_units!(db, 1);
+
+        sync!(&db);
+        assert_n_work_units!(db, 0);
+    }
+
+    #[test]
+    fn sync_reports_phases_and_bytes() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+        let file = path_service::create_at_path(&db1, path!(account, "test.md")).unwrap();
+        file_service::write_document(&db1, file.id, "some content".as_bytes()).unwrap();
+
+        let reports = Arc::new(Mutex::new(Vec::<SyncProgress>::new()));
+        let callback_reports = Arc::clone(&reports);
+        sync_service::sync(
+            &db1,
+            Some(Box::new(move |progress| {
+                callback_reports.lock().unwrap().push(progress)
+            })),
+        )
+        .unwrap();
+
+        let pushed = reports.lock().unwrap().clone();
+        assert_eq!(pushed.first().unwrap().phase, SyncPhase::Calculating);
+        assert!(pushed.iter().any(|p| p.phase == SyncPhase::Pushing));
+        let last = pushed.last().unwrap();
+        assert!(last.total_bytes > 0);
+        assert_eq!(last.bytes_transferred, last.total_bytes);
+
+        make_new_client!(db2, db1);
+        let reports = Arc::new(Mutex::new(Vec::<SyncProgress>::new()));
+        let callback_reports = Arc::clone(&reports);
+        sync_service::sync(
+            &db2,
+            Some(Box::new(move |progress| {
+                callback_reports.lock().unwrap().push(progress)
+            })),
+        )
+        .unwrap();
+
+        let pulled = reports.lock().unwrap().clone();
+        assert!(pulled.iter().any(|p| p.phase == SyncPhase::Pulling));
+        let last = pulled.last().unwrap();
+        assert!(last.total_bytes > 0);
+        assert_eq!(last.bytes_transferred, last.total_bytes);
+    }
+
+    fn large_document(edit: &str) -> String {
+        let mut state = 17u64;
+        (0..4000)
+            .map(|i| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                if i == 2000 {
+                    format!("{}\n", edit)
+                } else {
+                    format!("line {}: {:x}\n", i, state)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn large_document_edit_only_uploads_changed_chunks() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+        let file = path_service::create_at_path(&db1, path!(account, "large.md")).unwrap();
+        file_service::write_document(&db1, file.id, large_document("first").as_bytes()).unwrap();
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+
+        // The first edit stores the document in chunks on the server
+        file_service::write_document(&db1, file.id, large_document("second").as_bytes()).unwrap();
+        sync!(&db1);
+
+        file_service::write_document(&db1, file.id, large_document("third").as_bytes()).unwrap();
+        let reports = Arc::new(Mutex::new(Vec::<SyncProgress>::new()));
+        let callback_reports = Arc::clone(&reports);
+        sync_service::sync(
+            &db1,
+            Some(Box::new(move |progress| {
+                callback_reports.lock().unwrap().push(progress)
+            })),
+        )
+        .unwrap();
+
+        let document_size = document_repo::get(&db1, file.id).unwrap().value.len() as u64;
+        let last = reports.lock().unwrap().last().unwrap().clone();
+        assert!(last.bytes_transferred > 0);
+        assert!(
+            last.bytes_transferred * 4 < document_size,
+            "sent {} bytes of a {} byte document",
+            last.bytes_transferred,
+            document_size
+        );
+        assert_eq!(last.bytes_transferred, last.total_bytes);
+
+        sync!(&db2);
+        assert_eq!(
+            file_service::read_document(&db2, file.id).unwrap(),
+            large_document("third").as_bytes()
+        );
+        assert_dbs_eq(&db1, &db2);
+    }
+
+    fn stream(db: &Config, id: Uuid, edit: &str) {
+        file_service::write_document_from(db, id, &mut large_document(edit).as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn streamed_document_syncs() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+        let file = path_service::create_at_path(&db1, path!(account, "large.bin")).unwrap();
+        stream(&db1, file.id, "first");
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+        assert!(document_repo::is_streamed(&db2, file.id).unwrap());
+        assert_eq!(
+            file_service::read_document(&db2, file.id).unwrap(),
+            large_document("first").as_bytes()
+        );
+
+        stream(&db2, file.id, "second");
+        sync!(&db2);
+        sync!(&db1);
+
+        let mut content = vec![];
+        file_service::read_document_to(&db1, file.id, &mut content).unwrap();
+        assert_eq!(content, large_document("second").as_bytes());
+        assert_dbs_eq(&db1, &db2);
+    }
+
+    #[test]
+    fn streamed_document_conflict_keeps_both_versions() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+        let file = path_service::create_at_path(&db1, path!(account, "large.bin")).unwrap();
+        stream(&db1, file.id, "first");
+        sync!(&db1);
+
+        make_and_sync_new_client!(db2, db1);
+        stream(&db1, file.id, "from db1");
+        sync!(&db1);
+
+        stream(&db2, file.id, "from db2");
+        sync!(&db2);
+        sync!(&db1);
+
+        assert_eq!(
+            file_service::read_document(&db1, file.id).unwrap(),
+            large_document("from db1").as_bytes()
+        );
+        let conflict = path_service::get_all_paths(&db1, None)
+            .unwrap()
+            .into_iter()
+            .find(|path| path.contains("CONTENT-CONFLICT"))
+            .unwrap();
+        let conflict = path_service::get_by_path(&db1, &conflict).unwrap();
+        assert_eq!(
+            file_service::read_document(&db1, conflict.id).unwrap(),
+            large_document("from db2").as_bytes()
+        );
+        assert_dbs_eq(&db1, &db2);
+    }
+
+    #[test]
+    fn sync_emits_change_events() {
+        let db1 = test_config();
+        let account = make_account!(db1);
+        let file = path_service::create_at_path(&db1, path!(account, "test.md")).unwrap();
+        sync!(&db1);
+        make_and_sync_new_client!(db2, db1);
+
+        file_service::rename_file(&db1, file.id, "renamed.md").unwrap();
+        file_service::write_document(&db1, file.id, "new content".as_bytes()).unwrap();
+        sync!(&db1);
+
+        let events = Arc::new(Mutex::new(Vec::<ChangeEvent>::new()));
+        let callback_events = Arc::clone(&events);
+        let subscription = event_service::subscribe(
+            &db2,
+            Arc::new(move |event: &ChangeEvent| {
+                callback_events.lock().unwrap().push(event.clone())
+            }),
+        )
+        .unwrap();
+        sync!(&db2);
+        assert!(event_service::unsubscribe(subscription).unwrap());
+
+        let events = events.lock().unwrap().clone();
+        assert_eq!(
+            events.first(),
+            Some(&ChangeEvent::SyncStateChanged(SyncState::Started))
+        );
+        assert_eq!(
+            events.last(),
+            Some(&ChangeEvent::SyncStateChanged(SyncState::Completed))
+        );
+        assert!(events.contains(&ChangeEvent::Renamed { id: file.id }));
+        assert!(events.contains(&ChangeEvent::ContentChanged { id: file.id }));
+    }
 }
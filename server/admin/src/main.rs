@@ -1,7 +1,9 @@
 mod delete_account;
+mod migrate_blobs;
 
 use crate::delete_account::delete_account;
-use crate::Subcommands::DeleteAccount;
+use crate::migrate_blobs::migrate_blobs;
+use crate::Subcommands::{DeleteAccount, MigrateBlobs};
 
 use lockbook_server_lib::config::Config;
 use lockbook_server_lib::{file_content_client, file_index_repo, ServerState};
@@ -9,6 +11,7 @@ use lockbook_server_lib::{file_content_client, file_index_repo, ServerState};
 use s3::bucket::Bucket;
 use sqlx::PgPool;
 use structopt::StructOpt;
+use tokio::sync::broadcast;
 
 #[derive(Debug, PartialEq, StructOpt)]
 #[structopt(about = "A utility for a lockbook server administrator.")]
@@ -22,6 +25,10 @@ enum Subcommands {
     /// immutable, if a username is compromised or deleted, it is consumed forever, someone else cannot
     /// assume that identity.
     DeleteAccount { username: String },
+
+    /// Rewrite documents stored in s3 as JSON in the binary format. Both are readable, so this can
+    /// run while the server is up and be re-run if it's interrupted
+    MigrateBlobs,
 }
 
 #[tokio::main]
@@ -32,10 +39,12 @@ async fn main() {
         config,
         index_db_client,
         files_db_client,
+        metadata_updates: broadcast::channel(1).0,
     };
 
     let ok = match Subcommands::from_args() {
         DeleteAccount { username: user } => delete_account(server_state, &user).await,
+        MigrateBlobs => migrate_blobs(server_state).await,
     };
 
     if ok {
@@ -0,0 +1,52 @@
+use lockbook_server_lib::{file_content_client, file_index_repo, ServerState};
+
+pub async fn migrate_blobs(server_state: ServerState) -> bool {
+    let mut transaction = server_state.index_db_client.begin().await.unwrap();
+
+    let documents = file_index_repo::get_document_versions(&mut transaction)
+        .await
+        .expect("Failed to get documents");
+
+    transaction
+        .commit()
+        .await
+        .expect("Failed to commit transaction");
+
+    let mut ok = true;
+    let mut migrated = 0;
+
+    for (id, _) in documents {
+        // Holds the document's row until its blobs are rewritten, so that the server can't replace
+        // or delete the version being rewritten and leave the rewrite behind as an orphan
+        let mut transaction = server_state.index_db_client.begin().await.unwrap();
+        let content_version =
+            match file_index_repo::lock_document_version(&mut transaction, id).await {
+                Ok(Some(content_version)) => content_version,
+                // Deleted since it was listed
+                Ok(None) => continue,
+                Err(err) => {
+                    eprintln!("Failed to lock file: {}, error: {:#?}", id, err);
+                    ok = false;
+                    continue;
+                }
+            };
+
+        match file_content_client::migrate(&server_state.files_db_client, id, content_version).await
+        {
+            Ok(count) => migrated += count,
+            Err(err) => {
+                eprintln!("Failed to migrate file in s3: {}, error: {:#?}", id, err);
+                ok = false;
+            }
+        }
+
+        transaction
+            .commit()
+            .await
+            .expect("Failed to commit transaction");
+    }
+
+    println!("Migrated {} blobs", migrated);
+
+    ok
+}
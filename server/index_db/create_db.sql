@@ -59,6 +59,8 @@ CREATE TABLE IF NOT EXISTS files
     metadata_version  BIGINT  NOT NULL,
     content_version   BIGINT  NOT NULL,
     document_size     BIGINT,
+    signature         TEXT,
+    content_checksum  TEXT,
     CONSTRAINT pk_files PRIMARY KEY (id),
     CONSTRAINT fk_files_parent_files_id FOREIGN KEY (parent) REFERENCES files (id),
     CONSTRAINT fk_files_owner_accounts_name FOREIGN KEY (owner) REFERENCES accounts (name),
@@ -67,8 +69,27 @@ CREATE TABLE IF NOT EXISTS files
     )
 );
 
+ALTER TABLE files ADD COLUMN IF NOT EXISTS signature TEXT;
+ALTER TABLE files ADD COLUMN IF NOT EXISTS content_checksum TEXT;
+
 CREATE UNIQUE INDEX IF NOT EXISTS uk_files_name_parent ON files (parent, name_hmac) WHERE (NOT deleted AND id != parent);
 
+-- Wakes the long polls waiting on the owner, sent when the write commits
+CREATE OR REPLACE FUNCTION notify_metadata_updates() RETURNS TRIGGER AS
+$$
+BEGIN
+    PERFORM pg_notify('metadata_updates', (SELECT public_key FROM accounts WHERE name = NEW.owner));
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS files_metadata_updates ON files;
+CREATE TRIGGER files_metadata_updates
+    AFTER INSERT OR UPDATE
+    ON files
+    FOR EACH ROW
+EXECUTE FUNCTION notify_metadata_updates();
+
 CREATE TABLE IF NOT EXISTS user_access_keys
 (
     file_id       TEXT NOT NULL,
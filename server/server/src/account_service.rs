@@ -40,6 +40,8 @@ pub async fn new_account(
         &context.public_key,
         &request.parent_access_key,
         None,
+        &None,
+        &None,
     )
     .await;
     let new_version = create_folder_result.map_err(|e| match e {
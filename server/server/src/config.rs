@@ -54,6 +54,7 @@ impl FilesDbConfig {
 pub struct ServerConfig {
     pub port: u16,
     pub max_auth_delay: u128,
+    pub max_long_poll_ms: u64,
     pub log_path: String,
     pub pd_api_key: Option<String>,
 }
@@ -63,6 +64,7 @@ impl ServerConfig {
         ServerConfig {
             port: env_or_panic("SERVER_PORT").parse().unwrap(),
             max_auth_delay: env_or_panic("MAX_AUTH_DELAY").parse().unwrap(),
+            max_long_poll_ms: env_or_panic("MAX_LONG_POLL").parse().unwrap(),
             log_path: env_or_panic("LOG_PATH").parse().unwrap(),
             pd_api_key: env_or_empty("PD_KEY"),
         }
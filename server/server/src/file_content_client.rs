@@ -1,18 +1,96 @@
 use crate::config::FilesDbConfig;
-use lockbook_models::crypto::EncryptedDocument;
+use lockbook_models::api::{WireError, WireFormat};
+use lockbook_models::crypto::{DocumentManifest, EncryptedChunk, EncryptedDocument};
 use s3::bucket::Bucket as S3Client;
 use s3::creds::Credentials;
 use s3::region::Region;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::fs::File;
 use uuid::Uuid;
 
+/// What's stored under a document version. Versions uploaded in chunks only store their manifest,
+/// the chunks live under the document and are shared between its versions. Streamed versions are
+/// stored as is next to a record of their size.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum StoredDocument {
+    Whole(EncryptedDocument),
+    Chunked(DocumentManifest),
+    Streamed(StreamedDocument),
+}
+
+/// Documents were stored as JSON without saying which kind they were.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyStoredDocument {
+    Whole(EncryptedDocument),
+    Chunked(DocumentManifest),
+    Streamed(StreamedDocument),
+}
+
+impl From<LegacyStoredDocument> for StoredDocument {
+    fn from(legacy: LegacyStoredDocument) -> Self {
+        match legacy {
+            LegacyStoredDocument::Whole(document) => StoredDocument::Whole(document),
+            LegacyStoredDocument::Chunked(manifest) => StoredDocument::Chunked(manifest),
+            LegacyStoredDocument::Streamed(streamed) => StoredDocument::Streamed(streamed),
+        }
+    }
+}
+
+/// Blobs are stored as bincode after this prefix. Blobs without it were written as JSON, which
+/// always starts with `{`, and are read as such until `migrate` rewrites them.
+const BINARY_BLOB_PREFIX: &[u8; 4] = b"LBB1";
+
+fn serialize_blob<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut blob = BINARY_BLOB_PREFIX.to_vec();
+    blob.extend(
+        WireFormat::Bincode
+            .serialize(value)
+            .map_err(Error::Serialization)?,
+    );
+    Ok(blob)
+}
+
+fn is_binary_blob(blob: &[u8]) -> bool {
+    blob.starts_with(BINARY_BLOB_PREFIX)
+}
+
+fn deserialize_stored(blob: &[u8]) -> Result<StoredDocument, Error> {
+    if is_binary_blob(blob) {
+        WireFormat::Bincode
+            .deserialize(&blob[BINARY_BLOB_PREFIX.len()..])
+            .map_err(Error::Deserialization)
+    } else {
+        WireFormat::Json
+            .deserialize::<LegacyStoredDocument>(blob)
+            .map(StoredDocument::from)
+            .map_err(Error::Deserialization)
+    }
+}
+
+fn deserialize_chunk(blob: &[u8]) -> Result<EncryptedChunk, Error> {
+    if is_binary_blob(blob) {
+        WireFormat::Bincode.deserialize(&blob[BINARY_BLOB_PREFIX.len()..])
+    } else {
+        WireFormat::Json.deserialize(blob)
+    }
+    .map_err(Error::Deserialization)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StreamedDocument {
+    pub stream_size: u64,
+}
+
 #[derive(Debug)]
 pub enum Error {
     InvalidAccessKeyId(String),
     NoSuchKey(String),
     ResponseNotUtf8(String),
     SignatureDoesNotMatch(String),
-    Serialization(serde_json::Error),
-    Deserialization(serde_json::Error),
+    Serialization(WireError),
+    Deserialization(WireError),
     Unknown(Option<String>),
 }
 
@@ -70,12 +148,47 @@ pub async fn create(
     content_version: u64,
     file_contents: &EncryptedDocument,
 ) -> Result<(), Error> {
+    put_stored(
+        client,
+        file_id,
+        content_version,
+        &StoredDocument::Whole(file_contents.clone()),
+    )
+    .await
+}
+
+pub async fn create_manifest(
+    client: &S3Client,
+    file_id: Uuid,
+    content_version: u64,
+    manifest: &DocumentManifest,
+) -> Result<(), Error> {
+    put_stored(
+        client,
+        file_id,
+        content_version,
+        &StoredDocument::Chunked(manifest.clone()),
+    )
+    .await
+}
+
+async fn put_stored(
+    client: &S3Client,
+    file_id: Uuid,
+    content_version: u64,
+    stored: &StoredDocument,
+) -> Result<(), Error> {
+    put_object(
+        client,
+        &format!("/{}-{}", file_id, content_version),
+        &serialize_blob(stored)?,
+    )
+    .await
+}
+
+async fn put_object(client: &S3Client, path: &str, content: &[u8]) -> Result<(), Error> {
     match client
-        .put_object_with_content_type(
-            &format!("/{}-{}", file_id, content_version),
-            &serde_json::to_vec(file_contents).map_err(Error::Serialization)?,
-            "text/plain",
-        )
+        .put_object_with_content_type(path, content, "application/octet-stream")
         .await
         .map_err(|err| err.to_string())?
     {
@@ -84,9 +197,113 @@ pub async fn create(
     }
 }
 
+pub async fn create_chunk(
+    client: &S3Client,
+    file_id: Uuid,
+    chunk_id: &str,
+    chunk: &EncryptedChunk,
+) -> Result<(), Error> {
+    put_object(
+        client,
+        &chunk_path(file_id, chunk_id),
+        &serialize_blob(chunk)?,
+    )
+    .await
+}
+
+fn chunk_path(file_id: Uuid, chunk_id: &str) -> String {
+    format!("/{}/chunks/{}", file_id, chunk_id)
+}
+
+/// Stores a streamed document version, reading `size` bytes of content from `content`.
+pub async fn create_stream(
+    client: &S3Client,
+    file_id: Uuid,
+    content_version: u64,
+    content: &mut File,
+    size: u64,
+) -> Result<(), Error> {
+    match client
+        .put_object_stream(content, &stream_path(file_id, content_version))
+        .await
+        .map_err(|err| err.to_string())?
+    {
+        200 => {}
+        status => return Err(Error::Unknown(Some(format!("status {}", status)))),
+    }
+
+    put_stored(
+        client,
+        file_id,
+        content_version,
+        &StoredDocument::Streamed(StreamedDocument { stream_size: size }),
+    )
+    .await
+}
+
+/// Writes the content of a streamed document version to `destination`.
+pub async fn get_stream(
+    client: &S3Client,
+    file_id: Uuid,
+    content_version: u64,
+    destination: &mut File,
+) -> Result<(), Error> {
+    match client
+        .get_object_stream(&stream_path(file_id, content_version), destination)
+        .await
+        .map_err(|err| err.to_string())?
+    {
+        200 => Ok(()),
+        status => Err(Error::Unknown(Some(format!("status {}", status)))),
+    }
+}
+
+fn stream_path(file_id: Uuid, content_version: u64) -> String {
+    format!("/{}-{}/stream", file_id, content_version)
+}
+
+/// Deletes a document version. Chunks of a chunked version go with it.
 pub async fn delete(client: &S3Client, file_id: Uuid, content_version: u64) -> Result<(), Error> {
+    delete_keeping_chunks(client, file_id, content_version, &HashSet::new()).await
+}
+
+/// Deletes a document version along with any of its chunks that aren't in `keep`.
+pub async fn delete_keeping_chunks(
+    client: &S3Client,
+    file_id: Uuid,
+    content_version: u64,
+    keep: &HashSet<String>,
+) -> Result<(), Error> {
+    let stored = match get_stored(client, file_id, content_version).await {
+        Ok(stored) => Some(stored),
+        Err(Error::NoSuchKey(_)) => None,
+        Err(err) => return Err(err),
+    };
+
+    match stored {
+        Some(StoredDocument::Chunked(manifest)) => {
+            let unused = manifest
+                .chunks
+                .into_iter()
+                .map(|chunk| chunk.id)
+                .filter(|id| !keep.contains(id))
+                .collect::<HashSet<String>>();
+            for chunk_id in unused {
+                delete_object(client, &chunk_path(file_id, chunk_id)).await?;
+            }
+        }
+        Some(StoredDocument::Streamed(_)) => {
+            delete_object(client, &stream_path(file_id, content_version)).await?
+        }
+        Some(StoredDocument::Whole(_)) | None => {}
+    }
+
+    delete_object(client, &format!("/{}-{}", file_id, content_version)).await
+}
+
+async fn delete_object(client: &S3Client, path: &str) -> Result<(), Error> {
     match client
-        .delete_object(&format!("/{}-{}", file_id, content_version))
+        .delete_object(path)
         .await
         .map_err(|err| err.to_string())?
     {
@@ -100,12 +317,71 @@ pub async fn get(
     file_id: Uuid,
     content_version: u64,
 ) -> Result<EncryptedDocument, Error> {
+    match get_stored(client, file_id, content_version).await? {
+        StoredDocument::Whole(document) => Ok(document),
+        StoredDocument::Chunked(_) | StoredDocument::Streamed(_) => Err(Error::Unknown(Some(
+            format!("{}-{} is not stored whole", file_id, content_version),
+        ))),
+    }
+}
+
+pub async fn get_stored(
+    client: &S3Client,
+    file_id: Uuid,
+    content_version: u64,
+) -> Result<StoredDocument, Error> {
+    deserialize_stored(&get_object(client, &format!("/{}-{}", file_id, content_version)).await?)
+}
+
+pub async fn get_chunk(
+    client: &S3Client,
+    file_id: Uuid,
+    chunk_id: &str,
+) -> Result<EncryptedChunk, Error> {
+    deserialize_chunk(&get_object(client, &chunk_path(file_id, chunk_id)).await?)
+}
+
+async fn get_object(client: &S3Client, path: &str) -> Result<Vec<u8>, Error> {
     match client
-        .get_object(&format!("/{}-{}", file_id, content_version))
+        .get_object(path)
         .await
         .map_err(|err| err.to_string())?
     {
-        (data, 200) => Ok(serde_json::from_slice(&data).map_err(Error::Deserialization)?),
+        (data, 200) => Ok(data),
         (body, _) => Err(Error::from(body)),
     }
 }
+
+/// Rewrites a document version, and its chunks, that were stored as JSON in the binary format.
+/// Returns the number of blobs rewritten. The version must be kept from changing until this
+/// returns, and any of its blobs that are missing are an error.
+pub async fn migrate(client: &S3Client, file_id: Uuid, content_version: u64) -> Result<u64, Error> {
+    let path = format!("/{}-{}", file_id, content_version);
+    let blob = get_object(client, &path).await?;
+    let stored = deserialize_stored(&blob)?;
+    let mut migrated = 0;
+
+    if let StoredDocument::Chunked(manifest) = &stored {
+        let chunk_ids = manifest
+            .chunks
+            .iter()
+            .map(|chunk| chunk.id.as_str())
+            .collect::<HashSet<&str>>();
+        for chunk_id in chunk_ids {
+            let chunk_path = chunk_path(file_id, chunk_id);
+            let chunk_blob = get_object(client, &chunk_path).await?;
+            if !is_binary_blob(&chunk_blob) {
+                let chunk = deserialize_chunk(&chunk_blob)?;
+                put_object(client, &chunk_path, &serialize_blob(&chunk)?).await?;
+                migrated += 1;
+            }
+        }
+    }
+
+    if !is_binary_blob(&blob) {
+        put_object(client, &path, &serialize_blob(&stored)?).await?;
+        migrated += 1;
+    }
+
+    Ok(migrated)
+}
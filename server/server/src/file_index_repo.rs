@@ -4,9 +4,9 @@ use lockbook_models::api::FileUsage;
 use lockbook_models::crypto::{
     EncryptedFolderAccessKey, EncryptedUserAccessKey, SecretFileName, UserAccessInfo,
 };
-use lockbook_models::file_metadata::FileMetadata;
 use lockbook_models::file_metadata::FileType;
-use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use lockbook_models::file_metadata::{FileMetadata, MetadataSignature};
+use sqlx::postgres::{PgConnectOptions, PgListener, PgPoolOptions};
 use sqlx::{ConnectOptions, PgPool, Postgres, Transaction};
 use std::array::IntoIter;
 use std::collections::HashMap;
@@ -14,7 +14,6 @@ use uuid::Uuid;
 
 // TODO:
 // * check ownership
-// * signatures
 // * better serialization
 
 #[derive(Debug)]
@@ -43,9 +42,20 @@ pub async fn connect(config: &IndexDbConfig) -> Result<PgPool, ConnectError> {
         .map_err(ConnectError::Postgres)
 }
 
+/// The channel `create_db.sql`'s trigger notifies whenever a row of `files` is written, with the
+/// owner's public key as it's stored in `accounts`. Notifications are sent when the write commits.
+static METADATA_UPDATES_CHANNEL: &str = "metadata_updates";
+
+pub async fn listen_for_metadata_updates(pool: &PgPool) -> Result<PgListener, sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(METADATA_UPDATES_CHANNEL).await?;
+    Ok(listener)
+}
+
 #[derive(Debug)]
 pub enum ChangeDocumentVersionAndSizeError {
     Postgres(sqlx::Error),
+    Serialize(serde_json::Error),
     Deserialize(serde_json::Error),
     DoesNotExist,
     Deleted,
@@ -57,6 +67,7 @@ pub async fn change_document_version_and_size(
     id: Uuid,
     document_size_bytes: u64,
     old_metadata_version: u64,
+    content_checksum: &Option<[u8; 32]>,
 ) -> Result<(u64, u64), ChangeDocumentVersionAndSizeError> {
     match sqlx::query!(
         r#"
@@ -74,7 +85,11 @@ SET
     document_size = 
         (CASE WHEN NOT old.deleted AND old.metadata_version = $2 AND NOT old.is_folder
         THEN $3
-        ELSE old.document_size END)
+        ELSE old.document_size END),
+    content_checksum =
+        (CASE WHEN NOT old.deleted AND old.metadata_version = $2 AND NOT old.is_folder
+        THEN $4
+        ELSE old.content_checksum END)
 FROM old
 WHERE old.id = new.id
 RETURNING
@@ -89,7 +104,12 @@ RETURNING
             .encode_lower(&mut Uuid::encode_buffer())
             .to_owned(),
         &(old_metadata_version as i64),
-        &(document_size_bytes as i64)
+        &(document_size_bytes as i64),
+        content_checksum
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(ChangeDocumentVersionAndSizeError::Serialize)?
     )
     .fetch_optional(transaction)
     .await
@@ -111,6 +131,137 @@ RETURNING
     }
 }
 
+#[derive(Debug)]
+pub enum CheckDocumentChangeError {
+    Postgres(sqlx::Error),
+    Serialize(serde_json::Error),
+    DoesNotExist,
+    NotPermissioned,
+    Deleted,
+    IncorrectOldVersion,
+}
+
+/// Whether the account with `public_key` could change a document's content from
+/// `old_metadata_version`, for checking a change before its content is received. Nothing is
+/// locked, so the change itself checks the version again.
+pub async fn check_document_change(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    old_metadata_version: u64,
+    public_key: &PublicKey,
+) -> Result<(), CheckDocumentChangeError> {
+    match sqlx::query!(
+        r#"
+SELECT
+    files.deleted,
+    files.metadata_version,
+    accounts.public_key = $2 AS "owned!"
+FROM files
+JOIN accounts ON files.owner = accounts.name
+WHERE files.id = $1 AND NOT files.is_folder;
+        "#,
+        &id.to_simple()
+            .encode_lower(&mut Uuid::encode_buffer())
+            .to_owned(),
+        &serde_json::to_string(public_key).map_err(CheckDocumentChangeError::Serialize)?,
+    )
+    .fetch_optional(transaction)
+    .await
+    .map_err(CheckDocumentChangeError::Postgres)?
+    {
+        None => Err(CheckDocumentChangeError::DoesNotExist),
+        Some(row) => {
+            if !row.owned {
+                Err(CheckDocumentChangeError::NotPermissioned)
+            } else if row.deleted {
+                Err(CheckDocumentChangeError::Deleted)
+            } else if row.metadata_version as u64 != old_metadata_version {
+                Err(CheckDocumentChangeError::IncorrectOldVersion)
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GetDocumentContentVersionError {
+    Postgres(sqlx::Error),
+    DoesNotExist,
+}
+
+pub async fn get_document_content_version(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<u64, GetDocumentContentVersionError> {
+    sqlx::query!(
+        r#"
+SELECT content_version FROM files WHERE id = $1 AND NOT is_folder;
+        "#,
+        &id.to_simple()
+            .encode_lower(&mut Uuid::encode_buffer())
+            .to_owned()
+    )
+    .fetch_optional(transaction)
+    .await
+    .map_err(GetDocumentContentVersionError::Postgres)?
+    .map(|row| row.content_version as u64)
+    .ok_or(GetDocumentContentVersionError::DoesNotExist)
+}
+
+#[derive(Debug)]
+pub enum LockDocumentVersionError {
+    Postgres(sqlx::Error),
+}
+
+/// The content version of a document that hasn't been deleted, locked until `transaction` ends so
+/// that it can't be changed or deleted in the meantime.
+pub async fn lock_document_version(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<Option<u64>, LockDocumentVersionError> {
+    Ok(sqlx::query!(
+        r#"
+SELECT content_version FROM files WHERE id = $1 AND NOT is_folder AND NOT deleted FOR UPDATE;
+        "#,
+        &id.to_simple()
+            .encode_lower(&mut Uuid::encode_buffer())
+            .to_owned()
+    )
+    .fetch_optional(transaction)
+    .await
+    .map_err(LockDocumentVersionError::Postgres)?
+    .map(|row| row.content_version as u64))
+}
+
+#[derive(Debug)]
+pub enum GetDocumentVersionsError {
+    Postgres(sqlx::Error),
+    UuidDeserialize(uuid::Error),
+}
+
+/// The id and content version of every document that hasn't been deleted.
+pub async fn get_document_versions(
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<(Uuid, u64)>, GetDocumentVersionsError> {
+    sqlx::query!(
+        r#"
+SELECT id, content_version FROM files WHERE NOT is_folder AND NOT deleted;
+        "#
+    )
+    .fetch_all(transaction)
+    .await
+    .map_err(GetDocumentVersionsError::Postgres)?
+    .into_iter()
+    .map(|row| {
+        Ok((
+            Uuid::parse_str(&row.id).map_err(GetDocumentVersionsError::UuidDeserialize)?,
+            row.content_version as u64,
+        ))
+    })
+    .collect()
+}
+
 #[derive(Debug)]
 pub enum CreateFileError {
     Postgres(sqlx::Error),
@@ -131,6 +282,8 @@ pub async fn create_file(
     public_key: &PublicKey,
     access_key: &EncryptedFolderAccessKey,
     maybe_document_bytes: Option<u64>,
+    signature: &Option<MetadataSignature>,
+    content_checksum: &Option<[u8; 32]>,
 ) -> Result<u64, CreateFileError> {
     match sqlx::query!(
         r#"
@@ -153,7 +306,9 @@ WITH RECURSIVE file_ancestors AS (
             deleted,
             metadata_version,
             content_version,
-            document_size
+            document_size,
+            signature,
+            content_checksum
         )
         SELECT
             $1,
@@ -170,7 +325,9 @@ WITH RECURSIVE file_ancestors AS (
             FALSE,
             CAST(EXTRACT(EPOCH FROM NOW()) * 1000 AS BIGINT),
             CAST(EXTRACT(EPOCH FROM NOW()) * 1000 AS BIGINT),
-            $8
+            $8,
+            $9,
+            $10
         WHERE NOT EXISTS(SELECT * FROM file_ancestors WHERE deleted)
         RETURNING NULL
     )
@@ -190,7 +347,17 @@ SELECT
         &serde_json::to_string(&name.encrypted_value).map_err(CreateFileError::Serialize)?,
         &serde_json::to_string(&name.hmac).map_err(CreateFileError::Serialize)?,
         &serde_json::to_string(public_key).map_err(CreateFileError::Serialize)?,
-        (maybe_document_bytes.map(|bytes_u64| bytes_u64 as i64))
+        (maybe_document_bytes.map(|bytes_u64| bytes_u64 as i64)),
+        signature
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(CreateFileError::Serialize)?,
+        content_checksum
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(CreateFileError::Serialize)?
     )
     .fetch_one(transaction)
     .await
@@ -235,6 +402,7 @@ pub enum DeleteFileError {
 pub async fn delete_file(
     transaction: &mut Transaction<'_, Postgres>,
     id: Uuid,
+    signature: &Option<MetadataSignature>,
 ) -> Result<Vec<FileDeleteResponse>, DeleteFileError> {
     match sqlx::query!(
         r#"
@@ -269,7 +437,14 @@ SET
             NOT old.deleted AND
             old.id != old.parent
         THEN CAST(EXTRACT(EPOCH FROM NOW()) * 1000 AS BIGINT)
-        ELSE old.metadata_version END)
+        ELSE old.metadata_version END),
+    signature =
+        (CASE WHEN
+            NOT old.deleted AND
+            old.id != old.parent AND
+            old.id = $1
+        THEN $2
+        ELSE old.signature END)
 FROM old
 WHERE old.id = new.id
 RETURNING
@@ -282,7 +457,12 @@ RETURNING
         "#,
         &id.to_simple()
             .encode_lower(&mut Uuid::encode_buffer())
-            .to_owned()
+            .to_owned(),
+        signature
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(DeleteFileError::Serialize)?
     )
     .fetch_all(transaction)
     .await
@@ -336,6 +516,7 @@ pub async fn move_file(
     old_metadata_version: u64,
     parent: Uuid,
     access_key: EncryptedFolderAccessKey,
+    signature: &Option<MetadataSignature>,
 ) -> Result<u64, MoveFileError> {
     match sqlx::query!(
         r#"
@@ -378,7 +559,16 @@ SET
             AND NOT EXISTS(SELECT * FROM file_descendants WHERE id = $3)
             AND EXISTS(SELECT * FROM parent WHERE NOT deleted)
         THEN $4
-        ELSE old.parent_access_key END)
+        ELSE old.parent_access_key END),
+    signature =
+        (CASE WHEN
+            NOT old.deleted
+            AND old.id != old.parent
+            AND old.metadata_version = $2
+            AND NOT EXISTS(SELECT * FROM file_descendants WHERE id = $3)
+            AND EXISTS(SELECT * FROM parent WHERE NOT deleted)
+        THEN $5
+        ELSE old.signature END)
 FROM old
 LEFT JOIN parent ON TRUE
 WHERE old.id = new.id
@@ -396,6 +586,7 @@ RETURNING
         &(old_metadata_version as i64),
         &parent.to_simple().encode_lower(&mut Uuid::encode_buffer()).to_owned(),
         &serde_json::to_string(&access_key).map_err(MoveFileError::Serialize)?,
+        signature.as_ref().map(serde_json::to_string).transpose().map_err(MoveFileError::Serialize)?,
     )
         .fetch_optional(transaction)
         .await
@@ -443,6 +634,7 @@ pub async fn rename_file(
     old_metadata_version: u64,
     file_type: FileType,
     name: &SecretFileName,
+    signature: &Option<MetadataSignature>,
 ) -> Result<u64, RenameFileError> {
     match sqlx::query!(
         r#"
@@ -469,7 +661,14 @@ SET
         AND old.is_folder = $3
         AND old.id != old.parent
         THEN CAST(EXTRACT(EPOCH FROM NOW()) * 1000 AS BIGINT)
-        ELSE old.metadata_version END)
+        ELSE old.metadata_version END),
+    signature =
+        (CASE WHEN NOT old.deleted
+        AND old.metadata_version = $2
+        AND old.is_folder = $3
+        AND old.id != old.parent
+        THEN $6
+        ELSE old.signature END)
 FROM old
 WHERE old.id = new.id
 RETURNING
@@ -487,6 +686,11 @@ RETURNING
         &(file_type == FileType::Folder),
         &serde_json::to_string(&name.encrypted_value).map_err(RenameFileError::Serialize)?,
         &serde_json::to_string(&name.hmac).map_err(RenameFileError::Serialize)?,
+        signature
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(RenameFileError::Serialize)?,
     )
     .fetch_optional(transaction)
     .await
@@ -605,6 +809,10 @@ WHERE
         },
         folder_access_keys: serde_json::from_str(&row.parent_access_key)
         .map_err(GetFilesError::Deserialize)?,
+        signature: row.signature.as_deref().map(serde_json::from_str).transpose()
+        .map_err(GetFilesError::Deserialize)?,
+        content_checksum: row.content_checksum.as_deref().map(serde_json::from_str).transpose()
+        .map_err(GetFilesError::Deserialize)?,
     }))
     .collect()
 }
@@ -677,10 +885,39 @@ WHERE
         },
         folder_access_keys: serde_json::from_str(&row.parent_access_key)
         .map_err(GetUpdatesError::Deserialize)?,
+        signature: row.signature.as_deref().map(serde_json::from_str).transpose()
+        .map_err(GetUpdatesError::Deserialize)?,
+        content_checksum: row.content_checksum.as_deref().map(serde_json::from_str).transpose()
+        .map_err(GetUpdatesError::Deserialize)?,
     }))
     .collect()
 }
 
+#[derive(Debug)]
+pub enum GetMostRecentMetadataVersionError {
+    Postgres(sqlx::Error),
+    Serialize(serde_json::Error),
+}
+
+pub async fn get_most_recent_metadata_version(
+    transaction: &mut Transaction<'_, Postgres>,
+    public_key: &PublicKey,
+) -> Result<u64, GetMostRecentMetadataVersionError> {
+    sqlx::query!(
+        r#"
+SELECT COALESCE(MAX(files.metadata_version), 0) AS "metadata_version!"
+FROM files
+JOIN accounts ON files.owner = accounts.name
+WHERE accounts.public_key = $1;
+        "#,
+        &serde_json::to_string(public_key).map_err(GetMostRecentMetadataVersionError::Serialize)?
+    )
+    .fetch_one(transaction)
+    .await
+    .map(|row| row.metadata_version as u64)
+    .map_err(GetMostRecentMetadataVersionError::Postgres)
+}
+
 #[derive(Debug)]
 pub enum GetRootError {
     Postgres(sqlx::Error),
@@ -749,6 +986,18 @@ WHERE
         },
         folder_access_keys: serde_json::from_str(&row.parent_access_key)
             .map_err(GetRootError::Deserialize)?,
+        signature: row
+            .signature
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(GetRootError::Deserialize)?,
+        content_checksum: row
+            .content_checksum
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(GetRootError::Deserialize)?,
     })
 }
 
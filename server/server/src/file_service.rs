@@ -1,11 +1,28 @@
+use crate::file_content_client::StoredDocument;
 use crate::file_index_repo;
 use crate::file_index_repo::{
-    ChangeDocumentVersionAndSizeError, CreateFileError, DeleteFileError, MoveFileError,
-    RenameFileError,
+    ChangeDocumentVersionAndSizeError, CheckDocumentChangeError, CreateFileError, DeleteFileError,
+    GetDocumentContentVersionError, MoveFileError, RenameFileError,
 };
-use crate::{file_content_client, RequestContext};
+use crate::{file_content_client, RequestContext, ServerState};
+use hyper::body::{Bytes, HttpBody};
+use hyper::Body;
+use lockbook_crypto::clock_service;
 use lockbook_models::api::*;
-use lockbook_models::file_metadata::FileType;
+use lockbook_models::crypto::{ChunkRef, DocumentManifest};
+use lockbook_models::file_metadata::{FileType, MetadataSignature};
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{self, Duration, Instant};
+use uuid::Uuid;
+
+static LISTEN_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
 
 pub async fn change_document_content(
     context: &mut RequestContext<'_, ChangeDocumentContentRequest>,
@@ -24,6 +41,7 @@ pub async fn change_document_content(
         request.id,
         request.new_content.value.len() as u64,
         request.old_metadata_version,
+        &request.content_checksum,
     )
     .await;
 
@@ -38,6 +56,7 @@ pub async fn change_document_content(
             Ok(ChangeDocumentContentError::DocumentDeleted)
         }
         ChangeDocumentVersionAndSizeError::Postgres(_)
+        | ChangeDocumentVersionAndSizeError::Serialize(_)
         | ChangeDocumentVersionAndSizeError::Deserialize(_) => Err(format!(
             "Cannot change document content version in Postgres: {:?}",
             e
@@ -79,9 +98,179 @@ pub async fn change_document_content(
     }
 }
 
+pub async fn change_document_content_chunks(
+    context: &mut RequestContext<'_, ChangeDocumentContentChunksRequest>,
+) -> Result<ChangeDocumentContentResponse, Result<ChangeDocumentContentChunksError, String>> {
+    let request = &context.request;
+    let server_state = &mut context.server_state;
+    let mut transaction = match server_state.index_db_client.begin().await {
+        Ok(t) => t,
+        Err(e) => {
+            return Err(Err(format!("Cannot begin transaction: {:?}", e)));
+        }
+    };
+
+    let old_metadata_result =
+        file_index_repo::get_document_content_version(&mut transaction, request.id).await;
+    let old_content_version = old_metadata_result.map_err(|e| match e {
+        GetDocumentContentVersionError::DoesNotExist => {
+            Ok(ChangeDocumentContentChunksError::DocumentNotFound)
+        }
+        GetDocumentContentVersionError::Postgres(_) => Err(format!(
+            "Cannot get document content version from Postgres: {:?}",
+            e
+        )),
+    })?;
+
+    // Chunks the server already holds for this document are the ones in its current version
+    let old_chunk_sizes: HashMap<String, u64> = match file_content_client::get_stored(
+        &server_state.files_db_client,
+        request.id,
+        old_content_version,
+    )
+    .await
+    {
+        Ok(StoredDocument::Chunked(manifest)) => manifest
+            .chunks
+            .into_iter()
+            .map(|chunk| (chunk.id, chunk.size))
+            .collect(),
+        Ok(StoredDocument::Whole(_)) | Ok(StoredDocument::Streamed(_)) => HashMap::new(),
+        Err(e) => return Err(Err(format!("Cannot get file from S3: {:?}", e))),
+    };
+
+    let mut manifest = DocumentManifest { chunks: vec![] };
+    let mut missing = vec![];
+    for chunk_id in &request.chunks {
+        let size = match request.new_chunks.get(chunk_id) {
+            Some(chunk) => chunk.value.len() as u64,
+            None => match old_chunk_sizes.get(chunk_id) {
+                Some(size) => *size,
+                None => {
+                    missing.push(chunk_id.clone());
+                    continue;
+                }
+            },
+        };
+        manifest.chunks.push(ChunkRef {
+            id: chunk_id.clone(),
+            size,
+        });
+    }
+    if !missing.is_empty() {
+        missing.dedup();
+        return Err(Ok(ChangeDocumentContentChunksError::ChunksMissing(missing)));
+    }
+
+    let result = file_index_repo::change_document_version_and_size(
+        &mut transaction,
+        request.id,
+        manifest.chunks.iter().map(|chunk| chunk.size).sum(),
+        request.old_metadata_version,
+        &request.content_checksum,
+    )
+    .await;
+
+    let (changed_content_version, new_version) = result.map_err(|e| match e {
+        ChangeDocumentVersionAndSizeError::DoesNotExist => {
+            Ok(ChangeDocumentContentChunksError::DocumentNotFound)
+        }
+        ChangeDocumentVersionAndSizeError::IncorrectOldVersion => {
+            Ok(ChangeDocumentContentChunksError::EditConflict)
+        }
+        ChangeDocumentVersionAndSizeError::Deleted => {
+            Ok(ChangeDocumentContentChunksError::DocumentDeleted)
+        }
+        ChangeDocumentVersionAndSizeError::Postgres(_)
+        | ChangeDocumentVersionAndSizeError::Serialize(_)
+        | ChangeDocumentVersionAndSizeError::Deserialize(_) => Err(format!(
+            "Cannot change document content version in Postgres: {:?}",
+            e
+        )),
+    })?;
+    if changed_content_version != old_content_version {
+        return Err(Ok(ChangeDocumentContentChunksError::EditConflict));
+    }
+
+    let new_chunk_ids: HashSet<String> = request.chunks.iter().cloned().collect();
+    for (chunk_id, chunk) in &request.new_chunks {
+        if !new_chunk_ids.contains(chunk_id) {
+            continue;
+        }
+        let create_result = file_content_client::create_chunk(
+            &server_state.files_db_client,
+            request.id,
+            chunk_id,
+            chunk,
+        )
+        .await;
+        if create_result.is_err() {
+            return Err(Err(format!(
+                "Cannot create chunk in S3: {:?}",
+                create_result
+            )));
+        };
+    }
+
+    let create_result = file_content_client::create_manifest(
+        &server_state.files_db_client,
+        request.id,
+        new_version,
+        &manifest,
+    )
+    .await;
+    if create_result.is_err() {
+        return Err(Err(format!(
+            "Cannot create file in S3: {:?}",
+            create_result
+        )));
+    };
+
+    let delete_result = file_content_client::delete_keeping_chunks(
+        &server_state.files_db_client,
+        request.id,
+        old_content_version,
+        &new_chunk_ids,
+    )
+    .await;
+    if delete_result.is_err() {
+        return Err(Err(format!(
+            "Cannot delete file in S3: {:?}",
+            delete_result
+        )));
+    };
+
+    match transaction.commit().await {
+        Ok(()) => Ok(ChangeDocumentContentResponse {
+            new_metadata_and_content_version: new_version,
+        }),
+        Err(e) => Err(Err(format!("Cannot commit transaction: {:?}", e))),
+    }
+}
+
+/// Whether a metadata signature sent along with a file change was made just now by the account
+/// making the request. Most signed fields are encrypted, so the server stores the signature as-is
+/// and leaves checking it against the metadata to clients.
+fn signature_permitted<TRequest>(
+    context: &RequestContext<'_, TRequest>,
+    signature: &Option<MetadataSignature>,
+) -> bool {
+    match signature {
+        None => true,
+        Some(signature) => {
+            let max_delay = context.server_state.config.server.max_auth_delay as i64;
+            signature.public_key == context.public_key
+                && (clock_service::get_time().0 - signature.timestamp).abs() <= max_delay
+        }
+    }
+}
+
 pub async fn create_document(
     context: &mut RequestContext<'_, CreateDocumentRequest>,
 ) -> Result<CreateDocumentResponse, Result<CreateDocumentError, String>> {
+    if !signature_permitted(context, &context.request.signature) {
+        return Err(Ok(CreateDocumentError::NotPermissioned));
+    }
     let request = &context.request;
     let server_state = &mut context.server_state;
     let mut transaction = match server_state.index_db_client.begin().await {
@@ -100,6 +289,8 @@ pub async fn create_document(
         &context.public_key,
         &request.parent_access_key,
         Some(request.content.value.len() as u64),
+        &request.signature,
+        &request.content_checksum,
     )
     .await;
     let new_version = index_result.map_err(|e| match e {
@@ -136,6 +327,9 @@ pub async fn create_document(
 pub async fn delete_document(
     context: &mut RequestContext<'_, DeleteDocumentRequest>,
 ) -> Result<DeleteDocumentResponse, Result<DeleteDocumentError, String>> {
+    if !signature_permitted(context, &context.request.signature) {
+        return Err(Ok(DeleteDocumentError::NotPermissioned));
+    }
     let request = &context.request;
     let server_state = &mut context.server_state;
     let mut transaction = match server_state.index_db_client.begin().await {
@@ -145,7 +339,8 @@ pub async fn delete_document(
         }
     };
 
-    let index_result = file_index_repo::delete_file(&mut transaction, request.id).await;
+    let index_result =
+        file_index_repo::delete_file(&mut transaction, request.id, &request.signature).await;
     let index_responses = index_result.map_err(|e| match e {
         DeleteFileError::DoesNotExist => Ok(DeleteDocumentError::DocumentNotFound),
         DeleteFileError::Deleted => Ok(DeleteDocumentError::DocumentDeleted),
@@ -187,6 +382,9 @@ pub async fn delete_document(
 pub async fn move_document(
     context: &mut RequestContext<'_, MoveDocumentRequest>,
 ) -> Result<MoveDocumentResponse, Result<MoveDocumentError, String>> {
+    if !signature_permitted(context, &context.request.signature) {
+        return Err(Ok(MoveDocumentError::NotPermissioned));
+    }
     let request = &context.request;
     let server_state = &mut context.server_state;
     let mut transaction = match server_state.index_db_client.begin().await {
@@ -202,6 +400,7 @@ pub async fn move_document(
         request.old_metadata_version,
         request.new_parent,
         request.new_folder_access.clone(),
+        &request.signature,
     )
     .await;
     let new_version = result.map_err(|e| match e {
@@ -228,6 +427,9 @@ pub async fn move_document(
 pub async fn rename_document(
     context: &mut RequestContext<'_, RenameDocumentRequest>,
 ) -> Result<RenameDocumentResponse, Result<RenameDocumentError, String>> {
+    if !signature_permitted(context, &context.request.signature) {
+        return Err(Ok(RenameDocumentError::NotPermissioned));
+    }
     let request = &context.request;
     let server_state = &mut context.server_state;
     let mut transaction = match server_state.index_db_client.begin().await {
@@ -243,6 +445,7 @@ pub async fn rename_document(
         request.old_metadata_version,
         FileType::Document,
         &request.new_name,
+        &request.signature,
     )
     .await;
     let new_version = result.map_err(|e| match e {
@@ -270,14 +473,16 @@ pub async fn get_document(
 ) -> Result<GetDocumentResponse, Result<GetDocumentError, String>> {
     let request = &context.request;
     let server_state = &mut context.server_state;
-    let files_result = file_content_client::get(
+    let files_result = file_content_client::get_stored(
         &server_state.files_db_client,
         request.id,
         request.content_version,
     )
     .await;
     match files_result {
-        Ok(c) => Ok(GetDocumentResponse { content: c }),
+        Ok(StoredDocument::Whole(c)) => Ok(GetDocumentResponse { content: c }),
+        Ok(StoredDocument::Chunked(_)) => Err(Ok(GetDocumentError::DocumentIsChunked)),
+        Ok(StoredDocument::Streamed(_)) => Err(Ok(GetDocumentError::DocumentIsStreamed)),
         Err(file_content_client::Error::NoSuchKey(_)) => {
             Err(Ok(GetDocumentError::DocumentNotFound))
         }
@@ -285,9 +490,288 @@ pub async fn get_document(
     }
 }
 
+pub async fn get_document_chunks(
+    context: &mut RequestContext<'_, GetDocumentChunksRequest>,
+) -> Result<GetDocumentChunksResponse, Result<GetDocumentChunksError, String>> {
+    let request = &context.request;
+    let server_state = &mut context.server_state;
+    let files_result = file_content_client::get_stored(
+        &server_state.files_db_client,
+        request.id,
+        request.content_version,
+    )
+    .await;
+    let manifest = match files_result {
+        Ok(StoredDocument::Whole(c)) => {
+            return Ok(GetDocumentChunksResponse {
+                content: DocumentContent::Whole(c),
+            })
+        }
+        Ok(StoredDocument::Chunked(manifest)) => manifest,
+        Ok(StoredDocument::Streamed(streamed)) => {
+            return Ok(GetDocumentChunksResponse {
+                content: DocumentContent::Streamed {
+                    size: streamed.stream_size,
+                },
+            })
+        }
+        Err(file_content_client::Error::NoSuchKey(_)) => {
+            return Err(Ok(GetDocumentChunksError::DocumentNotFound))
+        }
+        Err(e) => return Err(Err(format!("Cannot get file from S3: {:?}", e))),
+    };
+
+    let known_chunks: HashSet<&String> = request.known_chunks.iter().collect();
+    let mut chunks = HashMap::new();
+    for chunk in &manifest.chunks {
+        if known_chunks.contains(&chunk.id) || chunks.contains_key(&chunk.id) {
+            continue;
+        }
+        match file_content_client::get_chunk(&server_state.files_db_client, request.id, &chunk.id)
+            .await
+        {
+            Ok(content) => {
+                chunks.insert(chunk.id.clone(), content);
+            }
+            Err(e) => return Err(Err(format!("Cannot get chunk from S3: {:?}", e))),
+        }
+    }
+
+    Ok(GetDocumentChunksResponse {
+        content: DocumentContent::Chunked { manifest, chunks },
+    })
+}
+
+/// Streamed content passes through a temporary file so that it's never held in memory.
+fn spool_path() -> PathBuf {
+    env::temp_dir().join(format!("lockbook-stream-{}", Uuid::new_v4()))
+}
+
+async fn spool_body(mut body: Body, path: &Path) -> Result<u64, String> {
+    let mut file = File::create(path)
+        .await
+        .map_err(|e| format!("Cannot create spool file: {:?}", e))?;
+    let mut size = 0;
+    while let Some(data) = body.data().await {
+        let data = data.map_err(|e| format!("Cannot read request body: {:?}", e))?;
+        file.write_all(&data)
+            .await
+            .map_err(|e| format!("Cannot write spool file: {:?}", e))?;
+        size += data.len() as u64;
+    }
+    Ok(size)
+}
+
+pub async fn change_document_content_stream(
+    context: &mut RequestContext<'_, ChangeDocumentContentStreamRequest>,
+    body: Body,
+) -> Result<ChangeDocumentContentResponse, Result<ChangeDocumentContentStreamError, String>> {
+    let path = spool_path();
+    let result = change_document_content_from_spool(context, body, &path).await;
+    let _ = fs::remove_file(&path).await;
+    result
+}
+
+async fn change_document_content_from_spool(
+    context: &mut RequestContext<'_, ChangeDocumentContentStreamRequest>,
+    body: Body,
+    path: &Path,
+) -> Result<ChangeDocumentContentResponse, Result<ChangeDocumentContentStreamError, String>> {
+    let request = &context.request;
+    let server_state = &mut context.server_state;
+
+    // Checked before the content is received, so no one can spool uploads to documents they
+    // can't change
+    let mut check = match server_state.index_db_client.begin().await {
+        Ok(t) => t,
+        Err(e) => {
+            return Err(Err(format!("Cannot begin transaction: {:?}", e)));
+        }
+    };
+    let check_result = file_index_repo::check_document_change(
+        &mut check,
+        request.id,
+        request.old_metadata_version,
+        &context.public_key,
+    )
+    .await;
+    check_result.map_err(|e| match e {
+        CheckDocumentChangeError::DoesNotExist => {
+            Ok(ChangeDocumentContentStreamError::DocumentNotFound)
+        }
+        CheckDocumentChangeError::NotPermissioned => {
+            Ok(ChangeDocumentContentStreamError::NotPermissioned)
+        }
+        CheckDocumentChangeError::Deleted => Ok(ChangeDocumentContentStreamError::DocumentDeleted),
+        CheckDocumentChangeError::IncorrectOldVersion => {
+            Ok(ChangeDocumentContentStreamError::EditConflict)
+        }
+        CheckDocumentChangeError::Postgres(_) | CheckDocumentChangeError::Serialize(_) => {
+            Err(format!("Cannot check document in Postgres: {:?}", e))
+        }
+    })?;
+    drop(check);
+
+    if spool_body(body, path).await.map_err(Err)? != request.size {
+        return Err(Ok(ChangeDocumentContentStreamError::SizeMismatch));
+    }
+
+    let mut transaction = match server_state.index_db_client.begin().await {
+        Ok(t) => t,
+        Err(e) => {
+            return Err(Err(format!("Cannot begin transaction: {:?}", e)));
+        }
+    };
+
+    let result = file_index_repo::change_document_version_and_size(
+        &mut transaction,
+        request.id,
+        request.size,
+        request.old_metadata_version,
+        &request.content_checksum,
+    )
+    .await;
+
+    let (old_content_version, new_version) = result.map_err(|e| match e {
+        ChangeDocumentVersionAndSizeError::DoesNotExist => {
+            Ok(ChangeDocumentContentStreamError::DocumentNotFound)
+        }
+        ChangeDocumentVersionAndSizeError::IncorrectOldVersion => {
+            Ok(ChangeDocumentContentStreamError::EditConflict)
+        }
+        ChangeDocumentVersionAndSizeError::Deleted => {
+            Ok(ChangeDocumentContentStreamError::DocumentDeleted)
+        }
+        ChangeDocumentVersionAndSizeError::Postgres(_)
+        | ChangeDocumentVersionAndSizeError::Serialize(_)
+        | ChangeDocumentVersionAndSizeError::Deserialize(_) => Err(format!(
+            "Cannot change document content version in Postgres: {:?}",
+            e
+        )),
+    })?;
+
+    let mut content = File::open(path)
+        .await
+        .map_err(|e| Err(format!("Cannot open spool file: {:?}", e)))?;
+    let create_result = file_content_client::create_stream(
+        &server_state.files_db_client,
+        request.id,
+        new_version,
+        &mut content,
+        request.size,
+    )
+    .await;
+    if create_result.is_err() {
+        return Err(Err(format!(
+            "Cannot create file in S3: {:?}",
+            create_result
+        )));
+    };
+
+    let delete_result = file_content_client::delete(
+        &server_state.files_db_client,
+        request.id,
+        old_content_version,
+    )
+    .await;
+    if delete_result.is_err() {
+        return Err(Err(format!(
+            "Cannot delete file in S3: {:?}",
+            delete_result
+        )));
+    };
+
+    match transaction.commit().await {
+        Ok(()) => Ok(ChangeDocumentContentResponse {
+            new_metadata_and_content_version: new_version,
+        }),
+        Err(e) => Err(Err(format!("Cannot commit transaction: {:?}", e))),
+    }
+}
+
+/// Returns the size of a streamed document version along with a body that streams its content.
+pub async fn get_document_stream(
+    context: &mut RequestContext<'_, GetDocumentStreamRequest>,
+) -> Result<(GetDocumentStreamResponse, Body), Result<GetDocumentStreamError, String>> {
+    let request = &context.request;
+    let server_state = &mut context.server_state;
+    let files_result = file_content_client::get_stored(
+        &server_state.files_db_client,
+        request.id,
+        request.content_version,
+    )
+    .await;
+    let size = match files_result {
+        Ok(StoredDocument::Streamed(streamed)) => streamed.stream_size,
+        Ok(StoredDocument::Whole(_)) | Ok(StoredDocument::Chunked(_)) => {
+            return Err(Ok(GetDocumentStreamError::DocumentIsNotStreamed))
+        }
+        Err(file_content_client::Error::NoSuchKey(_)) => {
+            return Err(Ok(GetDocumentStreamError::DocumentNotFound))
+        }
+        Err(e) => return Err(Err(format!("Cannot get file from S3: {:?}", e))),
+    };
+
+    let path = spool_path();
+    let spool_result = async {
+        let mut file = File::create(&path)
+            .await
+            .map_err(|e| format!("Cannot create spool file: {:?}", e))?;
+        file_content_client::get_stream(
+            &server_state.files_db_client,
+            request.id,
+            request.content_version,
+            &mut file,
+        )
+        .await
+        .map_err(|e| format!("Cannot get file from S3: {:?}", e))?;
+        File::open(&path)
+            .await
+            .map_err(|e| format!("Cannot open spool file: {:?}", e))
+    }
+    .await;
+    let mut file = match spool_result {
+        Ok(file) => file,
+        Err(e) => {
+            let _ = fs::remove_file(&path).await;
+            return Err(Err(e));
+        }
+    };
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut buffer = vec![0u8; STREAM_BUFFER_SIZE];
+        loop {
+            match file.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(read) => {
+                    if sender
+                        .send_data(Bytes::copy_from_slice(&buffer[..read]))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    log::error!("Cannot read spool file: {:?}", e);
+                    sender.abort();
+                    break;
+                }
+            }
+        }
+        let _ = fs::remove_file(&path).await;
+    });
+
+    Ok((GetDocumentStreamResponse { size }, body))
+}
+
 pub async fn create_folder(
     context: &mut RequestContext<'_, CreateFolderRequest>,
 ) -> Result<CreateFolderResponse, Result<CreateFolderError, String>> {
+    if !signature_permitted(context, &context.request.signature) {
+        return Err(Ok(CreateFolderError::NotPermissioned));
+    }
     let request = &context.request;
     let server_state = &mut context.server_state;
     let mut transaction = match server_state.index_db_client.begin().await {
@@ -306,6 +790,8 @@ pub async fn create_folder(
         &context.public_key,
         &request.parent_access_key,
         None,
+        &request.signature,
+        &None,
     )
     .await;
     let new_version = result.map_err(|e| match e {
@@ -330,6 +816,9 @@ pub async fn create_folder(
 pub async fn delete_folder(
     context: &mut RequestContext<'_, DeleteFolderRequest>,
 ) -> Result<DeleteFolderResponse, Result<DeleteFolderError, String>> {
+    if !signature_permitted(context, &context.request.signature) {
+        return Err(Ok(DeleteFolderError::NotPermissioned));
+    }
     let request = &context.request;
     let server_state = &mut context.server_state;
     let mut transaction = match server_state.index_db_client.begin().await {
@@ -339,7 +828,8 @@ pub async fn delete_folder(
         }
     };
 
-    let index_result = file_index_repo::delete_file(&mut transaction, request.id).await;
+    let index_result =
+        file_index_repo::delete_file(&mut transaction, request.id, &request.signature).await;
     let index_responses = index_result.map_err(|e| match e {
         DeleteFileError::DoesNotExist => Ok(DeleteFolderError::FolderNotFound),
         DeleteFileError::Deleted => Ok(DeleteFolderError::FolderDeleted),
@@ -386,6 +876,9 @@ pub async fn delete_folder(
 pub async fn move_folder(
     context: &mut RequestContext<'_, MoveFolderRequest>,
 ) -> Result<MoveFolderResponse, Result<MoveFolderError, String>> {
+    if !signature_permitted(context, &context.request.signature) {
+        return Err(Ok(MoveFolderError::NotPermissioned));
+    }
     let request = &context.request;
     let server_state = &mut context.server_state;
     let mut transaction = match server_state.index_db_client.begin().await {
@@ -401,6 +894,7 @@ pub async fn move_folder(
         request.old_metadata_version,
         request.new_parent,
         request.new_folder_access.clone(),
+        &request.signature,
     )
     .await;
     let new_version = result.map_err(|e| match e {
@@ -428,6 +922,9 @@ pub async fn move_folder(
 pub async fn rename_folder(
     context: &mut RequestContext<'_, RenameFolderRequest>,
 ) -> Result<RenameFolderResponse, Result<RenameFolderError, String>> {
+    if !signature_permitted(context, &context.request.signature) {
+        return Err(Ok(RenameFolderError::NotPermissioned));
+    }
     let request = &context.request;
     let server_state = &mut context.server_state;
     let mut transaction = match server_state.index_db_client.begin().await {
@@ -443,6 +940,7 @@ pub async fn rename_folder(
         request.old_metadata_version,
         FileType::Folder,
         &request.new_name,
+        &request.signature,
     )
     .await;
     let new_version = result.map_err(|e| match e {
@@ -490,3 +988,96 @@ pub async fn get_updates(
         Err(e) => Err(Err(format!("Cannot commit transaction: {:?}", e))),
     }
 }
+
+pub async fn wait_for_updates(
+    context: &mut RequestContext<'_, WaitForUpdatesRequest>,
+) -> Result<WaitForUpdatesResponse, Result<WaitForUpdatesError, String>> {
+    let request = &context.request;
+    let server_state = &mut context.server_state;
+    let deadline = Instant::now()
+        + Duration::from_millis(cmp::min(
+            request.timeout_ms,
+            server_state.config.server.max_long_poll_ms,
+        ));
+    let public_key = serde_json::to_string(&context.public_key)
+        .map_err(|e| Err(format!("Cannot serialize public key: {:?}", e)))?;
+
+    // Subscribed before checking, so that changes committed after the check aren't missed
+    let mut updates = server_state.metadata_updates.subscribe();
+    loop {
+        let mut transaction = match server_state.index_db_client.begin().await {
+            Ok(t) => t,
+            Err(e) => {
+                return Err(Err(format!("Cannot begin transaction: {:?}", e)));
+            }
+        };
+        let result = file_index_repo::get_most_recent_metadata_version(
+            &mut transaction,
+            &context.public_key,
+        )
+        .await;
+        let most_recent_metadata_version = result.map_err(|e| {
+            Err(format!(
+                "Cannot get most recent metadata version from Postgres: {:?}",
+                e
+            ))
+        })?;
+        if let Err(e) = transaction.commit().await {
+            return Err(Err(format!("Cannot commit transaction: {:?}", e)));
+        }
+
+        let response = WaitForUpdatesResponse {
+            most_recent_metadata_version,
+        };
+        if most_recent_metadata_version > request.since_metadata_version {
+            return Ok(response);
+        }
+
+        let updated = async {
+            loop {
+                match updates.recv().await {
+                    Ok(Some(updated)) if updated != public_key => continue,
+                    Err(RecvError::Closed) => time::sleep_until(deadline).await,
+                    _ => return,
+                }
+            }
+        };
+        if time::timeout_at(deadline, updated).await.is_err() {
+            return Ok(response);
+        }
+    }
+}
+
+/// Sends the public keys Postgres notifies of when files change to `server_state.metadata_updates`,
+/// waking the long polls of their owners. Postgres notifies whichever server instance made the
+/// change, so this runs on every instance for as long as it's up.
+pub async fn forward_metadata_updates(server_state: &ServerState) {
+    loop {
+        let mut listener =
+            match file_index_repo::listen_for_metadata_updates(&server_state.index_db_client).await
+            {
+                Ok(listener) => listener,
+                Err(e) => {
+                    log::error!("Cannot listen for metadata updates: {:?}", e);
+                    time::sleep(LISTEN_RETRY_INTERVAL).await;
+                    continue;
+                }
+            };
+
+        loop {
+            // `None` means the connection was lost, and notifications with it, so every long
+            // poll checks again. The listener reconnects on the next call.
+            let update = match listener.try_recv().await {
+                Ok(notification) => notification.map(|n| n.payload().to_string()),
+                Err(e) => {
+                    log::error!("Cannot receive metadata updates: {:?}", e);
+                    let _ = server_state.metadata_updates.send(None);
+                    time::sleep(LISTEN_RETRY_INTERVAL).await;
+                    break;
+                }
+            };
+            // Fails only when no long polls are waiting
+            let _ = server_state.metadata_updates.send(update);
+        }
+    }
+}
@@ -9,11 +9,15 @@ pub mod utils;
 extern crate log;
 
 use libsecp256k1::PublicKey;
+use tokio::sync::broadcast;
 
 pub struct ServerState {
     pub config: config::Config,
     pub index_db_client: sqlx::PgPool,
     pub files_db_client: s3::bucket::Bucket,
+    /// Public keys of users whose files changed, see `file_service::forward_metadata_updates`.
+    /// `None` when changes may have been missed.
+    pub metadata_updates: broadcast::Sender<Option<String>>,
 }
 
 pub struct RequestContext<'a, TRequest> {
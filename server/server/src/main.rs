@@ -6,6 +6,7 @@ extern crate tokio;
 extern crate log;
 
 use hyper::body::Bytes;
+use hyper::header::{HeaderName, ACCEPT, CONTENT_TYPE};
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{body, Body, Response, StatusCode};
 use libsecp256k1::PublicKey;
@@ -21,9 +22,12 @@ use std::convert::Infallible;
 use std::path::Path;
 use std::sync::Arc;
 use tokio::runtime::Handle;
+use tokio::sync::broadcast;
 
 static LOG_FILE: &str = "lockbook_server.log";
 static CARGO_PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Long polls that fall further behind than this are woken to check for themselves.
+const METADATA_UPDATES_CAPACITY: usize = 1024;
 
 shadow!(build_info);
 
@@ -63,7 +67,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         config,
         index_db_client,
         files_db_client,
+        metadata_updates: broadcast::channel(METADATA_UPDATES_CAPACITY).0,
     });
+    let forwarding_state = Arc::clone(&server_state);
+    tokio::spawn(async move { file_service::forward_metadata_updates(&forwarding_state).await });
     let addr = format!("0.0.0.0:{}", port).parse()?;
 
     // https://www.fpcomplete.com/blog/ownership-puzzle-rust-async-hyper/
@@ -101,7 +108,8 @@ macro_rules! route_handler {
             <$TRequest>::ROUTE
         );
 
-        pack::<$TRequest>(match unpack(&$server_state, $hyper_request).await {
+        let (request_format, response_format) = negotiate(&$hyper_request);
+        let result = match unpack(&$server_state, request_format, $hyper_request).await {
             Ok((request, public_key)) => {
                 let request_string = format!("{:?}", request);
                 let result = $handler(&mut RequestContext {
@@ -116,7 +124,75 @@ macro_rules! route_handler {
                 wrap_err::<$TRequest>(result)
             }
             Err(e) => Err(e),
-        })
+        };
+        pack::<$TRequest>(response_format, result)
+    }};
+}
+
+/// Streamed uploads read their request from a header and hand the body to the handler. Headers are
+/// text, so streamed requests and responses are always JSON
+macro_rules! streamed_upload_route_handler {
+    ($TRequest:ty, $handler:path, $hyper_request:ident, $server_state: ident) => {{
+        info!(
+            "Request matched {}{}",
+            <$TRequest>::METHOD,
+            <$TRequest>::ROUTE
+        );
+
+        let result = match unpack_header(&$server_state, &$hyper_request) {
+            Ok((request, public_key)) => {
+                let request_string = format!("{:?}", request);
+                let result = $handler(
+                    &mut RequestContext {
+                        server_state: &$server_state,
+                        request,
+                        public_key,
+                    },
+                    $hyper_request.into_body(),
+                )
+                .await;
+                if let Err(Err(ref e)) = result {
+                    error!("Internal error! Request: {}, Error: {}", request_string, e);
+                }
+                wrap_err::<$TRequest>(result)
+            }
+            Err(e) => Err(e),
+        };
+        pack::<$TRequest>(WireFormat::Json, result)
+    }};
+}
+
+/// Streamed downloads read their request from a header and respond with the body the handler
+/// returns, errors are packed like any other response
+macro_rules! streamed_download_route_handler {
+    ($TRequest:ty, $handler:path, $hyper_request:ident, $server_state: ident) => {{
+        info!(
+            "Request matched {}{}",
+            <$TRequest>::METHOD,
+            <$TRequest>::ROUTE
+        );
+
+        match unpack_header(&$server_state, &$hyper_request) {
+            Ok((request, public_key)) => {
+                let request_string = format!("{:?}", request);
+                let result = $handler(&mut RequestContext {
+                    server_state: &$server_state,
+                    request,
+                    public_key,
+                })
+                .await;
+                match result {
+                    Ok((response, body)) => pack_streamed::<$TRequest>(response, body),
+                    Err(e) => {
+                        if let Err(ref e) = e {
+                            error!("Internal error! Request: {}, Error: {}", request_string, e);
+                        }
+                        pack::<$TRequest>(WireFormat::Json, wrap_err::<$TRequest>(Err(e)))
+                    }
+                }
+            }
+            Err(e) => pack::<$TRequest>(WireFormat::Json, Err(e)),
+        }
     }};
 }
 
@@ -131,6 +207,18 @@ async fn route(
             hyper_request,
             server_state
         ),
+        route_case!(ChangeDocumentContentChunksRequest) => route_handler!(
+            ChangeDocumentContentChunksRequest,
+            file_service::change_document_content_chunks,
+            hyper_request,
+            server_state
+        ),
+        route_case!(ChangeDocumentContentStreamRequest) => streamed_upload_route_handler!(
+            ChangeDocumentContentStreamRequest,
+            file_service::change_document_content_stream,
+            hyper_request,
+            server_state
+        ),
         route_case!(CreateDocumentRequest) => route_handler!(
             CreateDocumentRequest,
             file_service::create_document,
@@ -161,6 +249,18 @@ async fn route(
             hyper_request,
             server_state
         ),
+        route_case!(GetDocumentChunksRequest) => route_handler!(
+            GetDocumentChunksRequest,
+            file_service::get_document_chunks,
+            hyper_request,
+            server_state
+        ),
+        route_case!(GetDocumentStreamRequest) => streamed_download_route_handler!(
+            GetDocumentStreamRequest,
+            file_service::get_document_stream,
+            hyper_request,
+            server_state
+        ),
         route_case!(CreateFolderRequest) => route_handler!(
             CreateFolderRequest,
             file_service::create_folder,
@@ -197,6 +297,12 @@ async fn route(
             hyper_request,
             server_state
         ),
+        route_case!(WaitForUpdatesRequest) => route_handler!(
+            WaitForUpdatesRequest,
+            file_service::wait_for_updates,
+            hyper_request,
+            server_state
+        ),
         route_case!(NewAccountRequest) => route_handler!(
             NewAccountRequest,
             account_service::new_account,
@@ -242,13 +348,35 @@ fn wrap_err<TRequest: Request>(
 ) -> Result<TRequest::Response, ErrorWrapper<TRequest::Error>> {
     match result {
         Ok(response) => Ok(response),
+        Err(Ok(e)) if TRequest::requires_client_update(&e) => {
+            Err(ErrorWrapper::ClientUpdateRequired)
+        }
         Err(Ok(e)) => Err(ErrorWrapper::Endpoint(e)),
         Err(Err(_)) => Err(ErrorWrapper::InternalError),
     }
 }
 
+/// Requests are JSON unless their `Content-Type` says otherwise. Responses are encoded the way the
+/// request's `Accept` asks, or the way the request was.
+fn negotiate(hyper_request: &hyper::Request<Body>) -> (WireFormat, WireFormat) {
+    let header = |name: HeaderName| {
+        hyper_request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let request_format = header(CONTENT_TYPE)
+        .and_then(WireFormat::from_content_type)
+        .unwrap_or(WireFormat::Json);
+    let response_format = header(ACCEPT)
+        .and_then(WireFormat::from_accept)
+        .unwrap_or(request_format);
+    (request_format, response_format)
+}
+
 async fn unpack<TRequest: Request + Serialize + DeserializeOwned>(
     server_state: &ServerState,
+    format: WireFormat,
     hyper_request: hyper::Request<Body>,
 ) -> Result<(TRequest, PublicKey), ErrorWrapper<TRequest::Error>> {
     let request_bytes = match from_request(hyper_request).await {
@@ -258,7 +386,33 @@ async fn unpack<TRequest: Request + Serialize + DeserializeOwned>(
             return Err(ErrorWrapper::<TRequest::Error>::BadRequest);
         }
     };
-    let request: RequestWrapper<TRequest> = match deserialize_request(request_bytes.clone()) {
+    verify_request(server_state, format, request_bytes)
+}
+
+/// Streamed requests carry the request in a header, leaving the body for the content.
+fn unpack_header<TRequest: Request + Serialize + DeserializeOwned>(
+    server_state: &ServerState,
+    hyper_request: &hyper::Request<Body>,
+) -> Result<(TRequest, PublicKey), ErrorWrapper<TRequest::Error>> {
+    match hyper_request.headers().get(STREAMED_REQUEST_HEADER) {
+        Some(header) => verify_request(
+            server_state,
+            WireFormat::Json,
+            Bytes::copy_from_slice(header.as_bytes()),
+        ),
+        None => {
+            warn!("Streamed request is missing its request header");
+            Err(ErrorWrapper::<TRequest::Error>::BadRequest)
+        }
+    }
+}
+
+fn verify_request<TRequest: Request + Serialize + DeserializeOwned>(
+    server_state: &ServerState,
+    format: WireFormat,
+    request_bytes: Bytes,
+) -> Result<(TRequest, PublicKey), ErrorWrapper<TRequest::Error>> {
+    let request: RequestWrapper<TRequest> = match format.deserialize(&request_bytes) {
         Ok(o) => o,
         Err(e) => {
             warn!(
@@ -292,6 +446,7 @@ async fn unpack<TRequest: Request + Serialize + DeserializeOwned>(
 }
 
 fn pack<TRequest>(
+    format: WireFormat,
     result: Result<TRequest::Response, ErrorWrapper<TRequest::Error>>,
 ) -> Result<hyper::Response<Body>, hyper::http::Error>
 where
@@ -299,7 +454,7 @@ where
     TRequest::Response: Serialize,
     TRequest::Error: Serialize,
 {
-    let response_bytes = match serialize_response::<TRequest>(result) {
+    let response_bytes = match serialize_response::<TRequest>(format, result) {
         Ok(o) => o,
         Err(e) => {
             warn!("Error serializing response: {:?}", e);
@@ -307,17 +462,34 @@ where
         }
     };
 
-    to_response(response_bytes)
+    to_response(format, response_bytes)
 }
 
-async fn from_request(request: hyper::Request<Body>) -> Result<Bytes, hyper::Error> {
-    body::to_bytes(request.into_body()).await
+fn pack_streamed<TRequest>(
+    response: TRequest::Response,
+    body: Body,
+) -> Result<hyper::Response<Body>, hyper::http::Error>
+where
+    TRequest: Request,
+    TRequest::Response: Serialize,
+    TRequest::Error: Serialize,
+{
+    let response_bytes = match serialize_response::<TRequest>(WireFormat::Json, Ok(response)) {
+        Ok(o) => o,
+        Err(e) => {
+            warn!("Error serializing response: {:?}", e);
+            return empty_response();
+        }
+    };
+
+    hyper::Response::builder()
+        .status(StatusCode::OK)
+        .header(STREAMED_RESPONSE_HEADER, response_bytes.as_ref())
+        .body(body)
 }
 
-fn deserialize_request<TRequest: Request + DeserializeOwned>(
-    request: Bytes,
-) -> Result<RequestWrapper<TRequest>, serde_json::error::Error> {
-    serde_json::from_slice(&request)
+async fn from_request(request: hyper::Request<Body>) -> Result<Bytes, hyper::Error> {
+    body::to_bytes(request.into_body()).await
 }
 
 fn verify_client_version<TRequest: Request>(request: &RequestWrapper<TRequest>) -> Result<(), ()> {
@@ -327,7 +499,8 @@ fn verify_client_version<TRequest: Request>(request: &RequestWrapper<TRequest>)
         "0.1.1" => Err(()),
         "0.1.2" => Err(()),
         "0.1.3" => Err(()),
-        "0.1.4" => Ok(()),
+        "0.1.4" => Err(()),
+        "0.1.5" => Ok(()),
         _ => Err(()),
     }
 }
@@ -346,19 +519,24 @@ fn verify_auth<TRequest: Request + Serialize>(
 }
 
 fn serialize_response<TRequest>(
+    format: WireFormat,
     response: Result<TRequest::Response, ErrorWrapper<TRequest::Error>>,
-) -> Result<Bytes, serde_json::error::Error>
+) -> Result<Bytes, WireError>
 where
     TRequest: Request,
     TRequest::Response: Serialize,
     TRequest::Error: Serialize,
 {
-    Ok(Bytes::from(serde_json::to_vec(&response)?))
+    Ok(Bytes::from(format.serialize(&response)?))
 }
 
-fn to_response(response: Bytes) -> Result<hyper::Response<Body>, hyper::http::Error> {
+fn to_response(
+    format: WireFormat,
+    response: Bytes,
+) -> Result<hyper::Response<Body>, hyper::http::Error> {
     hyper::Response::builder()
         .status(StatusCode::OK)
+        .header(CONTENT_TYPE, format.content_type())
         .body(response.into())
 }
 
@@ -8,6 +8,7 @@ use uuid::Uuid;
 pub fn test_config() -> Config {
     Config {
         writeable_path: format!("/tmp/{}", Uuid::new_v4().to_string()),
+        storage: None,
     }
 }
 